@@ -0,0 +1,408 @@
+//! The `#[derive(ShiCommand)]` macro of shi. It is re-exported by shi behind its `derive` feature,
+//! and documented there, in `shi::command::derive`.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Lit,
+    LitChar, LitStr, Meta, Path, PathArguments, Token, Type,
+};
+
+/// Derives `shi::command::ShiCommand` for a struct whose fields are the arguments of a command.
+#[proc_macro_derive(ShiCommand, attributes(shi))]
+pub fn derive_shi_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The `#[shi(...)]` attributes of the struct, i.e., of the command.
+#[derive(Default)]
+struct CommandAttrs {
+    name: Option<String>,
+    help: Option<String>,
+    long_help: Option<String>,
+    since: Option<String>,
+    category: Option<String>,
+    confirm: Option<String>,
+    hidden: bool,
+    state: Option<Type>,
+}
+
+/// How a field is given to the command.
+enum Kind {
+    /// A positional argument, e.g. `80` in `listen 80`.
+    Arg,
+    /// A flag that takes no value, e.g. `--verbose`.
+    Flag,
+    /// A flag that takes a value, e.g. `--backlog 16`.
+    Option,
+}
+
+/// The `#[shi(...)]` attributes of a field, i.e., of an argument or a flag.
+struct FieldAttrs {
+    kind: Kind,
+    name: Option<String>,
+    short: Option<char>,
+    rest: bool,
+    values: Vec<String>,
+    choices: Vec<String>,
+    source: Option<Path>,
+    ignore_case: bool,
+}
+
+fn parse_string(meta: &ParseNestedMeta) -> syn::Result<String> {
+    Ok(meta.value()?.parse::<LitStr>()?.value())
+}
+
+fn parse_strings(meta: &ParseNestedMeta) -> syn::Result<Vec<String>> {
+    let value = meta.value()?;
+    let content;
+    syn::bracketed!(content in value);
+    let strings = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+    Ok(strings.iter().map(LitStr::value).collect())
+}
+
+fn parse_command_attrs(attrs: &[Attribute]) -> syn::Result<CommandAttrs> {
+    let mut parsed = CommandAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("shi")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                parsed.name = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("help") {
+                parsed.help = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("long_help") {
+                parsed.long_help = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("since") {
+                parsed.since = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("category") {
+                parsed.category = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("confirm") {
+                parsed.confirm = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("hidden") {
+                parsed.hidden = true;
+            } else if meta.path.is_ident("state") {
+                parsed.state = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown command attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+fn parse_field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut parsed = FieldAttrs {
+        kind: Kind::Arg,
+        name: None,
+        short: None,
+        rest: false,
+        values: Vec::new(),
+        choices: Vec::new(),
+        source: None,
+        ignore_case: false,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("shi")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("arg") {
+                parsed.kind = Kind::Arg;
+            } else if meta.path.is_ident("flag") {
+                parsed.kind = Kind::Flag;
+            } else if meta.path.is_ident("option") {
+                parsed.kind = Kind::Option;
+            } else if meta.path.is_ident("name") {
+                parsed.name = Some(parse_string(&meta)?);
+            } else if meta.path.is_ident("short") {
+                parsed.short = Some(meta.value()?.parse::<LitChar>()?.value());
+            } else if meta.path.is_ident("rest") {
+                parsed.rest = true;
+            } else if meta.path.is_ident("values") {
+                parsed.values = parse_strings(&meta)?;
+            } else if meta.path.is_ident("choices") {
+                parsed.choices = parse_strings(&meta)?;
+            } else if meta.path.is_ident("source") {
+                parsed.source = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("ignore_case") {
+                parsed.ignore_case = true;
+            } else {
+                return Err(meta.error("unknown field attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+/// Returns the doc comment of an item, split into its first paragraph, as a single line, and the
+/// rest, if any.
+fn docs(attrs: &[Attribute]) -> (Option<String>, Option<String>) {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(doc) if doc.path.is_ident("doc") => match &doc.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(line) => Some(line.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let split = lines
+        .iter()
+        .position(String::is_empty)
+        .unwrap_or(lines.len());
+    let first = lines[..split].join(" ");
+    let rest = lines[split..].join("\n").trim().to_string();
+
+    (
+        Some(first).filter(|first| !first.is_empty()),
+        Some(rest).filter(|rest| !rest.is_empty()),
+    )
+}
+
+/// Returns `T` if the given type is `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(GenericArgument::Type(inner)) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident("bool"))
+}
+
+/// Turns the name of a struct into that of its command, e.g. `ListUsers` into `list-users`.
+fn kebab_case(ident: &str) -> String {
+    let mut kebab = String::with_capacity(ident.len() + 4);
+    let mut prev = None;
+    for c in ident.chars() {
+        // Words only start where a lowercase letter is followed by an uppercase one, so that
+        // acronyms stay whole, e.g. `HTTPServer` becomes `httpserver`.
+        if c.is_uppercase() && prev.is_some_and(char::is_lowercase) {
+            kebab.push('-');
+        }
+        kebab.extend(c.to_lowercase());
+        prev = Some(c);
+    }
+
+    kebab
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields: Vec<&syn::Field> = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "ShiCommand can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ShiCommand can only be derived for structs",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "ShiCommand cannot be derived for generic structs",
+        ));
+    }
+
+    let ident = &input.ident;
+    let attrs = parse_command_attrs(&input.attrs)?;
+    let (doc_help, doc_long_help) = docs(&input.attrs);
+    let name = attrs.name.unwrap_or_else(|| kebab_case(&ident.to_string()));
+    let state = match attrs.state {
+        Some(state) => quote!(#state),
+        None => quote!(()),
+    };
+
+    let mut builder = Vec::new();
+    if let Some(help) = attrs.help.or(doc_help) {
+        builder.push(quote!(.help(#help)));
+    }
+    if let Some(long_help) = attrs.long_help.or(doc_long_help) {
+        builder.push(quote!(.long_help(#long_help)));
+    }
+    if let Some(since) = attrs.since {
+        builder.push(quote!(.since(#since)));
+    }
+    if let Some(category) = attrs.category {
+        builder.push(quote!(.category(#category)));
+    }
+    if let Some(confirm) = attrs.confirm {
+        builder.push(quote!(.confirm(#confirm)));
+    }
+    if attrs.hidden {
+        builder.push(quote!(.hidden()));
+    }
+
+    let mut inits = Vec::new();
+    let mut index = 0usize;
+    let mut seen_optional = false;
+    let mut seen_rest = false;
+    for field in fields {
+        let field_ident = &field.ident;
+        let ty = &field.ty;
+        let attrs = parse_field_attrs(&field.attrs)?;
+        let long = attrs.name.clone().unwrap_or_else(|| {
+            field_ident
+                .as_ref()
+                .map(|ident| ident.to_string().replace('_', "-"))
+                .unwrap_or_default()
+        });
+
+        if let Kind::Arg = attrs.kind {
+            if attrs.short.is_some() {
+                return Err(Error::new_spanned(
+                    field,
+                    "`short` only applies to flags and options",
+                ));
+            }
+        } else if attrs.rest
+            || attrs.ignore_case
+            || attrs.source.is_some()
+            || !attrs.values.is_empty()
+            || !attrs.choices.is_empty()
+        {
+            return Err(Error::new_spanned(
+                field,
+                "`rest`, `values`, `choices`, `source` and `ignore_case` only apply to arguments",
+            ));
+        }
+
+        match attrs.kind {
+            Kind::Flag | Kind::Option => {
+                let inner = match attrs.kind {
+                    Kind::Option => Some(option_inner(ty).ok_or_else(|| {
+                        Error::new_spanned(ty, "`option` fields must be of type Option<T>")
+                    })?),
+                    _ if is_bool(ty) => None,
+                    _ => return Err(Error::new_spanned(ty, "`flag` fields must be of type bool")),
+                };
+
+                let mut flag = quote!(::shi::command::FlagSpec::new(#long));
+                if let Some(short) = attrs.short {
+                    flag.extend(quote!(.with_short(#short)));
+                }
+                if inner.is_some() {
+                    flag.extend(quote!(.takes_value()));
+                }
+                if let (Some(help), _) = docs(&field.attrs) {
+                    flag.extend(quote!(.with_help(#help)));
+                }
+                builder.push(quote!(.flag(#flag)));
+
+                inits.push(match inner {
+                    Some(inner) => quote!(#field_ident: args.get_value::<#inner>(#long)?),
+                    None => quote!(#field_ident: args.flag(#long)),
+                });
+            }
+            Kind::Arg => {
+                if seen_rest {
+                    return Err(Error::new_spanned(
+                        field,
+                        "no arguments may follow a `rest` argument",
+                    ));
+                }
+                let (inner, required) = match option_inner(ty) {
+                    Some(inner) => (inner, false),
+                    None => (ty, true),
+                };
+                if required && seen_optional {
+                    return Err(Error::new_spanned(
+                        field,
+                        "required arguments must come before optional ones",
+                    ));
+                }
+                seen_optional |= !required;
+                seen_rest = attrs.rest;
+
+                let mut spec = if required {
+                    quote!(::shi::command::ArgSpec::required(#long))
+                } else {
+                    quote!(::shi::command::ArgSpec::optional(#long))
+                };
+                spec.extend(quote!(.parses_as::<#inner>()));
+                if attrs.rest {
+                    spec.extend(quote!(.rest_of_line()));
+                }
+                if !attrs.values.is_empty() {
+                    let values = &attrs.values;
+                    spec.extend(quote!(.with_values(&[#(#values),*])));
+                }
+                if !attrs.choices.is_empty() {
+                    let choices = &attrs.choices;
+                    spec.extend(quote!(.with_choices(&[#(#choices),*])));
+                }
+                if let Some(source) = &attrs.source {
+                    spec.extend(quote!(.with_source(#source)));
+                }
+                if attrs.ignore_case {
+                    spec.extend(quote!(.ignore_case()));
+                }
+                if let (Some(help), _) = docs(&field.attrs) {
+                    spec.extend(quote!(.with_help(#help)));
+                }
+                builder.push(quote!(.arg(#spec)));
+
+                inits.push(if required {
+                    quote!(#field_ident: args.get::<#inner>(#index)?)
+                } else {
+                    quote! {
+                        #field_ident: match args.positional(#index) {
+                            Some(_) => Some(args.get::<#inner>(#index)?),
+                            None => None,
+                        }
+                    }
+                });
+                index += 1;
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl ::shi::command::ShiCommand for #ident {
+            type State = #state;
+
+            fn builder() -> ::shi::command::BasicCommandBuilder<'static, Self::State> {
+                ::shi::command::BasicCommand::builder(#name) #(#builder)*
+            }
+
+            #[allow(unused_variables)]
+            fn from_args(args: &::shi::command::ParsedArgs) -> ::shi::Result<Self> {
+                Ok(#ident { #(#inits),* })
+            }
+        }
+    })
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+/// Abbreviations are short words that expand into longer text, e.g. `gs` into `server status`.
+///
+/// Unlike aliases, which are resolved when a command is run, abbreviations are expanded in the
+/// line itself, so that the expanded form is what the user sees and what lands in the history.
+/// Only the first word of a line is expanded, as that is where commands are typed.
+#[derive(Debug, Default)]
+pub(crate) struct Abbreviations {
+    expansions: BTreeMap<String, String>,
+}
+
+impl Abbreviations {
+    /// Creates a new, empty Abbreviations.
+    pub fn new() -> Abbreviations {
+        Abbreviations::default()
+    }
+
+    /// Adds an abbreviation, replacing any existing abbreviation of the same word.
+    ///
+    /// # Arguments
+    /// `word` - The word to expand.
+    /// `expansion` - The text to expand it into.
+    pub fn add(&mut self, word: &str, expansion: &str) {
+        self.expansions
+            .insert(word.to_string(), expansion.to_string());
+    }
+
+    /// Restores the abbreviation of the given word to what it was before it was shadowed, i.e., to
+    /// the given expansion, or to nothing if there was none.
+    ///
+    /// # Arguments
+    /// `word` - The word of the abbreviation.
+    /// `expansion` - The expansion to restore.
+    pub fn restore(&mut self, word: &str, expansion: Option<&str>) {
+        match expansion {
+            Some(expansion) => self.add(word, expansion),
+            None => {
+                self.remove(word);
+            }
+        }
+    }
+
+    /// Removes the abbreviation of the given word, returning whether there was one.
+    ///
+    /// # Arguments
+    /// `word` - The word to no longer expand.
+    pub fn remove(&mut self, word: &str) -> bool {
+        self.expansions.remove(word).is_some()
+    }
+
+    /// Returns the expansion of the given word, if it is an abbreviation.
+    ///
+    /// # Arguments
+    /// `word` - The word to expand.
+    pub fn expansion(&self, word: &str) -> Option<&str> {
+        self.expansions.get(word).map(String::as_str)
+    }
+
+    /// Expands the first word of the given line, if it is an abbreviation.
+    ///
+    /// # Arguments
+    /// `line` - The line to expand.
+    ///
+    /// # Returns
+    /// `Option<String>` - The expanded line, or None if its first word is not an abbreviation.
+    pub fn expand(&self, line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        let (word, rest) = match trimmed.find(' ') {
+            Some(end) => trimmed.split_at(end),
+            None => (trimmed, ""),
+        };
+        let expansion = self.expansion(word)?;
+
+        let indent = &line[..line.len() - trimmed.len()];
+        Some(format!("{}{}{}", indent, expansion, rest))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn expands_first_word() {
+        let mut abbreviations = Abbreviations::new();
+        abbreviations.add("gs", "server status");
+
+        assert_eq!(
+            abbreviations.expand("gs"),
+            Some(String::from("server status"))
+        );
+        assert_eq!(
+            abbreviations.expand("  gs --all"),
+            Some(String::from("  server status --all"))
+        );
+        assert_eq!(abbreviations.expand("echo gs"), None);
+        assert_eq!(abbreviations.expand("gsx"), None);
+        assert_eq!(abbreviations.expand(""), None);
+    }
+}
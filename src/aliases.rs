@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Result;
+
+/// Aliases are shortcuts that users define at runtime, e.g. `ll` for `list --long`.
+///
+/// Unlike abbreviations, aliases are resolved when a line is evaluated, before the command it
+/// invokes is looked up, so the line in the history is the one the user typed. Only the first word
+/// of a line is resolved, and the expansion of an alias is not itself resolved again.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Aliases {
+    expansions: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    /// Creates a new, empty Aliases.
+    pub fn new() -> Aliases {
+        Aliases::default()
+    }
+
+    /// Adds an alias, replacing any existing alias of the same name.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias.
+    /// `expansion` - The text that the alias stands for.
+    pub fn add(&mut self, name: &str, expansion: &str) {
+        self.expansions
+            .insert(name.to_string(), expansion.to_string());
+    }
+
+    /// Restores the alias of the given name to what it was before it was shadowed, i.e., to
+    /// the given expansion, or to nothing if there was none.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias.
+    /// `expansion` - The expansion to restore.
+    pub fn restore(&mut self, name: &str, expansion: Option<&str>) {
+        match expansion {
+            Some(expansion) => self.add(name, expansion),
+            None => {
+                self.remove(name);
+            }
+        }
+    }
+
+    /// Removes the alias of the given name, returning whether there was one.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.expansions.remove(name).is_some()
+    }
+
+    /// Returns the names and expansions of the aliases, ordered by name.
+    pub fn expansions(&self) -> &BTreeMap<String, String> {
+        &self.expansions
+    }
+
+    /// Resolves the first word of the given line, if it is an alias.
+    ///
+    /// # Arguments
+    /// `line` - The line to resolve.
+    ///
+    /// # Returns
+    /// `Option<String>` - The resolved line, or None if its first word is not an alias.
+    pub fn resolve(&self, line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        let (name, rest) = match trimmed.find(' ') {
+            Some(end) => trimmed.split_at(end),
+            None => (trimmed, ""),
+        };
+
+        self.expansions
+            .get(name)
+            .map(|expansion| format!("{}{}", expansion, rest))
+    }
+
+    /// Loads the aliases from the given file, replacing any aliases of the same names. A missing
+    /// file is treated as empty, and malformed lines are skipped.
+    ///
+    /// # Arguments
+    /// `path` - The path to the aliases file.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for line in contents.lines() {
+            if let Some((name, expansion)) = line.split_once('\t') {
+                self.add(name, expansion);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the aliases to the given file, one `name<TAB>expansion` line per alias.
+    ///
+    /// # Arguments
+    /// `path` - The path to the aliases file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let contents: String = self
+            .expansions
+            .iter()
+            .map(|(name, expansion)| format!("{}\t{}\n", name, expansion))
+            .collect();
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn resolves_first_word() {
+        let mut aliases = Aliases::new();
+        aliases.add("ll", "list --long");
+
+        assert_eq!(aliases.resolve("ll"), Some(String::from("list --long")));
+        assert_eq!(
+            aliases.resolve("  ll /tmp"),
+            Some(String::from("list --long /tmp"))
+        );
+        assert_eq!(aliases.resolve("echo ll"), None);
+        assert_eq!(aliases.resolve("llama"), None);
+    }
+
+    #[test]
+    fn persistence() -> Result<()> {
+        let path = env::temp_dir().join(format!("shi-aliases-test-{}", process::id()));
+
+        let mut aliases = Aliases::new();
+        aliases.add("st", "server status");
+        aliases.add("ll", "list --long");
+        aliases.save(&path)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "ll\tlist --long\nst\tserver status\n"
+        );
+
+        let mut loaded = Aliases::new();
+        loaded.add("ll", "ls");
+        loaded.load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(loaded.expansions(), aliases.expansions());
+
+        // A missing file is just no aliases.
+        loaded.load(&path)?;
+        assert_eq!(loaded.expansions().len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Copies text to the system clipboard.
+//!
+//! The platform's clipboard tool is used if one is installed, e.g. `pbcopy` on macOS or `xclip` on
+//! X11. Otherwise, the text is sent to the terminal as an OSC 52 escape sequence, which most
+//! modern terminals place on the clipboard, even over SSH. If there is no tool and stdout is not a
+//! terminal, copying fails.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::error::ShiError;
+use crate::Result;
+
+/// The clipboard tools to try, in order, as a program and its arguments.
+fn tools() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![("clip", &[])]
+    } else {
+        let mut tools: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            tools.push(("wl-copy", &[]));
+        }
+        tools.push(("xclip", &["-selection", "clipboard"]));
+        tools.push(("xsel", &["--clipboard", "--input"]));
+        tools
+    }
+}
+
+/// Pipes the text into the given program, returning whether it succeeded.
+fn pipe_to(program: &str, args: &[&str], text: &str) -> bool {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+        None => false,
+    };
+
+    matches!(child.wait(), Ok(status) if status.success()) && written
+}
+
+/// Encodes the given bytes as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Copies the given text to the system clipboard.
+///
+/// # Arguments
+/// `text` - The text to copy.
+pub(crate) fn copy(text: &str) -> Result<()> {
+    if tools()
+        .into_iter()
+        .any(|(program, args)| pipe_to(program, args, text))
+    {
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout();
+    // Escape sequences written anywhere but a terminal, e.g. a pipe, reach no clipboard.
+    if !stdout.is_terminal() {
+        return Err(ShiError::general(
+            "failed to copy: no clipboard tool was found, and stdout is not a terminal",
+        ));
+    }
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(b"hello, world!"), "aGVsbG8sIHdvcmxkIQ==");
+    }
+}
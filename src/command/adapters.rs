@@ -0,0 +1,301 @@
+//! A module for adapting commands written for one state to shells of another, so that packs of
+//! commands can be reused across applications.
+//!
+//! Commands that need no state are written for `()`, and those that need part of the state of an
+//! application for that part alone. Either is then lifted into a shell of any state:
+//!
+//! ```
+//! # use shi::command::Command;
+//! # use shi::shell::Shell;
+//! # use shi::{cmd, parent};
+//! struct App {
+//!     counter: u32,
+//! }
+//!
+//! fn counter_pack<'a>() -> Vec<Command<'a, u32>> {
+//!     vec![cmd!("bump", |counter: &mut u32, _| {
+//!         *counter += 1;
+//!         Ok(counter.to_string())
+//!     })]
+//! }
+//!
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new_with_state("| ", App { counter: 0 });
+//! shell.register(cmd!("ping", |_: &mut (), _| Ok(String::from("pong"))).ignore_state())?;
+//! for cmd in counter_pack() {
+//!     shell.register(cmd.map_state(|app: &mut App| &mut app.counter))?;
+//! }
+//!
+//! assert_eq!(shell.eval("ping")?, "pong");
+//! assert_eq!(shell.eval("bump")?, "1");
+//! assert_eq!(shell.state().counter, 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+
+use super::{BaseCommand, Command, Completion, ConcurrentJob};
+use crate::output::CommandOutput;
+use crate::Result;
+
+/// IgnoreState lifts a command written for `()` into a command of any state, `S`, which it
+/// ignores. See `Command::ignore_state()` for lifting whole trees of commands.
+pub struct IgnoreState<C, S> {
+    cmd: C,
+    phantom: PhantomData<fn(&mut S)>,
+}
+
+impl<C, S> IgnoreState<C, S>
+where
+    C: BaseCommand<State = ()>,
+{
+    /// Creates a new IgnoreState.
+    ///
+    /// # Arguments
+    /// `cmd` - The command to lift.
+    pub fn new(cmd: C) -> IgnoreState<C, S> {
+        IgnoreState {
+            cmd,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, S> BaseCommand for IgnoreState<C, S>
+where
+    C: BaseCommand<State = ()>,
+{
+    type State = S;
+
+    fn name(&self) -> &str {
+        self.cmd.name()
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        self.cmd.validate_args(args)
+    }
+
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        self.cmd.validate_args_strict(args)
+    }
+
+    fn execute(&self, _: &mut S, args: &[String]) -> Result<String> {
+        self.cmd.execute(&mut (), args)
+    }
+
+    fn execute_output(&self, _: &mut S, args: &[String]) -> Result<CommandOutput> {
+        self.cmd.execute_output(&mut (), args)
+    }
+
+    fn execute_with_input(&self, _: &mut S, args: &[String], input: &str) -> Result<String> {
+        self.cmd.execute_with_input(&mut (), args, input)
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        self.cmd.autocomplete(args, trailing_space)
+    }
+
+    fn help(&self) -> String {
+        self.cmd.help()
+    }
+
+    fn since(&self) -> Option<String> {
+        self.cmd.since()
+    }
+
+    fn long_help(&self) -> Option<String> {
+        self.cmd.long_help()
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.cmd.is_hidden()
+    }
+
+    fn category(&self) -> Option<String> {
+        self.cmd.category()
+    }
+
+    fn confirmation(&self) -> Option<String> {
+        self.cmd.confirmation()
+    }
+
+    fn concurrent(&self, _: &mut S, args: &[String]) -> Option<ConcurrentJob> {
+        self.cmd.concurrent(&mut (), args)
+    }
+}
+
+/// MapState lifts a command written for a part of a state, e.g. a field of it, into a command of
+/// the whole state, `S`. See `Command::map_state()` for lifting whole trees of commands.
+pub struct MapState<F, C, S> {
+    map: F,
+    cmd: C,
+    phantom: PhantomData<fn(&mut S)>,
+}
+
+impl<F, C, S> MapState<F, C, S>
+where
+    C: BaseCommand,
+    F: Fn(&mut S) -> &mut C::State,
+{
+    /// Creates a new MapState.
+    ///
+    /// # Arguments
+    /// `map` - Returns the part of the state that `cmd` is written for.
+    /// `cmd` - The command to lift.
+    pub fn new(map: F, cmd: C) -> MapState<F, C, S> {
+        MapState {
+            map,
+            cmd,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F, C, S> BaseCommand for MapState<F, C, S>
+where
+    C: BaseCommand,
+    F: Fn(&mut S) -> &mut C::State,
+{
+    type State = S;
+
+    fn name(&self) -> &str {
+        self.cmd.name()
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        self.cmd.validate_args(args)
+    }
+
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        self.cmd.validate_args_strict(args)
+    }
+
+    fn execute(&self, state: &mut S, args: &[String]) -> Result<String> {
+        self.cmd.execute((self.map)(state), args)
+    }
+
+    fn execute_output(&self, state: &mut S, args: &[String]) -> Result<CommandOutput> {
+        self.cmd.execute_output((self.map)(state), args)
+    }
+
+    fn execute_with_input(&self, state: &mut S, args: &[String], input: &str) -> Result<String> {
+        self.cmd.execute_with_input((self.map)(state), args, input)
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        self.cmd.autocomplete(args, trailing_space)
+    }
+
+    fn help(&self) -> String {
+        self.cmd.help()
+    }
+
+    fn since(&self) -> Option<String> {
+        self.cmd.since()
+    }
+
+    fn long_help(&self) -> Option<String> {
+        self.cmd.long_help()
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.cmd.is_hidden()
+    }
+
+    fn category(&self) -> Option<String> {
+        self.cmd.category()
+    }
+
+    fn confirmation(&self) -> Option<String> {
+        self.cmd.confirmation()
+    }
+
+    fn concurrent(&self, state: &mut S, args: &[String]) -> Option<ConcurrentJob> {
+        self.cmd.concurrent((self.map)(state), args)
+    }
+}
+
+/// Converts every leaf of the given tree of commands via `wrap`, keeping its parents as they are.
+/// Children that have not been constructed yet are converted once they are.
+pub(crate) fn lift<'a, S, T, W>(cmd: Command<'a, S>, wrap: W) -> Command<'a, T>
+where
+    W: Fn(Command<'a, S>) -> Command<'a, T> + Clone + 'a,
+    S: 'a,
+{
+    match cmd {
+        Command::Leaf(_) => wrap(cmd),
+        Command::Parent(parent) => {
+            Command::Parent(parent.map_commands(move |child| lift(child, wrap.clone())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::command::BasicCommand;
+    use crate::shell::Shell;
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    #[derive(Default)]
+    struct App {
+        log: Vec<String>,
+        name: String,
+    }
+
+    #[test]
+    fn lifts_trees() -> Result<()> {
+        let mut shell = Shell::new_with_state("| ", App::default());
+
+        let mut util = parent!(
+            "util",
+            cmd!("ping", |_: &mut (), _| Ok(String::from("pong"))),
+        );
+        if let Command::Parent(parent) = &mut util {
+            parent.add_lazy("echo", || {
+                cmd!("echo", |_: &mut (), args| Ok(args.join(" ")))
+            });
+        }
+        shell.register(util.ignore_state())?;
+
+        shell.register(
+            parent!(
+                "log",
+                cmd!("add", |log: &mut Vec<String>, args| {
+                    log.extend(args.iter().cloned());
+                    Ok(log.len().to_string())
+                }),
+            )
+            .map_state(|app: &mut App| &mut app.log),
+        )?;
+        shell.register(Command::new_leaf(MapState::new(
+            |app: &mut App| &mut app.name,
+            BasicCommand::new("rename", |name: &mut String, args| {
+                *name = args.join(" ");
+                Ok(format!("renamed to {}", name))
+            }),
+        )))?;
+
+        assert_eq!(shell.eval("util ping")?, "pong");
+        // Lifting does not construct lazy children.
+        match shell.cmds.borrow().get("util") {
+            Some(Command::Parent(parent)) => {
+                assert!(!parent.sub_commands().is_constructed("echo"))
+            }
+            _ => panic!("expected 'util' to be a parent command"),
+        }
+        assert_eq!(shell.eval("util echo a b")?, "a b");
+        assert_eq!(shell.eval("log add x y")?, "2");
+        assert_eq!(shell.eval("rename shi")?, "renamed to shi");
+        assert!(shell.eval("util nope").is_err());
+
+        assert_eq!(shell.state().log, vec!["x", "y"]);
+        assert_eq!(shell.state().name, "shi");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::marker::PhantomData;
+
+use super::args::join_unquoted;
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// AliasCommand manages aliases, i.e., shortcuts that users define for commands at runtime. See
+/// `Shell::add_alias()`.
+///
+/// * `alias ll "list --long"` defines `ll` as a shortcut for `list --long`, so that `ll /tmp`
+///   runs `list --long /tmp`.
+/// * `alias ll` shows what `ll` stands for.
+/// * `alias` lists the aliases.
+/// * `alias --remove ll` removes an alias.
+pub struct AliasCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for AliasCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> AliasCommand<'a, S> {
+    /// Creates a new AliasCommand.
+    pub fn new() -> AliasCommand<'a, S> {
+        AliasCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for AliasCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "alias"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args.first().map(|arg| &arg[..]) {
+            Some("--remove") if args.len() != 2 => Err(ShiError::general(
+                "expected the name of an alias after '--remove'",
+            )),
+            Some(name) if name.starts_with('-') && name != "--remove" => {
+                Err(ShiError::general(format!("invalid alias name '{}'", name)))
+            }
+            Some(name) if name == self.name() => {
+                Err(ShiError::general("'alias' cannot be aliased"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        match args {
+            [] => {
+                let aliases = &shell.aliases;
+                if aliases.expansions().is_empty() {
+                    return Ok(String::from("no aliases"));
+                }
+
+                Ok(aliases
+                    .expansions()
+                    .iter()
+                    .map(|(name, expansion)| format!("{} - {}", name, expansion))
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+            [flag, name] if flag == "--remove" => {
+                if !shell.aliases.remove(name) {
+                    return Err(ShiError::general(format!("no alias named '{}'", name)));
+                }
+                Ok(format!("removed alias '{}'", name))
+            }
+            [name] => match shell.aliases.expansions().get(name) {
+                Some(expansion) => Ok(format!("{} - {}", name, expansion)),
+                None => Err(ShiError::general(format!("no alias named '{}'", name))),
+            },
+            [name, expansion @ ..] => {
+                shell.add_alias(name, &join_unquoted(expansion));
+                Ok(format!("added alias '{}'", name))
+            }
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Defines, shows, lists or removes shortcuts for commands")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn manages_aliases() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("list", |_, args| Ok(args.join(" "))))?;
+
+        assert_eq!(shell.eval("alias")?, "no aliases");
+        assert_eq!(shell.eval("alias ll \"list --long\"")?, "added alias 'll'");
+        assert_eq!(shell.eval("ll /tmp")?, "--long /tmp");
+        assert_eq!(shell.eval("alias ll")?, "ll - list --long");
+
+        shell.add_alias("la", "list --all");
+        assert_eq!(shell.eval("alias")?, "la - list --all\nll - list --long");
+
+        assert_eq!(shell.eval("alias --remove ll")?, "removed alias 'll'");
+        assert!(shell.eval("ll").is_err());
+        assert!(shell.eval("alias --remove ll").is_err());
+        assert!(shell.eval("alias ll").is_err());
+        assert!(shell.eval("alias --remove").is_err());
+        assert!(shell.eval("alias -x list").is_err());
+        assert!(shell.eval("alias alias list").is_err());
+
+        Ok(())
+    }
+}
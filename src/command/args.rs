@@ -0,0 +1,846 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::result;
+use std::str::FromStr;
+
+use super::Completion;
+use crate::error::ShiError;
+use crate::Result;
+
+/// Produces every value that a positional argument may take, for completion.
+pub type ArgSource = Rc<dyn Fn() -> Vec<String>>;
+
+/// Checks a value of a positional argument, returning why it is invalid if it is.
+pub type ArgValidator = Rc<dyn Fn(&str) -> result::Result<(), String>>;
+
+#[derive(Clone, Default)]
+/// ArgSpec declares how a single positional argument of a command behaves, e.g. what it completes
+/// to. Commands that take `ArgSpec`s, like `BasicCommand`, use them to complete their arguments,
+/// so that they needn't work out which argument is being completed themselves.
+///
+/// ```
+/// # use shi::command::{ArgSpec, BasicCommand};
+/// let mut connect = BasicCommand::new("connect", |_: &mut (), args: &[String]| {
+///     Ok(format!("connecting to {}:{}", args[0], args[1]))
+/// });
+/// connect.set_arg(0, ArgSpec::new().with_source(|| vec![String::from("localhost")]));
+/// connect.set_arg(1, ArgSpec::new().with_values(&["80", "443"]));
+/// ```
+///
+/// Arguments that may only take one of a fixed set of values can be declared with
+/// `with_choices()`, in which case they are both completed and validated against that set:
+///
+/// ```
+/// # use shi::command::{ArgSpec, BaseCommand, BasicCommand};
+/// let mut level = BasicCommand::new("level", |_: &mut (), args: &[String]| {
+///     Ok(format!("level is now {}", args[0]))
+/// });
+/// level.set_arg(0, ArgSpec::new().with_choices(&["debug", "info", "warn"]).ignore_case());
+/// assert!(level.validate_args(&[String::from("INFO")]).is_ok());
+/// assert!(level.validate_args(&[String::from("trace")]).is_err());
+/// ```
+///
+/// Arguments declared via `required()` or `optional()` form the signature of the command, whose
+/// arity is then enforced before the command is executed, and which is shown in its help:
+///
+/// ```
+/// # use shi::command::{ArgSpec, BaseCommand, BasicCommand};
+/// let connect = BasicCommand::builder("connect")
+///     .help("Connects to a server")
+///     .arg(ArgSpec::required("host"))
+///     .arg(ArgSpec::optional("port").parses_as::<u16>())
+///     .build(|_: &mut (), args: &[String]| Ok(format!("connecting to {}", args[0])));
+/// assert_eq!(connect.help(), "Connects to a server (usage: connect <host> [port])");
+/// assert!(connect.validate_args(&[]).is_err());
+/// assert!(connect.validate_args(&[String::from("localhost"), String::from("http")]).is_err());
+/// ```
+///
+/// The last argument may take the rest of the line via `rest_of_line()`, so that free text needn't
+/// be quoted:
+///
+/// ```
+/// # use shi::command::{ArgSpec, BaseCommand, BasicCommand};
+/// let add = BasicCommand::builder("add")
+///     .arg(ArgSpec::required("text").rest_of_line())
+///     .build(|_: &mut (), args: &[String]| Ok(format!("added '{}'", args[0])));
+/// let args = ["buy", "milk,", "", "eggs"].map(String::from);
+/// assert_eq!(add.execute(&mut (), &args).unwrap(), "added 'buy milk,  eggs'");
+/// ```
+pub struct ArgSpec {
+    name: Option<String>,
+    source: Option<ArgSource>,
+    choices: Option<Rc<[String]>>,
+    ignore_case: bool,
+    // None if the argument is not part of a signature, i.e., was not declared via `required()` or
+    // `optional()`.
+    required: Option<bool>,
+    validator: Option<ArgValidator>,
+    rest_of_line: bool,
+    help: String,
+}
+
+impl fmt::Debug for ArgSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArgSpec")
+            .field("name", &self.name)
+            .field("source", &self.source.as_ref().map(|_| "<fn>"))
+            .field("choices", &self.choices)
+            .field("ignore_case", &self.ignore_case)
+            .field("required", &self.required)
+            .field("validator", &self.validator.as_ref().map(|_| "<fn>"))
+            .field("rest_of_line", &self.rest_of_line)
+            .field("help", &self.help)
+            .finish()
+    }
+}
+
+impl ArgSpec {
+    /// Creates a new ArgSpec, for an argument that completes to nothing.
+    pub fn new() -> ArgSpec {
+        ArgSpec::default()
+    }
+
+    /// Creates a new ArgSpec, for a named argument that must be given.
+    ///
+    /// # Arguments
+    /// `name` - The name of the argument.
+    pub fn required(name: &str) -> ArgSpec {
+        ArgSpec {
+            required: Some(true),
+            ..ArgSpec::new().with_name(name)
+        }
+    }
+
+    /// Creates a new ArgSpec, for a named argument that may be left out. Optional arguments must
+    /// come after the required ones.
+    ///
+    /// # Arguments
+    /// `name` - The name of the argument.
+    pub fn optional(name: &str) -> ArgSpec {
+        ArgSpec {
+            required: Some(false),
+            ..ArgSpec::new().with_name(name)
+        }
+    }
+
+    /// Returns whether the argument must be given, or None if it is not part of a signature. See
+    /// `required()` and `optional()`.
+    pub fn is_required(&self) -> Option<bool> {
+        self.required
+    }
+
+    /// Makes the argument take the rest of the line, i.e., everything from it on is delivered as
+    /// one argument, with the spacing it was typed with. Only the last argument of a command may
+    /// take the rest of the line.
+    pub fn rest_of_line(mut self) -> ArgSpec {
+        self.rest_of_line = true;
+        self
+    }
+
+    /// Returns whether the argument takes the rest of the line. See `rest_of_line()`.
+    pub fn is_rest_of_line(&self) -> bool {
+        self.rest_of_line
+    }
+
+    /// Names the argument, as it is shown in usage messages, e.g. `host`.
+    ///
+    /// # Arguments
+    /// `name` - The name of the argument.
+    pub fn with_name(mut self, name: &str) -> ArgSpec {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Returns the name of the argument, if it was named via `with_name()`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the help message of the argument.
+    ///
+    /// # Arguments
+    /// `help` - The help message to use.
+    pub fn with_help(mut self, help: &str) -> ArgSpec {
+        self.help = help.to_string();
+        self
+    }
+
+    /// Returns the help message of the argument.
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+
+    /// Completes the argument to the values produced by the given source, which is called anew
+    /// for every completion, e.g. to complete hostnames that may change.
+    ///
+    /// # Arguments
+    /// `source` - Produces the values the argument may take.
+    pub fn with_source<F>(mut self, source: F) -> ArgSpec
+    where
+        F: Fn() -> Vec<String> + 'static,
+    {
+        self.source = Some(Rc::new(source));
+        self
+    }
+
+    /// Completes the argument to the given values.
+    ///
+    /// # Arguments
+    /// `values` - The values the argument may take.
+    pub fn with_values(self, values: &[&str]) -> ArgSpec {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        self.with_source(move || values.clone())
+    }
+
+    /// Restricts the argument to the given values. Unlike `with_values()`, any other value is
+    /// rejected when the arguments of the command are validated.
+    ///
+    /// # Arguments
+    /// `choices` - The only values the argument may take.
+    pub fn with_choices(self, choices: &[&str]) -> ArgSpec {
+        let mut spec = self.with_values(choices);
+        spec.choices = Some(choices.iter().map(|choice| choice.to_string()).collect());
+        spec
+    }
+
+    /// Checks values of the argument with the given validator, which returns why a value is
+    /// invalid if it is.
+    ///
+    /// # Arguments
+    /// `validator` - Checks a value of the argument.
+    pub fn with_validator<F>(mut self, validator: F) -> ArgSpec
+    where
+        F: Fn(&str) -> result::Result<(), String> + 'static,
+    {
+        self.validator = Some(Rc::new(validator));
+        self
+    }
+
+    /// Rejects values of the argument that do not parse as a `T`, e.g. `u16` for a port.
+    pub fn parses_as<T>(self) -> ArgSpec
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.with_validator(|arg| arg.parse::<T>().map(|_| ()).map_err(|err| err.to_string()))
+    }
+
+    /// Matches the argument against its values without regard to case, both when completing and
+    /// when validating it.
+    pub fn ignore_case(mut self) -> ArgSpec {
+        self.ignore_case = true;
+        self
+    }
+
+    /// Returns the values this argument completes to.
+    pub fn candidates(&self) -> Vec<String> {
+        self.source
+            .as_ref()
+            .map(|source| source())
+            .unwrap_or_default()
+    }
+
+    /// Completes a partially typed value of this argument.
+    ///
+    /// # Arguments
+    /// `partial` - The partially typed value.
+    ///
+    /// # Returns
+    /// `Completion` - The completion.
+    pub fn complete(&self, partial: &str) -> Completion {
+        if !self.ignore_case {
+            return Completion::from_partial(partial, self.candidates());
+        }
+
+        let partial_lower = partial.to_lowercase();
+        let suffixes: Vec<String> = self
+            .candidates()
+            .into_iter()
+            .filter(|candidate| candidate.len() > partial.len())
+            .filter(|candidate| {
+                candidate
+                    .get(..partial.len())
+                    .is_some_and(|prefix| prefix.to_lowercase() == partial_lower)
+            })
+            .map(|candidate| candidate[partial.len()..].to_string())
+            .collect();
+
+        if suffixes.is_empty() {
+            Completion::Nothing
+        } else {
+            Completion::PartialArgCompletion(suffixes)
+        }
+    }
+
+    /// Validates a value of this argument. Only arguments declared with `with_choices()` or
+    /// `with_validator()` can be invalid.
+    ///
+    /// # Arguments
+    /// `arg` - The value of the argument.
+    ///
+    /// # Returns
+    /// `Result<()>` - An error listing the valid choices if the value is not one of them, or
+    /// saying why the validator rejected it.
+    pub fn validate(&self, arg: &str) -> Result<()> {
+        if let Some(validator) = &self.validator {
+            if let Err(reason) = validator(arg) {
+                let name = match &self.name {
+                    Some(name) => format!(" for <{}>", name),
+                    None => String::new(),
+                };
+                return Err(ShiError::general(format!(
+                    "invalid value '{}'{}: {}",
+                    arg, name, reason
+                )));
+            }
+        }
+
+        let choices = match &self.choices {
+            Some(choices) => choices,
+            None => return Ok(()),
+        };
+
+        let matches = |choice: &String| {
+            if self.ignore_case {
+                choice.to_lowercase() == arg.to_lowercase()
+            } else {
+                choice == arg
+            }
+        };
+        if choices.iter().any(matches) {
+            return Ok(());
+        }
+
+        Err(ShiError::general(format!(
+            "invalid value '{}', expected one of: {}",
+            arg,
+            choices.join(", ")
+        )))
+    }
+}
+
+/// Joins the given arguments with spaces, without the quotes around the result, if any, e.g. for
+/// `"deploy {env}"` in `snippet add deploy "deploy {env}"`.
+pub(crate) fn join_unquoted(args: &[String]) -> String {
+    let joined = args.join(" ");
+    for quote in &['"', '\''] {
+        if joined.len() > 1 && joined.starts_with(*quote) && joined.ends_with(*quote) {
+            return joined[1..joined.len() - 1].to_string();
+        }
+    }
+
+    joined
+}
+
+/// Splits off the first argument, which may be quoted and so span several of the given arguments,
+/// e.g. `"dog woof"` in `filter "dog woof" pets`, from the ones that follow it.
+///
+/// # Returns
+/// `Result<(String, &[String])>` - The first argument without its quotes, and the arguments
+/// following it, or an error if there are no arguments or the quote is never closed.
+pub(crate) fn split_first_unquoted(args: &[String]) -> Result<(String, &[String])> {
+    let first = args.first().ok_or(ShiError::NoArgs)?;
+    let quote = match first.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => quote,
+        _ => return Ok((first.clone(), &args[1..])),
+    };
+
+    // The quote may be closed by the first argument itself, unless it is nothing but the quote.
+    let closed = args
+        .iter()
+        .enumerate()
+        .position(|(i, arg)| arg.ends_with(quote) && (i > 0 || arg.len() > 1));
+    match closed {
+        Some(last) => Ok((join_unquoted(&args[..=last]), &args[last + 1..])),
+        None => Err(ShiError::general(format!(
+            "unterminated quote in '{}'",
+            args.join(" ")
+        ))),
+    }
+}
+
+/// Completes the arguments of a command from the specs of its positional arguments, where
+/// `specs[i]` describes the `i`th argument. Arguments without a spec complete to nothing.
+///
+/// # Arguments
+/// `specs` - The specs of the positional arguments.
+/// `args` - The arguments typed so far, as given to `BaseCommand::autocomplete()`.
+/// `trailing_space` - Whether the arguments are followed by a space, i.e., whether the next
+/// argument is being started rather than the last one being typed.
+///
+/// # Returns
+/// `Completion` - The completion.
+pub fn complete_positional(specs: &[ArgSpec], args: Vec<&str>, trailing_space: bool) -> Completion {
+    match args.split_last() {
+        Some((partial, _)) if !trailing_space => match specs.get(args.len() - 1) {
+            Some(spec) => spec.complete(partial),
+            None => Completion::Nothing,
+        },
+        _ => match specs.get(args.len()).map(ArgSpec::candidates) {
+            Some(candidates) if !candidates.is_empty() => Completion::Possibilities(candidates),
+            _ => Completion::Nothing,
+        },
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// FlagSpec declares a flag that a command accepts, e.g. `--force`, `-f` or `--output <value>`.
+/// See `parse_args()`.
+pub struct FlagSpec {
+    long: String,
+    short: Option<char>,
+    takes_value: bool,
+    help: String,
+}
+
+impl FlagSpec {
+    /// Creates a new FlagSpec, for a flag that takes no value.
+    ///
+    /// # Arguments
+    /// `long` - The long name of the flag, without its leading dashes, e.g. `force` for `--force`.
+    pub fn new(long: &str) -> FlagSpec {
+        FlagSpec {
+            long: long.to_string(),
+            short: None,
+            takes_value: false,
+            help: String::new(),
+        }
+    }
+
+    /// Lets the flag also be given by a short name, e.g. `f` for `-f`.
+    ///
+    /// # Arguments
+    /// `short` - The short name of the flag, without its leading dash.
+    pub fn with_short(mut self, short: char) -> FlagSpec {
+        self.short = Some(short);
+        self
+    }
+
+    /// Makes the flag take a value, given either as the next argument, e.g. `--output out.txt`, or
+    /// after an `=`, e.g. `--output=out.txt`.
+    pub fn takes_value(mut self) -> FlagSpec {
+        self.takes_value = true;
+        self
+    }
+
+    /// Sets the help message of the flag.
+    ///
+    /// # Arguments
+    /// `help` - The help message to use.
+    pub fn with_help(mut self, help: &str) -> FlagSpec {
+        self.help = help.to_string();
+        self
+    }
+
+    /// Returns the long name of the flag, without its leading dashes.
+    pub fn long(&self) -> &str {
+        &self.long
+    }
+
+    /// Returns whether the flag takes a value. See `takes_value()`.
+    pub fn expects_value(&self) -> bool {
+        self.takes_value
+    }
+
+    /// Returns the help message of the flag.
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+
+    /// Returns how the flag is shown in usage messages, e.g. `[-o|--output <value>]`.
+    pub fn usage(&self) -> String {
+        let names = match self.short {
+            Some(short) => format!("-{}|--{}", short, self.long),
+            None => format!("--{}", self.long),
+        };
+        if self.takes_value {
+            format!("[{} <value>]", names)
+        } else {
+            format!("[{}]", names)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// ParsedArgs are the arguments of a command, split into flags and positional arguments by
+/// `parse_args()`.
+pub struct ParsedArgs {
+    positionals: Vec<String>,
+    flags: HashMap<String, Option<String>>,
+}
+
+impl ParsedArgs {
+    /// Returns the positional arguments, in order.
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    /// Returns the positional argument at the given index, if it was given.
+    ///
+    /// # Arguments
+    /// `index` - The index of the positional argument, starting from 0.
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+
+    /// Returns whether the flag with the given long name was given.
+    ///
+    /// # Arguments
+    /// `long` - The long name of the flag, without its leading dashes.
+    pub fn flag(&self, long: &str) -> bool {
+        self.flags.contains_key(long)
+    }
+
+    /// Returns the value of the flag with the given long name, if it was given. If it was given
+    /// more than once, the last value wins.
+    ///
+    /// # Arguments
+    /// `long` - The long name of the flag, without its leading dashes.
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.flags.get(long)?.as_deref()
+    }
+
+    /// Parses the positional argument at the given index into the given type, e.g.
+    /// `args.get::<u32>(0)?`.
+    ///
+    /// ```
+    /// # use shi::command::{BasicCommand, ParsedArgs};
+    /// let repeat = BasicCommand::new_parsed("repeat", |_: &mut (), args: &ParsedArgs| {
+    ///     let times = args.get::<usize>(0)?;
+    ///     Ok(args.get::<String>(1)?.repeat(times))
+    /// });
+    /// ```
+    ///
+    /// # Arguments
+    /// `index` - The index of the positional argument, starting from 0.
+    ///
+    /// # Returns
+    /// `Result<T>` - The parsed argument, or an error if it was not given or could not be parsed.
+    pub fn get<T>(&self, index: usize) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let arg = self
+            .positional(index)
+            .ok_or_else(|| ShiError::general(format!("missing argument {}", index)))?;
+
+        arg.parse().map_err(|err| {
+            ShiError::general(format!("invalid argument {} '{}': {}", index, arg, err))
+        })
+    }
+
+    /// Parses the value of the flag with the given long name into the given type, if it was
+    /// given, e.g. `args.get_value::<u16>("port")?`.
+    ///
+    /// # Arguments
+    /// `long` - The long name of the flag, without its leading dashes.
+    ///
+    /// # Returns
+    /// `Result<Option<T>>` - The parsed value, `None` if the flag was not given, or an error if
+    /// the value could not be parsed.
+    pub fn get_value<T>(&self, long: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value(long)
+            .map(|value| {
+                value.parse().map_err(|err| {
+                    ShiError::general(format!(
+                        "invalid value '{}' for flag '--{}': {}",
+                        value, long, err
+                    ))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Returns whether the given argument looks like a flag, i.e., starts with a dash but isn't a
+/// negative number or a lone dash.
+pub(crate) fn is_flag(arg: &str) -> bool {
+    let mut chars = arg.chars();
+    chars.next() == Some('-') && chars.next().is_some_and(|c| !c.is_ascii_digit())
+}
+
+/// Parses the given arguments into flags and positional arguments, given the flags that may
+/// appear. Flags may appear anywhere among the positional arguments. Short flags that take no
+/// value may be combined, e.g. `-vf`. Everything after a `--` is positional.
+///
+/// # Arguments
+/// `flags` - The flags that may appear.
+/// `args` - The arguments to parse.
+///
+/// # Returns
+/// `Result<ParsedArgs>` - The parsed arguments, or an error if an undeclared flag appears or a
+/// flag is missing its value.
+pub fn parse_args(flags: &[FlagSpec], args: &[String]) -> Result<ParsedArgs> {
+    let mut parsed = ParsedArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.positionals.extend(args.cloned());
+            break;
+        }
+        if !is_flag(arg) {
+            parsed.positionals.push(arg.clone());
+            continue;
+        }
+
+        // Each flag in the argument, e.g. both `v` and `f` in `-vf`, with an inline value if any.
+        let given: Vec<(&FlagSpec, Option<String>)> = if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let spec = flags
+                .iter()
+                .find(|spec| spec.long == name)
+                .ok_or_else(|| ShiError::general(format!("unknown flag '--{}'", name)))?;
+            vec![(spec, value)]
+        } else {
+            let mut given = Vec::new();
+            let shorts = &arg[1..];
+            for (i, short) in shorts.char_indices() {
+                let spec = flags
+                    .iter()
+                    .find(|spec| spec.short == Some(short))
+                    .ok_or_else(|| ShiError::general(format!("unknown flag '-{}'", short)))?;
+                let rest = &shorts[i + short.len_utf8()..];
+                if spec.takes_value && !rest.is_empty() {
+                    given.push((spec, Some(rest.to_string())));
+                    break;
+                }
+                given.push((spec, None));
+            }
+            given
+        };
+
+        for (spec, value) in given {
+            let value = match (spec.takes_value, value) {
+                (true, Some(value)) => Some(value),
+                (true, None) => Some(args.next().cloned().ok_or_else(|| {
+                    ShiError::general(format!("flag '--{}' expects a value", spec.long))
+                })?),
+                (false, Some(_)) => {
+                    return Err(ShiError::general(format!(
+                        "flag '--{}' does not take a value",
+                        spec.long
+                    )))
+                }
+                (false, None) => None,
+            };
+            parsed.flags.insert(spec.long.clone(), value);
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn specs() -> Vec<ArgSpec> {
+        vec![
+            ArgSpec::new().with_source(|| vec![String::from("alpha"), String::from("beta")]),
+            ArgSpec::new(),
+            ArgSpec::new().with_values(&["80", "8080", "443"]),
+        ]
+    }
+
+    #[test]
+    fn next_arg() {
+        let specs = specs();
+
+        assert_eq!(
+            complete_positional(&specs, vec![], true),
+            Completion::Possibilities(vec![String::from("alpha"), String::from("beta")])
+        );
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha", "x"], true),
+            Completion::Possibilities(vec![
+                String::from("80"),
+                String::from("8080"),
+                String::from("443")
+            ])
+        );
+        // Arguments without candidates, or without specs at all, complete to nothing.
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha"], true),
+            Completion::Nothing
+        );
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha", "x", "80"], true),
+            Completion::Nothing
+        );
+    }
+
+    #[test]
+    fn partial_arg() {
+        let specs = specs();
+
+        assert_eq!(
+            complete_positional(&specs, vec!["al"], false),
+            Completion::PartialArgCompletion(vec![String::from("pha")])
+        );
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha", "x", "80"], false),
+            Completion::PartialArgCompletion(vec![String::from("80")])
+        );
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha", "x"], false),
+            Completion::Nothing
+        );
+        assert_eq!(
+            complete_positional(&specs, vec!["alpha", "x", "80", "1"], false),
+            Completion::Nothing
+        );
+    }
+
+    #[test]
+    fn choices() {
+        let spec = ArgSpec::new().with_choices(&["debug", "info"]);
+        assert!(spec.validate("info").is_ok());
+        assert_eq!(
+            spec.validate("INFO").unwrap_err().to_string(),
+            "error: invalid value 'INFO', expected one of: debug, info"
+        );
+        assert_eq!(
+            spec.complete("d"),
+            Completion::PartialArgCompletion(vec![String::from("ebug")])
+        );
+        assert_eq!(spec.complete("D"), Completion::Nothing);
+
+        // Plain values are completed, but never rejected.
+        assert!(ArgSpec::new().with_values(&["80"]).validate("81").is_ok());
+    }
+
+    #[test]
+    fn choices_ignore_case() {
+        let spec = ArgSpec::new()
+            .with_choices(&["debug", "Info"])
+            .ignore_case();
+        assert!(spec.validate("INFO").is_ok());
+        assert!(spec.validate("debug").is_ok());
+        assert!(spec.validate("trace").is_err());
+        assert_eq!(
+            spec.complete("I"),
+            Completion::PartialArgCompletion(vec![String::from("nfo")])
+        );
+        assert_eq!(
+            spec.complete("DE"),
+            Completion::PartialArgCompletion(vec![String::from("bug")])
+        );
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn flags() -> Vec<FlagSpec> {
+        vec![
+            FlagSpec::new("force").with_short('f'),
+            FlagSpec::new("verbose").with_short('v'),
+            FlagSpec::new("output").with_short('o').takes_value(),
+        ]
+    }
+
+    #[test]
+    fn parses_flags() -> Result<()> {
+        let parsed = parse_args(&flags(), &args(&["a", "--force", "-o", "out", "b", "-5"]))?;
+        assert_eq!(parsed.positionals(), &args(&["a", "b", "-5"])[..]);
+        assert!(parsed.flag("force"));
+        assert!(!parsed.flag("verbose"));
+        assert_eq!(parsed.value("output"), Some("out"));
+        assert_eq!(parsed.value("force"), None);
+
+        let parsed = parse_args(&flags(), &args(&["-vfoout", "--output=x", "--", "--force"]))?;
+        assert!(parsed.flag("verbose") && parsed.flag("force"));
+        assert_eq!(parsed.value("output"), Some("x"));
+        assert_eq!(parsed.positional(0), Some("--force"));
+        assert_eq!(parsed.positional(1), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn typed_args() -> Result<()> {
+        let parsed = parse_args(&flags(), &args(&["3", "x", "-o", "8080"]))?;
+        assert_eq!(parsed.get::<u32>(0)?, 3);
+        assert_eq!(parsed.get::<String>(1)?, "x");
+        assert_eq!(parsed.get_value::<u16>("output")?, Some(8080));
+        assert_eq!(parsed.get_value::<u16>("force")?, None);
+
+        assert_eq!(
+            parsed.get::<u32>(1).unwrap_err().to_string(),
+            "error: invalid argument 1 'x': invalid digit found in string"
+        );
+        assert_eq!(
+            parsed.get::<u32>(2).unwrap_err().to_string(),
+            "error: missing argument 2"
+        );
+        assert_eq!(
+            parsed.get_value::<u8>("output").unwrap_err().to_string(),
+            "error: invalid value '8080' for flag '--output': number too large to fit in target type"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn flag_errors() {
+        let err = |a: &[&str]| parse_args(&flags(), &args(a)).unwrap_err().to_string();
+
+        assert_eq!(err(&["--froce"]), "error: unknown flag '--froce'");
+        assert_eq!(err(&["-fx"]), "error: unknown flag '-x'");
+        assert_eq!(err(&["--output"]), "error: flag '--output' expects a value");
+        assert_eq!(
+            err(&["--force=yes"]),
+            "error: flag '--force' does not take a value"
+        );
+    }
+
+    #[test]
+    fn flag_usage() {
+        assert_eq!(FlagSpec::new("force").usage(), "[--force]");
+        assert_eq!(
+            FlagSpec::new("output")
+                .with_short('o')
+                .takes_value()
+                .usage(),
+            "[-o|--output <value>]"
+        );
+    }
+
+    #[test]
+    fn first_unquoted() -> Result<()> {
+        let split = |a: &[&str]| -> Result<(String, Vec<String>)> {
+            let given = args(a);
+            let (first, rest) = split_first_unquoted(&given)?;
+            Ok((first, rest.to_vec()))
+        };
+
+        assert_eq!(
+            split(&["dog", "pets"])?,
+            (String::from("dog"), args(&["pets"]))
+        );
+        assert_eq!(
+            split(&["'cat'", "pets"])?,
+            (String::from("cat"), args(&["pets"]))
+        );
+        assert_eq!(
+            split(&["\"dog", "woof\"", "pets"])?,
+            (String::from("dog woof"), args(&["pets"]))
+        );
+        assert_eq!(split(&["'", "'", "2"])?, (String::from(" "), args(&["2"])));
+        assert!(split(&["'dog", "pets"]).is_err());
+        assert!(split(&[]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+//! Commands that execute asynchronously, e.g. because they await calls to a network API.
+//!
+//! An `AsyncBaseCommand` is registered with a shell by wrapping it in an `AsyncCommand`, which
+//! drives its future to completion with an executor. By default, that is `block_on()`, which
+//! suffices for futures that do not depend on a particular runtime. Futures that do, e.g. those of
+//! tokio-based HTTP clients, should be driven by that runtime instead:
+//!
+//! ```ignore
+//! let runtime = Rc::new(tokio::runtime::Runtime::new()?);
+//! shell.register(Command::new_leaf(AsyncCommand::new_with_executor(
+//!     FetchCommand::new(),
+//!     move |fut| runtime.block_on(fut),
+//! )))?;
+//! ```
+//!
+//! Only commands are asynchronous; the shell is not. There is no async counterpart of
+//! `Shell::eval()` or `Shell::run()`, so the executor blocks the thread, and with it the prompt,
+//! until the command's future completes. Commands are thus still executed one at a time.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+use super::{BaseCommand, Completion};
+use crate::Result;
+
+/// The future returned by `AsyncBaseCommand::execute()`.
+pub type BoxFuture<'f> = Pin<Box<dyn Future<Output = Result<String>> + 'f>>;
+
+/// Drives the future of an `AsyncBaseCommand` to completion. See `AsyncCommand`.
+pub type Executor = Rc<dyn Fn(BoxFuture<'_>) -> Result<String>>;
+
+/// AsyncBaseCommand is the asynchronous counterpart of `BaseCommand`, for commands whose
+/// execution awaits, e.g. network calls.
+///
+/// Since futures returned from traits must be boxed to be used through trait objects, `execute()`
+/// returns a `BoxFuture`, which is most easily made with `Box::pin(async move { ... })`.
+pub trait AsyncBaseCommand {
+    /// The State of the command. Expected to be bound to a containing `Shell`.
+    type State;
+
+    /// Returns the name of the command. This is equivalent to how the command would be invoked.
+    fn name(&self) -> &str;
+
+    /// Validates the given arguments, returning a `Result<()>` indicating the result of
+    /// validation.
+    ///
+    /// # Arguments
+    /// `args` - The arguments to validate.
+    fn validate_args(&self, args: &[String]) -> Result<()>;
+
+    /// Executes the command.
+    ///
+    /// # Arguments
+    /// `state` - The state to execute with.
+    /// `args` - The arguments to the command invocation.
+    ///
+    /// # Returns
+    /// `BoxFuture` - A future of the result of the execution of this command.
+    fn execute<'f>(&'f self, state: &'f mut Self::State, args: &'f [String]) -> BoxFuture<'f>;
+
+    /// Autocompletes a command, given arguments. See `BaseCommand::autocomplete()`.
+    ///
+    /// The default implementation provides no autocompletion.
+    fn autocomplete(&self, _args: Vec<&str>, _trailing_space: bool) -> Completion {
+        Completion::Nothing
+    }
+
+    /// Returns a String representing the help text of this command.
+    /// By default, returns nothing.
+    fn help(&self) -> String {
+        "".to_string()
+    }
+
+    /// Returns the version in which this command was added, if known. See `BaseCommand::since()`.
+    /// By default, returns None.
+    fn since(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Wakes a thread that is blocked in `block_on()`.
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drives the given future to completion on the current thread, parking it while the future is
+/// pending.
+///
+/// This is the default executor of `AsyncCommand`. It does not provide a runtime, e.g. timers or
+/// IO, so futures that need one should be driven by their runtime instead.
+///
+/// # Arguments
+/// `fut` - The future to drive.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = Box::pin(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// AsyncCommand adapts an `AsyncBaseCommand` into a `BaseCommand`, so that it can be registered
+/// with a shell like any other command. Its future is driven to completion by an executor when it
+/// executes, blocking the shell until it does.
+pub struct AsyncCommand<C> {
+    cmd: C,
+    executor: Executor,
+}
+
+impl<C: AsyncBaseCommand> AsyncCommand<C> {
+    /// Creates a new AsyncCommand, which drives the given command's future with `block_on()`.
+    ///
+    /// # Arguments
+    /// `cmd` - The command to adapt.
+    pub fn new(cmd: C) -> AsyncCommand<C> {
+        AsyncCommand::new_with_executor(cmd, |fut| block_on(fut))
+    }
+
+    /// Creates a new AsyncCommand, which drives the given command's future with the given
+    /// executor, e.g. `tokio::runtime::Runtime::block_on()`.
+    ///
+    /// # Arguments
+    /// `cmd` - The command to adapt.
+    /// `executor` - The executor to drive the command's future with.
+    pub fn new_with_executor<F>(cmd: C, executor: F) -> AsyncCommand<C>
+    where
+        F: Fn(BoxFuture<'_>) -> Result<String> + 'static,
+    {
+        AsyncCommand {
+            cmd,
+            executor: Rc::new(executor),
+        }
+    }
+}
+
+impl<C: AsyncBaseCommand> BaseCommand for AsyncCommand<C> {
+    type State = C::State;
+
+    fn name(&self) -> &str {
+        self.cmd.name()
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        self.cmd.validate_args(args)
+    }
+
+    fn execute(&self, state: &mut Self::State, args: &[String]) -> Result<String> {
+        (self.executor)(self.cmd.execute(state, args))
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        self.cmd.autocomplete(args, trailing_space)
+    }
+
+    fn help(&self) -> String {
+        self.cmd.help()
+    }
+
+    fn since(&self) -> Option<String> {
+        self.cmd.since()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+
+    use crate::command::Command;
+    use crate::shell::Shell;
+
+    use pretty_assertions::assert_eq;
+
+    /// A future that is pending once, waking itself from another thread.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+
+            self.0 = true;
+            let waker = cx.waker().clone();
+            thread::spawn(move || waker.wake());
+            Poll::Pending
+        }
+    }
+
+    struct FetchCommand;
+
+    impl AsyncBaseCommand for FetchCommand {
+        type State = Vec<String>;
+
+        fn name(&self) -> &str {
+            "fetch"
+        }
+
+        fn validate_args(&self, _: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        fn execute<'f>(&'f self, state: &'f mut Vec<String>, args: &'f [String]) -> BoxFuture<'f> {
+            Box::pin(async move {
+                YieldOnce(false).await;
+                state.extend_from_slice(args);
+                Ok(format!("fetched {}", args.join(" ")))
+            })
+        }
+    }
+
+    #[test]
+    fn executes_async_commands() -> Result<()> {
+        let mut shell = Shell::new_with_state("| ", Vec::new());
+        shell.register(Command::new_leaf(AsyncCommand::new(FetchCommand)))?;
+
+        assert_eq!(shell.eval("fetch a b")?, "fetched a b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn uses_the_given_executor() -> Result<()> {
+        let executions = Rc::new(Cell::new(0));
+        let counter = executions.clone();
+        let mut shell = Shell::new_with_state("| ", Vec::new());
+        shell.register(Command::new_leaf(AsyncCommand::new_with_executor(
+            FetchCommand,
+            move |fut| {
+                counter.set(counter.get() + 1);
+                block_on(fut)
+            },
+        )))?;
+
+        assert_eq!(shell.eval("fetch c")?, "fetched c");
+        assert_eq!(executions.get(), 1);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,58 @@
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 use std::rc::Rc;
 
-use super::BaseCommand;
+use super::args::is_flag;
+use super::{
+    complete_positional, parse_args, ArgSpec, BaseCommand, Completion, FlagSpec, ParsedArgs,
+};
+use crate::error::ShiError;
 use crate::Result;
 
+/// Executes a `BasicCommand` on its raw arguments.
+type RawExec<S> = Rc<dyn Fn(&mut S, &[String]) -> Result<String>>;
+
+/// Executes a `BasicCommand` on its parsed arguments.
+type ParsedExec<S> = Rc<dyn Fn(&mut S, &ParsedArgs) -> Result<String>>;
+
+/// The closure of a `BasicCommand`, which takes either the raw arguments or the parsed ones.
+enum Exec<S> {
+    Raw(RawExec<S>),
+    Parsed(ParsedExec<S>),
+}
+
+/// Completes the arguments of a `BasicCommand`, like `BaseCommand::autocomplete()`.
+type Completer = Rc<dyn Fn(Vec<&str>, bool) -> Completion>;
+
+/// The metadata of a `BasicCommand` beyond its name, help and arguments. See
+/// `BasicCommandBuilder`.
+#[derive(Default)]
+struct Metadata<'a> {
+    long_help: Option<&'a str>,
+    usage: Option<&'a str>,
+    // The minimum and, if any, maximum number of positional arguments.
+    arity: Option<(usize, Option<usize>)>,
+    completer: Option<Completer>,
+    hidden: bool,
+    category: Option<&'a str>,
+    confirmation: Option<&'a str>,
+}
+
 /// A BasicCommand is a very simple command type. It has a name, and it has a closure that it
 /// executes when it is invoked. The closure takes a state, as determined by its containing shell,
 /// and a vector of String arguments.
+///
+/// Alternatively, via `new_parsed()`, the closure takes the arguments as `ParsedArgs`, parsed
+/// according to the flags declared via `add_flag()`. Such commands reject undeclared flags and
+/// flags missing their values before they are executed.
 pub struct BasicCommand<'a, S> {
     name: &'a str,
     help: &'a str,
-    exec: Rc<dyn Fn(&mut S, &[String]) -> Result<String>>,
+    since: Option<&'a str>,
+    args: Vec<ArgSpec>,
+    flags: Vec<FlagSpec>,
+    meta: Metadata<'a>,
+    exec: Exec<S>,
 }
 
 impl<'a, S> BasicCommand<'a, S> {
@@ -22,11 +65,7 @@ impl<'a, S> BasicCommand<'a, S> {
     where
         F: Fn(&mut S, &[String]) -> Result<String> + 'static,
     {
-        BasicCommand {
-            name,
-            help: "",
-            exec: Rc::new(exec),
-        }
+        BasicCommand::new_with_help(name, "", exec)
     }
 
     /// Creates a new BasicCommand with the given name, closure and help message.
@@ -42,9 +81,295 @@ impl<'a, S> BasicCommand<'a, S> {
         BasicCommand {
             name,
             help,
-            exec: Rc::new(exec),
+            since: None,
+            args: Vec::new(),
+            flags: Vec::new(),
+            meta: Metadata::default(),
+            exec: Exec::Raw(Rc::new(exec)),
         }
     }
+
+    /// Creates a new BasicCommand with the given name and a closure that takes parsed arguments.
+    ///
+    /// ```
+    /// # use shi::command::{BasicCommand, FlagSpec, ParsedArgs};
+    /// let mut deploy = BasicCommand::new_parsed("deploy", |_: &mut (), args: &ParsedArgs| {
+    ///     let dry_run = if args.flag("dry-run") { " (dry run)" } else { "" };
+    ///     Ok(format!("deploying {}{}", args.positional(0).unwrap_or("everything"), dry_run))
+    /// });
+    /// deploy.add_flag(FlagSpec::new("dry-run").with_short('n'));
+    /// ```
+    ///
+    /// # Arguments
+    /// * `name` - The name of the command. This is how users will execute the command.
+    /// * `exec` - The closure that will be executed when this command is invoked.
+    pub fn new_parsed<F>(name: &'a str, exec: F) -> BasicCommand<'a, S>
+    where
+        F: Fn(&mut S, &ParsedArgs) -> Result<String> + 'static,
+    {
+        BasicCommand {
+            name,
+            help: "",
+            since: None,
+            args: Vec::new(),
+            flags: Vec::new(),
+            meta: Metadata::default(),
+            exec: Exec::Parsed(Rc::new(exec)),
+        }
+    }
+
+    /// Creates a builder for a BasicCommand with the given name, e.g. for declaring its signature
+    /// and the rest of its metadata in one place:
+    ///
+    /// ```
+    /// # use shi::command::{ArgSpec, BasicCommand};
+    /// let serve = BasicCommand::builder("serve")
+    ///     .help("Serves the site")
+    ///     .long_help("Serves the site on the given port, until interrupted.")
+    ///     .category("site")
+    ///     .arg(ArgSpec::required("port").parses_as::<u16>())
+    ///     .build(|_: &mut (), args: &[String]| Ok(format!("serving on {}", args[0])));
+    /// ```
+    ///
+    /// # Arguments
+    /// * `name` - The name of the command. This is how users will execute the command.
+    pub fn builder(name: &'a str) -> BasicCommandBuilder<'a, S> {
+        BasicCommandBuilder {
+            name,
+            help: "",
+            since: None,
+            args: Vec::new(),
+            flags: Vec::new(),
+            meta: Metadata::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the help message of this command.
+    ///
+    /// # Arguments
+    /// * `help` - The new help message to use.
+    pub fn set_help(&mut self, help: &'a str) {
+        self.help = help;
+    }
+
+    /// Sets the version in which this command was added. See `BaseCommand::since()`.
+    ///
+    /// # Arguments
+    /// * `since` - The version, e.g. `1.2.0`.
+    pub fn set_since(&mut self, since: &'a str) {
+        self.since = Some(since);
+    }
+
+    /// Sets the spec of the positional argument at the given index, which is used to complete it.
+    /// Arguments without a spec complete to nothing.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the argument, starting from 0.
+    /// * `spec` - The spec of the argument.
+    pub fn set_arg(&mut self, index: usize, spec: ArgSpec) {
+        if self.args.len() <= index {
+            self.args.resize_with(index + 1, ArgSpec::default);
+        }
+        self.args[index] = spec;
+    }
+
+    /// Declares the next positional argument of this command, after those declared so far. See
+    /// `ArgSpec::required()` and `ArgSpec::optional()` for declaring a signature.
+    ///
+    /// # Arguments
+    /// * `spec` - The spec of the argument.
+    pub fn add_arg(&mut self, spec: ArgSpec) {
+        self.args.push(spec);
+    }
+
+    /// Returns the specs of the positional arguments of this command, in order.
+    pub fn args(&self) -> &[ArgSpec] {
+        &self.args
+    }
+
+    /// Declares a flag that this command accepts, e.g. `--force`. Flags may appear anywhere among
+    /// the positional arguments. Commands created via `new_parsed()` always reject undeclared
+    /// flags, while other commands only do so in shells in strict mode.
+    ///
+    /// # Arguments
+    /// * `flag` - The spec of the flag.
+    pub fn add_flag(&mut self, flag: FlagSpec) {
+        self.flags.push(flag);
+    }
+
+    /// Returns the usage of this command, e.g. `connect <host> [port] [--force]`. Positional
+    /// arguments without a name are shown by their index, and optional ones in brackets. A usage
+    /// set via `BasicCommandBuilder::usage()` is returned as is.
+    pub fn usage(&self) -> String {
+        if let Some(usage) = self.meta.usage {
+            return usage.to_string();
+        }
+
+        let args = self.args.iter().enumerate().map(|(i, spec)| {
+            let ellipsis = if spec.is_rest_of_line() { "..." } else { "" };
+            match (spec.name(), spec.is_required()) {
+                (Some(name), Some(false)) => format!("[{}{}]", name, ellipsis),
+                (Some(name), _) => format!("<{}{}>", name, ellipsis),
+                (None, _) => format!("<arg{}{}>", i, ellipsis),
+            }
+        });
+        let flags = self.flags.iter().map(FlagSpec::usage);
+
+        std::iter::once(self.name.to_string())
+            .chain(args)
+            .chain(flags)
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn usage_error(&self, msg: String) -> ShiError {
+        ShiError::general(format!("{}\nusage: {}", msg, self.usage()))
+    }
+
+    /// Returns whether any positional argument was declared as part of a signature, via
+    /// `ArgSpec::required()` or `ArgSpec::optional()`.
+    fn has_signature(&self) -> bool {
+        self.args.iter().any(|spec| spec.is_required().is_some())
+    }
+
+    /// Checks the number of the given positional arguments against the arity and the signature,
+    /// if there are any.
+    fn check_arity<T: AsRef<str>>(&self, positionals: &[T]) -> Result<()> {
+        if let Some((min, max)) = self.meta.arity {
+            if positionals.len() < min {
+                return Err(
+                    self.usage_error(format!("too few arguments, expected at least {}", min))
+                );
+            }
+            if let Some(max) = max.filter(|max| positionals.len() > *max) {
+                return Err(
+                    self.usage_error(format!("too many arguments, expected at most {}", max))
+                );
+            }
+        }
+
+        if !self.has_signature() {
+            return Ok(());
+        }
+
+        if let Some(missing) = self
+            .args
+            .iter()
+            .skip(positionals.len())
+            .find(|spec| spec.is_required() == Some(true))
+        {
+            return Err(self.usage_error(format!(
+                "missing argument <{}>",
+                missing.name().unwrap_or_default()
+            )));
+        }
+        if positionals.len() > self.args.len() {
+            let extra: Vec<&str> = positionals[self.args.len()..]
+                .iter()
+                .map(AsRef::as_ref)
+                .collect();
+            return Err(self.usage_error(format!("unexpected arguments {:?}", extra)));
+        }
+
+        Ok(())
+    }
+
+    /// Parses the given arguments according to the declared flags, and validates the positional
+    /// arguments against their specs, reporting any problem as a usage error.
+    fn parse(&self, args: &[String]) -> Result<ParsedArgs> {
+        let parsed = match parse_args(&self.flags, args) {
+            Ok(parsed) => parsed,
+            Err(ShiError::General { msg }) => return Err(self.usage_error(msg)),
+            Err(err) => return Err(err),
+        };
+
+        for (arg, spec) in parsed.positionals().iter().zip(&self.args) {
+            if let Err(ShiError::General { msg }) = spec.validate(arg) {
+                return Err(self.usage_error(msg));
+            }
+        }
+        self.check_arity(parsed.positionals())?;
+
+        Ok(parsed)
+    }
+
+    /// Gathers the given arguments from where the rest-of-line argument starts, if one was declared
+    /// and given, into that one argument. With the default tokenizer, arguments are split by single
+    /// spaces, so joining them back together restores the spacing they were typed with. The empty
+    /// arguments left by extra spaces before it are dropped.
+    fn gather_rest(&self, args: &[String]) -> Vec<String> {
+        let fixed = match self.args.last() {
+            Some(spec) if spec.is_rest_of_line() => self.args.len() - 1,
+            _ => return args.to_vec(),
+        };
+
+        let mut seen = 0;
+        let mut dashes = false;
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            if arg == "--" && !dashes {
+                // Everything after a `--` is positional.
+                dashes = true;
+                i += 1;
+                continue;
+            }
+            if is_flag(arg) && !dashes {
+                // Skip the value of a long flag that takes one, unless it was given inline.
+                let takes_value = arg
+                    .strip_prefix("--")
+                    .and_then(|long| self.flags.iter().find(|flag| flag.long() == long))
+                    .is_some_and(FlagSpec::expects_value);
+                i += if takes_value { 2 } else { 1 };
+                continue;
+            }
+            // Empty arguments are just the extra spaces between the others.
+            if !arg.is_empty() {
+                if seen == fixed {
+                    let mut gathered: Vec<String> = args[..i]
+                        .iter()
+                        .filter(|arg| !arg.is_empty())
+                        .cloned()
+                        .collect();
+                    gathered.push(args[i..].join(" "));
+                    return gathered;
+                }
+                seen += 1;
+            }
+            i += 1;
+        }
+
+        args.to_vec()
+    }
+
+    /// Returns the positional arguments among the given ones, as far as can be told from the
+    /// declared flags. Used for completion, where the arguments may be partial.
+    fn positionals<'s>(&self, args: Vec<&'s str>) -> Vec<&'s str> {
+        let mut positionals = Vec::with_capacity(args.len());
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positionals.extend(args);
+                break;
+            }
+            if !is_flag(arg) {
+                positionals.push(arg);
+                continue;
+            }
+
+            // Skip the value of a long flag that takes one, unless it was given inline.
+            let takes_value = arg
+                .strip_prefix("--")
+                .and_then(|long| self.flags.iter().find(|flag| flag.long() == long))
+                .is_some_and(FlagSpec::expects_value);
+            if takes_value {
+                args.next();
+            }
+        }
+
+        positionals
+    }
 }
 
 impl<'a, S> BaseCommand for BasicCommand<'a, S> {
@@ -54,15 +379,540 @@ impl<'a, S> BaseCommand for BasicCommand<'a, S> {
         self.name
     }
 
-    fn validate_args(&self, _: &[String]) -> Result<()> {
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let args = &self.gather_rest(args)[..];
+        if let Exec::Parsed(_) = self.exec {
+            return self.parse(args).map(|_| ());
+        }
+
+        if self.has_signature() {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let positionals = self.positionals(args);
+            for (arg, spec) in positionals.iter().zip(&self.args) {
+                if let Err(ShiError::General { msg }) = spec.validate(arg) {
+                    return Err(self.usage_error(msg));
+                }
+            }
+            return self.check_arity(&positionals);
+        }
+
+        for (arg, spec) in args.iter().zip(&self.args) {
+            spec.validate(arg)?;
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.check_arity(&self.positionals(args))
+    }
+
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        // Commands that declare nothing can't tell what they'd be rejecting.
+        if self.args.is_empty() && self.flags.is_empty() {
+            return self.validate_args(args);
+        }
+
+        let args = &self.gather_rest(args)[..];
+        let parsed = self.parse(args)?;
+        if parsed.positionals().len() > self.args.len() {
+            let extra = &parsed.positionals()[self.args.len()..];
+            return Err(self.usage_error(format!("unexpected arguments {:?}", extra)));
+        }
+
         Ok(())
     }
 
     fn execute(&self, state: &mut S, args: &[String]) -> Result<String> {
-        (self.exec)(state, args)
+        let args = &self.gather_rest(args)[..];
+        match &self.exec {
+            Exec::Raw(exec) => exec(state, args),
+            Exec::Parsed(exec) => exec(state, &self.parse(args)?),
+        }
     }
 
     fn help(&self) -> String {
-        self.help.to_string()
+        if !self.has_signature() && self.meta.usage.is_none() {
+            return self.help.to_string();
+        }
+
+        if self.help.is_empty() {
+            format!("usage: {}", self.usage())
+        } else {
+            format!("{} (usage: {})", self.help, self.usage())
+        }
+    }
+
+    fn since(&self) -> Option<String> {
+        self.since.map(String::from)
+    }
+
+    fn long_help(&self) -> Option<String> {
+        self.meta.long_help.map(String::from)
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.meta.hidden
+    }
+
+    fn category(&self) -> Option<String> {
+        self.meta.category.map(String::from)
+    }
+
+    fn confirmation(&self) -> Option<String> {
+        self.meta.confirmation.map(String::from)
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        if let (Some(partial), false) = (args.last(), trailing_space) {
+            if partial.starts_with("--") {
+                let flags = self
+                    .flags
+                    .iter()
+                    .map(|flag| format!("--{}", flag.long()))
+                    .collect();
+                return Completion::from_partial(partial, flags);
+            }
+        }
+
+        if let Some(completer) = &self.meta.completer {
+            return completer(args, trailing_space);
+        }
+
+        complete_positional(&self.args, self.positionals(args), trailing_space)
+    }
+}
+
+/// BasicCommandBuilder builds a `BasicCommand`, declaring its help message, arguments, flags and
+/// other metadata up front, so that commands built from closures can have everything that one
+/// implementing `BaseCommand` could. See `BasicCommand::builder()`.
+pub struct BasicCommandBuilder<'a, S> {
+    name: &'a str,
+    help: &'a str,
+    since: Option<&'a str>,
+    args: Vec<ArgSpec>,
+    flags: Vec<FlagSpec>,
+    meta: Metadata<'a>,
+    phantom: PhantomData<S>,
+}
+
+impl<'a, S> BasicCommandBuilder<'a, S> {
+    /// Sets the help message of the command.
+    ///
+    /// # Arguments
+    /// * `help` - The help message to use.
+    pub fn help(mut self, help: &'a str) -> Self {
+        self.help = help;
+        self
+    }
+
+    /// Sets the version in which the command was added. See `BaseCommand::since()`.
+    ///
+    /// # Arguments
+    /// * `since` - The version, e.g. `1.2.0`.
+    pub fn since(mut self, since: &'a str) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Sets the longer description of the command. See `BaseCommand::long_help()`.
+    ///
+    /// # Arguments
+    /// * `long_help` - The longer description, e.g. with examples.
+    pub fn long_help(mut self, long_help: &'a str) -> Self {
+        self.meta.long_help = Some(long_help);
+        self
+    }
+
+    /// Sets the usage of the command, in place of the one generated from its arguments and
+    /// flags. See `BasicCommand::usage()`.
+    ///
+    /// # Arguments
+    /// * `usage` - The usage, e.g. `copy <from>... <to>`.
+    pub fn usage(mut self, usage: &'a str) -> Self {
+        self.meta.usage = Some(usage);
+        self
+    }
+
+    /// Sets how many positional arguments the command takes, e.g. `1..=2`, or `1..` for at least
+    /// one. Commands given more or fewer fail with a usage error before they are executed.
+    ///
+    /// # Arguments
+    /// * `arity` - The range of the number of positional arguments.
+    pub fn arity<R: RangeBounds<usize>>(mut self, arity: R) -> Self {
+        let min = match arity.start_bound() {
+            Bound::Included(&min) => min,
+            Bound::Excluded(&min) => min + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match arity.end_bound() {
+            Bound::Included(&max) => Some(max),
+            Bound::Excluded(&max) => Some(max.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        self.meta.arity = Some((min, max));
+        self
+    }
+
+    /// Sets the closure that completes the arguments of the command, in place of completing them
+    /// from their specs. See `BaseCommand::autocomplete()`.
+    ///
+    /// # Arguments
+    /// * `completer` - Completes the given arguments.
+    pub fn completer<F>(mut self, completer: F) -> Self
+    where
+        F: Fn(Vec<&str>, bool) -> Completion + 'static,
+    {
+        self.meta.completer = Some(Rc::new(completer));
+        self
+    }
+
+    /// Hides the command from `help`, `helptree` and completions. See `BaseCommand::is_hidden()`.
+    pub fn hidden(mut self) -> Self {
+        self.meta.hidden = true;
+        self
+    }
+
+    /// Sets the category under which `help` lists the command. See `BaseCommand::category()`.
+    ///
+    /// # Arguments
+    /// * `category` - The category, e.g. `network`.
+    pub fn category(mut self, category: &'a str) -> Self {
+        self.meta.category = Some(category);
+        self
+    }
+
+    /// Requires the user to confirm before the command runs. See `BaseCommand::confirmation()`.
+    ///
+    /// # Arguments
+    /// * `question` - The question to confirm, e.g. `Delete everything?`.
+    pub fn confirm(mut self, question: &'a str) -> Self {
+        self.meta.confirmation = Some(question);
+        self
+    }
+
+    /// Declares the next positional argument of the command. See `BasicCommand::add_arg()`.
+    ///
+    /// # Arguments
+    /// * `spec` - The spec of the argument.
+    pub fn arg(mut self, spec: ArgSpec) -> Self {
+        self.args.push(spec);
+        self
+    }
+
+    /// Declares a flag that the command accepts. See `BasicCommand::add_flag()`.
+    ///
+    /// # Arguments
+    /// * `flag` - The spec of the flag.
+    pub fn flag(mut self, flag: FlagSpec) -> Self {
+        self.flags.push(flag);
+        self
+    }
+
+    /// Builds the command, with a closure that takes the raw arguments.
+    ///
+    /// # Arguments
+    /// * `exec` - The closure that will be executed when the command is invoked.
+    pub fn build<F>(self, exec: F) -> BasicCommand<'a, S>
+    where
+        F: Fn(&mut S, &[String]) -> Result<String> + 'static,
+    {
+        self.finish(Exec::Raw(Rc::new(exec)))
+    }
+
+    /// Builds the command, with a closure that takes the parsed arguments. See
+    /// `BasicCommand::new_parsed()`.
+    ///
+    /// # Arguments
+    /// * `exec` - The closure that will be executed when the command is invoked.
+    pub fn build_parsed<F>(self, exec: F) -> BasicCommand<'a, S>
+    where
+        F: Fn(&mut S, &ParsedArgs) -> Result<String> + 'static,
+    {
+        self.finish(Exec::Parsed(Rc::new(exec)))
+    }
+
+    fn finish(self, exec: Exec<S>) -> BasicCommand<'a, S> {
+        BasicCommand {
+            name: self.name,
+            help: self.help,
+            since: self.since,
+            args: self.args,
+            flags: self.flags,
+            meta: self.meta,
+            exec,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn arg_completion() {
+        let mut cmd = BasicCommand::new("connect", |_: &mut (), _: &[String]| Ok(String::new()));
+        assert_eq!(cmd.autocomplete(vec![], true), Completion::Nothing);
+
+        cmd.set_arg(1, ArgSpec::new().with_values(&["80", "443"]));
+        assert_eq!(cmd.autocomplete(vec![], true), Completion::Nothing);
+        assert_eq!(
+            cmd.autocomplete(vec!["localhost"], true),
+            Completion::Possibilities(vec![String::from("80"), String::from("443")])
+        );
+        assert_eq!(
+            cmd.autocomplete(vec!["localhost", "4"], false),
+            Completion::PartialArgCompletion(vec![String::from("43")])
+        );
+    }
+
+    #[test]
+    fn builder_metadata() {
+        let cp = BasicCommand::builder("cp")
+            .help("Copies files")
+            .long_help("Copies the given files into the last one, a directory.")
+            .usage("cp <from>... <to>")
+            .arity(2..)
+            .completer(|args, _| match args.len() {
+                0 => Completion::Possibilities(vec![String::from("a.txt")]),
+                _ => Completion::Nothing,
+            })
+            .category("files")
+            .confirm("Overwrite existing files?")
+            .build(|_: &mut (), args: &[String]| Ok(args.join(" ")));
+
+        assert_eq!(cp.usage(), "cp <from>... <to>");
+        assert_eq!(cp.help(), "Copies files (usage: cp <from>... <to>)");
+        assert_eq!(
+            cp.long_help(),
+            Some(String::from(
+                "Copies the given files into the last one, a directory."
+            ))
+        );
+        assert_eq!(cp.category(), Some(String::from("files")));
+        assert_eq!(
+            cp.confirmation(),
+            Some(String::from("Overwrite existing files?"))
+        );
+        assert!(!cp.is_hidden());
+        assert_eq!(
+            cp.autocomplete(vec![], true),
+            Completion::Possibilities(vec![String::from("a.txt")])
+        );
+
+        assert_eq!(
+            cp.validate_args(&[String::from("a")])
+                .unwrap_err()
+                .to_string(),
+            "error: too few arguments, expected at least 2\nusage: cp <from>... <to>"
+        );
+        assert!(cp
+            .validate_args(&[String::from("a"), String::from("b"), String::from("c")])
+            .is_ok());
+
+        let rm = BasicCommand::builder("rm")
+            .arity(..=1)
+            .hidden()
+            .build(|_: &mut (), _: &[String]| Ok(String::new()));
+        assert!(rm.is_hidden());
+        assert_eq!(rm.category(), None);
+        assert!(rm
+            .validate_args(&[String::from("a"), String::from("--force")])
+            .is_ok());
+        assert_eq!(
+            rm.validate_args(&[String::from("a"), String::from("b")])
+                .unwrap_err()
+                .to_string(),
+            "error: too many arguments, expected at most 1\nusage: rm"
+        );
+    }
+
+    #[test]
+    fn arg_validation() {
+        let mut cmd = BasicCommand::new("level", |_: &mut (), _: &[String]| Ok(String::new()));
+        cmd.set_arg(1, ArgSpec::new().with_choices(&["on", "off"]));
+
+        assert!(cmd.validate_args(&[]).is_ok());
+        assert!(cmd.validate_args(&[String::from("anything")]).is_ok());
+        assert!(cmd
+            .validate_args(&[String::from("x"), String::from("on")])
+            .is_ok());
+        assert_eq!(
+            cmd.validate_args(&[String::from("x"), String::from("maybe")])
+                .unwrap_err()
+                .to_string(),
+            "error: invalid value 'maybe', expected one of: on, off"
+        );
+    }
+
+    #[test]
+    fn strict_validation() {
+        let mut cmd = BasicCommand::new("connect", |_: &mut (), _: &[String]| Ok(String::new()));
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        // Without declarations, strictness changes nothing.
+        assert!(cmd.validate_args_strict(&args(&["a", "--b"])).is_ok());
+
+        cmd.set_arg(0, ArgSpec::new().with_name("host"));
+        cmd.set_arg(1, ArgSpec::new().with_choices(&["80", "443"]));
+        cmd.add_flag(FlagSpec::new("force"));
+        assert_eq!(cmd.usage(), "connect <host> <arg1> [--force]");
+
+        assert!(cmd.validate_args_strict(&args(&["localhost"])).is_ok());
+        assert!(cmd
+            .validate_args_strict(&args(&["--force", "localhost", "80"]))
+            .is_ok());
+        assert_eq!(
+            cmd.validate_args_strict(&args(&["localhost", "80", "x"]))
+                .unwrap_err()
+                .to_string(),
+            "error: unexpected arguments [\"x\"]\nusage: connect <host> <arg1> [--force]"
+        );
+        assert_eq!(
+            cmd.validate_args_strict(&args(&["localhost", "--froce"]))
+                .unwrap_err()
+                .to_string(),
+            "error: unknown flag '--froce'\nusage: connect <host> <arg1> [--force]"
+        );
+        assert_eq!(
+            cmd.validate_args_strict(&args(&["localhost", "8080"]))
+                .unwrap_err()
+                .to_string(),
+            "error: invalid value '8080', expected one of: 80, 443\nusage: connect <host> <arg1> \
+            [--force]"
+        );
+
+        // Outside of strict mode, extra arguments are ignored.
+        assert!(cmd.validate_args(&args(&["localhost", "80", "x"])).is_ok());
+    }
+
+    #[test]
+    fn signature() -> Result<()> {
+        let cmd = BasicCommand::builder("connect")
+            .help("Connects to a server")
+            .arg(ArgSpec::required("host"))
+            .arg(ArgSpec::optional("port").parses_as::<u16>())
+            .flag(FlagSpec::new("force"))
+            .build(|_: &mut (), args: &[String]| Ok(args.join(" ")));
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        assert_eq!(
+            cmd.help(),
+            "Connects to a server (usage: connect <host> [port] [--force])"
+        );
+        assert!(cmd.validate_args(&args(&["localhost"])).is_ok());
+        assert!(cmd
+            .validate_args(&args(&["localhost", "--force", "8080"]))
+            .is_ok());
+        assert_eq!(
+            cmd.validate_args(&[]).unwrap_err().to_string(),
+            "error: missing argument <host>\nusage: connect <host> [port] [--force]"
+        );
+        assert_eq!(
+            cmd.validate_args(&args(&["localhost", "http"]))
+                .unwrap_err()
+                .to_string(),
+            "error: invalid value 'http' for <port>: invalid digit found in string\nusage: \
+            connect <host> [port] [--force]"
+        );
+        assert_eq!(
+            cmd.validate_args(&args(&["localhost", "80", "x"]))
+                .unwrap_err()
+                .to_string(),
+            "error: unexpected arguments [\"x\"]\nusage: connect <host> [port] [--force]"
+        );
+
+        let parsed = BasicCommand::builder("ping")
+            .arg(ArgSpec::required("host"))
+            .build_parsed(|_: &mut (), args: &ParsedArgs| Ok(args.positionals().join(" ")));
+        assert_eq!(parsed.help(), "usage: ping <host>");
+        assert!(parsed.validate_args(&[]).is_err());
+        assert_eq!(parsed.execute(&mut (), &args(&["localhost"]))?, "localhost");
+
+        Ok(())
+    }
+
+    #[test]
+    fn rest_of_line() -> Result<()> {
+        let cmd = BasicCommand::builder("note")
+            .arg(ArgSpec::required("title"))
+            .arg(ArgSpec::optional("text").rest_of_line())
+            .flag(FlagSpec::new("tag").takes_value())
+            .build_parsed(|_: &mut (), args: &ParsedArgs| {
+                Ok(format!("{:?} {:?}", args.positionals(), args.value("tag")))
+            });
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        assert_eq!(cmd.help(), "usage: note <title> [text...] [--tag <value>]");
+        assert_eq!(
+            cmd.execute(
+                &mut (),
+                &args(&[
+                    "--tag",
+                    "todo",
+                    "groceries",
+                    "",
+                    "milk",
+                    "",
+                    "",
+                    "--and",
+                    "eggs"
+                ])
+            )?,
+            "[\"groceries\", \"milk   --and eggs\"] Some(\"todo\")"
+        );
+        assert_eq!(
+            cmd.execute(&mut (), &args(&["groceries", "--", "-1", "egg"]))?,
+            "[\"groceries\", \"-1 egg\"] None"
+        );
+        assert_eq!(
+            cmd.execute(&mut (), &args(&["groceries"]))?,
+            "[\"groceries\"] None"
+        );
+        assert!(cmd
+            .validate_args_strict(&args(&["groceries", "milk", "eggs"]))
+            .is_ok());
+        assert!(cmd.validate_args(&[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parsed_args() -> Result<()> {
+        let mut cmd = BasicCommand::new_parsed("deploy", |_: &mut (), args: &ParsedArgs| {
+            Ok(format!(
+                "{:?} {} {:?}",
+                args.positionals(),
+                args.flag("force"),
+                args.value("env")
+            ))
+        });
+        cmd.add_flag(FlagSpec::new("force").with_short('f'));
+        cmd.add_flag(FlagSpec::new("env").takes_value());
+        cmd.set_arg(0, ArgSpec::new().with_values(&["api", "web"]));
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        assert_eq!(
+            cmd.execute(&mut (), &args(&["--env", "prod", "api", "-f"]))?,
+            "[\"api\"] true Some(\"prod\")"
+        );
+        assert_eq!(
+            cmd.validate_args(&args(&["api", "--verbose"]))
+                .unwrap_err()
+                .to_string(),
+            "error: unknown flag '--verbose'\nusage: deploy <arg0> [-f|--force] [--env <value>]"
+        );
+        assert!(cmd.validate_args(&args(&["--env"])).is_err());
+
+        assert_eq!(
+            cmd.autocomplete(vec!["--e"], false),
+            Completion::PartialArgCompletion(args(&["nv"]))
+        );
+        // Flags, and the values of flags, don't count as positional arguments.
+        assert_eq!(
+            cmd.autocomplete(vec!["--env", "prod", "w"], false),
+            Completion::PartialArgCompletion(args(&["eb"]))
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,138 @@
+use std::marker::PhantomData;
+
+use super::{BaseCommand, Completion};
+use crate::completions::ShellKind;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// CompletionsCommand prints a completion script for a host shell, e.g. `completions bash mytool`,
+/// derived from the registered commands. See `Shell::generate_completions()`.
+///
+/// The name of the program may be omitted if the shell was given an `AppInfo`, in which case its
+/// name is used.
+pub struct CompletionsCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for CompletionsCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> CompletionsCommand<'a, S> {
+    /// Creates a new CompletionsCommand.
+    pub fn new() -> CompletionsCommand<'a, S> {
+        CompletionsCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for CompletionsCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "completions"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let kind = match args.first() {
+            Some(kind) => kind,
+            None => return Err(ShiError::NoArgs),
+        };
+        kind.parse::<ShellKind>()?;
+
+        if args.len() > 2 {
+            return Err(ShiError::ExtraArgs {
+                got: args[2..].to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let kind = args[0].parse::<ShellKind>()?;
+        let program = match (args.get(1), shell.app_info()) {
+            (Some(program), _) => program.clone(),
+            (None, Some(info)) => info.name.clone(),
+            (None, None) => {
+                return Err(ShiError::general(
+                    "expected the name of the program to complete",
+                ))
+            }
+        };
+
+        Ok(shell.generate_completions(kind, &program))
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        let kinds = ShellKind::all()
+            .iter()
+            .map(|kind| kind.to_string())
+            .collect();
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(kinds),
+            ([kind], false) => Completion::from_partial(kind, kinds),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints a completion script for bash, zsh or fish")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::command::{BasicCommand, Command};
+    use crate::shell::AppInfo;
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn prints_completions() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            "Manages the server",
+            cmd!("listen", "Listens", |_, _| {
+                Ok(String::from("listening"))
+            }),
+        ))?;
+        // Hidden commands are left out.
+        shell.register(Command::new_leaf(
+            BasicCommand::builder("debug")
+                .hidden()
+                .build(|_, _| Ok(String::new())),
+        ))?;
+
+        assert_eq!(
+            shell.eval("completions fish mytool")?,
+            "function __mytool_at\n    \
+                set -l tokens (commandline -opc)\n    \
+                test \"$tokens[2..-1]\" = \"$argv\"\n\
+            end\n\n\
+            complete -c mytool -f\n\
+            complete -c mytool -n '__mytool_at' -a 'server' -d 'Manages the server'\n\
+            complete -c mytool -n '__mytool_at server' -a 'listen' -d 'Listens'\n"
+        );
+
+        assert!(shell.eval("completions bash").is_err());
+        shell.set_app_info(AppInfo::new("tool", "1.0.0"));
+        assert!(shell
+            .eval("completions bash")?
+            .ends_with("complete -F _tool_completions tool\n"));
+
+        assert!(shell.eval("completions csh mytool").is_err());
+        assert!(shell.eval("completions").is_err());
+
+        Ok(())
+    }
+}
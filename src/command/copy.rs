@@ -0,0 +1,105 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::clipboard;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// CopyCommand runs the command it is given, e.g. `copy server status`, and copies that command's
+/// output to the system clipboard, besides displaying it as usual.
+pub struct CopyCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for CopyCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> CopyCommand<'a, S> {
+    /// Creates a new CopyCommand.
+    pub fn new() -> CopyCommand<'a, S> {
+        CopyCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    /// Runs the command given by the arguments, passing its output to `copy` if it succeeds.
+    fn run<F>(&self, shell: &mut Shell<S>, args: &[String], copy: F) -> Result<String>
+    where
+        F: FnOnce(&str) -> Result<()>,
+    {
+        let output = shell.eval_without_history(&args.join(" "))?;
+        copy(&output)?;
+
+        Ok(output)
+    }
+}
+
+impl<'a, S> BaseCommand for CopyCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "copy"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        self.run(shell, args, clipboard::copy)
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and copies its output to the clipboard")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn copies_output() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+        let copy_cmd = CopyCommand::new();
+
+        let mut copied = None;
+        let output = copy_cmd.run(
+            &mut shell,
+            &[String::from("echo"), String::from("hi")],
+            |output| {
+                copied = Some(output.to_string());
+                Ok(())
+            },
+        )?;
+        assert_eq!(output, "hi");
+        assert_eq!(copied, Some(String::from("hi")));
+
+        // Nothing is copied if the command fails.
+        let mut copied = false;
+        assert!(copy_cmd
+            .run(&mut shell, &[String::from("DNE")], |_| {
+                copied = true;
+                Ok(())
+            })
+            .is_err());
+        assert!(!copied);
+        assert!(copy_cmd.validate_args(&[]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::prompt::Prompter;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// DemoCommand steps through a script of annotated commands, e.g. to onboard new users.
+///
+/// Each line of the script is either a command, or a `#` comment annotating the command after it.
+/// Every command is shown along with its annotations, and is executed once the user presses Enter.
+/// The user may quit the demo early by entering `q` instead.
+pub struct DemoCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for DemoCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> DemoCommand<'a, S> {
+    /// Creates a new DemoCommand.
+    pub fn new() -> DemoCommand<'a, S> {
+        DemoCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    /// Runs the given demo script in the given shell, prompting via the given prompter.
+    fn run_demo<R: BufRead, W: Write>(
+        &self,
+        shell: &mut Shell<S>,
+        script: &str,
+        prompter: &mut Prompter<R, W>,
+    ) -> Result<String> {
+        let mut annotations = Vec::new();
+        for line in script.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(annotation) = line.strip_prefix('#') {
+                annotations.push(annotation.trim().to_string());
+                continue;
+            }
+
+            for annotation in annotations.drain(..) {
+                prompter.println(&format!("# {}", annotation))?;
+            }
+            prompter.println(&format!("{}{}", shell.prompt, line))?;
+
+            let response = prompter.read_line("(press Enter to run, or 'q' to quit) ")?;
+            if response.trim() == "q" {
+                return Ok(String::from("demo stopped"));
+            }
+
+            match shell.eval_without_history(line) {
+                Ok(output) => prompter.println(&output)?,
+                Err(err) => prompter.println(&format!("{} {}", shell.error_prefix(), err))?,
+            }
+        }
+
+        Ok(String::from("demo finished"))
+    }
+}
+
+impl<'a, S> BaseCommand for DemoCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "demo"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args.len() {
+            0 => Err(ShiError::NoArgs),
+            1 => Ok(()),
+            _ => Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            }),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let script = fs::read_to_string(&args[0])?;
+        self.run_demo(shell, &script, &mut Prompter::stdio())
+    }
+
+    fn help(&self) -> String {
+        String::from("Steps through a script of annotated commands")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    const SCRIPT: &str = "\
+        # Dogs bark.\n\
+        dog\n\
+        \n\
+        # Cats don't exist.\n\
+        # This one fails.\n\
+        cat\n\
+        dog\n";
+
+    fn make_shell<'a>() -> Result<Shell<'a, ()>> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+
+        Ok(shell)
+    }
+
+    #[test]
+    fn demo() -> Result<()> {
+        let mut shell = make_shell()?;
+        let mut out = Vec::new();
+        let mut prompter = Prompter::new("\n\n\n".as_bytes(), &mut out);
+
+        let output = DemoCommand::new().run_demo(&mut shell, SCRIPT, &mut prompter)?;
+        assert_eq!(output, "demo finished");
+
+        let transcript = String::from_utf8(out).unwrap();
+        assert!(transcript.starts_with(
+            "# Dogs bark.\n| dog\n(press Enter to run, or 'q' to quit) woof\n# Cats don't \
+            exist.\n# This one fails.\n| cat\n(press Enter to run, or 'q' to quit) Error: "
+        ));
+        assert!(transcript.ends_with("| dog\n(press Enter to run, or 'q' to quit) woof\n"));
+        // Demo commands are not part of the user's history.
+        assert_eq!(shell.history_len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn quit() -> Result<()> {
+        let mut shell = make_shell()?;
+        let mut out = Vec::new();
+        let mut prompter = Prompter::new("\nq\n".as_bytes(), &mut out);
+
+        let output = DemoCommand::new().run_demo(&mut shell, SCRIPT, &mut prompter)?;
+        assert_eq!(output, "demo stopped");
+
+        let transcript = String::from_utf8(out).unwrap();
+        assert!(!transcript.contains("Error: "));
+
+        Ok(())
+    }
+}
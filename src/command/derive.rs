@@ -0,0 +1,259 @@
+//! Commands declared as structs, whose fields are their arguments and flags, via
+//! `#[derive(ShiCommand)]`.
+//!
+//! The derive generates the name, help, arguments, flags and completions of the command from the
+//! struct and its attributes, and the parsing of its arguments into a value of the struct. What
+//! the command does is left to `RunCommand`:
+//!
+//! ```
+//! # use shi::command::{Command, RunCommand, ShiCommand};
+//! # use shi::shell::Shell;
+//! /// Listens for connections
+//! ///
+//! /// Listens on the given port, on all interfaces unless a host is given.
+//! #[derive(ShiCommand)]
+//! #[shi(state = Vec<u16>, category = "network")]
+//! struct Listen {
+//!     #[shi(values = ["80", "443"])]
+//!     port: u16,
+//!     host: Option<String>,
+//!     /// Logs every connection
+//!     #[shi(flag, short = 'v')]
+//!     verbose: bool,
+//!     #[shi(option)]
+//!     backlog: Option<u32>,
+//! }
+//!
+//! impl RunCommand for Listen {
+//!     fn run(self, ports: &mut Vec<u16>) -> shi::Result<String> {
+//!         ports.push(self.port);
+//!         let host = self.host.unwrap_or_else(|| String::from("0.0.0.0"));
+//!         Ok(format!("listening on {}:{}", host, self.port))
+//!     }
+//! }
+//!
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new_with_state("| ", Vec::new());
+//! shell.register(Command::new_leaf(Listen::command()))?;
+//!
+//! assert_eq!(shell.eval("listen 80 -v")?, "listening on 0.0.0.0:80");
+//! assert!(shell.eval("listen http").is_err());
+//! assert_eq!(shell.state(), &vec![80]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The struct takes the following attributes, all optional:
+//! * `#[shi(name = "...")]` - The name of the command. Defaults to that of the struct, in kebab
+//!   case, e.g. `list-users` for `ListUsers`. Acronyms are kept whole, e.g. `httpserver` for
+//!   `HTTPServer`.
+//! * `#[shi(help = "...", long_help = "...")]` - The help of the command. Default to the first
+//!   paragraph of the doc comment of the struct, and the rest of it.
+//! * `#[shi(state = T)]` - The state of the command. Defaults to `()`.
+//! * `#[shi(since = "...", category = "...", confirm = "...", hidden)]` - As in
+//!   `BasicCommandBuilder`.
+//!
+//! Each field is a positional argument, in order, unless it is marked as a flag:
+//! * Fields of type `T` are required arguments, and of type `Option<T>` optional ones. Either is
+//!   parsed via `FromStr`, and rejected before the command runs if it does not parse.
+//! * `#[shi(values = ["a", "b"])]`, `#[shi(choices = ["a", "b"])]`, `#[shi(source = path)]` and
+//!   `#[shi(ignore_case)]` declare the completions of an argument, as in `ArgSpec`.
+//! * `#[shi(rest)]` makes the last argument take the rest of the line.
+//! * `#[shi(flag)]` makes a `bool` field a flag, e.g. `--verbose`, and `#[shi(option)]` makes an
+//!   `Option<T>` field a flag that takes a value, e.g. `--backlog 16`. Their help is the doc
+//!   comment of the field, and `#[shi(short = 'v')]` gives them a short name.
+//! * The help of an argument, as in `ArgSpec::with_help()`, is the doc comment of its field.
+//! * `#[shi(name = "...")]` renames an argument or flag. Names default to that of the field, in
+//!   kebab case.
+
+use super::{BasicCommand, BasicCommandBuilder, ParsedArgs};
+use crate::Result;
+
+pub use shi_derive::ShiCommand;
+
+/// ShiCommand is a command whose arguments are parsed into a value of the implementing type.
+/// It is meant to be derived, via `#[derive(ShiCommand)]`. See the module documentation.
+pub trait ShiCommand: Sized {
+    /// The State of the command. Expected to be bound to a containing `Shell`.
+    type State;
+
+    /// Returns a builder for the command, with its name, help, arguments and flags declared.
+    fn builder() -> BasicCommandBuilder<'static, Self::State>;
+
+    /// Creates a value from the given arguments, which have been validated against the arguments
+    /// and flags declared by `builder()`.
+    ///
+    /// # Arguments
+    /// `args` - The parsed arguments.
+    fn from_args(args: &ParsedArgs) -> Result<Self>;
+
+    /// Creates the command, which parses its arguments and runs the result via `RunCommand`.
+    fn command() -> BasicCommand<'static, Self::State>
+    where
+        Self: RunCommand + 'static,
+    {
+        Self::builder().build_parsed(|state, args| Self::from_args(args)?.run(state))
+    }
+}
+
+/// RunCommand is what a `ShiCommand` does, once its arguments have been parsed.
+pub trait RunCommand: ShiCommand {
+    /// Runs the command.
+    ///
+    /// # Arguments
+    /// `state` - The state of the shell.
+    ///
+    /// # Returns
+    /// `Result<String>` - The output of the command.
+    fn run(self, state: &mut Self::State) -> Result<String>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::command::{BaseCommand, Command, Completion, FlagSpec};
+    use crate::shell::Shell;
+
+    use pretty_assertions::assert_eq;
+
+    fn users() -> Vec<String> {
+        vec![String::from("alice"), String::from("bob")]
+    }
+
+    /// Sends a message
+    #[derive(Debug, PartialEq, ShiCommand)]
+    #[shi(state = Vec<String>, category = "chat", confirm = "Send it?")]
+    struct SendMessage {
+        /// Who to send it to
+        #[shi(source = users)]
+        to: String,
+        #[shi(rest)]
+        text: String,
+        /// Sends it at a later time
+        #[shi(option, name = "at")]
+        at_hour: Option<u8>,
+        #[shi(flag, short = 'l')]
+        loud: bool,
+    }
+
+    impl RunCommand for SendMessage {
+        fn run(self, sent: &mut Vec<String>) -> Result<String> {
+            let text = if self.loud {
+                self.text.to_uppercase()
+            } else {
+                self.text
+            };
+            sent.push(format!("{}: {}", self.to, text));
+            Ok(format!(
+                "sent to {} at {}",
+                self.to,
+                self.at_hour.unwrap_or(0)
+            ))
+        }
+    }
+
+    #[derive(ShiCommand)]
+    #[shi(name = "level", help = "Sets the log level", hidden)]
+    struct Level {
+        #[shi(choices = ["debug", "info"], ignore_case)]
+        level: Option<String>,
+    }
+
+    impl RunCommand for Level {
+        fn run(self, _: &mut ()) -> Result<String> {
+            Ok(self.level.unwrap_or_else(|| String::from("info")))
+        }
+    }
+
+    #[derive(ShiCommand)]
+    struct HTTPStatus;
+
+    impl RunCommand for HTTPStatus {
+        fn run(self, _: &mut ()) -> Result<String> {
+            Ok(String::from("ok"))
+        }
+    }
+
+    #[test]
+    fn declares_the_command() {
+        let send = SendMessage::command();
+        assert_eq!(send.name(), "send-message");
+        assert_eq!(
+            send.usage(),
+            "send-message <to> <text...> [--at <value>] [-l|--loud]"
+        );
+        assert_eq!(
+            send.help(),
+            "Sends a message (usage: send-message <to> <text...> [--at <value>] [-l|--loud])"
+        );
+        assert_eq!(send.args()[0].help(), "Who to send it to");
+        assert_eq!(send.args()[1].help(), "");
+        assert_eq!(send.category(), Some(String::from("chat")));
+        assert_eq!(send.confirmation(), Some(String::from("Send it?")));
+        assert_eq!(
+            send.autocomplete(vec![], true),
+            Completion::Possibilities(users())
+        );
+
+        let level = Level::command();
+        assert_eq!(level.help(), "Sets the log level (usage: level [level])");
+        assert!(level.is_hidden());
+        assert!(level.validate_args(&[String::from("DEBUG")]).is_ok());
+        assert!(level.validate_args(&[String::from("trace")]).is_err());
+
+        assert_eq!(HTTPStatus::command().name(), "httpstatus");
+    }
+
+    #[test]
+    fn parses_args() -> Result<()> {
+        let send = SendMessage::command();
+        // Flags must come before the rest of the line, which would otherwise take them in.
+        let args = ["bob", "--at", "9", "-l", "see", "you"].map(String::from);
+        send.validate_args(&args)?;
+        let mut sent = Vec::new();
+        assert_eq!(send.execute(&mut sent, &args)?, "sent to bob at 9");
+        assert_eq!(sent, vec!["bob: SEE YOU"]);
+
+        let parsed = crate::command::parse_args(
+            &[FlagSpec::new("at").takes_value(), FlagSpec::new("loud")],
+            &["alice", "hi"].map(String::from),
+        )?;
+        assert_eq!(
+            SendMessage::from_args(&parsed)?,
+            SendMessage {
+                to: String::from("alice"),
+                text: String::from("hi"),
+                at_hour: None,
+                loud: false,
+            }
+        );
+
+        assert!(send.validate_args(&[String::from("bob")]).is_err());
+        assert_eq!(
+            send.execute(&mut sent, &["bob", "--at", "noon", "hi"].map(String::from))
+                .unwrap_err()
+                .to_string(),
+            "error: invalid value 'noon' for flag '--at': invalid digit found in string"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn runs_in_a_shell() -> Result<()> {
+        let mut shell = Shell::new_with_state("| ", Vec::new());
+        shell.set_assume_yes(true);
+        shell.register(Command::new_leaf(SendMessage::command()))?;
+        shell.register(Command::new_leaf(Level::command()).ignore_state())?;
+
+        assert_eq!(
+            shell.eval("send-message alice hello there")?,
+            "sent to alice at 0"
+        );
+        assert_eq!(shell.eval("level")?, "info");
+        assert_eq!(shell.state(), &vec![String::from("alice: hello there")]);
+
+        Ok(())
+    }
+}
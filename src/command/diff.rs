@@ -0,0 +1,280 @@
+use std::fs;
+use std::marker::PhantomData;
+
+#[cfg(feature = "color")]
+use colored::*;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// DiffCommand runs the command it is given, e.g. `diff baseline.txt config show`, and compares
+/// that command's output against the contents of the given file, line by line. Removed lines are
+/// prefixed with `-`, added lines with `+`, and unchanged lines with a space.
+///
+/// A baseline to compare against can be captured with `tee`, e.g. `tee baseline.txt config show`.
+pub struct DiffCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for DiffCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> DiffCommand<'a, S> {
+    /// Creates a new DiffCommand.
+    pub fn new() -> DiffCommand<'a, S> {
+        DiffCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// A line of a diff.
+pub(crate) enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Diffs the given lines, via their longest common subsequence. This takes time proportional to
+/// the product of the numbers of lines that differ, but only linear space, so that large outputs,
+/// e.g. of `watch -d`, can be diffed.
+pub(crate) fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    // Lines at either end that are unchanged, which is most of them when diffing successive
+    // outputs of a command, needn't be searched.
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    lines.extend(old[..prefix].iter().map(|line| DiffLine::Same(line)));
+    diff_middle(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+        &mut lines,
+    );
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+
+    lines
+}
+
+/// Diffs the given lines into `lines`, by splitting `old` in half and finding where in `new` the
+/// longest common subsequence crosses the split, i.e. via Hirschberg's algorithm.
+fn diff_middle<'a>(old: &[&'a str], new: &[&'a str], lines: &mut Vec<DiffLine<'a>>) {
+    if old.is_empty() {
+        lines.extend(new.iter().map(|line| DiffLine::Added(line)));
+        return;
+    }
+    if new.is_empty() {
+        lines.extend(old.iter().map(|line| DiffLine::Removed(line)));
+        return;
+    }
+    if old.len() == 1 {
+        match new.iter().position(|line| *line == old[0]) {
+            Some(pos) => {
+                lines.extend(new[..pos].iter().map(|line| DiffLine::Added(line)));
+                lines.push(DiffLine::Same(old[0]));
+                lines.extend(new[pos + 1..].iter().map(|line| DiffLine::Added(line)));
+            }
+            None => {
+                lines.push(DiffLine::Removed(old[0]));
+                lines.extend(new.iter().map(|line| DiffLine::Added(line)));
+            }
+        }
+        return;
+    }
+
+    let mid = old.len() / 2;
+    let before = lcs_lengths(old[..mid].iter(), new.iter());
+    let mut after = lcs_lengths(old[mid..].iter().rev(), new.iter().rev());
+    after.reverse();
+    // Of the splits of `new` with the longest common subsequence, take the first, so that
+    // removals come before additions. `max_by_key()` takes the last, hence the reversal.
+    let split = (0..=new.len())
+        .rev()
+        .max_by_key(|&j| before[j] + after[j])
+        .unwrap_or(0);
+
+    diff_middle(&old[..mid], &new[..split], lines);
+    diff_middle(&old[mid..], &new[split..], lines);
+}
+
+/// Returns the lengths of the longest common subsequences of `old` and each prefix of `new`.
+fn lcs_lengths<'a, 'b, I, J>(old: I, new: J) -> Vec<usize>
+where
+    I: Iterator<Item = &'a &'b str>,
+    J: Iterator<Item = &'a &'b str> + Clone,
+    'b: 'a,
+{
+    let mut row = vec![0; new.clone().count() + 1];
+    for old_line in old {
+        // The length for the previous prefix of `new`, before it was updated for `old_line`.
+        let mut diagonal = 0;
+        for (j, new_line) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if old_line == new_line {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+impl<'a, S> BaseCommand for DiffCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "diff"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args.len() {
+            0 => Err(ShiError::NoArgs),
+            1 => Err(ShiError::general(format!(
+                "expected a command to run after '{}'",
+                args[0]
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let baseline = fs::read_to_string(&args[0])
+            .map_err(|err| ShiError::general(format!("failed to read '{}': {}", args[0], err)))?;
+        let output = shell.eval_without_history(&args[1..].join(" "))?;
+
+        let old: Vec<&str> = baseline.lines().collect();
+        let new: Vec<&str> = output.lines().collect();
+        let diff = diff_lines(&old, &new);
+        if diff.iter().all(|line| matches!(line, DiffLine::Same(_))) {
+            return Ok(String::from("no differences"));
+        }
+
+        let plain = shell.is_plain_output();
+        Ok(diff
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(line) => format!("  {}", line),
+                DiffLine::Removed(line) => highlight(format!("- {}", line), false, plain),
+                DiffLine::Added(line) => highlight(format!("+ {}", line), true, plain),
+            })
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and diffs its output against a file")
+    }
+}
+
+/// Colors the given added or removed line green or red respectively, unless output is plain or
+/// colors are disabled.
+fn highlight(line: String, added: bool, plain: bool) -> String {
+    #[cfg(feature = "color")]
+    if !plain {
+        let colored = if added { line.green() } else { line.red() };
+        return colored.to_string();
+    }
+    #[cfg(not(feature = "color"))]
+    let _ = (added, plain);
+
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(
+            diff_lines(&["a", "b", "c", "d"], &["a", "c", "e", "d", "f"]),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Same("c"),
+                DiffLine::Added("e"),
+                DiffLine::Same("d"),
+                DiffLine::Added("f"),
+            ]
+        );
+        assert_eq!(diff_lines(&["a"], &[]), vec![DiffLine::Removed("a")]);
+        assert_eq!(diff_lines(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn diffs_large_outputs() {
+        let old: Vec<String> = (0..3000).map(|n| (n % 7).to_string()).collect();
+        let new: Vec<String> = (0..3000).map(|n| (n % 5).to_string()).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        let diff = diff_lines(&old, &new);
+        let kept = |added| -> Vec<&str> {
+            diff.iter()
+                .filter_map(|line| match (line, added) {
+                    (DiffLine::Same(line), _)
+                    | (DiffLine::Removed(line), false)
+                    | (DiffLine::Added(line), true) => Some(*line),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(kept(false), old);
+        assert_eq!(kept(true), new);
+    }
+
+    #[test]
+    fn diffs_against_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-diff-test-{}", std::process::id()));
+        let path_str = path.to_str().unwrap().to_string();
+        fs::write(&path, "a\nb\nc\n")?;
+
+        let mut shell = Shell::new("| ");
+        shell.set_plain_output(true);
+        shell.register(cmd!("echo", |_, args| Ok(args.join("\n"))))?;
+
+        assert_eq!(
+            shell.eval(&format!("diff {} echo a c d", path_str))?,
+            "  a\n- b\n  c\n+ d"
+        );
+        // A baseline captured with `tee` has no differences.
+        fs::write(&path, "")?;
+        assert_eq!(shell.eval(&format!("tee {} echo x", path_str))?, "x");
+        assert_eq!(
+            shell.eval(&format!("diff {} echo x", path_str))?,
+            "no differences"
+        );
+
+        fs::remove_file(&path)?;
+        assert!(shell.eval(&format!("diff {} echo x", path_str)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::Result;
+
+#[derive(Debug)]
+/// ExternalCommand is a command that runs an external executable as a subprocess.
+///
+/// The arguments of the command are passed along to the executable, and the executable's stdout
+/// becomes the output of the command. If the executable exits unsuccessfully, its stderr is
+/// included in the resulting error.
+pub struct ExternalCommand<S> {
+    name: String,
+    path: PathBuf,
+    phantom: PhantomData<S>,
+}
+
+impl<S> ExternalCommand<S> {
+    /// Creates a new ExternalCommand.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the command. This is how users will execute the command.
+    /// * `path` - The path of the executable to run.
+    pub fn new<P: AsRef<Path>>(name: &str, path: P) -> ExternalCommand<S> {
+        ExternalCommand {
+            name: name.to_string(),
+            path: path.as_ref().to_path_buf(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the path of the executable this command runs.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts the executable with the given arguments, without waiting for it to finish. See
+    /// `collect()`.
+    pub(crate) fn spawn(&self, args: &[String]) -> Result<process::Child> {
+        self.spawn_with_stdin(args, process::Stdio::null())
+    }
+
+    fn spawn_with_stdin(&self, args: &[String], stdin: process::Stdio) -> Result<process::Child> {
+        Ok(process::Command::new(&self.path)
+            .args(args)
+            .stdin(stdin)
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()?)
+    }
+
+    /// Waits for the given child, started by `spawn()`, to finish, returning its output.
+    pub(crate) fn collect(&self, child: process::Child) -> Result<String> {
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            return Err(ShiError::general(format!(
+                "'{}' failed ({}): {}",
+                self.name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches('\n')
+            .to_string())
+    }
+}
+
+impl<S> BaseCommand for ExternalCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn validate_args(&self, _: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut S, args: &[String]) -> Result<String> {
+        self.collect(self.spawn(args)?)
+    }
+
+    fn execute_with_input(&self, _: &mut S, args: &[String], input: &str) -> Result<String> {
+        let mut child = self.spawn_with_stdin(args, process::Stdio::piped())?;
+
+        // Write from another thread, so that a child that fills its stdout before reading all of
+        // its stdin does not deadlock with us.
+        let writer = child.stdin.take().map(|mut stdin| {
+            let input = format!("{}\n", input);
+            thread::spawn(move || {
+                // The child may well exit without reading all of its input, e.g. `head`.
+                let _ = stdin.write_all(input.as_bytes());
+            })
+        });
+        let res = self.collect(child);
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        res
+    }
+
+    fn help(&self) -> String {
+        format!("Runs {}", self.path.display())
+    }
+}
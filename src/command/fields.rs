@@ -0,0 +1,199 @@
+use std::marker::PhantomData;
+
+use super::args::split_first_unquoted;
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// FieldsCommand runs the command it is given and keeps only some of the fields of each line of
+/// its output, e.g. `fields 1,3 server list`. Combined with `filter`, this allows for basic
+/// wrangling of output without external tools.
+///
+/// Fields are numbered from 1, and are separated by whitespace, unless a delimiter is given with
+/// `-d`, e.g. `fields -d , 2 cat users.csv`. The delimiter may be quoted, e.g. `fields -d ' ' 2
+/// cat notes.txt` to split on single spaces. The kept fields are joined by a single space, or by
+/// the delimiter if there is one. Fields that a line does not have are left out.
+///
+/// Without a command, it selects fields of the output of the previous command of a pipeline
+/// instead, e.g. `server list | fields 1,3`.
+pub struct FieldsCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for FieldsCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The arguments to a `fields` invocation, as parsed by `FieldsCommand::parse()`.
+struct Invocation {
+    delimiter: Option<String>,
+    fields: Vec<usize>,
+    line: String,
+}
+
+impl Invocation {
+    /// Keeps the fields of each line of the given output.
+    fn select(&self, output: &str) -> String {
+        output
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = match &self.delimiter {
+                    Some(delimiter) => line.split(delimiter).collect(),
+                    None => line.split_whitespace().collect(),
+                };
+                let kept: Vec<&str> = self
+                    .fields
+                    .iter()
+                    .filter_map(|field| fields.get(field - 1).copied())
+                    .collect();
+                kept.join(self.delimiter.as_deref().unwrap_or(" "))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl<'a, S> FieldsCommand<'a, S> {
+    /// Creates a new FieldsCommand.
+    pub fn new() -> FieldsCommand<'a, S> {
+        FieldsCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    fn parse(args: &[String]) -> Result<Invocation> {
+        let (delimiter, rest) = match args.first().map(|arg| &arg[..]) {
+            Some("-d") => match split_first_unquoted(&args[1..]) {
+                Ok((delimiter, rest)) if !delimiter.is_empty() => (Some(delimiter), rest),
+                Ok(_) | Err(ShiError::NoArgs) => {
+                    return Err(ShiError::general("expected a delimiter after '-d'"))
+                }
+                Err(err) => return Err(err),
+            },
+            _ => (None, args),
+        };
+        if rest.is_empty() {
+            return Err(ShiError::general(
+                "expected fields and a command, e.g. 'fields 1,3 server list'",
+            ));
+        }
+
+        let fields = rest[0]
+            .split(',')
+            .map(|field| match field.parse::<usize>() {
+                Ok(field) if field > 0 => Ok(field),
+                _ => Err(ShiError::general(format!(
+                    "expected fields to be numbers from 1, got '{}'",
+                    field
+                ))),
+            })
+            .collect::<Result<Vec<usize>>>()?;
+
+        Ok(Invocation {
+            delimiter,
+            fields,
+            line: rest[1..].join(" "),
+        })
+    }
+}
+
+impl<'a, S> BaseCommand for FieldsCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "fields"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        Self::parse(args)?;
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let invocation = Self::parse(args)?;
+        if invocation.line.is_empty() {
+            return Err(ShiError::general(
+                "expected fields and a command, e.g. 'fields 1,3 server list', or input, e.g. \
+                 'server list | fields 1,3'",
+            ));
+        }
+
+        let output = shell.eval_without_history(&invocation.line)?;
+
+        Ok(invocation.select(&output))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let invocation = Self::parse(args)?;
+        if !invocation.line.is_empty() {
+            return Err(ShiError::general(format!(
+                "expected only fields to select from input, got '{}' after them",
+                invocation.line
+            )));
+        }
+
+        Ok(invocation.select(input))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and keeps some of the fields of each line of its output")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn selects_fields() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("servers", |_, _| {
+            Ok(String::from(
+                "web  up    10.0.0.1\ndb   down  10.0.0.2\ncache",
+            ))
+        }))?;
+        shell.register(cmd!("csv", |_, _| Ok(String::from("a,b,c\nd,,f"))))?;
+        shell.register(cmd!("notes", |_, _| Ok(String::from("a  b c\nd e"))))?;
+
+        assert_eq!(
+            shell.eval("fields 1,3 servers")?,
+            "web 10.0.0.1\ndb 10.0.0.2\ncache"
+        );
+        assert_eq!(
+            shell.eval("fields 3,1 servers")?,
+            "10.0.0.1 web\n10.0.0.2 db\ncache"
+        );
+        assert_eq!(shell.eval("fields -d , 2,3 csv")?, "b,c\n,f");
+        assert_eq!(shell.eval("fields 2 filter down servers")?, "down");
+        assert!(shell.eval("fields 1,3").is_err());
+        assert!(shell.eval("fields 0 servers").is_err());
+        assert!(shell.eval("fields one servers").is_err());
+        assert_eq!(shell.eval("fields -d ' ' 2,3 notes")?, " b\ne");
+        assert_eq!(shell.eval("fields -d \"c\" 1 csv")?, "a,b,\nd,,f");
+        assert!(shell.eval("fields -d").is_err());
+        assert!(shell.eval("fields -d '' 1 csv").is_err());
+        assert!(shell.eval("fields -d ' 1 csv").is_err());
+        assert!(shell.eval("fields 1 DNE").is_err());
+
+        assert_eq!(shell.eval("servers | fields 1")?, "web\ndb\ncache");
+        assert_eq!(shell.eval("csv | fields -d , 3")?, "c\nf");
+        assert_eq!(shell.eval("servers | filter up | fields 3")?, "10.0.0.1");
+        assert!(shell.eval("servers | fields 1 servers").is_err());
+        assert!(shell.eval("servers | fields").is_err());
+
+        Ok(())
+    }
+}
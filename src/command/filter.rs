@@ -0,0 +1,321 @@
+use std::marker::PhantomData;
+
+use super::args::split_first_unquoted;
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// What a single position of a `Pattern` matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Atom {
+    Any,
+    Char(char),
+}
+
+impl Atom {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => c == expected,
+        }
+    }
+}
+
+/// How many times an `Atom` may repeat. One or more is parsed as once followed by zero or more.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeat {
+    Once,
+    ZeroOrOne,
+    ZeroOrMore,
+}
+
+/// A pattern that lines are matched against: either some text to look for, or a small regular
+/// expression, written between slashes, e.g. `/^dog.*woof$/`.
+///
+/// Regular expressions support `.`, `*`, `+`, `?`, the `^` and `$` anchors, and escaping with `\`.
+#[derive(Debug, PartialEq)]
+enum Pattern {
+    Text(String),
+    Regex {
+        items: Vec<(Atom, Repeat)>,
+        anchored_start: bool,
+        anchored_end: bool,
+    },
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern> {
+        let regex = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) if pattern.len() > 1 => regex,
+            _ => return Ok(Pattern::Text(pattern.to_string())),
+        };
+
+        let mut chars = regex.chars().peekable();
+        let anchored_start = chars.next_if_eq(&'^').is_some();
+        let mut anchored_end = false;
+        let mut items: Vec<(Atom, Repeat)> = Vec::new();
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '.' => Atom::Any,
+                '$' if chars.peek().is_none() => {
+                    anchored_end = true;
+                    break;
+                }
+                '*' | '+' | '?' => {
+                    return Err(ShiError::general(format!(
+                        "invalid pattern '{}': '{}' must follow something to repeat",
+                        pattern, c
+                    )))
+                }
+                '\\' => match chars.next() {
+                    Some(escaped) => Atom::Char(escaped),
+                    None => {
+                        return Err(ShiError::general(format!(
+                            "invalid pattern '{}': nothing to escape at the end",
+                            pattern
+                        )))
+                    }
+                },
+                c => Atom::Char(c),
+            };
+            match chars.next_if(|c| matches!(c, '*' | '+' | '?')) {
+                Some('*') => items.push((atom, Repeat::ZeroOrMore)),
+                Some('+') => items.extend([(atom, Repeat::Once), (atom, Repeat::ZeroOrMore)]),
+                Some('?') => items.push((atom, Repeat::ZeroOrOne)),
+                _ => items.push((atom, Repeat::Once)),
+            }
+        }
+
+        Ok(Pattern::Regex {
+            items,
+            anchored_start,
+            anchored_end,
+        })
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Pattern::Text(text) => line.contains(text),
+            Pattern::Regex {
+                items,
+                anchored_start,
+                anchored_end,
+            } => match_items(items, line, *anchored_start, *anchored_end),
+        }
+    }
+}
+
+/// Matches the given items against the given line, by keeping track of every item that the
+/// matching may be at after each char, rather than backtracking, so that matching takes time
+/// proportional to the length of the line times the number of items, whatever the pattern.
+fn match_items(
+    items: &[(Atom, Repeat)],
+    line: &str,
+    anchored_start: bool,
+    anchored_end: bool,
+) -> bool {
+    // at[i] is whether the first i items may have been matched by the chars seen so far.
+    let mut at = vec![false; items.len() + 1];
+    at[0] = true;
+    skip_optional(items, &mut at);
+    for c in line.chars() {
+        if at[items.len()] && !anchored_end {
+            return true;
+        }
+
+        let mut next = vec![false; items.len() + 1];
+        for (i, (atom, repeat)) in items.iter().enumerate() {
+            if at[i] && atom.matches(c) {
+                match repeat {
+                    Repeat::ZeroOrMore => next[i] = true,
+                    _ => next[i + 1] = true,
+                }
+            }
+        }
+        // Unanchored matches may start at any char.
+        next[0] |= !anchored_start;
+        skip_optional(items, &mut next);
+        at = next;
+    }
+
+    at[items.len()]
+}
+
+/// Marks the items past those that may match nothing as reachable, e.g. `b` in `a*b` once `a*`
+/// is.
+fn skip_optional(items: &[(Atom, Repeat)], at: &mut [bool]) {
+    for (i, (_, repeat)) in items.iter().enumerate() {
+        if at[i] && *repeat != Repeat::Once {
+            at[i + 1] = true;
+        }
+    }
+}
+
+/// Keeps the lines of the given output that match the given pattern.
+fn filter_lines(pattern: &Pattern, output: &str) -> String {
+    output
+        .lines()
+        .filter(|line| pattern.is_match(line))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[derive(Debug)]
+/// FilterCommand runs the command it is given and keeps only the lines of its output that match
+/// a pattern, e.g. `filter error server logs`. Unlike piping to an external `grep`, it works on
+/// platforms that lack one.
+///
+/// The pattern is some text to look for, or a regular expression between slashes, e.g.
+/// `filter /^error:.*timeout$/ server logs`. It may be quoted, e.g. to look for text with spaces
+/// in it: `filter "connection refused" server logs`.
+///
+/// Without a command, it filters the output of the previous command of a pipeline instead, e.g.
+/// `server logs | filter error`.
+pub struct FilterCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for FilterCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> FilterCommand<'a, S> {
+    /// Creates a new FilterCommand.
+    pub fn new() -> FilterCommand<'a, S> {
+        FilterCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for FilterCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let (pattern, _) = split_first_unquoted(args)?;
+        Pattern::parse(&pattern)?;
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let (pattern, cmd) = split_first_unquoted(args)?;
+        if cmd.is_empty() {
+            return Err(ShiError::general(
+                "expected a pattern and a command, e.g. 'filter error server logs', or input, \
+                 e.g. 'server logs | filter error'",
+            ));
+        }
+
+        let pattern = Pattern::parse(&pattern)?;
+        let output = shell.eval_without_history(&cmd.join(" "))?;
+
+        Ok(filter_lines(&pattern, &output))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let (pattern, cmd) = split_first_unquoted(args)?;
+        if !cmd.is_empty() {
+            return Err(ShiError::general(format!(
+                "expected only a pattern to filter input by, got '{}' after it",
+                cmd.join(" ")
+            )));
+        }
+
+        Ok(filter_lines(&Pattern::parse(&pattern)?, input))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and keeps the lines of its output that match a pattern")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn patterns() -> Result<()> {
+        let cases = vec![
+            ("dog", "hotdog", true),
+            ("/", "a/b", true),
+            ("/dog/", "hotdog", true),
+            ("/^dog/", "hotdog", false),
+            ("/^hot.*g$/", "hotdog", true),
+            ("/^hot.*g$/", "hotdogs", false),
+            ("/o+g/", "hotdoooog", true),
+            ("/^dogs?$/", "dog", true),
+            ("/^dogs?$/", "dogss", false),
+            ("/a\\.b/", "a.b", true),
+            ("/a\\.b/", "axb", false),
+            ("/^$/", "", true),
+            (
+                "/a*a*a*a*a*a*a*a*a*b/",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                false,
+            ),
+            (
+                "/a*a*a*a*a*a*a*a*a*b/",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaab",
+                true,
+            ),
+            ("/^a+b$/", "b", false),
+            ("/x.?y/", "axyb", true),
+        ];
+        for (pattern, line, expected) in cases {
+            assert_eq!(
+                Pattern::parse(pattern)?.is_match(line),
+                expected,
+                "'{}' against '{}'",
+                pattern,
+                line
+            );
+        }
+
+        assert!(Pattern::parse("/*dog/").is_err());
+        assert!(Pattern::parse("/dog\\/").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn filters_output() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("pets", |_, args| {
+            Ok(format!("dog: woof\ncat: meow\nhotdog: {}", args.join(" ")))
+        }))?;
+
+        assert_eq!(shell.eval("filter dog pets")?, "dog: woof\nhotdog: ");
+        assert_eq!(shell.eval("filter /^dog/ pets")?, "dog: woof");
+        assert_eq!(
+            shell.eval("filter none pets none at all")?,
+            "hotdog: none at all"
+        );
+        assert_eq!(shell.eval("filter bird pets")?, "");
+        assert_eq!(shell.eval("filter 'cat' pets")?, "cat: meow");
+        assert_eq!(shell.eval("filter \"dog: woof\" pets")?, "dog: woof");
+        assert!(shell.eval("filter \"dog: woof pets").is_err());
+        assert!(shell.eval("filter dog").is_err());
+        assert!(shell.eval("filter /+/ pets").is_err());
+        assert!(shell.eval("filter dog DNE").is_err());
+
+        assert_eq!(shell.eval("pets | filter dog")?, "dog: woof\nhotdog: ");
+        assert_eq!(shell.eval("pets | filter 'cat: meow'")?, "cat: meow");
+        assert_eq!(shell.eval("pets | filter dog | filter /^hot/")?, "hotdog: ");
+        assert!(shell.eval("pets | filter dog pets").is_err());
+        assert!(shell.eval("pets | filter").is_err());
+
+        Ok(())
+    }
+}
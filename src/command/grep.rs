@@ -0,0 +1,95 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// GrepCommand filters the output of the previous command of a pipeline down to the lines that
+/// contain the given text, e.g. `history | grep server`.
+///
+/// Multiple arguments are joined by spaces into the text to look for.
+pub struct GrepCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for GrepCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> GrepCommand<'a, S> {
+    /// Creates a new GrepCommand.
+    pub fn new() -> GrepCommand<'a, S> {
+        GrepCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for GrepCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut Shell<S>, args: &[String]) -> Result<String> {
+        Err(ShiError::general(format!(
+            "expected input to search, e.g. 'history | grep {}'",
+            args.join(" ")
+        )))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let needle = args.join(" ");
+
+        Ok(input
+            .lines()
+            .filter(|line| line.contains(&needle))
+            .collect::<Vec<&str>>()
+            .join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Filters piped output down to the lines containing some text")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn filters_piped_output() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("pets", |_, _| {
+            Ok(String::from("dog: woof\ncat: meow\nhotdog: none"))
+        }))?;
+
+        assert_eq!(shell.eval("pets | grep dog")?, "dog: woof\nhotdog: none");
+        assert_eq!(shell.eval("pets | grep dog | grep hot")?, "hotdog: none");
+        assert_eq!(shell.eval("pets | grep cat:")?, "cat: meow");
+        assert!(shell.eval("grep dog").is_err());
+        assert!(shell.eval("pets | grep").is_err());
+        // Commands that do not take input can't be piped into.
+        assert!(shell.eval("pets | pets").is_err());
+        assert!(shell.eval("pets |").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// Parses the number of lines to keep, the first argument of `head` or `tail`.
+pub(crate) fn parse_count(args: &[String]) -> Result<usize> {
+    let count = args.first().ok_or(ShiError::NoArgs)?;
+
+    count
+        .parse::<usize>()
+        .map_err(|_| ShiError::general(format!("expected a number of lines, got '{}'", count)))
+}
+
+/// Splits the arguments of `head` or `tail` into the number of lines to keep and the command to
+/// run.
+pub(crate) fn count_and_line(name: &str, args: &[String]) -> Result<(usize, String)> {
+    if args.len() < 2 {
+        return Err(ShiError::general(format!(
+            "expected a number of lines and a command, e.g. '{} 10 server logs', or input, e.g. \
+             'server logs | {} 10'",
+            name, name
+        )));
+    }
+
+    Ok((parse_count(args)?, args[1..].join(" ")))
+}
+
+/// Returns the number of lines of input to keep, given the arguments of `head` or `tail` at the
+/// end of a pipeline.
+pub(crate) fn count_of_input(args: &[String]) -> Result<usize> {
+    if args.len() > 1 {
+        return Err(ShiError::general(format!(
+            "expected only a number of lines to keep of input, got '{}' after it",
+            args[1..].join(" ")
+        )));
+    }
+
+    parse_count(args)
+}
+
+#[derive(Debug)]
+/// HeadCommand runs the command it is given and keeps the first lines of its output, e.g.
+/// `head 10 server logs`, or of the output of the previous command of a pipeline, e.g.
+/// `server logs | head 10`. See `TailCommand` for the last lines.
+pub struct HeadCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for HeadCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> HeadCommand<'a, S> {
+    /// Creates a new HeadCommand.
+    pub fn new() -> HeadCommand<'a, S> {
+        HeadCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for HeadCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "head"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        parse_count(args)?;
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let (count, line) = count_and_line(self.name(), args)?;
+        let output = shell.eval_without_history(&line)?;
+
+        Ok(output.lines().take(count).collect::<Vec<&str>>().join("\n"))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let count = count_of_input(args)?;
+
+        Ok(input.lines().take(count).collect::<Vec<&str>>().join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and keeps the first lines of its output")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn keeps_first_lines() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("count", |_, _| Ok(String::from("1\n2\n3\n4"))))?;
+
+        assert_eq!(shell.eval("head 2 count")?, "1\n2");
+        assert_eq!(shell.eval("head 10 count")?, "1\n2\n3\n4");
+        assert_eq!(shell.eval("head 0 count")?, "");
+        assert_eq!(shell.eval("head 1 sort -r count")?, "4");
+        assert!(shell.eval("head count").is_err());
+        assert!(shell.eval("head -1 count").is_err());
+        assert!(shell.eval("head 2").is_err());
+
+        assert_eq!(shell.eval("count | head 1")?, "1");
+        assert_eq!(shell.eval("count | sort -r | head 2")?, "4\n3");
+        assert!(shell.eval("count | head 1 count").is_err());
+        assert!(shell.eval("count | head").is_err());
+
+        Ok(())
+    }
+}
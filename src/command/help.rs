@@ -1,8 +1,11 @@
 use std::marker::PhantomData;
 
+use unicode_width::UnicodeWidthChar;
+
 use crate::command::{BaseCommand, Command};
 use crate::command_set::CommandSet;
 use crate::error::ShiError;
+use crate::output::{self, json_string};
 use crate::parser::CommandType;
 use crate::shell::Shell;
 use crate::Result;
@@ -13,6 +16,9 @@ use crate::Result;
 /// It displays two separated sections, one for custom commands and one for builtins.
 /// It assumes that all commands it prints have meaningful implementations of Help(), as it
 /// includes it in the output.
+///
+/// With `--search <term>`, it instead lists the commands whose names or help text match the term,
+/// and with `--json`, it emits the entire command hierarchy as JSON, for consumption by tools.
 pub struct HelpCommand<'a, S> {
     // TODO: Not sure if we need this crap.
     phantom: &'a PhantomData<S>,
@@ -36,21 +42,57 @@ impl<'a, S> HelpCommand<'a, S> {
         // We expect there to be one line per command, +2 commands for headers of the two sections.
         let mut help_lines: Vec<String> =
             Vec::with_capacity(shell.cmds.borrow().len() + shell.builtins.len() + 2);
-        help_lines.push(String::from("Normal commands:"));
+        // Commands are grouped by category, with the uncategorized ones first, and the categories
+        // in the order they first appear in.
+        let mut categories: Vec<(Option<String>, Vec<String>)> = vec![(None, Vec::new())];
         for cmd in shell.cmds.borrow().iter() {
-            help_lines.push(format!("\t'{}' - {}", cmd.name(), cmd.help()));
+            if cmd.is_hidden() {
+                continue;
+            }
+
+            let help = shell.localized_help(&[cmd.name()], cmd.help());
+            let line = format!("\t'{}' - {}", cmd.name(), help);
+            let category = cmd.category();
+            match categories.iter_mut().find(|(name, _)| *name == category) {
+                Some((_, lines)) => lines.push(line),
+                None => categories.push((category, vec![line])),
+            }
+        }
+        if shell.is_deterministic() {
+            categories[1..].sort();
+        }
+        for (category, mut cmd_lines) in categories {
+            help_lines.push(match category {
+                Some(category) => format!("{} commands:", category),
+                None => String::from("Normal commands:"),
+            });
+            if shell.is_deterministic() {
+                cmd_lines.sort();
+            }
+            help_lines.extend(cmd_lines);
         }
 
         help_lines.push(String::from("Built-in commands:"));
-        for builtin in shell.builtins.iter() {
-            help_lines.push(format!("\t'{}' - {}", builtin.name(), builtin.help()))
+        let mut builtin_lines: Vec<String> = shell
+            .builtins
+            .iter()
+            .filter(|builtin| !builtin.is_hidden())
+            .map(|builtin| {
+                let help = shell.localized_help(&[builtin.name()], builtin.help());
+                format!("\t'{}' - {}", builtin.name(), help)
+            })
+            .collect();
+        if shell.is_deterministic() {
+            builtin_lines.sort();
         }
+        help_lines.extend(builtin_lines);
 
         help_lines.join("\n")
     }
 
     fn help_breakdown<T>(
         &self,
+        shell: &Shell<S>,
         cmd_path: Vec<&str>,
         invocation_args: Vec<&str>,
         cmds: &CommandSet<T>,
@@ -63,14 +105,19 @@ impl<'a, S> HelpCommand<'a, S> {
             match current_cmds.get(segment) {
                 Some(cmd) => {
                     let cmd_name = cmd.name();
-                    let help_msg = cmd.help();
+                    let help_msg = shell.localized_help(&cmd_path[..=indent], cmd.help());
+                    let since = match cmd.since() {
+                        Some(since) => format!(" (since {})", since),
+                        None => String::new(),
+                    };
                     lines.push(format!(
-                        "{}└─ {} - {}",
+                        "{}└─ {} - {}{}",
                         "   ".repeat(indent), // Use two spaces since we have 2 pipe-characters & a space.
                         cmd_name,
-                        help_msg
+                        help_msg,
+                        since
                     ));
-                    match &**cmd {
+                    match cmd {
                         Command::Parent(parent) => current_cmds = parent.sub_commands(),
                         Command::Leaf(_) => {
                             let mut called_with_msg =
@@ -84,6 +131,10 @@ impl<'a, S> HelpCommand<'a, S> {
                                 "   ".repeat(indent + 1),
                                 called_with_msg,
                             ));
+                            if let Some(long_help) = cmd.long_help() {
+                                lines.push(String::new());
+                                lines.push(long_help);
+                            }
                         }
                     };
                 }
@@ -98,6 +149,49 @@ impl<'a, S> HelpCommand<'a, S> {
         Ok(lines.join("\n"))
     }
 
+    /// Collects the paths and help text of the commands in the given set whose names or help text
+    /// contain the (lowercase) term.
+    fn search_cmds<T>(
+        &self,
+        shell: &Shell<S>,
+        term: &str,
+        cmds: &CommandSet<T>,
+        matches: &mut Vec<String>,
+    ) {
+        cmds.walk(|visit| {
+            // Commands under hidden ones are hidden too.
+            let hidden = (1..=visit.path.len()).any(|depth| {
+                cmds.get_path(&visit.path[..depth])
+                    .is_some_and(|cmd| cmd.is_hidden())
+            });
+            if hidden {
+                return;
+            }
+
+            let help = shell.localized_help(visit.path, visit.cmd.help());
+            let path = visit.path.join(" ");
+            if path.to_lowercase().contains(term) || help.to_lowercase().contains(term) {
+                matches.push(format!("\t'{}' - {}", path, snippet(&help, term)));
+            }
+        });
+    }
+
+    fn execute_search(&self, shell: &mut Shell<S>, term: &str) -> String {
+        let term = term.to_lowercase();
+        let mut matches = Vec::new();
+        self.search_cmds(shell, &term, &shell.cmds.borrow(), &mut matches);
+        self.search_cmds(shell, &term, &shell.builtins, &mut matches);
+
+        if matches.is_empty() {
+            return format!("No commands match '{}'.", term);
+        }
+        if shell.is_deterministic() {
+            matches.sort();
+        }
+
+        format!("Commands matching '{}':\n{}", term, matches.join("\n"))
+    }
+
     fn execute_with_args(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
         let invocation = args.join(" ");
         let outcome = shell.parse(&invocation);
@@ -106,10 +200,11 @@ impl<'a, S> HelpCommand<'a, S> {
         // break down for the command path:
         return match outcome.cmd_type {
             CommandType::Custom => {
-                self.help_breakdown(outcome.cmd_path, outcome.remaining, &shell.cmds.borrow())
+                let cmds = shell.cmds.borrow();
+                self.help_breakdown(shell, outcome.cmd_path, outcome.remaining, &cmds)
             }
             CommandType::Builtin => {
-                self.help_breakdown(outcome.cmd_path, outcome.remaining, &shell.builtins)
+                self.help_breakdown(shell, outcome.cmd_path, outcome.remaining, &shell.builtins)
             }
             CommandType::Unknown => Err(outcome
                 .error()
@@ -118,6 +213,114 @@ impl<'a, S> HelpCommand<'a, S> {
     }
 }
 
+/// Renders the given commands, and their descendants, as a JSON array. Each command is an object
+/// with its `name`, full `path`, `help` text, `usage`, `since` version (or null) and
+/// `subcommands`.
+///
+/// # Arguments
+/// `shell` - The shell the commands belong to, for looking up their help text.
+/// `path` - The names of the ancestors of `cmds`.
+/// `cmds` - The commands to render.
+fn cmds_to_json<S, T>(shell: &Shell<S>, path: &mut Vec<String>, cmds: &CommandSet<T>) -> String {
+    let mut objects = Vec::with_capacity(cmds.len());
+    for cmd in cmds.iter() {
+        path.push(cmd.name().to_string());
+        let joined = path.join(" ");
+        let (usage, subcommands) = match cmd {
+            Command::Leaf(_) => (format!("{} [args...]", joined), String::from("[]")),
+            Command::Parent(parent) => (
+                format!("{} <subcommand>", joined),
+                cmds_to_json(shell, path, parent.sub_commands()),
+            ),
+        };
+        let help = shell.localized_help(
+            &path.iter().map(String::as_str).collect::<Vec<&str>>(),
+            cmd.help(),
+        );
+        path.pop();
+        let since = match cmd.since() {
+            Some(since) => json_string(&since),
+            None => String::from("null"),
+        };
+
+        objects.push(format!(
+            "{{\"name\":{},\"path\":{},\"help\":{},\"usage\":{},\"since\":{},\"subcommands\":{}}}",
+            json_string(cmd.name()),
+            json_string(&joined),
+            json_string(&help),
+            json_string(&usage),
+            since,
+            subcommands
+        ));
+    }
+
+    format!("[{}]", objects.join(","))
+}
+
+/// Renders the command hierarchy of the given shell as a JSON object, with the normal commands
+/// under `commands` and the builtins under `builtins`. See `cmds_to_json()` for how each command
+/// is rendered.
+///
+/// # Arguments
+/// `shell` - The shell whose commands to render.
+pub(crate) fn shell_to_json<S>(shell: &Shell<S>) -> String {
+    format!(
+        "{{\"commands\":{},\"builtins\":{}}}",
+        cmds_to_json(shell, &mut Vec::new(), &shell.cmds.borrow()),
+        cmds_to_json(shell, &mut Vec::new(), &shell.builtins)
+    )
+}
+
+/// The maximum number of characters of help text shown in a search result.
+const SNIPPET_LEN: usize = 60;
+
+/// Returns a snippet of the given help text around the first occurrence of the (lowercase) term,
+/// shortened to at most `SNIPPET_LEN` columns, plus ellipses. Wide characters, e.g. CJK
+/// characters, take up two columns each.
+fn snippet(help: &str, term: &str) -> String {
+    if output::display_width(help) <= SNIPPET_LEN {
+        return help.to_string();
+    }
+
+    let chars: Vec<(char, usize)> = help
+        .chars()
+        .map(|c| (c, UnicodeWidthChar::width(c).unwrap_or(0)))
+        .collect();
+    // The column at which each character starts.
+    let columns: Vec<usize> = chars
+        .iter()
+        .scan(0, |column, (_, width)| {
+            let start = *column;
+            *column += width;
+            Some(start)
+        })
+        .collect();
+    let total = output::display_width(help);
+
+    // Center the snippet on the match, if the match is in the help text rather than the name.
+    let match_column = help
+        .to_lowercase()
+        .find(term)
+        .map_or(0, |byte_idx| output::display_width(&help[..byte_idx]));
+    let start_column = match_column
+        .saturating_sub(SNIPPET_LEN / 2)
+        .min(total - SNIPPET_LEN);
+    let start = columns.partition_point(|column| *column < start_column);
+    let end = columns.partition_point(|column| *column < start_column + SNIPPET_LEN);
+    // A wide character that would straddle the end of the snippet is left out.
+    let end = match chars[..end].last() {
+        Some((_, width)) if columns[end - 1] + width > start_column + SNIPPET_LEN => end - 1,
+        _ => end,
+    };
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "..." } else { "" },
+        chars[start..end].iter().map(|(c, _)| c).collect::<String>(),
+        if end < chars.len() { "..." } else { "" }
+    )
+}
+
 impl<'a, S> BaseCommand for HelpCommand<'a, S> {
     type State = Shell<'a, S>;
 
@@ -125,15 +328,28 @@ impl<'a, S> BaseCommand for HelpCommand<'a, S> {
         "help"
     }
 
-    fn validate_args(&self, _: &[String]) -> Result<()> {
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.len() == 1 && args[0] == "--search" {
+            return Err(ShiError::general("'--search' expects a term to search for"));
+        }
+
+        if args.len() > 1 && args[0] == "--json" {
+            return Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            });
+        }
+
         Ok(())
     }
 
     fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
-        if args.is_empty() {
-            Ok(self.execute_no_args(shell))
-        } else {
-            self.execute_with_args(shell, args)
+        match args.split_first() {
+            None => Ok(self.execute_no_args(shell)),
+            Some((flag, term)) if flag == "--search" => {
+                Ok(self.execute_search(shell, &term.join(" ")))
+            }
+            Some((flag, _)) if flag == "--json" => Ok(shell_to_json(shell)),
+            Some(_) => self.execute_with_args(shell, args),
         }
     }
 
@@ -145,7 +361,7 @@ impl<'a, S> BaseCommand for HelpCommand<'a, S> {
 #[cfg(test)]
 mod test {
     use super::HelpCommand;
-    use crate::command::BaseCommand;
+    use crate::command::{BaseCommand, BasicCommand, Command, ParentCommand};
     use crate::shell::Shell;
     use crate::Result;
     use crate::{leaf, parent};
@@ -236,10 +452,9 @@ mod test {
 
     #[test]
     fn help_with_no_args_gives_list() -> Result<()> {
-        run_help_test(
-            vec![],
-            String::from(
-                "\
+        #[allow(unused_mut)]
+        let mut expected = String::from(
+            "\
         Normal commands:\n\t\
             \'leaf\' - 1\n\t\
             \'foo\' - 2\n\
@@ -247,25 +462,77 @@ mod test {
             \'help\' - Prints help info for root commands or explains a given command invocation\n\t\
             \'helptree\' - Prints a tree depiction of all commands in this shell\n\t\
             \'exit\' - Exits the shell session\n\t\
-            \'history\' - Prints the history of commands",
-            ),
-        )
+            \'history\' - Prints, searches or clears the history of commands\n\t\
+            \'set\' - Sets an option of the shell\n\t\
+            \'show\' - Shows the options of the shell\n\t\
+            \'reload\' - Reloads the shell\'s configuration\n\t\
+            \'demo\' - Steps through a script of annotated commands\n\t\
+            \'tee\' - Runs a command and appends its output to a file\n\t\
+            \'diff\' - Runs a command and diffs its output against a file\n\t\
+            \'output\' - Re-displays the output of a previous command\n\t\
+            \'retry\' - Runs a command, retrying it with backoff if it fails\n\t\
+            \'parallel\' - Runs independent commands at once and gathers their outputs\n\t\
+            \'grep\' - Filters piped output down to the lines containing some text\n\t\
+            \'filter\' - Runs a command and keeps the lines of its output that match a pattern\n\t\
+            \'fields\' - Runs a command and keeps some of the fields of each line of its output\n\t\
+            \'sort\' - Runs a command and sorts the lines of its output\n\t\
+            \'head\' - Runs a command and keeps the first lines of its output\n\t\
+            \'tail\' - Runs a command and keeps the last lines of its output\n\t\
+            \'watch\' - Re-runs a command on an interval, showing its output full-screen\n\t\
+            \'snippet\' - Adds, uses, lists or removes templates of input lines\n\t\
+            \'alias\' - Defines, shows, lists or removes shortcuts for commands\n\t\
+            \'metrics\' - Prints counts describing the size and activity of the shell\n\t\
+            \'completions\' - Prints a completion script for bash, zsh or fish\n\t\
+            \'version\' - Prints version information",
+        );
+        #[cfg(feature = "palette")]
+        expected.push_str("\n\t'palette' - Fuzzy-searches for a command to run");
+        #[cfg(feature = "clipboard")]
+        expected.push_str("\n\t'copy' - Runs a command and copies its output to the clipboard");
+
+        run_help_test(vec![], expected)
     }
 
     #[test]
     fn help_with_no_args_and_no_cmds() {
-        run_help_test_no_cmds(
-            vec![],
-            String::from(
-                "\
+        #[allow(unused_mut)]
+        let mut expected = String::from(
+            "\
                 Normal commands:\n\
                 Built-in commands:\n\t\
                     \'help\' - Prints help info for root commands or explains a given command invocation\n\t\
                     \'helptree\' - Prints a tree depiction of all commands in this shell\n\t\
                     \'exit\' - Exits the shell session\n\t\
-                    \'history\' - Prints the history of commands\
-            "),
-        )
+                    \'history\' - Prints, searches or clears the history of commands\n\t\
+                    \'set\' - Sets an option of the shell\n\t\
+                    \'show\' - Shows the options of the shell\n\t\
+                    \'reload\' - Reloads the shell\'s configuration\n\t\
+                    \'demo\' - Steps through a script of annotated commands\n\t\
+                    \'tee\' - Runs a command and appends its output to a file\n\t\
+                    \'diff\' - Runs a command and diffs its output against a file\n\t\
+                    \'output\' - Re-displays the output of a previous command\n\t\
+                    \'retry\' - Runs a command, retrying it with backoff if it fails\n\t\
+                    \'parallel\' - Runs independent commands at once and gathers their outputs\n\t\
+                    \'grep\' - Filters piped output down to the lines containing some text\n\t\
+                    \'filter\' - Runs a command and keeps the lines of its output that match a pattern\n\t\
+                    \'fields\' - Runs a command and keeps some of the fields of each line of its output\n\t\
+                    \'sort\' - Runs a command and sorts the lines of its output\n\t\
+                    \'head\' - Runs a command and keeps the first lines of its output\n\t\
+                    \'tail\' - Runs a command and keeps the last lines of its output\n\t\
+                    \'watch\' - Re-runs a command on an interval, showing its output full-screen\n\t\
+                    \'snippet\' - Adds, uses, lists or removes templates of input lines\n\t\
+                    \'alias\' - Defines, shows, lists or removes shortcuts for commands\n\t\
+                    \'metrics\' - Prints counts describing the size and activity of the shell\n\t\
+                    \'completions\' - Prints a completion script for bash, zsh or fish\n\t\
+                    \'version\' - Prints version information\
+            ",
+        );
+        #[cfg(feature = "palette")]
+        expected.push_str("\n\t'palette' - Fuzzy-searches for a command to run");
+        #[cfg(feature = "clipboard")]
+        expected.push_str("\n\t'copy' - Runs a command and copies its output to the clipboard");
+
+        run_help_test_no_cmds(vec![], expected)
     }
 
     // NOTE: In some of the tests below, we can't use escaped multi-line strings because the escape
@@ -330,6 +597,201 @@ mod test {
         )
     }
 
+    #[test]
+    fn search() -> Result<()> {
+        run_help_test(
+            vec![String::from("--search"), String::from("2.3")],
+            String::from(
+                "Commands matching '2.3':\n\t'foo qux' - 2.3\n\t'foo qux quuz' - 2.3.1\n\t'foo \
+                qux corge' - 2.3.2",
+            ),
+        )?;
+        run_help_test(
+            vec![String::from("--search"), String::from("QU")],
+            String::from(
+                "Commands matching 'qu':\n\t'foo qux' - 2.3\n\t'foo qux quuz' - 2.3.1\n\t'foo \
+                qux corge' - 2.3.2\n\t'foo quux' - 2.4",
+            ),
+        )?;
+        run_help_test(
+            vec![String::from("--search"), String::from("tree depiction")],
+            String::from(
+                "Commands matching 'tree depiction':\n\t'helptree' - Prints a tree depiction of \
+                all commands in this shell",
+            ),
+        )?;
+        run_help_test(
+            vec![String::from("--search"), String::from("zebra")],
+            String::from("No commands match 'zebra'."),
+        )
+    }
+
+    #[test]
+    fn search_without_term() {
+        let help_cmd: HelpCommand<()> = HelpCommand::new();
+        assert!(help_cmd.validate_args(&[String::from("--search")]).is_err());
+    }
+
+    #[test]
+    fn snippets() {
+        use super::snippet;
+
+        assert_eq!(snippet("short help", "help"), "short help");
+
+        let long = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+            tempor incididunt ut labore et dolore magna aliqua.";
+        assert_eq!(
+            snippet(long, "lorem"),
+            "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed..."
+        );
+        assert_eq!(
+            snippet(long, "tempor"),
+            "...ipiscing elit, sed do eiusmod tempor incididunt ut labore et..."
+        );
+        assert_eq!(
+            snippet(long, "aliqua"),
+            "... eiusmod tempor incididunt ut labore et dolore magna aliqua."
+        );
+
+        // Each of these characters takes up two columns.
+        let wide = "検".repeat(40);
+        assert_eq!(snippet(&wide, "検"), format!("{}...", "検".repeat(30)));
+        let straddling = format!("a{}", wide);
+        assert_eq!(
+            snippet(&straddling, "a"),
+            format!("a{}...", "検".repeat(29))
+        );
+    }
+
+    #[test]
+    fn since() -> Result<()> {
+        let mut shell = Shell::new("");
+        let mut bar = BasicCommand::new_with_help("bar", "2.1", |_, _| Ok(String::new()));
+        bar.set_since("1.3");
+        shell.register(parent!("foo", "2", leaf!(bar)))?;
+
+        verify_help_output(
+            &mut shell,
+            vec![String::from("foo"), String::from("bar")],
+            String::from("└─ foo - 2\n   └─ bar - 2.1 (since 1.3)\n      └─ Called with no args"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn json() -> Result<()> {
+        let mut shell = Shell::new("");
+        shell.register(leaf!(TestCommand::new("leaf", "says \"hi\"")))?;
+        let mut foo =
+            ParentCommand::new_with_help("foo", "2", vec![leaf!(TestCommand::new("bar", "2.1"))]);
+        foo.set_since("1.2");
+        shell.register(Command::Parent(foo))?;
+
+        let help_cmd = HelpCommand::new();
+        help_cmd.validate_args(&[String::from("--json")])?;
+        let output = help_cmd.execute(&mut shell, &[String::from("--json")])?;
+
+        assert!(output.starts_with(
+            "{\"commands\":[\
+            {\"name\":\"leaf\",\"path\":\"leaf\",\"help\":\"says \\\"hi\\\"\",\
+            \"usage\":\"leaf [args...]\",\"since\":null,\"subcommands\":[]},\
+            {\"name\":\"foo\",\"path\":\"foo\",\"help\":\"2\",\"usage\":\"foo <subcommand>\",\
+            \"since\":\"1.2\",\"subcommands\":[{\"name\":\"bar\",\"path\":\"foo bar\",\
+            \"help\":\"2.1\",\"usage\":\"foo bar [args...]\",\"since\":null,\"subcommands\":[]}]}],\
+            \"builtins\":[{\"name\":\"help\","
+        ));
+        assert!(output.ends_with("]}"));
+
+        assert!(help_cmd
+            .validate_args(&[String::from("--json"), String::from("foo")])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn localized_help() -> Result<()> {
+        let mut shell = Shell::new("");
+        shell.register(parent!(
+            "foo",
+            "2",
+            leaf!(TestCommand::new("bar", "2.1")),
+            leaf!(TestCommand::new("baz", "2.2")),
+        ))?;
+        shell.set_help_lookup(|path| match path {
+            "foo bar" => Some(String::from("deux point un")),
+            "exit" => Some(String::from("Quitte le shell")),
+            _ => None,
+        });
+
+        let help_cmd = HelpCommand::new();
+        let list = help_cmd.execute(&mut shell, &[])?;
+        assert!(list.contains("'foo' - 2\n"));
+        assert!(list.contains("'exit' - Quitte le shell\n"));
+        assert_eq!(
+            help_cmd.execute(&mut shell, &[String::from("foo"), String::from("bar")])?,
+            "└─ foo - 2\n   └─ bar - deux point un\n      └─ Called with no args"
+        );
+        assert_eq!(
+            help_cmd.execute(
+                &mut shell,
+                &[String::from("--search"), String::from("deux")]
+            )?,
+            "Commands matching 'deux':\n\t'foo bar' - deux point un"
+        );
+        assert!(help_cmd
+            .execute(&mut shell, &[String::from("--json")])?
+            .contains("\"help\":\"deux point un\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn categories_and_hidden() -> Result<()> {
+        let noop = |_: &mut (), _: &[String]| Ok(String::new());
+        let mut shell = Shell::new("");
+        shell.register(leaf!(BasicCommand::builder("ls").help("lists").build(noop)))?;
+        shell.register(leaf!(BasicCommand::builder("ping")
+            .help("pings")
+            .long_help("Sends echo requests until interrupted.")
+            .category("network")
+            .build(noop)))?;
+        shell.register(leaf!(BasicCommand::builder("dump")
+            .help("dumps")
+            .hidden()
+            .build(noop)))?;
+        shell.register(leaf!(BasicCommand::builder("dig")
+            .help("resolves")
+            .category("network")
+            .build(noop)))?;
+
+        let help_cmd = HelpCommand::new();
+        let list = help_cmd.execute(&mut shell, &[])?;
+        assert!(list.starts_with(
+            "Normal commands:\n\t'ls' - lists\n\
+            network commands:\n\t'ping' - pings\n\t'dig' - resolves\n\
+            Built-in commands:\n"
+        ));
+        assert!(!list.contains("dump"));
+        assert_eq!(
+            help_cmd.execute(
+                &mut shell,
+                &[String::from("--search"), String::from("dump")]
+            )?,
+            "No commands match 'dump'."
+        );
+        assert!(!shell.eval("helptree")?.contains("dump"));
+        // Hidden commands can still be run, and explained.
+        assert_eq!(shell.eval("dump")?, "");
+        assert_eq!(
+            help_cmd.execute(&mut shell, &[String::from("ping")])?,
+            "└─ ping - pings\n   └─ Called with no args\n\nSends echo requests until interrupted."
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn invalid_command_invocation() -> Result<()> {
         run_help_test(
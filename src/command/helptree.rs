@@ -1,9 +1,11 @@
 use std::marker::PhantomData;
 
+use super::help::shell_to_json;
 use super::{BaseCommand, Command};
 use crate::command_set::CommandSet;
 use crate::error::ShiError;
 use crate::shell::Shell;
+use crate::tree::{Tree, TreeStyle};
 use crate::Result;
 
 #[derive(Debug)]
@@ -31,59 +33,18 @@ use crate::Result;
 /// ├── help
 /// └── history
 /// ```
-pub struct HelpTreeCommand<'a, S> {
-    // TODO: Not sure if we need this crap.
-    phantom: &'a PhantomData<S>,
-}
-
-#[derive(Clone)]
-/// A helper struct that records the context needed to determine how to correctly indent a line for
-/// the helptree visualization. It includes two pieces of relevant information:
 ///
-/// * Am I the last command of my level?
-/// * Of all my ancestors, were _they_ the last command of _their_ level?
+/// If the shell is in screen reader mode, the commands are instead listed one per line, prefixed
+/// with `COMMAND:` or `SUBCOMMAND:`. See `Shell::set_screen_reader()`.
 ///
-/// These two pieces of information allow us to correctly determine spacing and connectors needed
-/// to produce the tree.
+/// The glyphs used to draw the tree are determined by a `TreeStyle`.
 ///
-/// IndentContexts are produced by either _indenting_ them to a new level of recursion in the tree,
-/// OR, by traversing to the next element in the same level. It's methods, `indent` and `with_last`
-/// correspond to these two cases respectively. In other words, a tree can either get deeper or
-/// wider, respectively.
-struct IndentContext {
-    last: bool,
-    // This is a mouthful, but the idea is that if(parent_lastness_chain[i]) implies that parent_i was
-    // the last item in the level it belonged too. This is necessary to know when we need to figure
-    // out if we should continue a verticle pipe.
-    parent_lastness_chain: Vec<bool>,
-}
-
-impl IndentContext {
-    /// Produces a new IndentContext for the next indentation level (or, perhaps more accurately,
-    /// next level of the tree, or, next recursion).
-    fn indent(&self, last: bool) -> Self {
-        // We don't want future IndentContexts to hold references to prior IndentContexts' parent
-        // chains, since they should be different.
-        // There may be a way to avoid the copy and hold onto slices of a larger chain, but I do
-        // not think the addition in complexity is worth the negligible performance gain (if any).
-        let mut parent_chain_copy = self.parent_lastness_chain.to_vec();
-        parent_chain_copy.push(last);
-        IndentContext {
-            last,
-            parent_lastness_chain: parent_chain_copy,
-        }
-    }
-
-    /// Produces a new IndentContext, but does not indent it and therefore maintains the current
-    /// level of the tree. Thus, it keeps the `parent_lastness_chain` the same. However, since a
-    /// new IndentContext for a given level could be the _last_ element of that level, it takes an
-    /// argument for denoting that.
-    fn with_last(&self, new_last: bool) -> Self {
-        IndentContext {
-            last: new_last,
-            parent_lastness_chain: self.parent_lastness_chain.to_vec(),
-        }
-    }
+/// With `--json`, the command hierarchy is instead emitted as JSON, the same as `help --json`.
+pub struct HelpTreeCommand<'a, S> {
+    // TODO: Not sure if we need this crap.
+    phantom: &'a PhantomData<S>,
+    // None implies that we should use the shell's tree style.
+    style: Option<TreeStyle>,
 }
 
 impl<'a, S> Default for HelpTreeCommand<'a, S> {
@@ -92,104 +53,73 @@ impl<'a, S> Default for HelpTreeCommand<'a, S> {
     }
 }
 
+/// Builds a tree of the names of the given commands and their descendants, rooted at `root`.
+///
+/// # Arguments
+/// `root` - The value of the root of the tree, e.g. a heading.
+/// `cmds` - The commands to make the children of the root.
+///
+/// # Returns
+/// `Tree<String>` - The tree of command names.
+pub fn command_tree<T>(root: &str, cmds: &CommandSet<T>) -> Tree<String> {
+    let mut tree = Tree::new(root.to_string());
+    for cmd in cmds.iter().filter(|cmd| !cmd.is_hidden()) {
+        tree.add_child(match cmd {
+            Command::Leaf(_) => Tree::new(cmd.name().to_string()),
+            Command::Parent(parent_cmd) => command_tree(cmd.name(), parent_cmd.sub_commands()),
+        });
+    }
+
+    tree
+}
+
 impl<'a, S> HelpTreeCommand<'a, S> {
     /// Creates a new HelpTreeCommand.
     pub fn new() -> HelpTreeCommand<'a, S> {
         HelpTreeCommand {
             phantom: &PhantomData,
+            style: None,
         }
     }
 
-    // TODO: This works, but it isn't designed in the best way possible. What we should be doing is
-    // taking the commands and iterating them and their children into a tree. Then, we should pass
-    // the tree of strings (or, whatever type holding the information we want to print) to a
-    // function like this, responsible for rendering the tree.
-    // Right now, for example, there isn't any way to test this code without creating a shell,
-    // which is a code smell.
-    /// Adds the given name under the given indentation context to the given vector of strings,
-    /// maintaining the appearance of a tree.
+    /// Creates a new HelpTreeCommand that draws its tree in the given style, regardless of the
+    /// shell's tree style.
     ///
     /// # Arguments
-    ///
-    /// * `ctx` - The context of where in the tree we are adding lines to.
-    /// * `lines` - The lines of the helptree visualization. It is added to, and includes the
-    /// entire tree by the end of this function.
-    /// * `name` - The name of a command to add.
-    fn add_name_to_lines(&self, ctx: &IndentContext, lines: &mut Vec<String>, name: &str) {
-        // This is not to be confused with `lines`. Think of this as the columns; merging the
-        // elements in this vector gives you a line, to be added to `lines`.
-        let mut line_elems: Vec<&str> = Vec::new();
-
-        // For each of the parents in our chain, if they were last, then we only want a space
-        // because then their pipe is an elbow connector.
-        //   └─ Foo
-        //   │  └─ SubFoo <--- WRONG!
-        // Instead we want:
-        //   └─ Foo
-        //      └─ SubFoo <--- RIGHT!
-        // However, if they were _NOT_ last, then we want a vertical pipe, since their connector is
-        // a 3-way connector. So we'd want that continuation.
-        //   ├─ Foo
-        //   │  └─ SubFoo <--- RIGHT!
-        for parent_was_last in &ctx.parent_lastness_chain {
-            if *parent_was_last {
-                // If the parent was the last in the chain, we don't need to continue its vertical
-                // pipe, because it will have a clean elbow cut-off.
-                line_elems.push("    ");
-            } else {
-                line_elems.push("│   ");
-            }
-        }
-
-        // If we're the last guy, we want a clean elbow cut-off, otherwise, we want a fork.
-        // NOTE: This is for the _current_ command. So this is not to be confused with what we are
-        // doing above with the `parent_lastness_chain`.
-        if ctx.last {
-            line_elems.push("└");
-        } else {
-            line_elems.push("├");
+    /// `style` - The style in which to draw the tree.
+    pub fn new_with_style(style: TreeStyle) -> HelpTreeCommand<'a, S> {
+        HelpTreeCommand {
+            phantom: &PhantomData,
+            style: Some(style),
         }
-
-        // Write two horizontal pipes to lead to our name, with a space for separation...
-        let dash_name = format!("── {}", name);
-        line_elems.push(&dash_name);
-
-        lines.push(line_elems.join(""))
     }
 
-    /// Adds the lines of the helptree visualization.
+    /// Adds lines listing the descendants of the given tree without any box-drawing characters,
+    /// for screen readers. Children of the root are prefixed with `COMMAND:`, and their
+    /// descendants are prefixed with `SUBCOMMAND:` followed by their full path.
     ///
     /// # Arguments
     ///
-    /// * `ctx` - The context of where in the tree we are adding lines to.
-    /// * `lines` - The lines of the helptree visualization. It is added to, and includes the
-    /// entire tree by the end of this function.
-    /// * `cmds` - The set of Commands for which to create and add lines of the helptree
-    /// visualization.
-    fn add_tree_lines_for_children<T>(
+    /// * `path` - The names of the ancestors of the children of `tree`, excluding its root.
+    /// * `lines` - The lines of the listing. It is added to, and includes the entire listing by
+    /// the end of this function.
+    /// * `tree` - The tree whose descendants to add lines for.
+    fn add_plain_lines(
         &self,
-        ctx: &IndentContext,
+        path: &mut Vec<String>,
         lines: &mut Vec<String>,
-        cmds: &CommandSet<T>,
+        tree: &Tree<String>,
     ) {
-        for (i, cmd) in cmds.iter().enumerate() {
-            let last = i == cmds.len() - 1;
-
-            // Because we may recurse, we'll be going into a deeper level whose lines should come
-            // _after_, so add the current command's line to the vector now.
-            self.add_name_to_lines(&ctx.with_last(last), lines, cmd.name());
-
-            match &**cmd {
-                Command::Leaf(_) => continue, // We can't recurse in this case.
-                Command::Parent(parent_cmd) => {
-                    // We need to recurse another level for our children.
-                    self.add_tree_lines_for_children(
-                        &ctx.indent(last),
-                        lines,
-                        parent_cmd.sub_commands(),
-                    );
-                }
+        for child in &tree.children {
+            path.push(child.value.clone());
+            if path.len() == 1 {
+                lines.push(format!("COMMAND: {}", child.value));
+            } else {
+                lines.push(format!("SUBCOMMAND: {}", path.join(" ")));
             }
+
+            self.add_plain_lines(path, lines, child);
+            path.pop();
         }
     }
 
@@ -199,28 +129,30 @@ impl<'a, S> HelpTreeCommand<'a, S> {
     ///
     /// * `shell` - The shell for which to produce the helptree.
     fn to_lines(&self, shell: &Shell<'a, S>) -> Vec<String> {
-        // We tackle the initial two subtrees separately, since they have slightly differing types.
-        //  1: The normal commands (state = S).
-        //  2: The builtins (state = Shell<S>).
-        //
-        //  Since they are different types, we need to invoke `add_tree_lines_for_children()`
-        //  separately for each, and combine the resulting help lines.
-
-        // Start with an initial context with the lastness chain being empty.
-        // Of course, `last` should also be false, which we ensure with `.with_last(false)` in the
-        // invocations to `add_tree_lines_for_children()` below.
-        let ctx = IndentContext {
-            last: false,
-            parent_lastness_chain: Vec::new(),
-        };
-
-        let mut lines: Vec<String> = vec![String::from("Normal commands")];
-        self.add_tree_lines_for_children(&ctx.with_last(false), &mut lines, &shell.cmds.borrow());
+        // We tackle the normal commands (state = S) and the builtins (state = Shell<S>) as two
+        // separate trees, since they have differing types.
+        let mut trees = [
+            command_tree("Normal commands", &shell.cmds.borrow()),
+            command_tree("Builtins", &shell.builtins),
+        ];
+        if shell.is_deterministic() {
+            trees.iter_mut().for_each(Tree::sort);
+        }
+        let style = self.style.unwrap_or_else(|| shell.tree_style());
 
-        lines.push(String::from("\n"));
+        let mut lines: Vec<String> = Vec::new();
+        for (i, tree) in trees.iter().enumerate() {
+            if i > 0 {
+                lines.push(String::from("\n"));
+            }
 
-        lines.push(String::from("Builtins"));
-        self.add_tree_lines_for_children(&ctx.with_last(false), &mut lines, &shell.builtins);
+            if shell.is_screen_reader() {
+                lines.push(tree.value.clone());
+                self.add_plain_lines(&mut Vec::new(), &mut lines, tree);
+            } else {
+                lines.extend(tree.render(style));
+            }
+        }
 
         lines
     }
@@ -234,6 +166,10 @@ impl<'a, S> BaseCommand for HelpTreeCommand<'a, S> {
     }
 
     fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args == ["--json"] {
+            return Ok(());
+        }
+
         if !args.is_empty() {
             // TODO: We may want to make this actually take arguments, like a command name or
             // command name path.
@@ -243,7 +179,11 @@ impl<'a, S> BaseCommand for HelpTreeCommand<'a, S> {
         Ok(())
     }
 
-    fn execute(&self, shell: &mut Shell<'a, S>, _: &[String]) -> Result<String> {
+    fn execute(&self, shell: &mut Shell<'a, S>, args: &[String]) -> Result<String> {
+        if !args.is_empty() {
+            return Ok(shell_to_json(shell));
+        }
+
         let help_lines = self.to_lines(shell);
 
         Ok(help_lines.join("\n"))
@@ -253,3 +193,100 @@ impl<'a, S> BaseCommand for HelpTreeCommand<'a, S> {
         String::from("Prints a tree depiction of all commands in this shell")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    fn make_shell<'a>() -> Result<Shell<'a, ()>> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| Ok(String::new())))?;
+        shell.register(parent!(
+            "felid",
+            parent!("felinae", cmd!("domestic-cat", |_, _| Ok(String::new()))),
+            cmd!("panther", |_, _| Ok(String::new())),
+        ))?;
+
+        Ok(shell)
+    }
+
+    #[test]
+    fn styles() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        let unicode = HelpTreeCommand::new().to_lines(&shell);
+        assert_eq!(
+            unicode[..6].to_vec(),
+            vec![
+                "Normal commands",
+                "├── dog",
+                "└── felid",
+                "    ├── felinae",
+                "    │   └── domestic-cat",
+                "    └── panther",
+            ]
+        );
+
+        shell.set_tree_style(TreeStyle::Ascii);
+        let ascii = HelpTreeCommand::new().to_lines(&shell);
+        assert_eq!(
+            ascii[..6].to_vec(),
+            vec![
+                "Normal commands",
+                "|-- dog",
+                "\\-- felid",
+                "    |-- felinae",
+                "    |   \\-- domestic-cat",
+                "    \\-- panther",
+            ]
+        );
+
+        // The command's own style takes precedence over the shell's.
+        assert_eq!(
+            HelpTreeCommand::new_with_style(TreeStyle::Unicode).to_lines(&shell),
+            unicode
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn screen_reader() -> Result<()> {
+        let mut shell = make_shell()?;
+        shell.set_screen_reader(true);
+
+        let lines = HelpTreeCommand::new().to_lines(&shell);
+        assert_eq!(
+            lines[..6].to_vec(),
+            vec![
+                "Normal commands",
+                "COMMAND: dog",
+                "COMMAND: felid",
+                "SUBCOMMAND: felid felinae",
+                "SUBCOMMAND: felid felinae domestic-cat",
+                "SUBCOMMAND: felid panther",
+            ]
+        );
+        assert!(lines.contains(&String::from("COMMAND: helptree")));
+        assert!(lines.iter().all(|line| !line.contains('─')));
+
+        Ok(())
+    }
+
+    #[test]
+    fn json() -> Result<()> {
+        let mut shell = make_shell()?;
+        let helptree = HelpTreeCommand::new();
+        let args = [String::from("--json")];
+
+        helptree.validate_args(&args)?;
+        assert_eq!(helptree.execute(&mut shell, &args)?, shell_to_json(&shell));
+        assert!(helptree.validate_args(&[String::from("--yaml")]).is_err());
+
+        Ok(())
+    }
+}
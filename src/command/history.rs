@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use super::BaseCommand;
+use super::{BaseCommand, Completion};
 use crate::error::ShiError;
 use crate::shell::Shell;
 use crate::Result;
@@ -12,6 +12,11 @@ use crate::Result;
 /// also include prior sessions. This is dependent on how the containing Shell was configured.
 ///
 /// Repeated, subsequent command invocations are a single entry in the history.
+///
+/// * `history` lists the whole history.
+/// * `history search <term>` lists the entries containing `term`.
+/// * `history last <n>` lists the `n` most recent entries.
+/// * `history clear` clears the history.
 pub struct HistoryCommand<'a, S> {
     phantom: &'a PhantomData<S>,
 }
@@ -29,6 +34,21 @@ impl<'a, S> HistoryCommand<'a, S> {
             phantom: &PhantomData,
         }
     }
+
+    fn actions(&self) -> Vec<String> {
+        vec![
+            String::from("search"),
+            String::from("last"),
+            String::from("clear"),
+        ]
+    }
+}
+
+/// Lists the given history entries, one per indented line.
+fn listing(entries: Vec<String>) -> String {
+    // Add an extra tab because the first line won't have the join separator attached, and will
+    // therefore only have the \n from the print.
+    format!("\t{}", entries.join("\n\t"))
 }
 
 impl<'a, S> BaseCommand for HistoryCommand<'a, S> {
@@ -39,33 +59,120 @@ impl<'a, S> BaseCommand for HistoryCommand<'a, S> {
     }
 
     fn validate_args(&self, args: &[String]) -> Result<()> {
-        if !args.is_empty() {
-            // TODO: We will probably want to take an optional flag for searching.
-            // TODO: Maybe an optional flag for num items.
-            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        let action = match args.first() {
+            Some(action) => action,
+            None => return Ok(()),
+        };
+
+        match (&action[..], args.len()) {
+            ("search", 1) => Err(ShiError::general("expected a term after 'search'")),
+            ("search", _) | ("clear", 1) => Ok(()),
+            ("last", 2) => match args[1].parse::<usize>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ShiError::general(format!(
+                    "expected a number of entries after 'last', got '{}'",
+                    args[1]
+                ))),
+            },
+            ("last", 1) => Err(ShiError::general(
+                "expected a number of entries after 'last'",
+            )),
+            ("last", _) | ("clear", _) => Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            }),
+            _ => Err(ShiError::InvalidSubCommand {
+                got: action.clone(),
+                expected: self.actions(),
+            }),
         }
+    }
 
-        Ok(())
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        match args.first().map(|action| &action[..]) {
+            Some("search") => {
+                let term = args[1..].join(" ");
+                // The search itself is the newest entry, and would always match.
+                let invocation = format!("{} {}", self.name(), args.join(" "));
+                let mut entries: Vec<String> = shell.history_entries().collect();
+                if entries.last() == Some(&invocation) {
+                    entries.pop();
+                }
+                let matches: Vec<String> = entries
+                    .into_iter()
+                    .filter(|entry| entry.contains(&term))
+                    .collect();
+                if matches.is_empty() {
+                    return Ok(format!("no history entries contain '{}'", term));
+                }
+
+                Ok(listing(matches))
+            }
+            Some("last") => {
+                let n = args[1]
+                    .parse::<usize>()
+                    .map_err(|err| ShiError::general(err.to_string()))?;
+                let skip = shell.history_len().saturating_sub(n);
+                Ok(listing(shell.history_entries().skip(skip).collect()))
+            }
+            Some("clear") => {
+                shell.clear_history();
+                Ok(String::from("cleared history"))
+            }
+            _ => Ok(listing(shell.history_entries().collect())),
+        }
     }
 
-    fn execute(&self, shell: &mut Shell<S>, _: &[String]) -> Result<String> {
-        // A bit of a mouthful. We grab the underlying history of the shell, iterate it, map the
-        // elements to strings, then collection them into a vector of Strings before we join them
-        // with a newline + tab.
-        let history_output = shell
-            .rl
-            .history()
-            .iter()
-            .map(|h| h.to_string())
-            .collect::<Vec<String>>()
-            .join("\n\t");
-
-        // Add an extra tab because the first line won't have the join separator attached, and will
-        // therefore only have the \n from the print.
-        Ok(format!("\t{}", history_output))
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(self.actions()),
+            ([action], false) => Completion::from_partial(action, self.actions()),
+            _ => Completion::Nothing,
+        }
     }
 
     fn help(&self) -> String {
-        String::from("Prints the history of commands")
+        String::from("Prints, searches or clears the history of commands")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn queries_history() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, args| Ok(args.join(" "))))?;
+
+        shell.eval("dog woof")?;
+        shell.eval("dog bark")?;
+        shell.eval("dog woof woof")?;
+
+        assert_eq!(
+            shell.eval("history search woof")?,
+            "\tdog woof\n\tdog woof woof"
+        );
+        assert_eq!(
+            shell.eval("history search meow")?,
+            "no history entries contain 'meow'"
+        );
+        assert_eq!(
+            shell.eval("history last 2")?,
+            "\thistory search meow\n\thistory last 2"
+        );
+        assert_eq!(shell.eval("history clear")?, "cleared history");
+        assert_eq!(shell.history_len(), 0);
+
+        assert!(shell.eval("history search").is_err());
+        assert!(shell.eval("history last").is_err());
+        assert!(shell.eval("history last two").is_err());
+        assert!(shell.eval("history clear all").is_err());
+        assert!(shell.eval("history forget").is_err());
+
+        Ok(())
     }
 }
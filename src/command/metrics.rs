@@ -0,0 +1,119 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::output::CommandOutput;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// MetricsCommand prints counts describing the size and activity of the shell, e.g. how many
+/// commands are registered. See `Shell::metrics()`.
+pub struct MetricsCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for MetricsCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> MetricsCommand<'a, S> {
+    /// Creates a new MetricsCommand.
+    pub fn new() -> MetricsCommand<'a, S> {
+        MetricsCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for MetricsCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if !args.is_empty() {
+            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, _: &[String]) -> Result<String> {
+        Ok(shell.render(&metrics_output(shell)))
+    }
+
+    fn execute_output(&self, shell: &mut Shell<S>, _: &[String]) -> Result<CommandOutput> {
+        Ok(metrics_output(shell))
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints counts describing the size and activity of the shell")
+    }
+}
+
+/// Returns the metrics of the given shell, as pairs.
+fn metrics_output<S>(shell: &Shell<S>) -> CommandOutput {
+    let metrics = shell.metrics();
+
+    CommandOutput::key_value(vec![
+        ("commands", metrics.commands.to_string()),
+        (
+            "unconstructed commands",
+            metrics.unconstructed_commands.to_string(),
+        ),
+        ("builtins", metrics.builtins.to_string()),
+        ("history entries", metrics.history_entries.to_string()),
+        ("evaluations", metrics.evaluations.to_string()),
+        ("failures", metrics.failures.to_string()),
+        ("kept outputs", metrics.kept_outputs.to_string()),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::output::JsonRenderer;
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn prints_metrics() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| { Ok(String::from("listening")) }),
+        ))?;
+        shell.eval("server listen")?;
+        assert!(shell.eval("server crash").is_err());
+
+        let builtins = shell.metrics().builtins;
+        assert_eq!(
+            shell.eval("metrics")?,
+            format!(
+                "commands:               2\n\
+                unconstructed commands: 0\n\
+                builtins:               {}\n\
+                history entries:        3\n\
+                evaluations:            3\n\
+                failures:               1\n\
+                kept outputs:           1",
+                builtins
+            )
+        );
+
+        shell.set_renderer(Box::new(JsonRenderer));
+        assert!(shell
+            .eval("metrics")?
+            .starts_with(r#"{"commands":"2","unconstructed commands":"0","#));
+        assert!(shell.eval("metrics please").is_err());
+
+        Ok(())
+    }
+}
@@ -2,38 +2,134 @@
 //!
 //! This module includes all command-related functionality and interfaces for using shi.
 
+use crate::error::ShiError;
+use crate::output::CommandOutput;
 use crate::Result;
 
 // TODO: We should be re-exporting these _from_ the command module. They should be submodules
 // underneath the command module.
+pub mod alias;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod completions;
+#[cfg(feature = "clipboard")]
+pub mod copy;
+pub mod demo;
+pub mod diff;
 pub mod echo;
 pub mod exit;
+pub mod external;
+pub mod fields;
+pub mod filter;
+pub mod grep;
+pub mod head;
 pub mod help;
 pub mod helptree;
 pub mod history;
+pub mod metrics;
+pub mod output;
+#[cfg(feature = "palette")]
+pub mod palette;
+pub mod parallel;
+pub mod reload;
+pub mod retry;
+pub mod set;
+pub mod show;
+pub mod snippet;
+pub mod sort;
+pub mod tail;
+pub mod tee;
+pub mod version;
+pub mod watch;
 
+pub use alias::*;
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+pub use completions::*;
+#[cfg(feature = "clipboard")]
+pub use copy::*;
+pub use demo::*;
+pub use diff::*;
 pub use echo::*;
 pub use exit::*;
+pub use external::*;
+pub use fields::*;
+pub use filter::*;
+pub use grep::*;
+pub use head::*;
 pub use help::*;
 pub use helptree::*;
 pub use history::*;
+pub use metrics::*;
+pub use output::*;
+#[cfg(feature = "palette")]
+pub use palette::*;
+pub use parallel::*;
+pub use reload::*;
+pub use retry::*;
+pub use set::*;
+pub use show::*;
+pub use snippet::*;
+pub use sort::*;
+pub use tail::*;
+pub use tee::*;
+pub use version::*;
+pub use watch::*;
 
 pub mod example {
     pub use super::echo::EchoCommand;
 }
 
 pub(crate) mod builtin {
+    pub use super::alias::AliasCommand;
+    pub use super::completions::CompletionsCommand;
+    #[cfg(feature = "clipboard")]
+    pub use super::copy::CopyCommand;
+    pub use super::demo::DemoCommand;
+    pub use super::diff::DiffCommand;
     pub use super::exit::ExitCommand;
+    pub use super::fields::FieldsCommand;
+    pub use super::filter::FilterCommand;
+    pub use super::grep::GrepCommand;
+    pub use super::head::HeadCommand;
     pub use super::help::HelpCommand;
     pub use super::helptree::HelpTreeCommand;
     pub use super::history::HistoryCommand;
+    pub use super::metrics::MetricsCommand;
+    pub use super::output::OutputCommand;
+    #[cfg(feature = "palette")]
+    pub use super::palette::PaletteCommand;
+    pub use super::parallel::ParallelCommand;
+    pub use super::reload::ReloadCommand;
+    pub use super::retry::RetryCommand;
+    pub use super::set::SetCommand;
+    pub use super::show::ShowCommand;
+    pub use super::snippet::SnippetCommand;
+    pub use super::sort::SortCommand;
+    pub use super::tail::TailCommand;
+    pub use super::tee::TeeCommand;
+    pub use super::version::VersionCommand;
+    pub use super::watch::WatchCommand;
 }
 
 pub mod parent;
 pub use parent::ParentCommand;
 
+pub mod adapters;
+pub use adapters::{IgnoreState, MapState};
+
 pub mod basic;
-pub use basic::BasicCommand;
+pub use basic::{BasicCommand, BasicCommandBuilder};
+
+#[cfg(feature = "derive")]
+pub mod derive;
+#[cfg(feature = "derive")]
+pub use derive::{RunCommand, ShiCommand};
+
+pub mod args;
+pub use args::{
+    complete_positional, parse_args, ArgSource, ArgSpec, ArgValidator, FlagSpec, ParsedArgs,
+};
 
 /// Command represents all and any command that should exist in shi. It represents a clear
 /// bifurcation: a command is either a `Leaf` or a `Parent` command.
@@ -63,6 +159,30 @@ impl<'a, S> Command<'a, S> {
     pub fn new_parent(name: &'a str, sub_cmds: Vec<Command<'a, S>>) -> Self {
         Self::Parent(ParentCommand::new(name, sub_cmds))
     }
+
+    /// Lifts this command, and any subcommands of it, into commands of a larger state, `T`, e.g.
+    /// of an application of which `S` is a field. See `MapState`.
+    ///
+    /// # Arguments
+    /// `map` - Returns the part of the larger state that this command is written for.
+    pub fn map_state<T, F>(self, map: F) -> Command<'a, T>
+    where
+        F: Fn(&mut T) -> &mut S + Clone + 'a,
+        S: 'a,
+        T: 'a,
+    {
+        adapters::lift(self, move |leaf| {
+            Command::new_leaf(MapState::new(map.clone(), leaf))
+        })
+    }
+}
+
+impl<'a> Command<'a, ()> {
+    /// Lifts this command, and any subcommands of it, into commands of any state, which they
+    /// ignore. See `IgnoreState`.
+    pub fn ignore_state<T: 'a>(self) -> Command<'a, T> {
+        adapters::lift(self, |leaf| Command::new_leaf(IgnoreState::new(leaf)))
+    }
 }
 
 impl<'a, S> BaseCommand for Command<'a, S> {
@@ -82,6 +202,13 @@ impl<'a, S> BaseCommand for Command<'a, S> {
         }
     }
 
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        match self {
+            Self::Leaf(cmd) => cmd.validate_args_strict(args),
+            Self::Parent(parent_cmd) => parent_cmd.validate_args_strict(args),
+        }
+    }
+
     fn execute(&self, state: &mut Self::State, args: &[String]) -> Result<String> {
         match self {
             Self::Leaf(cmd) => cmd.execute(state, args),
@@ -89,31 +216,200 @@ impl<'a, S> BaseCommand for Command<'a, S> {
         }
     }
 
+    fn execute_output(&self, state: &mut Self::State, args: &[String]) -> Result<CommandOutput> {
+        match self {
+            Self::Leaf(cmd) => cmd.execute_output(state, args),
+            Self::Parent(parent_cmd) => parent_cmd.execute_output(state, args),
+        }
+    }
+
+    fn execute_with_input(
+        &self,
+        state: &mut Self::State,
+        args: &[String],
+        input: &str,
+    ) -> Result<String> {
+        match self {
+            Self::Leaf(cmd) => cmd.execute_with_input(state, args, input),
+            Self::Parent(parent_cmd) => parent_cmd.execute_with_input(state, args, input),
+        }
+    }
+
     fn help(&self) -> String {
         match self {
             Self::Leaf(cmd) => cmd.help(),
             Self::Parent(parent_cmd) => parent_cmd.help(),
         }
     }
+
+    fn since(&self) -> Option<String> {
+        match self {
+            Self::Leaf(cmd) => cmd.since(),
+            Self::Parent(parent_cmd) => parent_cmd.since(),
+        }
+    }
+
+    fn long_help(&self) -> Option<String> {
+        match self {
+            Self::Leaf(cmd) => cmd.long_help(),
+            Self::Parent(parent_cmd) => parent_cmd.long_help(),
+        }
+    }
+
+    fn is_hidden(&self) -> bool {
+        match self {
+            Self::Leaf(cmd) => cmd.is_hidden(),
+            Self::Parent(parent_cmd) => parent_cmd.is_hidden(),
+        }
+    }
+
+    fn category(&self) -> Option<String> {
+        match self {
+            Self::Leaf(cmd) => cmd.category(),
+            Self::Parent(parent_cmd) => parent_cmd.category(),
+        }
+    }
+
+    fn confirmation(&self) -> Option<String> {
+        match self {
+            Self::Leaf(cmd) => cmd.confirmation(),
+            Self::Parent(parent_cmd) => parent_cmd.confirmation(),
+        }
+    }
+
+    fn concurrent(&self, state: &mut Self::State, args: &[String]) -> Option<ConcurrentJob> {
+        match self {
+            Self::Leaf(cmd) => cmd.concurrent(state, args),
+            Self::Parent(parent_cmd) => parent_cmd.concurrent(state, args),
+        }
+    }
 }
 
+/// The work of executing a command, handed off to run on another thread, concurrently with other
+/// commands. See `BaseCommand::concurrent()`.
+pub type ConcurrentJob = Box<dyn FnOnce() -> Result<String> + Send>;
+
 /// Completion represents the result of an autocompletion for command arguments.
 ///
-/// There are two cases that case occur:
+/// There are a few cases that case occur:
 /// * `PartialArgCompletion` - The last argument is partially typed and can be completed to full.
-/// PartialArgCompletion contains the suffix which, when append to the partial argument,
-/// provides the full argument.
+///   PartialArgCompletion contains the suffix which, when append to the partial argument,
+///   provides the full argument.
 /// * `Possibilities` - The arguments are complete, and there are guesses as to what the next
-/// argument could be.
+///   argument could be.
+/// * `Replacement` - The last argument is replaced from `start` on, a byte offset within it, by
+///   one of the candidates. A `start` of 0 replaces the whole argument, e.g. to correct its case,
+///   while a `start` in the middle of it completes e.g. just the last component of a path.
+/// * `Scored` - Like `Replacement`, but each candidate carries a score, e.g. how recently it was
+///   used. Candidates are offered highest score first, and those of equal scores in their order.
 /// * `Nothing` - There are no completions to provide, either because there is no
-/// autocompletion, or because the command and its arguments are complete already.
+///   autocompletion, or because the command and its arguments are complete already.
 #[derive(Debug, PartialEq)]
 pub enum Completion {
     PartialArgCompletion(Vec<String>),
     Possibilities(Vec<String>),
+    Replacement {
+        start: usize,
+        candidates: Vec<String>,
+    },
+    Scored {
+        start: usize,
+        candidates: Vec<(String, i64)>,
+    },
     Nothing,
 }
 
+impl Completion {
+    /// Resolves the completion into the span of the last argument that it replaces, and the
+    /// candidates to replace it with.
+    ///
+    /// # Arguments
+    /// `arg` - The last argument, as typed up to the cursor, or empty if the cursor follows a
+    /// space.
+    ///
+    /// # Returns
+    /// `Option<(usize, Vec<String>)>` - The byte offset within `arg` from which the candidates
+    /// replace it, and the candidates. None if there is nothing to complete, or if the offset is
+    /// not within `arg`.
+    pub fn span(self, arg: &str) -> Option<(usize, Vec<String>)> {
+        match self {
+            Completion::PartialArgCompletion(suffixes) => Some((arg.len(), suffixes)),
+            Completion::Possibilities(candidates) => Some((arg.len(), candidates)),
+            Completion::Replacement { start, candidates } if arg.is_char_boundary(start) => {
+                Some((start, candidates))
+            }
+            Completion::Scored {
+                start,
+                mut candidates,
+            } if arg.is_char_boundary(start) => {
+                candidates.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+                Some((
+                    start,
+                    candidates
+                        .into_iter()
+                        .map(|(candidate, _)| candidate)
+                        .collect(),
+                ))
+            }
+            Completion::Replacement { .. } | Completion::Scored { .. } | Completion::Nothing => {
+                None
+            }
+        }
+    }
+
+    /// Completes the given partially typed argument, given the candidates it could be.
+    ///
+    /// # Arguments
+    /// `partial` - The partially typed argument.
+    /// `candidates` - The values that the argument could be.
+    ///
+    /// # Returns
+    /// `Completion` - A `PartialArgCompletion` of the remainders of the candidates that `partial`
+    /// is a prefix of, or `Nothing` if there are none.
+    pub fn from_partial(partial: &str, candidates: Vec<String>) -> Completion {
+        let suffixes: Vec<String> = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(partial) && candidate.len() > partial.len())
+            .map(|candidate| candidate[partial.len()..].to_string())
+            .collect();
+
+        if suffixes.is_empty() {
+            Completion::Nothing
+        } else {
+            Completion::PartialArgCompletion(suffixes)
+        }
+    }
+
+    /// Completes the given partially typed argument, given the candidates it could be, each with
+    /// a score by which to order them.
+    ///
+    /// # Arguments
+    /// `partial` - The partially typed argument.
+    /// `candidates` - The values that the argument could be, and their scores.
+    ///
+    /// # Returns
+    /// `Completion` - A `Scored` completion of the remainders of the candidates that `partial` is
+    /// a prefix of, or `Nothing` if there are none.
+    pub fn from_scored(partial: &str, candidates: Vec<(String, i64)>) -> Completion {
+        let suffixes: Vec<(String, i64)> = candidates
+            .into_iter()
+            .filter(|(candidate, _)| {
+                candidate.starts_with(partial) && candidate.len() > partial.len()
+            })
+            .map(|(candidate, score)| (candidate[partial.len()..].to_string(), score))
+            .collect();
+
+        if suffixes.is_empty() {
+            Completion::Nothing
+        } else {
+            Completion::Scored {
+                start: partial.len(),
+                candidates: suffixes,
+            }
+        }
+    }
+}
+
 /// BaseCommand is the lower-level command trait. It covers many of the behaviors one would expect
 /// from a shell command, e.g., a name (`name()`) or execution (`execute()`).
 ///
@@ -137,7 +433,17 @@ pub trait BaseCommand {
     /// `args` - The arguments to validate.
     fn validate_args(&self, args: &[String]) -> Result<()>;
 
-    // TODO: Execute should probably be returning something better than a Result<String>.
+    /// Validates the given arguments like `validate_args()`, but for shells in strict mode, where
+    /// arguments that the command does not declare should be rejected rather than ignored.
+    ///
+    /// By default, this is the same as `validate_args()`.
+    ///
+    /// # Arguments
+    /// `args` - The arguments to validate.
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        self.validate_args(args)
+    }
+
     // TODO: Execute should probably have &mut self.
     /// Executes the command.
     ///
@@ -150,6 +456,46 @@ pub trait BaseCommand {
     /// String that represents the output of the command.
     fn execute(&self, state: &mut Self::State, args: &[String]) -> Result<String>;
 
+    /// Executes the command, returning structured output, e.g. a table, which the shell renders
+    /// via its `Renderer`. This is what the shell calls, so commands that return structured output
+    /// should override it, and implement `execute()` by rendering it, e.g. via `PlainRenderer`.
+    ///
+    /// By default, this returns the output of `execute()` as text.
+    ///
+    /// # Arguments
+    /// `state` - The state to execute with.
+    /// `args` - The arguments to the command invocation.
+    ///
+    /// # Returns
+    /// `Result<CommandOutput>` - The result of the execution of this command.
+    fn execute_output(&self, state: &mut Self::State, args: &[String]) -> Result<CommandOutput> {
+        self.execute(state, args).map(CommandOutput::Text)
+    }
+
+    /// Executes the command with the output of the previous command of a pipeline as its input,
+    /// e.g. for `grep` in `history | grep foo`.
+    ///
+    /// By default, commands do not take input, and this returns an error.
+    ///
+    /// # Arguments
+    /// `state` - The state to execute with.
+    /// `args` - The arguments to the command invocation.
+    /// `input` - The output of the previous command.
+    ///
+    /// # Returns
+    /// `Result<String>` - The result of the execution of this command.
+    fn execute_with_input(
+        &self,
+        _state: &mut Self::State,
+        _args: &[String],
+        _input: &str,
+    ) -> Result<String> {
+        Err(ShiError::general(format!(
+            "'{}' does not take input from a pipe",
+            self.name()
+        )))
+    }
+
     /// Autocompletes a command, given arguments.
     ///
     /// The default implementation provides no autocompletion.
@@ -172,4 +518,54 @@ pub trait BaseCommand {
     fn help(&self) -> String {
         "".to_string()
     }
+
+    /// Returns the version in which this command was added, if known, e.g. `1.2.0`. It is shown
+    /// in detailed help and in exports of the command tree, so that users can tell whether their
+    /// version of a tool supports the command.
+    /// By default, returns None.
+    fn since(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns a longer description of this command than `help()`, e.g. with examples, which is
+    /// shown by `help <command>`.
+    /// By default, returns None.
+    fn long_help(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns whether this command is hidden, i.e., left out of `help`, `helptree` and
+    /// completions, e.g. for debugging or deprecated commands. Hidden commands can still be run.
+    /// By default, returns false.
+    fn is_hidden(&self) -> bool {
+        false
+    }
+
+    /// Returns the category of this command, e.g. `network`, under which `help` groups it.
+    /// By default, returns None, and the command is listed with the other uncategorized ones.
+    fn category(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the question to confirm before running this command, e.g. `Delete everything?`,
+    /// for commands that are destructive. See `Shell::set_assume_yes()`.
+    /// By default, returns None, and the command runs without confirmation.
+    fn confirmation(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the work of executing the command with the given, validated, arguments, for running
+    /// it on another thread, concurrently with other commands, e.g. by `parallel`. Commands whose
+    /// work needs nothing from the state, or only parts of it that can be sent to other threads,
+    /// e.g. an `Arc<Mutex<_>>`, can take those and return their work.
+    ///
+    /// By default, returns None, and the command is executed on the thread of the shell, one
+    /// command at a time.
+    ///
+    /// # Arguments
+    /// `state` - The state to take what the work needs from.
+    /// `args` - The arguments to the command invocation.
+    fn concurrent(&self, _state: &mut Self::State, _args: &[String]) -> Option<ConcurrentJob> {
+        None
+    }
 }
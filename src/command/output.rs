@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+
+use super::{BaseCommand, Completion};
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// OutputCommand re-displays the output of a previous evaluation, e.g. `output last` for the most
+/// recent one, or `output 3` for the one three evaluations ago. How many outputs are kept is set
+/// with `Shell::set_output_history_len()`.
+///
+/// Since `output` is itself an evaluation, its output is kept too; `output last` can therefore be
+/// repeated to show the same output again.
+pub struct OutputCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for OutputCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> OutputCommand<'a, S> {
+    /// Creates a new OutputCommand.
+    pub fn new() -> OutputCommand<'a, S> {
+        OutputCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    /// Parses how far back to look, where 1, or `last`, is the most recent evaluation.
+    fn nth(arg: &str) -> Result<usize> {
+        if arg == "last" {
+            return Ok(1);
+        }
+
+        match arg.parse::<usize>() {
+            Ok(nth) if nth > 0 => Ok(nth),
+            _ => Err(ShiError::general(format!(
+                "expected 'last' or a positive number, got '{}'",
+                arg
+            ))),
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for OutputCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "output"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let arg = match args.first() {
+            Some(arg) => arg,
+            None => return Err(ShiError::NoArgs),
+        };
+
+        if args.len() > 1 {
+            return Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            });
+        }
+
+        Self::nth(arg).map(|_| ())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let nth = Self::nth(&args[0])?;
+        shell.output(nth).ok_or_else(|| {
+            ShiError::general(format!("no output is kept from {} evaluation(s) ago", nth))
+        })
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        let last = vec![String::from("last")];
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(last),
+            ([arg], false) => Completion::from_partial(arg, last),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Re-displays the output of a previous command")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn recalls_outputs() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("first", |_, _| { Ok(String::from("one")) }))?;
+        shell.register(cmd!("second", |_, _| { Ok(String::from("two")) }))?;
+        shell.eval("first")?;
+        shell.eval("second")?;
+
+        assert_eq!(shell.eval("output last")?, "two");
+        assert_eq!(shell.eval("output 3")?, "one");
+        assert!(shell.eval("output 10").is_err());
+        assert!(shell.eval("output 0").is_err());
+        assert!(shell.eval("output").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn forgets_old_outputs() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("first", |_, _| { Ok(String::from("one")) }))?;
+        shell.register(cmd!("second", |_, _| { Ok(String::from("two")) }))?;
+        shell.set_output_history_len(1);
+        shell.eval("first")?;
+        shell.eval("second")?;
+
+        assert_eq!(shell.output(1), Some(String::from("two")));
+        assert_eq!(shell.output(2), None);
+        assert_eq!(
+            shell.invocation_context().output(1),
+            Some(String::from("two"))
+        );
+
+        Ok(())
+    }
+}
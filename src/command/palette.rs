@@ -0,0 +1,203 @@
+use std::marker::PhantomData;
+
+use super::{BaseCommand, Command};
+use crate::command_set::CommandSet;
+use crate::error::ShiError;
+use crate::prompt;
+use crate::shell::Shell;
+use crate::Result;
+
+/// The maximum number of matches offered for selection.
+const MAX_MATCHES: usize = 10;
+
+#[derive(Debug)]
+/// PaletteCommand fuzzy-searches over the paths and help text of every leaf command, and lets the
+/// user pick one of the matches, which is then placed on the next input line for the user to
+/// complete.
+///
+/// This is only available with the `palette` feature.
+pub struct PaletteCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for PaletteCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> PaletteCommand<'a, S> {
+    /// Creates a new PaletteCommand.
+    pub fn new() -> PaletteCommand<'a, S> {
+        PaletteCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    /// Collects the path & help text of every leaf command in the given set.
+    fn leaves<T>(shell: &Shell<S>, cmds: &CommandSet<T>, leaves: &mut Vec<(String, String)>) {
+        cmds.walk(|visit| {
+            if let Command::Leaf(_) = visit.cmd {
+                let help = shell.localized_help(visit.path, visit.cmd.help());
+                leaves.push((visit.path.join(" "), help));
+            }
+        });
+    }
+
+    /// Ranks the leaf commands of the given shell by how well they match the query, best first.
+    /// Commands that do not match at all are omitted.
+    fn rank(&self, shell: &Shell<S>, query: &str) -> Vec<(String, String)> {
+        let mut leaves = Vec::new();
+        Self::leaves(shell, &shell.cmds.borrow(), &mut leaves);
+        Self::leaves(shell, &shell.builtins, &mut leaves);
+
+        let usage = shell.usage.borrow();
+        let mut scored: Vec<(i64, (String, String))> = leaves
+            .into_iter()
+            .filter_map(|(path, help)| {
+                // Matches on the path are preferred over matches on the help text.
+                let score = fuzzy_score(query, &path)
+                    .map(|score| score + 1000)
+                    .or_else(|| fuzzy_score(query, &help))?;
+                // Frequently used commands are preferred over equally good matches, but never over
+                // a match with more matching characters.
+                let path_names: Vec<&str> = path.split(' ').collect();
+                let bonus = (usage.count(&path_names) * 10).min(99) as i64;
+                Some((score + bonus, (path, help)))
+            })
+            .collect();
+        // Sorting is stable, so equally good matches stay in tree order.
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        scored.into_iter().map(|(_, leaf)| leaf).collect()
+    }
+}
+
+/// Scores how well `query` fuzzily matches `candidate`, i.e., whether the characters of `query`
+/// appear in `candidate`, in order, ignoring case. Consecutive matched characters and matches at
+/// the start of words score higher.
+///
+/// # Returns
+/// `Option<i64>` - The score, or None if `query` does not match.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let i = next + candidate[next..].iter().position(|&c| c == q)?;
+
+        score += 1;
+        if prev_match == Some(i.wrapping_sub(1)) {
+            score += 5;
+        }
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        prev_match = Some(i);
+        next = i + 1;
+    }
+
+    // Shorter candidates are tighter matches.
+    Some(score * 100 - candidate.len() as i64)
+}
+
+impl<'a, S> BaseCommand for PaletteCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "palette"
+    }
+
+    fn validate_args(&self, _: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let query = args.join(" ");
+        let matches = self.rank(shell, &query);
+        if matches.is_empty() {
+            return Err(ShiError::general(format!("no commands match '{}'", query)));
+        }
+
+        let options: Vec<String> = matches
+            .iter()
+            .take(MAX_MATCHES)
+            .map(|(path, help)| format!("{} - {}", path, help))
+            .collect();
+        let selected = prompt::select("Matching commands:", &options)?;
+        let index = options
+            .iter()
+            .position(|option| option == selected)
+            .expect("selected an option that was not offered");
+
+        shell.set_next_input(&format!("{} ", matches[index].0));
+
+        Ok(String::new())
+    }
+
+    fn help(&self) -> String {
+        String::from("Fuzzy-searches for a command to run")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn fuzzy() {
+        assert!(fuzzy_score("tls", "config tls enable").is_some());
+        assert!(fuzzy_score("cte", "config tls enable").is_some());
+        assert!(fuzzy_score("TLS", "config tls enable").is_some());
+        assert!(fuzzy_score("slt", "config tls enable").is_none());
+        assert_eq!(fuzzy_score("", "anything"), Some(-8));
+
+        // Consecutive matches beat scattered ones.
+        assert!(fuzzy_score("tls", "tls") > fuzzy_score("tls", "t-l-s"));
+        // Word starts beat word middles.
+        assert!(fuzzy_score("e", "x enable") > fuzzy_score("e", "x table"));
+    }
+
+    #[test]
+    fn rank() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "config",
+            cmd!("tls", "Configures encryption", |_, _| Ok(String::new())),
+            cmd!("timeout", "Configures how long to wait", |_, _| Ok(
+                String::new()
+            )),
+        ))?;
+        shell.register(cmd!("dog", "Barks at strangers", |_, _| Ok(String::new())))?;
+
+        let palette = PaletteCommand::new();
+        let paths = |query: &str| -> Vec<String> {
+            palette
+                .rank(&shell, query)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect()
+        };
+
+        assert_eq!(paths("ctls")[0], "config tls");
+        // Path matches rank above help text matches.
+        assert_eq!(paths("strang")[0], "dog");
+        assert_eq!(paths("cto")[0], "config timeout");
+        assert!(paths("zzz").is_empty());
+        assert!(paths("").contains(&String::from("palette")));
+
+        // Frequently used commands come first among equally good matches.
+        assert_eq!(paths("config t")[0], "config tls");
+        shell.usage.borrow_mut().record(&["config", "timeout"]);
+        assert_eq!(paths("config t")[0], "config timeout");
+
+        Ok(())
+    }
+}
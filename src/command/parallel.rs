@@ -0,0 +1,295 @@
+use std::marker::PhantomData;
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{BaseCommand, ConcurrentJob, ExternalCommand};
+use crate::error::ShiError;
+use crate::shell::{ConcurrentRun, Shell};
+use crate::tokenizer::{DefaultTokenizer, Tokenizer};
+use crate::Result;
+
+#[derive(Debug)]
+/// ParallelCommand runs several independent commands at once, e.g.
+/// `parallel "server status" "db status"`, and gathers their outputs in the order they were given,
+/// each marked with whether its command succeeded.
+///
+/// Commands run concurrently if they can: external commands, i.e., those that run an executable,
+/// run as processes in the background, and commands that hand their work off to another thread,
+/// see `BaseCommand::concurrent()`, run on a pool of threads. The remaining commands share the
+/// shell's state, and run one after another on the shell's thread in the meantime.
+pub struct ParallelCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+/// A command given to `parallel`, and how far along it is.
+enum Job<S> {
+    /// An external command that is running in the background.
+    Running(ExternalCommand<S>, process::Child),
+    /// A command whose work is running on the pool.
+    Concurrent(ConcurrentRun),
+    /// A command that is run by the shell once its turn comes.
+    Pending,
+    /// A command that has already finished, e.g. because it failed to start.
+    Done(Result<String>),
+}
+
+/// How many threads the work of commands runs on at most. The work is typically waiting on the
+/// network, e.g. for health checks, rather than using the CPU, so this is not the number of CPUs.
+const MAX_WORKERS: usize = 16;
+
+/// Runs the given work on a pool of threads, sending the result of each along with its index. The
+/// returned receiver is closed once all of it is done.
+fn run_on_pool(work: Vec<(usize, ConcurrentJob)>) -> Receiver<(usize, Result<String>)> {
+    let (results, received) = mpsc::channel();
+    let workers = work.len().min(MAX_WORKERS);
+    let queue = Arc::new(Mutex::new(work));
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let results = results.clone();
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop();
+            match next {
+                Some((i, job)) => {
+                    // Nobody is left to tell if the receiver is gone.
+                    let _ = results.send((i, job()));
+                }
+                None => return,
+            }
+        });
+    }
+
+    received
+}
+
+impl<'a, S> Default for ParallelCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> ParallelCommand<'a, S> {
+    /// Creates a new ParallelCommand.
+    pub fn new() -> ParallelCommand<'a, S> {
+        ParallelCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for ParallelCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "parallel"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.iter().all(|arg| arg.is_empty()) {
+            return Err(ShiError::NoArgs);
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<'a, S>, args: &[String]) -> Result<String> {
+        // Each command is quoted, but the quotes were split on spaces along with everything else.
+        let joined = args.join(" ");
+        let lines = DefaultTokenizer::new(vec!['\'', '"'])
+            .tokenize(&joined)
+            .tokens;
+
+        let mut jobs: Vec<Job<S>> = Vec::with_capacity(lines.len());
+        let mut work = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let job = if let Some((cmd, args)) = shell.external_invocation(line) {
+                match cmd.spawn(&args) {
+                    Ok(child) => Job::Running(cmd, child),
+                    Err(err) => Job::Done(Err(err)),
+                }
+            } else {
+                match shell.start_concurrent(line) {
+                    Some(Ok((job, run))) => {
+                        work.push((i, job));
+                        Job::Concurrent(run)
+                    }
+                    Some(Err(err)) => Job::Done(Err(err)),
+                    None => Job::Pending,
+                }
+            };
+            jobs.push(job);
+        }
+
+        // The commands that cannot leave the shell's thread run while the others do.
+        let pool = run_on_pool(work);
+        let mut results: Vec<Option<Result<String>>> = Vec::with_capacity(lines.len());
+        for (line, job) in lines.iter().zip(&jobs) {
+            results.push(match job {
+                Job::Pending => Some(shell.eval_without_history(line)),
+                _ => None,
+            });
+        }
+        for (i, res) in pool {
+            results[i] = Some(res);
+        }
+
+        let mut failures = 0;
+        let mut report = Vec::new();
+        for ((line, job), res) in lines.iter().zip(jobs).zip(results) {
+            let res = match job {
+                Job::Running(cmd, child) => cmd.collect(child),
+                Job::Concurrent(run) => {
+                    let res = res.unwrap_or_else(|| {
+                        Err(ShiError::general("the command panicked on another thread"))
+                    });
+                    shell.finish_concurrent(run, &res);
+                    res
+                }
+                Job::Pending => res.expect("pending commands are evaluated in order"),
+                Job::Done(res) => res,
+            };
+
+            match res {
+                Ok(output) => report.push(format!("[ok] {}\n{}", line, output)),
+                Err(err) => {
+                    failures += 1;
+                    report.push(format!("[failed] {}\n{}", line, err));
+                }
+            }
+        }
+
+        let report = report.join("\n");
+        if failures > 0 {
+            return Err(ShiError::general(format!(
+                "{} of {} commands failed\n{}",
+                failures,
+                lines.len(),
+                report
+            )));
+        }
+
+        Ok(report)
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs independent commands at once and gathers their outputs")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn gathers_outputs_in_order() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+
+        assert_eq!(
+            shell.eval("parallel dog \"echo hi there\"")?,
+            "[ok] dog\nwoof\n[ok] echo hi there\nhi there"
+        );
+
+        let err = shell.eval("parallel dog cat").unwrap_err().to_string();
+        assert!(err.starts_with("error: 1 of 2 commands failed\n[ok] dog\nwoof\n[failed] cat\n"));
+
+        assert!(shell.eval("parallel").is_err());
+
+        Ok(())
+    }
+
+    /// Naps for a while on whichever thread its work runs on, and counts its naps in the state.
+    struct NapCommand;
+
+    impl BaseCommand for NapCommand {
+        type State = Arc<Mutex<u32>>;
+
+        fn name(&self) -> &str {
+            "nap"
+        }
+
+        fn validate_args(&self, _: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        fn execute(&self, naps: &mut Arc<Mutex<u32>>, args: &[String]) -> Result<String> {
+            let job = self.concurrent(naps, args).unwrap();
+            job()
+        }
+
+        fn concurrent(&self, naps: &mut Arc<Mutex<u32>>, args: &[String]) -> Option<ConcurrentJob> {
+            let naps = naps.clone();
+            let name = args.join(" ");
+            Some(Box::new(move || {
+                thread::sleep(std::time::Duration::from_millis(500));
+                *naps.lock().unwrap() += 1;
+                Ok(format!("rested {}", name))
+            }))
+        }
+    }
+
+    #[test]
+    fn runs_commands_concurrently() -> Result<()> {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use std::time::{Duration, Instant};
+
+        let mut shell = Shell::new_with_state("| ", Arc::new(Mutex::new(0)));
+        shell.register(crate::command::Command::new_leaf(NapCommand))?;
+        shell.register(cmd!("count", |naps: &mut Arc<Mutex<u32>>, _| Ok(naps
+            .lock()
+            .unwrap()
+            .to_string())))?;
+        let hooked = Rc::new(RefCell::new(Vec::new()));
+        let log = hooked.clone();
+        shell.add_post_hook(move |path, _, _| log.borrow_mut().push(path.to_string()));
+
+        let started = Instant::now();
+        let report = shell.eval("parallel 'nap a' 'nap b' count 'nap c'")?;
+        assert!(started.elapsed() < Duration::from_millis(1400));
+        assert!(report.starts_with("[ok] nap a\nrested a\n[ok] nap b\nrested b\n[ok] count\n"));
+        assert!(report.ends_with("[ok] nap c\nrested c"));
+        assert_eq!(*shell.state().lock().unwrap(), 3);
+        // Hooks run for the commands that ran on other threads too, in order.
+        assert_eq!(
+            *hooked.borrow(),
+            vec!["count", "nap", "nap", "nap", "parallel"]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_external_commands_concurrently() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, Instant};
+
+        let dir = std::env::temp_dir().join(format!("shi-parallel-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let script = dir.join("mytool-nap");
+        fs::write(&script, "#!/bin/sh\nsleep 1\necho \"rested $*\"\n")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+        let mut shell = Shell::new("| ");
+        shell.set_external_commands(&dir, "mytool");
+
+        let started = Instant::now();
+        assert_eq!(
+            shell.eval("parallel 'nap a' 'nap b' 'nap c'")?,
+            "[ok] nap a\nrested a\n[ok] nap b\nrested b\n[ok] nap c\nrested c"
+        );
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
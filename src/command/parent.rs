@@ -1,13 +1,16 @@
-use super::{BaseCommand, Command};
+use super::{BaseCommand, Command, ConcurrentJob};
 use crate::command_set::CommandSet;
 use crate::error::ShiError;
+use crate::output::CommandOutput;
 use crate::Result;
 
 /// ParentCommand represents a command with subcommands. It has a name, but it does not execute
-/// anything itself. It dispatches to the appropriate child command, if one exists.
+/// anything itself. It dispatches to the appropriate child command, if one exists, which also
+/// validates the arguments that follow it, e.g. against the arity declared for a leaf.
 pub struct ParentCommand<'a, S> {
     name: &'a str,
     help: &'a str,
+    since: Option<&'a str>,
     sub_cmds: CommandSet<'a, S>,
 }
 
@@ -25,6 +28,7 @@ impl<'a, S> ParentCommand<'a, S> {
         ParentCommand {
             name,
             help: "",
+            since: None,
             sub_cmds: command_set,
         }
     }
@@ -46,6 +50,7 @@ impl<'a, S> ParentCommand<'a, S> {
         ParentCommand {
             name,
             help,
+            since: None,
             sub_cmds: command_set,
         }
     }
@@ -56,8 +61,8 @@ impl<'a, S> ParentCommand<'a, S> {
     ///
     /// # Arguments
     /// `args` - The arguments that this command was invoked with.
-    fn get_sub_cmd_for_args(&self, args: &[String]) -> Result<&Command<S>> {
-        let first_arg = match args.get(0) {
+    fn get_sub_cmd_for_args(&self, args: &[String]) -> Result<&Command<'a, S>> {
+        let first_arg = match args.first() {
             Some(arg) => arg,
             None => return Err(ShiError::NoArgs),
         };
@@ -67,33 +72,75 @@ impl<'a, S> ParentCommand<'a, S> {
             None => {
                 return Err(ShiError::InvalidSubCommand {
                     got: first_arg.to_string(),
-                    expected: self
-                        .sub_commands()
-                        .iter()
-                        .map(|cmd| cmd.name().to_string())
-                        .collect::<Vec<String>>(),
+                    expected: self.sub_commands().names(),
                 })
             }
         }
     }
 
     /// Returns a `CommandSet` of the child commands under this `ParentCommand`.
-    pub fn sub_commands(&self) -> &CommandSet<S> {
+    pub fn sub_commands(&self) -> &CommandSet<'a, S> {
         &self.sub_cmds
     }
-}
 
-impl<'a, S> BaseCommand for ParentCommand<'a, S> {
-    type State = S;
+    /// Returns a mutable `CommandSet` of the child commands under this `ParentCommand`.
+    pub(crate) fn sub_commands_mut(&mut self) -> &mut CommandSet<'a, S> {
+        &mut self.sub_cmds
+    }
 
-    fn name(&self) -> &str {
-        self.name
+    /// Adds a child command that is only constructed once it is first looked up. See
+    /// `CommandSet::add_lazy()`.
+    ///
+    /// # Arguments
+    /// `name` - The name of the child command.
+    /// `factory` - Constructs the child command.
+    pub fn add_lazy<F>(&mut self, name: &str, factory: F)
+    where
+        F: FnOnce() -> Command<'a, S> + 'a,
+    {
+        self.sub_cmds.add_lazy(name, factory);
     }
 
-    fn validate_args(&self, args: &[String]) -> Result<()> {
+    /// Sets the help message of this command.
+    ///
+    /// # Arguments
+    /// `help` - The new help message to use.
+    pub fn set_help(&mut self, help: &'a str) {
+        self.help = help;
+    }
+
+    /// Sets the version in which this command was added. See `BaseCommand::since()`.
+    ///
+    /// # Arguments
+    /// `since` - The version, e.g. `1.2.0`.
+    pub fn set_since(&mut self, since: &'a str) {
+        self.since = Some(since);
+    }
+
+    /// Converts every child command via `f`, keeping the name, help and version of this command.
+    /// See `CommandSet::map_commands()`.
+    ///
+    /// # Arguments
+    /// `f` - Converts a child command.
+    pub(crate) fn map_commands<T, F>(self, f: F) -> ParentCommand<'a, T>
+    where
+        F: Fn(Command<'a, S>) -> Command<'a, T> + Clone + 'a,
+        S: 'a,
+    {
+        ParentCommand {
+            name: self.name,
+            help: self.help,
+            since: self.since,
+            sub_cmds: self.sub_cmds.map_commands(f),
+        }
+    }
+
+    /// Validates the given arguments by dispatching to the appropriate child command, strictly if
+    /// `strict` is set. See `BaseCommand::validate_args_strict()`.
+    fn validate_args_with(&self, args: &[String], strict: bool) -> Result<()> {
         if let Some(first_arg) = args.first() {
             // If args given...
-            if self.sub_commands().len() == 0 {
+            if self.sub_commands().is_empty() {
                 // But we expect no args...
                 return Err(ShiError::InvalidSubCommand {
                     got: first_arg.clone(),
@@ -101,13 +148,18 @@ impl<'a, S> BaseCommand for ParentCommand<'a, S> {
                 });
             } else {
                 // If we expect args...
-                // This will error if we do not find the command, but we don't actually care about the
-                // particular command we find here.
-                self.get_sub_cmd_for_args(args)?;
+                // This will error if we do not find the command. If we do find it, it gets to
+                // validate the rest of the args itself.
+                let sub_cmd = self.get_sub_cmd_for_args(args)?;
+                if strict {
+                    sub_cmd.validate_args_strict(&args[1..])?;
+                } else {
+                    sub_cmd.validate_args(&args[1..])?;
+                }
             }
         } else {
             // If no args given...
-            if self.sub_commands().len() != 0 {
+            if !self.sub_commands().is_empty() {
                 // But we expect args...
                 return Err(ShiError::NoArgs);
             }
@@ -115,6 +167,22 @@ impl<'a, S> BaseCommand for ParentCommand<'a, S> {
 
         Ok(())
     }
+}
+
+impl<'a, S> BaseCommand for ParentCommand<'a, S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        self.validate_args_with(args, false)
+    }
+
+    fn validate_args_strict(&self, args: &[String]) -> Result<()> {
+        self.validate_args_with(args, true)
+    }
 
     fn execute(&self, state: &mut S, args: &[String]) -> Result<String> {
         let sub_cmd = self.get_sub_cmd_for_args(args)?;
@@ -122,7 +190,29 @@ impl<'a, S> BaseCommand for ParentCommand<'a, S> {
         sub_cmd.execute(state, &args[1..].to_vec())
     }
 
+    fn execute_output(&self, state: &mut S, args: &[String]) -> Result<CommandOutput> {
+        let sub_cmd = self.get_sub_cmd_for_args(args)?;
+
+        sub_cmd.execute_output(state, &args[1..])
+    }
+
+    fn execute_with_input(&self, state: &mut S, args: &[String], input: &str) -> Result<String> {
+        let sub_cmd = self.get_sub_cmd_for_args(args)?;
+
+        sub_cmd.execute_with_input(state, &args[1..], input)
+    }
+
+    fn concurrent(&self, state: &mut S, args: &[String]) -> Option<ConcurrentJob> {
+        let sub_cmd = self.get_sub_cmd_for_args(args).ok()?;
+
+        sub_cmd.concurrent(state, &args[1..])
+    }
+
     fn help(&self) -> String {
         self.help.to_string()
     }
+
+    fn since(&self) -> Option<String> {
+        self.since.map(String::from)
+    }
 }
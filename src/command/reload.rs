@@ -0,0 +1,55 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// ReloadCommand reloads the configuration of the shell at runtime. See `Shell::reload()`.
+pub struct ReloadCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for ReloadCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> ReloadCommand<'a, S> {
+    /// Creates a new ReloadCommand.
+    pub fn new() -> ReloadCommand<'a, S> {
+        ReloadCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for ReloadCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "reload"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if !args.is_empty() {
+            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<'a, S>, _: &[String]) -> Result<String> {
+        if shell.reload()? {
+            Ok(String::from("reloaded"))
+        } else {
+            Ok(String::from("nothing to reload"))
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Reloads the shell's configuration")
+    }
+}
@@ -0,0 +1,238 @@
+use std::marker::PhantomData;
+use std::thread;
+use std::time::Duration;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::{Shell, Verbosity};
+use crate::Result;
+
+#[derive(Debug)]
+/// RetryCommand runs the command it is given, e.g. `retry 3 server status`, and re-runs it if it
+/// fails, up to the given number of attempts in total. This is useful for commands that talk to
+/// flaky remote services.
+///
+/// The delay between attempts doubles after every failed attempt, up to a maximum. Each failed
+/// attempt is reported as an error of the shell, e.g. on stderr, unless the shell is quiet. If
+/// every attempt fails, the error of the last one is returned.
+pub struct RetryCommand<'a, S> {
+    base_delay: Duration,
+    max_delay: Duration,
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for RetryCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> RetryCommand<'a, S> {
+    /// Creates a new RetryCommand, which waits half a second before its first retry, and at most
+    /// 30 seconds between any two attempts.
+    pub fn new() -> RetryCommand<'a, S> {
+        RetryCommand::new_with_delay(Duration::from_millis(500))
+    }
+
+    /// Creates a new RetryCommand with the given delay before its first retry.
+    ///
+    /// # Arguments
+    /// `base_delay` - How long to wait before the first retry. Later retries wait twice as long
+    /// as the one before, up to 30 seconds.
+    pub fn new_with_delay(base_delay: Duration) -> RetryCommand<'a, S> {
+        RetryCommand {
+            base_delay,
+            max_delay: Duration::from_secs(30),
+            _phantom: &PhantomData,
+        }
+    }
+
+    /// Sets the longest delay between two attempts, which the doubling delay is capped at.
+    ///
+    /// # Arguments
+    /// `max_delay` - The longest delay to wait.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> RetryCommand<'a, S> {
+        self.max_delay = max_delay;
+        self
+    }
+
+    fn attempts(arg: &str) -> Result<u32> {
+        match arg.parse::<u32>() {
+            Ok(attempts) if attempts > 0 => Ok(attempts),
+            _ => Err(ShiError::general(format!(
+                "expected a positive number of attempts, got '{}'",
+                arg
+            ))),
+        }
+    }
+
+    /// Runs the given line until it succeeds or the attempts run out, reporting failed attempts
+    /// through the shell.
+    fn run(&self, shell: &mut Shell<S>, attempts: u32, line: &str) -> Result<String> {
+        let mut delay = self.base_delay.min(self.max_delay);
+        let mut attempt = 1;
+        loop {
+            let err = match shell.eval_without_history(line) {
+                Ok(output) => return Ok(output),
+                Err(err) => err,
+            };
+
+            let quiet = shell.verbosity() == Verbosity::Quiet;
+            if attempt == attempts {
+                if !quiet {
+                    shell.write_error(&format!("attempt {}/{} failed: {}", attempt, attempts, err));
+                }
+                return Err(err);
+            }
+
+            if !quiet {
+                shell.write_error(&format!(
+                    "attempt {}/{} failed: {}; retrying in {:?}",
+                    attempt, attempts, err, delay
+                ));
+            }
+            thread::sleep(delay);
+            delay = delay.saturating_mul(2).min(self.max_delay);
+            attempt += 1;
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for RetryCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "retry"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args.len() {
+            0 => Err(ShiError::NoArgs),
+            1 => Err(ShiError::general(format!(
+                "expected a command to run after '{}'",
+                args[0]
+            ))),
+            _ => Self::attempts(&args[0]).map(|_| ()),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let attempts = Self::attempts(&args[0])?;
+        self.run(shell, attempts, &args[1..].join(" "))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command, retrying it with backoff if it fails")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use crate::cmd;
+    use crate::output::OutputSink;
+
+    use pretty_assertions::assert_eq;
+
+    /// Collects the errors written by the shell.
+    struct Reports(Rc<RefCell<Vec<String>>>);
+
+    impl OutputSink for Reports {
+        fn write_output(&mut self, _: &str) {}
+
+        fn write_error(&mut self, error: &str) {
+            self.0.borrow_mut().push(error.to_string());
+        }
+    }
+
+    type Fixture<'a> = (Shell<'a, ()>, Rc<Cell<u32>>, Rc<RefCell<Vec<String>>>);
+
+    fn make_shell<'a>(failures: u32) -> Result<Fixture<'a>> {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new("| ");
+        shell.set_output_sink(Box::new(Reports(reports.clone())));
+        shell.register(cmd!("flaky", move |_, _| {
+            counter.set(counter.get() + 1);
+            if counter.get() <= failures {
+                Err(ShiError::general("unavailable"))
+            } else {
+                Ok(String::from("ok"))
+            }
+        }))?;
+
+        Ok((shell, calls, reports))
+    }
+
+    #[test]
+    fn succeeds_after_retrying() -> Result<()> {
+        let (mut shell, calls, reports) = make_shell(2)?;
+
+        let retry = RetryCommand::new_with_delay(Duration::ZERO);
+        assert_eq!(retry.run(&mut shell, 3, "flaky")?, "ok");
+        assert_eq!(calls.get(), 3);
+        assert_eq!(
+            *reports.borrow(),
+            vec![
+                "attempt 1/3 failed: error: unavailable; retrying in 0ns",
+                "attempt 2/3 failed: error: unavailable; retrying in 0ns",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn gives_up() -> Result<()> {
+        let (mut shell, calls, reports) = make_shell(5)?;
+
+        let retry = RetryCommand::new_with_delay(Duration::ZERO);
+        assert!(retry.run(&mut shell, 2, "flaky").is_err());
+        assert_eq!(calls.get(), 2);
+        assert_eq!(
+            reports.borrow().last().map(String::as_str),
+            Some("attempt 2/2 failed: error: unavailable")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn caps_the_delay() -> Result<()> {
+        let (mut shell, _, reports) = make_shell(3)?;
+
+        let retry = RetryCommand::new_with_delay(Duration::from_millis(2))
+            .with_max_delay(Duration::from_millis(3));
+        assert_eq!(retry.run(&mut shell, 4, "flaky")?, "ok");
+        assert_eq!(
+            *reports.borrow(),
+            vec![
+                "attempt 1/4 failed: error: unavailable; retrying in 2ms",
+                "attempt 2/4 failed: error: unavailable; retrying in 3ms",
+                "attempt 3/4 failed: error: unavailable; retrying in 3ms",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn validates_attempts() {
+        let retry: RetryCommand<()> = RetryCommand::new();
+        assert!(retry
+            .validate_args(&[String::from("0"), String::from("flaky")])
+            .is_err());
+        assert!(retry
+            .validate_args(&[String::from("three"), String::from("flaky")])
+            .is_err());
+        assert!(retry.validate_args(&[String::from("3")]).is_err());
+        assert!(retry
+            .validate_args(&[String::from("3"), String::from("flaky")])
+            .is_ok());
+    }
+}
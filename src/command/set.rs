@@ -0,0 +1,374 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use super::{BaseCommand, Completion};
+use crate::error::ShiError;
+use crate::settings::{SettingKind, Settings};
+use crate::shell::{Shell, Verbosity};
+use crate::Result;
+
+/// ShellOption is an option of the shell itself, as opposed to one registered by the embedder via
+/// `Shell::options()`.
+pub(crate) struct ShellOption<'a, S> {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: SettingKind,
+    pub get: fn(&Shell<'a, S>) -> String,
+    pub set: fn(&mut Shell<'a, S>, &str) -> Result<()>,
+}
+
+fn on_off(on: bool) -> String {
+    String::from(if on { "on" } else { "off" })
+}
+
+/// Returns the options of the shell itself, in the order they are listed by `show options`.
+pub(crate) fn shell_options<'a, S>() -> Vec<ShellOption<'a, S>> {
+    let toggle = || SettingKind::Enum(vec![String::from("on"), String::from("off")]);
+
+    vec![
+        ShellOption {
+            name: "verbosity",
+            help: "How much is printed beyond the output of commands",
+            kind: SettingKind::Enum(Verbosity::all().iter().map(|v| v.to_string()).collect()),
+            get: |shell| shell.verbosity().to_string(),
+            set: |shell, value| {
+                shell.set_verbosity(value.parse()?);
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "plain-output",
+            help: "Whether output is stripped of colors",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_plain_output()),
+            set: |shell, value| {
+                shell.set_plain_output(value == "on");
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "screen-reader",
+            help: "Whether output is friendly to screen readers",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_screen_reader()),
+            set: |shell, value| {
+                shell.set_screen_reader(value == "on");
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "autocorrect",
+            help: "Whether mistyped commands are offered a correction",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_autocorrect()),
+            set: |shell, value| {
+                shell.set_autocorrect(value == "on");
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "inline-help",
+            help: "Whether lines ending in '?' list their valid continuations",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_inline_help()),
+            set: |shell, value| {
+                shell.set_inline_help(value == "on");
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "subcommand-chooser",
+            help: "Whether bare parent commands offer to choose a subcommand",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_subcommand_chooser()),
+            set: |shell, value| {
+                shell.set_subcommand_chooser(value == "on");
+                Ok(())
+            },
+        },
+        ShellOption {
+            name: "strict",
+            help: "Whether undeclared arguments are rejected",
+            kind: toggle(),
+            get: |shell| on_off(shell.is_strict()),
+            set: |shell, value| {
+                shell.set_strict(value == "on");
+                Ok(())
+            },
+        },
+    ]
+}
+
+#[derive(Debug)]
+/// SetCommand sets options at runtime, e.g. `set verbosity quiet`.
+///
+/// Besides the options registered by the embedder via `Shell::options()`, the supported options
+/// are:
+/// * `verbosity` - One of `quiet`, `normal` or `verbose`. See `Verbosity`.
+/// * `plain-output` - Either `on` or `off`. See `Shell::set_plain_output()`.
+/// * `screen-reader` - Either `on` or `off`. See `Shell::set_screen_reader()`.
+/// * `autocorrect` - Either `on` or `off`. See `Shell::set_autocorrect()`.
+/// * `inline-help` - Either `on` or `off`. See `Shell::set_inline_help()`.
+/// * `subcommand-chooser` - Either `on` or `off`. See `Shell::set_subcommand_chooser()`.
+/// * `strict` - Either `on` or `off`. See `Shell::set_strict()`.
+pub struct SetCommand<'a, S> {
+    options: Rc<RefCell<Settings>>,
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for SetCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> SetCommand<'a, S> {
+    /// Creates a new SetCommand, that only sets the options of the shell itself.
+    pub fn new() -> SetCommand<'a, S> {
+        SetCommand::new_with_options(Rc::new(RefCell::new(Settings::new())))
+    }
+
+    /// Creates a new SetCommand, that also sets the given options.
+    ///
+    /// # Arguments
+    /// `options` - The options registered by the embedder. See `Shell::options()`.
+    pub fn new_with_options(options: Rc<RefCell<Settings>>) -> SetCommand<'a, S> {
+        SetCommand {
+            options,
+            _phantom: &PhantomData,
+        }
+    }
+
+    fn options(&self) -> Vec<String> {
+        shell_options::<S>()
+            .iter()
+            .map(|option| option.name.to_string())
+            .chain(self.options.borrow().names())
+            .collect()
+    }
+
+    fn kind(&self, option: &str) -> Option<SettingKind> {
+        match shell_options::<S>().into_iter().find(|o| o.name == option) {
+            Some(option) => Some(option.kind),
+            None => self
+                .options
+                .borrow()
+                .setting(option)
+                .map(|setting| setting.kind.clone()),
+        }
+    }
+
+    fn values(&self, option: &str) -> Vec<String> {
+        self.kind(option)
+            .map_or_else(Vec::new, |kind| kind.values())
+    }
+}
+
+impl<'a, S> BaseCommand for SetCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "set"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let option = match args.first() {
+            Some(option) => option,
+            None => return Err(ShiError::NoArgs),
+        };
+
+        if !self.options().contains(option) {
+            return Err(ShiError::InvalidSubCommand {
+                got: option.clone(),
+                expected: self.options(),
+            });
+        }
+
+        match args.len() {
+            1 => Err(ShiError::general(format!(
+                "expected a value for '{}', one of {:?}",
+                option,
+                self.values(option)
+            ))),
+            2 => Ok(()),
+            _ => Err(ShiError::ExtraArgs {
+                got: args[2..].to_vec(),
+            }),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<'a, S>, args: &[String]) -> Result<String> {
+        let (option, value) = (&args[0], &args[1]);
+        match shell_options().into_iter().find(|o| o.name == option) {
+            Some(shell_option) => {
+                shell_option.kind.parse(value)?;
+                (shell_option.set)(shell, value)?;
+            }
+            None => self.options.borrow_mut().set(option, value)?,
+        }
+
+        Ok(format!("{} set to {}", option, value))
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(self.options()),
+            ([option], false) => Completion::from_partial(option, self.options()),
+            ([option], true) => Completion::Possibilities(self.values(option)),
+            ([option, value], false) => Completion::from_partial(value, self.values(option)),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Sets an option of the shell")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn set_verbosity() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        let set_cmd = SetCommand::new();
+
+        set_cmd.validate_args(&args(&["verbosity", "quiet"]))?;
+        assert_eq!(
+            set_cmd.execute(&mut shell, &args(&["verbosity", "quiet"]))?,
+            "verbosity set to quiet"
+        );
+        assert_eq!(shell.verbosity(), Verbosity::Quiet);
+
+        assert!(set_cmd
+            .execute(&mut shell, &args(&["verbosity", "loud"]))
+            .is_err());
+        assert_eq!(shell.verbosity(), Verbosity::Quiet);
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_plain_output() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        let set_cmd = SetCommand::new();
+
+        set_cmd.execute(&mut shell, &args(&["plain-output", "on"]))?;
+        assert!(shell.is_plain_output());
+        set_cmd.execute(&mut shell, &args(&["plain-output", "off"]))?;
+        assert!(!shell.is_plain_output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_screen_reader() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        let set_cmd = SetCommand::new();
+
+        set_cmd.execute(&mut shell, &args(&["screen-reader", "on"]))?;
+        assert!(shell.is_screen_reader());
+        set_cmd.execute(&mut shell, &args(&["screen-reader", "off"]))?;
+        assert!(!shell.is_screen_reader());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_toggles() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        let set_cmd = SetCommand::new();
+
+        set_cmd.execute(&mut shell, &args(&["autocorrect", "off"]))?;
+        assert!(!shell.is_autocorrect());
+        set_cmd.execute(&mut shell, &args(&["inline-help", "on"]))?;
+        assert!(shell.is_inline_help());
+        set_cmd.execute(&mut shell, &args(&["subcommand-chooser", "on"]))?;
+        assert!(shell.is_subcommand_chooser());
+        assert!(set_cmd
+            .execute(&mut shell, &args(&["subcommand-chooser", "yes"]))
+            .is_err());
+        assert!(shell.is_subcommand_chooser());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_registered_options() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell
+            .options()
+            .borrow_mut()
+            .register_bool("dry-run", "Whether to only pretend", false)?;
+
+        assert_eq!(shell.eval("set dry-run true")?, "dry-run set to true");
+        assert_eq!(shell.options().borrow().get_bool("dry-run"), Some(true));
+        assert!(shell.eval("set dry-run maybe").is_err());
+
+        let set_cmd: SetCommand<()> = SetCommand::new_with_options(shell.options());
+        assert_eq!(
+            set_cmd.autocomplete(vec!["dry"], false),
+            Completion::PartialArgCompletion(args(&["-run"]))
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["dry-run"], true),
+            Completion::Possibilities(args(&["true", "false"]))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_args() {
+        let set_cmd: SetCommand<()> = SetCommand::new();
+
+        assert!(set_cmd.validate_args(&args(&[])).is_err());
+        assert!(set_cmd.validate_args(&args(&["colors", "on"])).is_err());
+        assert!(set_cmd.validate_args(&args(&["verbosity"])).is_err());
+        assert!(set_cmd
+            .validate_args(&args(&["verbosity", "quiet", "please"]))
+            .is_err());
+    }
+
+    #[test]
+    fn autocomplete() {
+        let set_cmd: SetCommand<()> = SetCommand::new();
+
+        assert_eq!(
+            set_cmd.autocomplete(vec![], true),
+            Completion::Possibilities(args(&[
+                "verbosity",
+                "plain-output",
+                "screen-reader",
+                "autocorrect",
+                "inline-help",
+                "subcommand-chooser",
+                "strict"
+            ]))
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["verb"], false),
+            Completion::PartialArgCompletion(args(&["osity"]))
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["verbosity"], true),
+            Completion::Possibilities(args(&["quiet", "normal", "verbose"]))
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["plain-output", "o"], false),
+            Completion::PartialArgCompletion(args(&["n", "ff"]))
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["plain-output", "on"], true),
+            Completion::Nothing
+        );
+    }
+}
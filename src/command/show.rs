@@ -0,0 +1,133 @@
+use std::marker::PhantomData;
+
+use super::set::shell_options;
+use super::{BaseCommand, Completion};
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// ShowCommand shows the state of the shell. Currently, the only thing it can show is `options`,
+/// which lists every option that can be changed with `set`, along with its current value.
+pub struct ShowCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for ShowCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> ShowCommand<'a, S> {
+    /// Creates a new ShowCommand.
+    pub fn new() -> ShowCommand<'a, S> {
+        ShowCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    fn topics(&self) -> Vec<String> {
+        vec![String::from("options")]
+    }
+}
+
+impl<'a, S> BaseCommand for ShowCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "show"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let topic = match args.first() {
+            Some(topic) => topic,
+            None => return Err(ShiError::NoArgs),
+        };
+
+        if !self.topics().contains(topic) {
+            return Err(ShiError::InvalidSubCommand {
+                got: topic.clone(),
+                expected: self.topics(),
+            });
+        }
+
+        if args.len() > 1 {
+            return Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<'a, S>, _: &[String]) -> Result<String> {
+        let mut lines: Vec<String> = shell_options()
+            .iter()
+            .map(|option| {
+                format!(
+                    "{} = {} - {}",
+                    option.name,
+                    (option.get)(shell),
+                    option.help
+                )
+            })
+            .collect();
+        lines.extend(shell.options().borrow().settings().iter().map(|setting| {
+            format!(
+                "{} = {} - {}",
+                setting.name, setting.value, setting.description
+            )
+        }));
+
+        Ok(lines.join("\n"))
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(self.topics()),
+            ([topic], false) => Completion::from_partial(topic, self.topics()),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Shows the options of the shell")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn show_options() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.set_plain_output(true);
+        shell
+            .options()
+            .borrow_mut()
+            .register_bool("dry-run", "Whether to only pretend", false)?;
+        shell.eval("set autocorrect off")?;
+
+        assert_eq!(
+            shell.eval("show options")?,
+            "verbosity = normal - How much is printed beyond the output of commands\n\
+            plain-output = on - Whether output is stripped of colors\n\
+            screen-reader = off - Whether output is friendly to screen readers\n\
+            autocorrect = off - Whether mistyped commands are offered a correction\n\
+            inline-help = off - Whether lines ending in '?' list their valid continuations\n\
+            subcommand-chooser = off - Whether bare parent commands offer to choose a subcommand\n\
+            strict = off - Whether undeclared arguments are rejected\n\
+            dry-run = false - Whether to only pretend"
+        );
+
+        assert!(shell.eval("show").is_err());
+        assert!(shell.eval("show colors").is_err());
+        assert!(shell.eval("show options please").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+
+use super::args::join_unquoted;
+use super::{BaseCommand, Completion};
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// SnippetCommand manages snippets, i.e., templates of input lines with placeholders in braces.
+/// See `Shell::add_snippet()`.
+///
+/// * `snippet add deploy "deploy {env} {version}"` adds a snippet.
+/// * `snippet use deploy` fills the next line with the snippet, up to its first placeholder. Tab
+///   then moves on to the next placeholder.
+/// * `snippet list` lists the snippets.
+/// * `snippet remove deploy` removes a snippet.
+pub struct SnippetCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for SnippetCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> SnippetCommand<'a, S> {
+    /// Creates a new SnippetCommand.
+    pub fn new() -> SnippetCommand<'a, S> {
+        SnippetCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    fn actions(&self) -> Vec<String> {
+        vec![
+            String::from("add"),
+            String::from("use"),
+            String::from("list"),
+            String::from("remove"),
+        ]
+    }
+}
+
+impl<'a, S> BaseCommand for SnippetCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "snippet"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        let action = match args.first() {
+            Some(action) => action,
+            None => return Err(ShiError::NoArgs),
+        };
+
+        let expected = match &action[..] {
+            "add" if args.len() < 3 => {
+                "a name and a template, e.g. 'snippet add deploy \"deploy {env}\"'"
+            }
+            "use" | "remove" if args.len() != 2 => "the name of a snippet",
+            "list" if args.len() != 1 => {
+                return Err(ShiError::ExtraArgs {
+                    got: args[1..].to_vec(),
+                })
+            }
+            "add" | "use" | "remove" | "list" => return Ok(()),
+            _ => {
+                return Err(ShiError::InvalidSubCommand {
+                    got: action.clone(),
+                    expected: self.actions(),
+                })
+            }
+        };
+
+        Err(ShiError::general(format!(
+            "expected {} after '{}'",
+            expected, action
+        )))
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        match &args[0][..] {
+            "add" => {
+                shell.add_snippet(&args[1], &join_unquoted(&args[2..]));
+                Ok(format!("added snippet '{}'", args[1]))
+            }
+            "use" => {
+                let input = shell.snippets.borrow_mut().start(&args[1])?;
+                shell.set_next_input(&input);
+                Ok(String::new())
+            }
+            "remove" => {
+                if !shell.snippets.borrow_mut().remove(&args[1]) {
+                    return Err(ShiError::general(format!("no snippet named '{}'", args[1])));
+                }
+                Ok(format!("removed snippet '{}'", args[1]))
+            }
+            _ => {
+                let snippets = shell.snippets.borrow();
+                if snippets.templates().is_empty() {
+                    return Ok(String::from("no snippets"));
+                }
+
+                Ok(snippets
+                    .templates()
+                    .iter()
+                    .map(|(name, template)| format!("{} - {}", name, template))
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+        }
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        match (args.as_slice(), trailing_space) {
+            ([], _) => Completion::Possibilities(self.actions()),
+            ([action], false) => Completion::from_partial(action, self.actions()),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Adds, uses, lists or removes templates of input lines")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn manages_snippets() -> Result<()> {
+        let mut shell = Shell::new("| ");
+
+        assert_eq!(shell.eval("snippet list")?, "no snippets");
+        assert_eq!(
+            shell.eval("snippet add deploy \"deploy {env} {version}\"")?,
+            "added snippet 'deploy'"
+        );
+        shell.add_snippet("status", "server status");
+        assert_eq!(
+            shell.eval("snippet list")?,
+            "deploy - deploy {env} {version}\nstatus - server status"
+        );
+
+        assert_eq!(shell.eval("snippet use deploy")?, "");
+        assert_eq!(shell.next_input.as_deref(), Some("deploy "));
+        assert_eq!(
+            shell.snippets.borrow().hint(),
+            Some(String::from("{env} {version}"))
+        );
+
+        assert_eq!(
+            shell.eval("snippet remove status")?,
+            "removed snippet 'status'"
+        );
+        assert!(shell.eval("snippet remove status").is_err());
+        assert!(shell.eval("snippet use status").is_err());
+        assert!(shell.eval("snippet add deploy").is_err());
+        assert!(shell.eval("snippet list all").is_err());
+        assert!(shell.eval("snippet edit deploy").is_err());
+
+        Ok(())
+    }
+}
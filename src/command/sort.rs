@@ -0,0 +1,133 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// Splits the arguments of `sort` into whether to sort in reverse and the command to run.
+fn split_reverse(args: &[String]) -> (bool, &[String]) {
+    match args.split_first() {
+        Some((flag, rest)) if flag == "-r" => (true, rest),
+        _ => (false, args),
+    }
+}
+
+/// Sorts the lines of the given output, in reverse if asked to.
+fn sort_lines(output: &str, reverse: bool) -> String {
+    let mut lines: Vec<&str> = output.lines().collect();
+    lines.sort_unstable();
+    if reverse {
+        lines.reverse();
+    }
+
+    lines.join("\n")
+}
+
+#[derive(Debug)]
+/// SortCommand runs the command it is given and sorts the lines of its output, e.g.
+/// `sort server list`. With `-r`, the lines are sorted in reverse, e.g. `sort -r server list`.
+///
+/// Like `filter` and `fields`, it can be combined with the other output modifiers, e.g.
+/// `head 5 sort filter error server logs`, and without a command, it sorts the output of the
+/// previous command of a pipeline instead, e.g. `server logs | filter error | sort | head 5`.
+pub struct SortCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for SortCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> SortCommand<'a, S> {
+    /// Creates a new SortCommand.
+    pub fn new() -> SortCommand<'a, S> {
+        SortCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for SortCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "sort"
+    }
+
+    fn validate_args(&self, _: &[String]) -> Result<()> {
+        // Without a command, the input of a pipeline is sorted, which only execution knows of.
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let (reverse, line) = split_reverse(args);
+        if line.is_empty() {
+            return Err(ShiError::general(
+                "expected a command to sort the output of, e.g. 'sort -r server list', or input, \
+                 e.g. 'server list | sort -r'",
+            ));
+        }
+
+        let output = shell.eval_without_history(&line.join(" "))?;
+
+        Ok(sort_lines(&output, reverse))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let (reverse, line) = split_reverse(args);
+        if !line.is_empty() {
+            return Err(ShiError::general(format!(
+                "expected only '-r' to sort input with, got '{}'",
+                line.join(" ")
+            )));
+        }
+
+        Ok(sort_lines(input, reverse))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and sorts the lines of its output")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sorts_output() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("pets", |_, _| {
+            Ok(String::from("dog: woof\ncat: meow\nbird: tweet"))
+        }))?;
+
+        assert_eq!(
+            shell.eval("sort pets")?,
+            "bird: tweet\ncat: meow\ndog: woof"
+        );
+        assert_eq!(
+            shell.eval("sort -r pets")?,
+            "dog: woof\ncat: meow\nbird: tweet"
+        );
+        assert_eq!(shell.eval("sort filter o pets")?, "cat: meow\ndog: woof");
+        assert!(shell.eval("sort").is_err());
+        assert!(shell.eval("sort -r").is_err());
+        assert!(shell.eval("sort DNE").is_err());
+
+        assert_eq!(
+            shell.eval("pets | sort")?,
+            "bird: tweet\ncat: meow\ndog: woof"
+        );
+        assert_eq!(shell.eval("pets | sort -r | head 1")?, "dog: woof");
+        assert!(shell.eval("pets | sort pets").is_err());
+
+        Ok(())
+    }
+}
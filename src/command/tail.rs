@@ -0,0 +1,101 @@
+use std::marker::PhantomData;
+
+use super::head::{count_and_line, count_of_input, parse_count};
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// Keeps the given number of lines at the end of the given output.
+fn last_lines(output: &str, count: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let start = lines.len().saturating_sub(count);
+
+    lines[start..].join("\n")
+}
+
+#[derive(Debug)]
+/// TailCommand runs the command it is given and keeps the last lines of its output, e.g.
+/// `tail 10 server logs`, or of the output of the previous command of a pipeline, e.g.
+/// `server logs | tail 10`. See `HeadCommand` for the first lines.
+pub struct TailCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for TailCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> TailCommand<'a, S> {
+    /// Creates a new TailCommand.
+    pub fn new() -> TailCommand<'a, S> {
+        TailCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for TailCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "tail"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        parse_count(args)?;
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let (count, line) = count_and_line(self.name(), args)?;
+        let output = shell.eval_without_history(&line)?;
+
+        Ok(last_lines(&output, count))
+    }
+
+    fn execute_with_input(&self, _: &mut Shell<S>, args: &[String], input: &str) -> Result<String> {
+        let count = count_of_input(args)?;
+
+        Ok(last_lines(input, count))
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and keeps the last lines of its output")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn keeps_last_lines() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("count", |_, _| Ok(String::from("1\n2\n3\n4"))))?;
+
+        assert_eq!(shell.eval("tail 2 count")?, "3\n4");
+        assert_eq!(shell.eval("tail 10 count")?, "1\n2\n3\n4");
+        assert_eq!(shell.eval("tail 0 count")?, "");
+        assert_eq!(shell.eval("tail 1 head 3 count")?, "3");
+        assert!(shell.eval("tail count").is_err());
+        assert!(shell.eval("tail 2").is_err());
+
+        assert_eq!(shell.eval("count | tail 1")?, "4");
+        assert_eq!(shell.eval("count | tail 3 | head 1")?, "2");
+        assert!(shell.eval("count | tail 1 count").is_err());
+
+        Ok(())
+    }
+}
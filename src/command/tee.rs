@@ -0,0 +1,112 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// TeeCommand runs the command it is given, e.g. `tee evidence.log server status`, and appends
+/// that command's output to the given file, besides displaying it as usual.
+///
+/// The output of a failed command is not appended, but its error is, so that the file is a full
+/// record of what was seen.
+pub struct TeeCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for TeeCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> TeeCommand<'a, S> {
+    /// Creates a new TeeCommand.
+    pub fn new() -> TeeCommand<'a, S> {
+        TeeCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for TeeCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "tee"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args.len() {
+            0 => Err(ShiError::NoArgs),
+            1 => Err(ShiError::general(format!(
+                "expected a command to run after '{}'",
+                args[0]
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&args[0])
+            .map_err(|err| ShiError::general(format!("failed to open '{}': {}", args[0], err)))?;
+
+        let res = shell.eval_without_history(&args[1..].join(" "));
+        match &res {
+            Ok(output) => writeln!(file, "{}", output)?,
+            Err(err) => writeln!(file, "{}", err)?,
+        }
+
+        res
+    }
+
+    fn help(&self) -> String {
+        String::from("Runs a command and appends its output to a file")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn appends_output() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-tee-test-{}", std::process::id()));
+        let path_str = path.to_str().unwrap().to_string();
+
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+
+        assert_eq!(
+            shell.eval(&format!("tee {} echo hello", path_str))?,
+            "hello"
+        );
+        assert_eq!(
+            shell.eval(&format!("tee {} echo again", path_str))?,
+            "again"
+        );
+        assert!(shell.eval(&format!("tee {} DNE", path_str)).is_err());
+
+        let contents = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        assert!(contents.starts_with("hello\nagain\n"));
+        assert!(contents.contains("'DNE' is not a recognized command"));
+
+        assert!(shell.eval(&format!("tee {}", path_str)).is_err());
+        assert!(shell.eval("tee /does/not/exist/log echo hi").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::marker::PhantomData;
+
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+#[derive(Debug)]
+/// VersionCommand prints the name, version and build information of the application, as given via
+/// `Shell::set_app_info()`, followed by the version of shi itself.
+pub struct VersionCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for VersionCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, S> VersionCommand<'a, S> {
+    /// Creates a new VersionCommand.
+    pub fn new() -> VersionCommand<'a, S> {
+        VersionCommand {
+            _phantom: &PhantomData,
+        }
+    }
+}
+
+impl<'a, S> BaseCommand for VersionCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "version"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if !args.is_empty() {
+            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<'a, S>, _: &[String]) -> Result<String> {
+        let mut lines = Vec::new();
+        if let Some(info) = shell.app_info() {
+            lines.push(format!("{} {}", info.name, info.version));
+            if let Some(build) = &info.build {
+                lines.push(format!("build: {}", build));
+            }
+        }
+        lines.push(format!("shi {}", env!("CARGO_PKG_VERSION")));
+
+        Ok(lines.join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints version information")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::shell::AppInfo;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn shi_only() -> Result<()> {
+        let mut shell = Shell::new("| ");
+
+        assert_eq!(
+            VersionCommand::new().execute(&mut shell, &[])?,
+            format!("shi {}", env!("CARGO_PKG_VERSION"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn app_info() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.set_app_info(AppInfo::new("zoo", "1.2.0"));
+        assert_eq!(
+            VersionCommand::new().execute(&mut shell, &[])?,
+            format!("zoo 1.2.0\nshi {}", env!("CARGO_PKG_VERSION"))
+        );
+
+        shell.set_app_info(AppInfo::new("zoo", "1.2.0").with_build("abc123 (2021-05-01)"));
+        assert_eq!(
+            VersionCommand::new().execute(&mut shell, &[])?,
+            format!(
+                "zoo 1.2.0\nbuild: abc123 (2021-05-01)\nshi {}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "color")]
+use super::diff::{diff_lines, DiffLine};
+use super::BaseCommand;
+use crate::error::ShiError;
+use crate::screen::FullScreen;
+use crate::shell::Shell;
+use crate::Result;
+
+#[cfg(feature = "color")]
+const REVERSE_VIDEO: &str = "\x1b[7m";
+#[cfg(feature = "color")]
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug)]
+/// WatchCommand re-runs the command it is given on an interval, e.g. `watch server status`,
+/// showing its latest output full-screen until `q` is pressed.
+///
+/// The interval is two seconds, unless given in seconds with `-n`, e.g. `watch -n 0.5 jobs`.
+/// With `-d`, the lines that changed since the previous run are highlighted, like `watch -d`.
+pub struct WatchCommand<'a, S> {
+    _phantom: &'a PhantomData<S>,
+}
+
+impl<'a, S> Default for WatchCommand<'a, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The arguments to a `watch` invocation, as parsed by `WatchCommand::parse()`.
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    interval: Duration,
+    differences: bool,
+    line: String,
+}
+
+impl<'a, S> WatchCommand<'a, S> {
+    /// Creates a new WatchCommand.
+    pub fn new() -> WatchCommand<'a, S> {
+        WatchCommand {
+            _phantom: &PhantomData,
+        }
+    }
+
+    fn parse(args: &[String]) -> Result<Invocation> {
+        let mut interval = Duration::from_secs(2);
+        let mut differences = false;
+        let mut args = args.iter();
+        let mut rest = args.as_slice();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "-d" => differences = true,
+                "-n" => {
+                    let secs = args.next().and_then(|secs| secs.parse::<f64>().ok());
+                    interval = match secs {
+                        Some(secs) if secs > 0.0 => {
+                            Duration::try_from_secs_f64(secs).map_err(|_| {
+                                ShiError::general(format!(
+                                    "an interval of {} seconds is too long",
+                                    secs
+                                ))
+                            })?
+                        }
+                        _ => {
+                            return Err(ShiError::general(
+                                "expected a positive number of seconds after '-n'",
+                            ))
+                        }
+                    };
+                }
+                _ => break,
+            }
+            rest = args.as_slice();
+        }
+
+        if rest.is_empty() {
+            return Err(ShiError::general(
+                "expected a command to watch, e.g. 'watch -n 1 server status'",
+            ));
+        }
+
+        Ok(Invocation {
+            interval,
+            differences,
+            line: rest.join(" "),
+        })
+    }
+}
+
+/// Highlights the lines of the given output that are not in the previous output, in reverse
+/// video, unless output is plain or colors are disabled.
+fn highlight_changes(previous: &str, output: &str, plain: bool) -> String {
+    #[cfg(feature = "color")]
+    if !plain {
+        let old: Vec<&str> = previous.lines().collect();
+        let new: Vec<&str> = output.lines().collect();
+        return diff_lines(&old, &new)
+            .into_iter()
+            .filter_map(|line| match line {
+                DiffLine::Same(line) => Some(line.to_string()),
+                DiffLine::Added(line) => Some(format!("{}{}{}", REVERSE_VIDEO, line, RESET)),
+                DiffLine::Removed(_) => None,
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+    #[cfg(not(feature = "color"))]
+    let _ = (previous, plain);
+
+    output.to_string()
+}
+
+impl<'a, S> BaseCommand for WatchCommand<'a, S> {
+    type State = Shell<'a, S>;
+
+    fn name(&self) -> &str {
+        "watch"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        Self::parse(args)?;
+
+        Ok(())
+    }
+
+    fn execute(&self, shell: &mut Shell<S>, args: &[String]) -> Result<String> {
+        let invocation = Self::parse(args)?;
+
+        // The output of the previous run, kept to highlight what changed since.
+        let mut previous: Option<String> = None;
+        FullScreen::enter()?.run(invocation.interval, |_| {
+            let body = match shell.eval_without_history(&invocation.line) {
+                Ok(output) => {
+                    let body = match &previous {
+                        Some(previous) if invocation.differences => {
+                            highlight_changes(previous, &output, shell.is_plain_output())
+                        }
+                        _ => output.clone(),
+                    };
+                    previous = Some(output);
+                    body
+                }
+                Err(err) => format!("{} {}", shell.error_prefix(), err),
+            };
+
+            Ok(format!(
+                "Every {:?}: {}    {}\n\n{}\n\npress q to quit",
+                invocation.interval,
+                invocation.line,
+                shell.format_time(SystemTime::now()),
+                body
+            ))
+        })?;
+
+        Ok(String::new())
+    }
+
+    fn help(&self) -> String {
+        String::from("Re-runs a command on an interval, showing its output full-screen")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_args() -> Result<()> {
+        assert_eq!(
+            WatchCommand::<()>::parse(&args(&["server", "status"]))?,
+            Invocation {
+                interval: Duration::from_secs(2),
+                differences: false,
+                line: String::from("server status"),
+            }
+        );
+        assert_eq!(
+            WatchCommand::<()>::parse(&args(&["-d", "-n", "0.5", "jobs", "-d"]))?,
+            Invocation {
+                interval: Duration::from_millis(500),
+                differences: true,
+                line: String::from("jobs -d"),
+            }
+        );
+        assert!(WatchCommand::<()>::parse(&args(&["-d"])).is_err());
+        assert!(WatchCommand::<()>::parse(&args(&["-n", "0", "jobs"])).is_err());
+        assert!(WatchCommand::<()>::parse(&args(&["-n", "jobs"])).is_err());
+        assert!(WatchCommand::<()>::parse(&args(&["-n", "1e20", "jobs"])).is_err());
+        assert!(WatchCommand::<()>::parse(&args(&["-n", "NaN", "jobs"])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn highlights_changes() {
+        #[cfg(feature = "color")]
+        assert_eq!(
+            highlight_changes("web: up\ndb: up\n", "web: up\ndb: down\ncache: up", false),
+            format!(
+                "web: up\n{}db: down{}\n{}cache: up{}",
+                REVERSE_VIDEO, RESET, REVERSE_VIDEO, RESET
+            )
+        );
+        #[cfg(not(feature = "color"))]
+        assert_eq!(
+            highlight_changes("web: up\ndb: up\n", "web: up\ndb: down", false),
+            "web: up\ndb: down"
+        );
+        assert_eq!(highlight_changes("web: up", "web: down", true), "web: down");
+    }
+}
@@ -1,15 +1,106 @@
-use std::collections::HashMap;
+//! A module for `CommandSet`, the container of the commands of a shell, and of the subcommands of
+//! each `ParentCommand`, as returned by `ParentCommand::sub_commands()`.
+//!
+//! Sets are filled via `Shell::register()` and the constructors of `ParentCommand`, and are only
+//! handed out for reading. Commands are looked up by name, by path, or by prefix, and iterated or
+//! walked in the order they were added:
+//!
+//! ```
+//! # use shi::cmd;
+//! # use shi::command::{BaseCommand, Command};
+//! let admin: Command<()> = shi::parent!(
+//!     "admin",
+//!     shi::parent!(
+//!         "server",
+//!         cmd!("listen", |_, _| Ok(String::new())),
+//!         cmd!("stop", |_, _| Ok(String::new())),
+//!     ),
+//!     cmd!("status", |_, _| Ok(String::new())),
+//! );
+//! let cmds = match &admin {
+//!     Command::Parent(admin) => admin.sub_commands(),
+//!     Command::Leaf(_) => unreachable!(),
+//! };
+//!
+//! assert_eq!(cmds.get_path(&["server", "stop"]).map(|cmd| cmd.name()), Some("stop"));
+//! assert_eq!(cmds.names_with_prefix("s"), vec!["server", "status"]);
+//!
+//! let mut paths = Vec::new();
+//! cmds.walk(|visit| paths.push(visit.path.join(" ")));
+//! assert_eq!(paths, vec!["server", "server listen", "server stop", "status"]);
+//! ```
+
+use std::cell::{Cell, OnceCell};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+use std::rc::Rc;
 use std::slice::Iter;
 
 use crate::command::{BaseCommand, Command};
 
+/// A single step of a walk over a `CommandSet`. See `CommandSet::walk()`.
+pub struct Visit<'v, 'a, S> {
+    /// The names of the commands leading to, and including, the visited command.
+    pub path: &'v [&'v str],
+    /// How deep the visited command is. Commands at the root of the walked set have a depth of 0.
+    pub depth: usize,
+    /// Whether the visited command is the last of its siblings.
+    pub last: bool,
+    /// The visited command.
+    pub cmd: &'v Command<'a, S>,
+}
+
+/// Constructs a command that was added lazily. See `CommandSet::add_lazy()`.
+type Factory<'a, S> = Box<dyn FnOnce() -> Command<'a, S> + 'a>;
+
+/// A command in a `CommandSet`, which may not have been constructed yet.
+struct Entry<'a, S> {
+    name: Rc<str>,
+    cmd: OnceCell<Command<'a, S>>,
+    // Only set for commands that were added lazily and have not been constructed yet.
+    factory: Cell<Option<Factory<'a, S>>>,
+}
+
+impl<'a, S> Entry<'a, S> {
+    /// Returns the command, constructing it first if needed.
+    fn cmd(&self) -> &Command<'a, S> {
+        self.cmd.get_or_init(|| {
+            let factory = self
+                .factory
+                .take()
+                .expect("lazy command has neither been constructed nor has a factory");
+            factory()
+        })
+    }
+
+    /// Returns the command mutably, constructing it first if needed.
+    fn cmd_mut(&mut self) -> &mut Command<'a, S> {
+        self.cmd();
+        self.cmd.get_mut().expect("command was just constructed")
+    }
+
+    /// Returns the command, constructing it first if needed.
+    fn into_cmd(self) -> Command<'a, S> {
+        self.cmd();
+        self.cmd.into_inner().expect("command was just constructed")
+    }
+}
+
 /// A wrapper data structure that offers several basic container methods, specifically for
 /// Commands.
+///
+/// Commands are stored in a slab, in insertion order, with an index from their names to their
+/// position in it, and their names in a sorted tree, for listing them and looking them up by
+/// prefix. The slab and
+/// the indexes share a single copy of each name, which matters for very large trees. For the same
+/// reason, commands may be added lazily, so that they are only constructed once they are first
+/// looked up.
 pub struct CommandSet<'a, S> {
-    cmds: HashMap<String, Box<Command<'a, S>>>,
-    // Stores the insertion order of the keys of commands. For use in iteration to preserve command
-    // order.
-    order: Vec<String>,
+    cmds: Vec<Entry<'a, S>>,
+    // Maps the names of commands to their position in `cmds`.
+    index: HashMap<Rc<str>, usize>,
+    // The names of the commands, sorted.
+    sorted: BTreeSet<Rc<str>>,
 }
 
 impl<'a, S> Default for CommandSet<'a, S> {
@@ -20,16 +111,24 @@ impl<'a, S> Default for CommandSet<'a, S> {
 
 impl<'a, S> CommandSet<'a, S> {
     /// Creates a new, empty `CommandSet`.
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a new, empty `CommandSet`, with room for at least `capacity` commands before it
+    /// needs to reallocate.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
         CommandSet {
-            cmds: HashMap::new(),
-            order: Vec::new(),
+            cmds: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+            sorted: BTreeSet::new(),
         }
     }
 
     /// Creates a new `CommandSet` from the given `Vector` of `Command`'s.
-    pub fn new_from_vec(cmds: Vec<Command<'a, S>>) -> Self {
-        let mut cmd_set = CommandSet::new();
+    #[cfg(test)]
+    pub(crate) fn new_from_vec(cmds: Vec<Command<'a, S>>) -> Self {
+        let mut cmd_set = CommandSet::with_capacity(cmds.len());
         for cmd in cmds {
             cmd_set.add(cmd);
         }
@@ -46,19 +145,218 @@ impl<'a, S> CommandSet<'a, S> {
     ///
     /// # Returns
     /// `Option<&Command>` - The command with the name requested, or None if it was not found.
-    #[allow(clippy::borrowed_box)]
-    pub fn get(&self, name: &str) -> Option<&Box<Command<'a, S>>> {
-        self.cmds.get(name)
+    pub fn get(&self, name: &str) -> Option<&Command<'a, S>> {
+        self.index.get(name).map(|&i| self.cmds[i].cmd())
     }
 
-    /// Adds the given command to the set.
+    /// Retrieves the command that the given name is an unambiguous prefix of, i.e., the command of
+    /// that exact name, or otherwise the only command whose name starts with it.
+    ///
+    /// # Arguments
+    /// `prefix` - The name, or prefix of the name, of the command to retrieve.
+    ///
+    /// # Returns
+    /// `Option<&Command>` - The command, or None if no command, or more than one, starts with
+    /// `prefix`.
+    pub fn get_by_prefix(&self, prefix: &str) -> Option<&Command<'a, S>> {
+        if let Some(cmd) = self.get(prefix) {
+            return Some(cmd);
+        }
+
+        let mut names = self.sorted_with_prefix(prefix);
+        match (names.next(), names.next()) {
+            (Some(name), None) => self.get(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of the commands that start with the given prefix, sorted.
+    fn sorted_with_prefix<'s>(&'s self, prefix: &'s str) -> impl Iterator<Item = &'s Rc<str>> {
+        self.sorted
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |name| name.starts_with(prefix))
+    }
+
+    /// Retrieves a mutable reference to the command, if one exists, for the given name.
+    ///
+    /// Note that the name of the command must not be changed through this reference, as the
+    /// command will remain registered under its original name.
+    ///
+    /// # Arguments
+    /// `name` - The name of the command to retrieve.
+    ///
+    /// # Returns
+    /// `Option<&mut Command>` - The command with the name requested, or None if it was not found.
+    pub(crate) fn get_mut(&mut self, name: &str) -> Option<&mut Command<'a, S>> {
+        let i = *self.index.get(name)?;
+        Some(self.cmds[i].cmd_mut())
+    }
+
+    /// Retrieves the command, if one exists, at the given path of names.
+    ///
+    /// The path is walked from this set downwards, where every element but the last must name a
+    /// `Parent` command. The final element may name either a `Leaf` or a `Parent`.
+    ///
+    /// # Arguments
+    /// `path` - The names of the commands leading to, and including, the command to retrieve.
+    ///
+    /// # Returns
+    /// `Option<&Command>` - The command at the end of the path, or None if the path does not
+    /// resolve to a command.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Command<'a, S>> {
+        let (last, ancestors) = path.split_last()?;
+
+        let mut current_set = self;
+        for name in ancestors {
+            match current_set.get(name)? {
+                Command::Parent(parent) => current_set = parent.sub_commands(),
+                // A leaf has no children, so there is nothing further down the path to find.
+                Command::Leaf(_) => return None,
+            }
+        }
+
+        current_set.get(last)
+    }
+
+    /// Walks the entire command tree rooted at this set, invoking `visitor` for every command.
+    ///
+    /// The walk is depth-first and preserves insertion order, so a `Parent` command is always
+    /// visited before its children, and its children are visited before its next sibling.
+    ///
+    /// # Arguments
+    /// `visitor` - The closure to invoke for each visited command.
+    pub fn walk<F>(&self, mut visitor: F)
+    where
+        F: FnMut(&Visit<'_, 'a, S>),
+    {
+        self.walk_with_path(&mut Vec::new(), &mut visitor)
+    }
+
+    fn walk_with_path<'s, F>(&'s self, path: &mut Vec<&'s str>, visitor: &mut F)
+    where
+        F: FnMut(&Visit<'_, 'a, S>),
+    {
+        for (i, entry) in self.cmds.iter().enumerate() {
+            let cmd = entry.cmd();
+            path.push(&entry.name);
+            visitor(&Visit {
+                path,
+                depth: path.len() - 1,
+                last: i == self.cmds.len() - 1,
+                cmd,
+            });
+
+            if let Command::Parent(parent) = cmd {
+                parent.sub_commands().walk_with_path(path, visitor);
+            }
+            path.pop();
+        }
+    }
+
+    /// Adds the given command to the set. If a command of the same name already exists, it is
+    /// replaced, keeping its position.
     ///
     /// # Arguments
     /// `cmd` - The command to add to this set.
-    pub fn add(&mut self, cmd: Command<'a, S>) {
-        let cmd_name = cmd.name().to_string();
-        self.cmds.insert(cmd_name.clone(), Box::new(cmd));
-        self.order.push(cmd_name);
+    pub(crate) fn add(&mut self, cmd: Command<'a, S>) {
+        let name = cmd.name().to_string();
+        self.insert(Entry {
+            name: Rc::from(name),
+            cmd: OnceCell::from(cmd),
+            factory: Cell::new(None),
+        });
+    }
+
+    /// Adds a command to the set that is only constructed once it is first looked up, e.g. via
+    /// `get()` or by walking the set. If a command of the same name already exists, it is
+    /// replaced, keeping its position.
+    ///
+    /// # Arguments
+    /// `name` - The name of the command. This must be the name of the command that `factory`
+    /// constructs.
+    /// `factory` - Constructs the command.
+    pub(crate) fn add_lazy<F>(&mut self, name: &str, factory: F)
+    where
+        F: FnOnce() -> Command<'a, S> + 'a,
+    {
+        self.insert(Entry {
+            name: Rc::from(name),
+            cmd: OnceCell::new(),
+            factory: Cell::new(Some(Box::new(factory))),
+        });
+    }
+
+    fn insert(&mut self, entry: Entry<'a, S>) {
+        if let Some(&i) = self.index.get(&entry.name) {
+            self.cmds[i] = entry;
+            return;
+        }
+
+        self.index.insert(Rc::clone(&entry.name), self.cmds.len());
+        self.sorted.insert(Rc::clone(&entry.name));
+        self.cmds.push(entry);
+    }
+
+    /// Converts every command of the set via `f`, in order. Commands that have not been
+    /// constructed yet stay that way, and are converted once they are.
+    ///
+    /// # Arguments
+    /// `f` - Converts a command.
+    pub(crate) fn map_commands<T, F>(self, f: F) -> CommandSet<'a, T>
+    where
+        F: Fn(Command<'a, S>) -> Command<'a, T> + Clone + 'a,
+        S: 'a,
+    {
+        let mut mapped = CommandSet::with_capacity(self.cmds.len());
+        for entry in self.cmds {
+            match entry.cmd.into_inner() {
+                Some(cmd) => mapped.add(f(cmd)),
+                None => {
+                    let factory = entry
+                        .factory
+                        .into_inner()
+                        .expect("lazy command has neither been constructed nor has a factory");
+                    let f = f.clone();
+                    mapped.add_lazy(&entry.name, move || f(factory()));
+                }
+            }
+        }
+
+        mapped
+    }
+
+    /// Tests whether the command with the given name has been constructed. This is only false for
+    /// commands added via `add_lazy()` that have not been looked up yet.
+    ///
+    /// # Arguments
+    /// `name` - The name of the command.
+    ///
+    /// # Returns
+    /// `bool` - Whether the command exists and has been constructed.
+    pub fn is_constructed(&self, name: &str) -> bool {
+        self.index
+            .get(name)
+            .is_some_and(|&i| self.cmds[i].cmd.get().is_some())
+    }
+
+    /// Removes the command with the given name from the set, if it exists.
+    ///
+    /// # Arguments
+    /// `name` - The name of the command to remove.
+    ///
+    /// # Returns
+    /// `Option<Command>` - The removed command, or None if it was not found.
+    pub(crate) fn remove(&mut self, name: &str) -> Option<Command<'a, S>> {
+        let removed = self.index.remove(name)?;
+        self.sorted.remove(name);
+        // Every command after the removed one moves down a slot.
+        for i in self.index.values_mut() {
+            if *i > removed {
+                *i -= 1;
+            }
+        }
+
+        Some(self.cmds.remove(removed).into_cmd())
     }
 
     /// Tests for existence of a `Command` with the given `name`.
@@ -69,7 +367,7 @@ impl<'a, S> CommandSet<'a, S> {
     /// # Returns
     /// `bool` - Whether or not a `Command` with the given `name` exists in this set.
     pub fn contains(&self, name: &str) -> bool {
-        self.cmds.contains_key(name)
+        self.index.contains_key(name)
     }
 
     /// Returns the length of this `CommandSet`.
@@ -80,62 +378,154 @@ impl<'a, S> CommandSet<'a, S> {
         self.cmds.len()
     }
 
-    /// Retrieves the command names of this command set.
+    /// Returns whether this `CommandSet` has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    /// Counts the commands in the entire tree rooted at this set, without constructing any that
+    /// were added lazily.
+    ///
+    /// # Returns
+    /// `(usize, usize)` - The number of commands, and how many of those have not been constructed
+    /// yet. The subcommands of commands that have not been constructed are not counted.
+    pub fn count(&self) -> (usize, usize) {
+        self.cmds
+            .iter()
+            .fold((0, 0), |(count, unconstructed), entry| {
+                match entry.cmd.get() {
+                    Some(cmd) => match cmd {
+                        Command::Parent(parent) => {
+                            let (sub_count, sub_unconstructed) = parent.sub_commands().count();
+                            (count + 1 + sub_count, unconstructed + sub_unconstructed)
+                        }
+                        Command::Leaf(_) => (count + 1, unconstructed),
+                    },
+                    None => (count + 1, unconstructed + 1),
+                }
+            })
+    }
+
+    /// Retrieves the command names of this command set, in the order they were added. See
+    /// `names_with_prefix()` for sorted names.
     /// Note that this only includes the names at the topmost/root level, it does not potentially
     /// recurse into parent commands and flatten the hierarchy
     ///
     /// # Returns
     /// `Vec<String>` - The top-level `Command` names.
     pub fn names(&self) -> Vec<String> {
-        self.order.clone()
+        self.cmds
+            .iter()
+            .map(|entry| entry.name.to_string())
+            .collect()
+    }
+
+    /// Retrieves the names of the commands of this command set that start with the given prefix,
+    /// sorted. Like `names()`, this only includes the names at the topmost/root level.
+    ///
+    /// # Arguments
+    /// `prefix` - The prefix of the names to retrieve.
+    ///
+    /// # Returns
+    /// `Vec<String>` - The top-level `Command` names that start with `prefix`.
+    pub fn names_with_prefix(&self, prefix: &str) -> Vec<String> {
+        self.sorted_with_prefix(prefix)
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Retrieves the names of the commands of this command set that start with the given prefix,
+    /// in the order they were added, like `names()`. They are found via the sorted names, so
+    /// this takes time in the number of matching names rather than of all names, e.g. for
+    /// completion.
+    ///
+    /// # Arguments
+    /// `prefix` - The prefix of the names to retrieve.
+    pub(crate) fn names_with_prefix_in_order(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<(usize, &Rc<str>)> = self
+            .sorted_with_prefix(prefix)
+            .map(|name| (self.index[name], name))
+            .collect();
+        names.sort_unstable_by_key(|&(i, _)| i);
+
+        names
+            .into_iter()
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+
+    /// Returns how many commands this `CommandSet` can hold without reallocating.
+    ///
+    /// # Returns
+    /// `usize` - The capacity of this `CommandSet`.
+    pub fn capacity(&self) -> usize {
+        self.cmds.capacity().min(self.index.capacity())
+    }
+
+    /// Reserves room for at least `additional` more commands, e.g. before adding many generated
+    /// commands at once.
+    ///
+    /// # Arguments
+    /// `additional` - The number of commands to make room for.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.cmds.reserve(additional);
+        self.index.reserve(additional);
+    }
+
+    /// Shrinks the storage of this `CommandSet`, and of the sets of all the `Parent` commands in
+    /// it, as much as possible. This is useful for trees that are built once and never changed.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.cmds.shrink_to_fit();
+        self.index.shrink_to_fit();
+
+        // Commands that have not been constructed yet have nothing to shrink.
+        for entry in self.cmds.iter_mut() {
+            if let Some(Command::Parent(parent)) = entry.cmd.get_mut() {
+                parent.sub_commands_mut().shrink_to_fit();
+            }
+        }
     }
 
     /// Produces an iterator over this set.
     ///
     /// # Returns
     /// `CommandSetIterator` - An iterator over this `CommandSet`.
-    pub fn iter(&self) -> CommandSetIterator<S> {
+    pub fn iter(&self) -> CommandSetIterator<'_, 'a, S> {
         CommandSetIterator {
-            iter: self.order.iter(),
-            cmds: self,
+            iter: self.cmds.iter(),
         }
     }
 }
 
 /// An iterator for `CommandSet`'s.
-pub struct CommandSetIterator<'a, S> {
-    iter: Iter<'a, String>,
-    cmds: &'a CommandSet<'a, S>,
+pub struct CommandSetIterator<'s, 'a, S> {
+    iter: Iter<'s, Entry<'a, S>>,
 }
 
-impl<'a, S: 'a> Iterator for CommandSetIterator<'a, S> {
-    type Item = &'a Box<Command<'a, S>>;
+impl<'s, 'a, S> Iterator for CommandSetIterator<'s, 'a, S> {
+    type Item = &'s Command<'a, S>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|k| {
-            self.cmds
-                .get(k)
-                .expect("order vector and command map mismatch")
-        })
+        self.iter.next().map(Entry::cmd)
     }
 }
 
-impl<'a, S: 'a> IntoIterator for &'a CommandSet<'a, S> {
-    type Item = &'a Box<Command<'a, S>>;
-    type IntoIter = CommandSetIterator<'a, S>;
+impl<'s, 'a, S> IntoIterator for &'s CommandSet<'a, S> {
+    type Item = &'s Command<'a, S>;
+    type IntoIter = CommandSetIterator<'s, 'a, S>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-// Since the CommandSet is just a wrapper around a HashMap, we don't really care too much about
-// making very exhaustive or precise tests.
+// Since the CommandSet is just a wrapper around Vecs and a HashMap, we don't really care too much
+// about making very exhaustive or precise tests.
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use crate::command::{BaseCommand, Command};
+    use crate::command::{BaseCommand, Command, ParentCommand};
     use crate::Result;
 
     struct EmptyCommand<'a> {
@@ -181,6 +571,105 @@ mod test {
         assert_eq!(cmd_set.get("b").unwrap().name(), "b");
     }
 
+    #[test]
+    fn get_mut() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_leaf(EmptyCommand::new("b")),
+        ]);
+
+        assert!(cmd_set.get_mut("DNE").is_none());
+
+        // Swap out 'b' for a new command of the same name, and make sure it sticks.
+        if let Some(cmd) = cmd_set.get_mut("b") {
+            *cmd = Command::new_parent("b", vec![Command::new_leaf(EmptyCommand::new("c"))]);
+        }
+
+        assert_eq!(cmd_set.get_path(&["b", "c"]).unwrap().name(), "c");
+        assert_eq!(cmd_set.names(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn get_path() {
+        let cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_parent(
+                "b",
+                vec![
+                    Command::new_leaf(EmptyCommand::new("c")),
+                    Command::new_parent("d", vec![Command::new_leaf(EmptyCommand::new("e"))]),
+                ],
+            ),
+        ]);
+
+        // Single element paths behave just like get().
+        assert_eq!(cmd_set.get_path(&["a"]).unwrap().name(), "a");
+        assert_eq!(cmd_set.get_path(&["b"]).unwrap().name(), "b");
+
+        // Nested paths resolve down to both parents and leaves.
+        assert_eq!(cmd_set.get_path(&["b", "c"]).unwrap().name(), "c");
+        assert_eq!(cmd_set.get_path(&["b", "d"]).unwrap().name(), "d");
+        assert_eq!(cmd_set.get_path(&["b", "d", "e"]).unwrap().name(), "e");
+
+        // Nonexistent commands, paths through leaves and empty paths resolve to nothing.
+        assert!(cmd_set.get_path(&["b", "DNE"]).is_none());
+        assert!(cmd_set.get_path(&["a", "c"]).is_none());
+        assert!(cmd_set.get_path(&[]).is_none());
+    }
+
+    #[test]
+    fn count() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_parent(
+                "b",
+                vec![
+                    Command::new_leaf(EmptyCommand::new("c")),
+                    Command::new_leaf(EmptyCommand::new("d")),
+                ],
+            ),
+        ]);
+        cmd_set.add_lazy("e", || {
+            Command::new_parent("e", vec![Command::new_leaf(EmptyCommand::new("f"))])
+        });
+
+        assert_eq!(cmd_set.count(), (5, 1));
+        // Counting does not construct lazy commands.
+        assert!(!cmd_set.is_constructed("e"));
+    }
+
+    #[test]
+    fn walk() {
+        let cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_parent(
+                "b",
+                vec![
+                    Command::new_parent("c", vec![Command::new_leaf(EmptyCommand::new("d"))]),
+                    Command::new_leaf(EmptyCommand::new("e")),
+                ],
+            ),
+        ]);
+
+        let mut visited = Vec::new();
+        cmd_set.walk(|visit| {
+            visited.push((visit.path.join(" "), visit.depth, visit.last));
+            // The path should always end with the visited command.
+            assert_eq!(visit.path.last(), Some(&visit.cmd.name()));
+        });
+
+        assert_eq!(
+            visited,
+            vec![
+                (String::from("a"), 0, false),
+                (String::from("b"), 0, true),
+                (String::from("b c"), 1, false),
+                (String::from("b c d"), 2, true),
+                (String::from("b e"), 1, true),
+            ]
+        );
+    }
+
     #[test]
     fn add() {
         let mut cmd_set = CommandSet::new();
@@ -194,6 +683,104 @@ mod test {
         assert_eq!(cmd_set.get("a").unwrap().name(), "a");
     }
 
+    #[test]
+    fn remove() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_leaf(EmptyCommand::new("b")),
+            Command::new_leaf(EmptyCommand::new("c")),
+        ]);
+
+        assert_eq!(cmd_set.remove("b").unwrap().name(), "b");
+        assert!(cmd_set.remove("b").is_none());
+
+        assert!(!cmd_set.contains("b"));
+        assert_eq!(cmd_set.len(), 2);
+        assert_eq!(cmd_set.names(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn remove_keeps_lookups() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_leaf(EmptyCommand::new("b")),
+            Command::new_leaf(EmptyCommand::new("c")),
+        ]);
+
+        cmd_set.remove("a");
+
+        // The commands after the removed one are still found under their own names.
+        assert_eq!(cmd_set.get("b").unwrap().name(), "b");
+        assert_eq!(cmd_set.get("c").unwrap().name(), "c");
+    }
+
+    #[test]
+    fn add_replaces() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("a")),
+            Command::new_leaf(EmptyCommand::new("b")),
+        ]);
+
+        cmd_set.add(Command::new_parent(
+            "a",
+            vec![Command::new_leaf(EmptyCommand::new("c"))],
+        ));
+
+        assert_eq!(cmd_set.len(), 2);
+        assert_eq!(cmd_set.names(), vec!["a", "b"]);
+        assert_eq!(cmd_set.get_path(&["a", "c"]).unwrap().name(), "c");
+    }
+
+    #[test]
+    fn lazy() {
+        let built = Rc::new(Cell::new(0));
+        let mut cmd_set = CommandSet::new();
+        cmd_set.add(Command::new_leaf(EmptyCommand::new("a")));
+        let counter = Rc::clone(&built);
+        cmd_set.add_lazy("b", move || {
+            counter.set(counter.get() + 1);
+            let mut parent = ParentCommand::new("b", Vec::new());
+            parent.add_lazy("c", || Command::new_leaf(EmptyCommand::new("c")));
+            Command::Parent(parent)
+        });
+
+        // Lazy commands are known by name without being constructed.
+        assert!(cmd_set.contains("b"));
+        assert_eq!(cmd_set.len(), 2);
+        assert_eq!(cmd_set.names(), vec!["a", "b"]);
+        assert!(!cmd_set.is_constructed("b"));
+        assert_eq!(built.get(), 0);
+
+        // Looking them up constructs them, but only once.
+        assert_eq!(cmd_set.get_path(&["b", "c"]).unwrap().name(), "c");
+        assert_eq!(cmd_set.get("b").unwrap().name(), "b");
+        assert!(cmd_set.is_constructed("b"));
+        assert_eq!(built.get(), 1);
+    }
+
+    #[test]
+    fn capacity() {
+        let mut cmd_set = CommandSet::with_capacity(16);
+        assert!(cmd_set.capacity() >= 16);
+
+        cmd_set.add(Command::new_parent("a", Vec::new()));
+        if let Some(Command::Parent(parent)) = cmd_set.get_mut("a") {
+            parent.sub_commands_mut().reserve(64);
+            parent
+                .sub_commands_mut()
+                .add(Command::new_leaf(EmptyCommand::new("b")));
+        }
+
+        cmd_set.shrink_to_fit();
+
+        assert!(cmd_set.capacity() < 16);
+        match cmd_set.get("a").unwrap() {
+            Command::Parent(parent) => assert!(parent.sub_commands().capacity() < 64),
+            Command::Leaf(_) => unreachable!(),
+        }
+        assert_eq!(cmd_set.get_path(&["a", "b"]).unwrap().name(), "b");
+    }
+
     #[test]
     fn contains() {
         let cmd_set = CommandSet::new_from_vec(vec![Command::new_leaf(EmptyCommand::new("b"))]);
@@ -264,4 +851,37 @@ mod test {
 
         assert_eq!(vec!["a", "b", "c"], names);
     }
+
+    #[test]
+    fn prefixes() {
+        let mut cmd_set = CommandSet::new_from_vec(vec![
+            Command::new_leaf(EmptyCommand::new("history")),
+            Command::new_leaf(EmptyCommand::new("help")),
+            Command::new_leaf(EmptyCommand::new("helptree")),
+            Command::new_leaf(EmptyCommand::new("exit")),
+        ]);
+
+        assert_eq!(cmd_set.names_with_prefix("he"), vec!["help", "helptree"]);
+        assert_eq!(
+            cmd_set.names_with_prefix(""),
+            vec!["exit", "help", "helptree", "history"]
+        );
+        assert!(cmd_set.names_with_prefix("z").is_empty());
+        assert_eq!(
+            cmd_set.names_with_prefix_in_order("h"),
+            vec!["history", "help", "helptree"]
+        );
+
+        assert_eq!(cmd_set.get_by_prefix("hist").unwrap().name(), "history");
+        assert_eq!(cmd_set.get_by_prefix("e").unwrap().name(), "exit");
+        // An exact name wins over the longer names it is a prefix of.
+        assert_eq!(cmd_set.get_by_prefix("help").unwrap().name(), "help");
+        assert!(cmd_set.get_by_prefix("h").is_none());
+        assert!(cmd_set.get_by_prefix("z").is_none());
+
+        cmd_set.remove("helptree");
+        cmd_set.add(Command::new_leaf(EmptyCommand::new("help")));
+        assert_eq!(cmd_set.names_with_prefix("he"), vec!["help"]);
+        assert_eq!(cmd_set.get_by_prefix("hi").unwrap().name(), "history");
+    }
 }
@@ -0,0 +1,257 @@
+//! Generates completion scripts for host shells, e.g. bash, from a shell's command tree.
+//!
+//! This is useful for programs that also accept commands via argv, e.g. `mytool server listen`,
+//! so that those get completed by the user's login shell too. See `Shell::generate_completions()`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::ShiError;
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A host shell that completion scripts can be generated for.
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl ShellKind {
+    /// Returns all the kinds of shells.
+    pub fn all() -> Vec<ShellKind> {
+        vec![ShellKind::Bash, ShellKind::Zsh, ShellKind::Fish]
+    }
+}
+
+impl fmt::Display for ShellKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShellKind::Bash => "bash",
+            ShellKind::Zsh => "zsh",
+            ShellKind::Fish => "fish",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ShellKind {
+    type Err = ShiError;
+
+    fn from_str(s: &str) -> Result<ShellKind> {
+        ShellKind::all()
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| ShiError::InvalidSubCommand {
+                got: s.to_string(),
+                expected: ShellKind::all()
+                    .iter()
+                    .map(|kind| kind.to_string())
+                    .collect(),
+            })
+    }
+}
+
+/// A level of the command tree: the path of a parent command, or the root, and the commands
+/// directly beneath it along with their help text.
+pub(crate) struct Level {
+    pub path: Vec<String>,
+    pub children: Vec<(String, String)>,
+}
+
+/// Generates a completion script for the given program.
+///
+/// # Arguments
+/// `kind` - The shell to generate the script for.
+/// `program` - The name of the program, as it is invoked.
+/// `levels` - The levels of the command tree, starting with the root.
+pub(crate) fn generate(kind: ShellKind, program: &str, levels: &[Level]) -> String {
+    // Function names are derived from the program's name, which may contain e.g. dots.
+    let ident: String = program
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match kind {
+        ShellKind::Bash => bash(program, &ident, levels),
+        ShellKind::Zsh => zsh(program, &ident, levels),
+        ShellKind::Fish => fish(program, &ident, levels),
+    }
+}
+
+/// Quotes the given string in single quotes for a POSIX-like shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn bash(program: &str, ident: &str, levels: &[Level]) -> String {
+    let mut script = format!(
+        "_{}_completions() {{\n    \
+            local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    \
+            local cmd_path=\"${{COMP_WORDS[*]:1:COMP_CWORD-1}}\"\n    \
+            local words=\"\"\n    \
+            case \"$cmd_path\" in\n",
+        ident
+    );
+    for level in levels {
+        let names: Vec<&str> = level.children.iter().map(|(name, _)| &name[..]).collect();
+        script.push_str(&format!(
+            "        {}) words={} ;;\n",
+            quote(&level.path.join(" ")),
+            quote(&names.join(" "))
+        ));
+    }
+    script.push_str(&format!(
+        "    esac\n    \
+            COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n\
+        }}\n\
+        complete -F _{}_completions {}\n",
+        ident, program
+    ));
+
+    script
+}
+
+fn zsh(program: &str, ident: &str, levels: &[Level]) -> String {
+    let mut script = format!(
+        "#compdef {}\n\n\
+        _{}() {{\n    \
+            local cmd_path=\"${{(j: :)words[2,CURRENT-1]}}\"\n    \
+            local -a candidates\n    \
+            case \"$cmd_path\" in\n",
+        program, ident
+    );
+    for level in levels {
+        let candidates: Vec<String> = level
+            .children
+            .iter()
+            .map(|(name, help)| quote(&format!("{}:{}", name.replace(':', "\\:"), help)))
+            .collect();
+        script.push_str(&format!(
+            "        {}) candidates=({}) ;;\n",
+            quote(&level.path.join(" ")),
+            candidates.join(" ")
+        ));
+    }
+    script.push_str(&format!(
+        "    esac\n    \
+            _describe 'command' candidates\n\
+        }}\n\n\
+        compdef _{} {}\n",
+        ident, program
+    ));
+
+    script
+}
+
+/// Quotes the given string in single quotes for fish.
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(program: &str, ident: &str, levels: &[Level]) -> String {
+    let mut script = format!(
+        "function __{}_at\n    \
+            set -l tokens (commandline -opc)\n    \
+            test \"$tokens[2..-1]\" = \"$argv\"\n\
+        end\n\n\
+        complete -c {} -f\n",
+        ident, program
+    );
+    for level in levels {
+        let condition = format!("__{}_at {}", ident, level.path.join(" "));
+        for (name, help) in &level.children {
+            script.push_str(&format!(
+                "complete -c {} -n {} -a {} -d {}\n",
+                program,
+                fish_quote(condition.trim_end()),
+                fish_quote(name),
+                fish_quote(help)
+            ));
+        }
+    }
+
+    script
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn levels() -> Vec<Level> {
+        vec![
+            Level {
+                path: vec![],
+                children: vec![
+                    (String::from("server"), String::from("Manages the server")),
+                    (String::from("dog"), String::from("Says 'woof'")),
+                ],
+            },
+            Level {
+                path: vec![String::from("server")],
+                children: vec![(String::from("listen"), String::from("Listens"))],
+            },
+        ]
+    }
+
+    #[test]
+    fn parses_kinds() -> Result<()> {
+        assert_eq!("zsh".parse::<ShellKind>()?, ShellKind::Zsh);
+        assert!("csh".parse::<ShellKind>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bash() {
+        assert_eq!(
+            generate(ShellKind::Bash, "my-tool", &levels()),
+            "_my_tool_completions() {\n    \
+                local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n    \
+                local cmd_path=\"${COMP_WORDS[*]:1:COMP_CWORD-1}\"\n    \
+                local words=\"\"\n    \
+                case \"$cmd_path\" in\n        \
+                    '') words='server dog' ;;\n        \
+                    'server') words='listen' ;;\n    \
+                esac\n    \
+                COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n\
+            }\n\
+            complete -F _my_tool_completions my-tool\n"
+        );
+    }
+
+    #[test]
+    fn zsh() {
+        assert_eq!(
+            generate(ShellKind::Zsh, "mytool", &levels()),
+            "#compdef mytool\n\n\
+            _mytool() {\n    \
+                local cmd_path=\"${(j: :)words[2,CURRENT-1]}\"\n    \
+                local -a candidates\n    \
+                case \"$cmd_path\" in\n        \
+                    '') candidates=('server:Manages the server' 'dog:Says '\\''woof'\\''') ;;\n        \
+                    'server') candidates=('listen:Listens') ;;\n    \
+                esac\n    \
+                _describe 'command' candidates\n\
+            }\n\n\
+            compdef _mytool mytool\n"
+        );
+    }
+
+    #[test]
+    fn fish() {
+        assert_eq!(
+            generate(ShellKind::Fish, "mytool", &levels()),
+            "function __mytool_at\n    \
+                set -l tokens (commandline -opc)\n    \
+                test \"$tokens[2..-1]\" = \"$argv\"\n\
+            end\n\n\
+            complete -c mytool -f\n\
+            complete -c mytool -n '__mytool_at' -a 'server' -d 'Manages the server'\n\
+            complete -c mytool -n '__mytool_at' -a 'dog' -d 'Says \\'woof\\''\n\
+            complete -c mytool -n '__mytool_at server' -a 'listen' -d 'Listens'\n"
+        );
+    }
+}
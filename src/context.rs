@@ -0,0 +1,178 @@
+//! A module for contexts: sets of commands that are only available while some condition holds,
+//! e.g. while the working directory is inside a project, or while a cluster is connected.
+//!
+//! A context is added to a shell via `Shell::add_context()`. Before each line is read, the shell
+//! checks every context, mounting the commands, abbreviations and aliases of those that became
+//! active, and unmounting those of the contexts that stopped being active:
+//!
+//! ```
+//! # use shi::cmd;
+//! # use shi::context::Context;
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new("| ");
+//! shell.add_context(
+//!     Context::in_dir("project", "/home/me/project")
+//!         .commands(|| vec![cmd!("build", |_, _| Ok(String::from("built")))])
+//!         .abbreviation("b", "build")
+//!         .alias("rebuild", "build --clean"),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::command::Command;
+
+/// What a context mounted into a shell, so that it can be unmounted once the context is no longer
+/// active.
+#[derive(Debug, Default)]
+pub(crate) struct Mounted {
+    /// The names of the commands that were mounted.
+    pub commands: Vec<String>,
+    /// The abbreviations that were mounted, with the expansions they shadowed, if any.
+    pub abbreviations: Vec<(String, Option<String>)>,
+    /// The aliases that were mounted, with the expansions they shadowed, if any.
+    pub aliases: Vec<(String, Option<String>)>,
+}
+
+/// Context is a set of commands, abbreviations and aliases that are mounted into a shell while a
+/// predicate holds, and unmounted once it no longer does.
+pub struct Context<'a, S> {
+    name: String,
+    detect: Box<dyn Fn() -> bool + 'a>,
+    commands: Option<Box<dyn Fn() -> Vec<Command<'a, S>> + 'a>>,
+    abbreviations: Vec<(String, String)>,
+    aliases: Vec<(String, String)>,
+    // What was mounted for this context, if it is active.
+    mounted: Option<Mounted>,
+}
+
+impl<'a, S> Context<'a, S> {
+    /// Creates a new Context, which is active whenever `detect` returns true.
+    ///
+    /// # Arguments
+    /// `name` - The name of the context, used in warnings.
+    /// `detect` - Returns whether the context is active. It is called before every line is read,
+    /// so it should be cheap.
+    pub fn new<F>(name: &str, detect: F) -> Context<'a, S>
+    where
+        F: Fn() -> bool + 'a,
+    {
+        Context {
+            name: name.to_string(),
+            detect: Box::new(detect),
+            commands: None,
+            abbreviations: Vec::new(),
+            aliases: Vec::new(),
+            mounted: None,
+        }
+    }
+
+    /// Creates a new Context, which is active whenever the working directory is `dir`, or is
+    /// inside of it.
+    ///
+    /// # Arguments
+    /// `name` - The name of the context, used in warnings.
+    /// `dir` - The directory that the context is for.
+    pub fn in_dir<P: Into<PathBuf>>(name: &str, dir: P) -> Context<'a, S> {
+        let dir = dir.into();
+        Context::new(name, move || match env::current_dir() {
+            Ok(cwd) => cwd.starts_with(&dir),
+            Err(_) => false,
+        })
+    }
+
+    /// Sets the commands of the context. The commands are constructed anew each time the context
+    /// becomes active.
+    ///
+    /// # Arguments
+    /// `commands` - Constructs the commands to mount.
+    pub fn commands<F>(mut self, commands: F) -> Self
+    where
+        F: Fn() -> Vec<Command<'a, S>> + 'a,
+    {
+        self.commands = Some(Box::new(commands));
+        self
+    }
+
+    /// Adds an abbreviation to the context, which is only expanded while the context is active.
+    /// An abbreviation of the same word that it shadows meanwhile is restored once the context is
+    /// no longer active.
+    ///
+    /// # Arguments
+    /// `word` - The word to expand.
+    /// `expansion` - The text to expand it into.
+    pub fn abbreviation(mut self, word: &str, expansion: &str) -> Self {
+        self.abbreviations
+            .push((word.to_string(), expansion.to_string()));
+        self
+    }
+
+    /// Adds an alias to the context, which is only resolved while the context is active. An alias
+    /// of the same name that it shadows meanwhile is restored once the context is no longer
+    /// active. See `Shell::add_alias()`.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias.
+    /// `expansion` - The text that the alias stands for.
+    pub fn alias(mut self, name: &str, expansion: &str) -> Self {
+        self.aliases.push((name.to_string(), expansion.to_string()));
+        self
+    }
+
+    /// Returns the name of the context.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the context is currently mounted.
+    pub fn is_active(&self) -> bool {
+        self.mounted.is_some()
+    }
+
+    pub(crate) fn detect(&self) -> bool {
+        (self.detect)()
+    }
+
+    pub(crate) fn build_commands(&self) -> Vec<Command<'a, S>> {
+        match &self.commands {
+            Some(commands) => commands(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn abbreviations(&self) -> &[(String, String)] {
+        &self.abbreviations
+    }
+
+    pub(crate) fn aliases(&self) -> &[(String, String)] {
+        &self.aliases
+    }
+
+    pub(crate) fn mounted(&self) -> Option<&Mounted> {
+        self.mounted.as_ref()
+    }
+
+    pub(crate) fn set_mounted(&mut self, mounted: Option<Mounted>) {
+        self.mounted = mounted;
+    }
+
+    pub(crate) fn take_mounted(&mut self) -> Option<Mounted> {
+        self.mounted.take()
+    }
+}
+
+impl<'a, S> fmt::Debug for Context<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("name", &self.name)
+            .field("abbreviations", &self.abbreviations)
+            .field("aliases", &self.aliases)
+            .field("mounted", &self.mounted)
+            .finish()
+    }
+}
@@ -5,6 +5,8 @@ use thiserror::Error;
 pub enum ShiError {
     #[error("readline error")]
     ReadlineError(#[from] rustyline::error::ReadlineError),
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
     #[error("expected a non-zero number of args, got none")]
     NoArgs,
     #[error("expected no args, but got {got:?}")]
@@ -13,6 +15,11 @@ pub enum ShiError {
     InvalidSubCommand { got: String, expected: Vec<String> },
     #[error("unrecognized command: '{got}'")]
     UnrecognizedCommand { got: String },
+    #[error("ambiguous command '{got}', could be any of: {}", .candidates.join(", "))]
+    AmbiguousCommand {
+        got: String,
+        candidates: Vec<String>,
+    },
     #[error("command already registered: {cmd}")]
     AlreadyRegistered { cmd: String },
     #[error("command failed to parse: {msg}")]
@@ -0,0 +1,149 @@
+//! A module for notifying embedders of changes that commands make to their state.
+//!
+//! Commands publish events via a `Notifier`, obtained from `Shell::notifier()` when they are
+//! constructed. Embedders subscribe to events via `Shell::subscribe()`, e.g. to refresh a view of
+//! state that a command mutated:
+//!
+//! ```
+//! # use std::cell::Cell;
+//! # use std::rc::Rc;
+//! # use shi::command::BasicCommand;
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new_with_state("| ", Vec::new());
+//!
+//! let notifier = shell.notifier();
+//! shell.register(shi::cmd!("push", move |items: &mut Vec<String>, args: &[String]| {
+//!     items.extend_from_slice(args);
+//!     notifier.notify("list.changed");
+//!     Ok(String::new())
+//! }))?;
+//!
+//! let refreshes = Rc::new(Cell::new(0));
+//! let counter = refreshes.clone();
+//! shell.subscribe("list.changed", move |_| counter.set(counter.get() + 1));
+//!
+//! shell.eval("push a b")?;
+//! assert_eq!(refreshes.get(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Callback = Box<dyn FnMut(&str)>;
+
+#[derive(Default)]
+struct Inner {
+    pending: RefCell<Vec<String>>,
+    subscribers: RefCell<Vec<(String, Callback)>>,
+}
+
+#[derive(Clone, Default)]
+/// A Notifier publishes events to subscribers.
+///
+/// Events are queued when they are published, and delivered once the line being evaluated has
+/// finished evaluating, so that subscribers observe the state after the command is done with it.
+/// Clones of a Notifier share the same queue and subscribers.
+pub struct Notifier {
+    inner: Rc<Inner>,
+}
+
+impl Notifier {
+    /// Creates a new Notifier, without any subscribers.
+    pub fn new() -> Notifier {
+        Notifier::default()
+    }
+
+    /// Publishes an event.
+    ///
+    /// # Arguments
+    /// `event` - The name of the event, e.g. `list.changed`.
+    pub fn notify(&self, event: &str) {
+        self.inner.pending.borrow_mut().push(event.to_string());
+    }
+
+    /// Subscribes the given callback to an event. The callback is called with the name of the
+    /// event.
+    ///
+    /// Callbacks may publish further events, but may not subscribe to any.
+    ///
+    /// # Arguments
+    /// `event` - The name of the event to subscribe to, or `*` for every event.
+    /// `callback` - The callback to call when the event is published.
+    pub fn subscribe<F: FnMut(&str) + 'static>(&self, event: &str, callback: F) {
+        self.inner
+            .subscribers
+            .borrow_mut()
+            .push((event.to_string(), Box::new(callback)));
+    }
+
+    /// Delivers the queued events to their subscribers, including any events published by the
+    /// subscribers themselves.
+    pub fn dispatch(&self) {
+        loop {
+            let pending: Vec<String> = self.inner.pending.borrow_mut().drain(..).collect();
+            if pending.is_empty() {
+                return;
+            }
+
+            let mut subscribers = self.inner.subscribers.borrow_mut();
+            for event in pending {
+                for (subscribed, callback) in subscribers.iter_mut() {
+                    if subscribed == "*" || *subscribed == event {
+                        callback(&event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dispatch() {
+        let notifier = Notifier::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let all = seen.clone();
+        notifier.subscribe("*", move |event| {
+            all.borrow_mut().push(format!("*: {}", event))
+        });
+        let one = seen.clone();
+        notifier.subscribe("b", move |event| {
+            one.borrow_mut().push(format!("b: {}", event))
+        });
+
+        notifier.notify("a");
+        notifier.notify("b");
+        assert!(seen.borrow().is_empty());
+
+        notifier.dispatch();
+        assert_eq!(*seen.borrow(), vec!["*: a", "*: b", "b: b"]);
+
+        // Events are only delivered once.
+        notifier.dispatch();
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn cascading() {
+        let notifier = Notifier::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let cascader = notifier.clone();
+        notifier.subscribe("a", move |_| cascader.notify("b"));
+        let b = seen.clone();
+        notifier.subscribe("b", move |event| b.borrow_mut().push(event.to_string()));
+
+        notifier.notify("a");
+        notifier.dispatch();
+        assert_eq!(*seen.borrow(), vec!["b"]);
+    }
+}
@@ -4,13 +4,39 @@
 
 use std::result;
 
+// Lets the code generated by `#[derive(ShiCommand)]`, which refers to `::shi`, be used within this
+// crate too.
+#[cfg(feature = "derive")]
+extern crate self as shi;
+
+mod abbreviations;
+mod aliases;
+#[cfg(feature = "clipboard")]
+mod clipboard;
 pub mod command;
-mod command_set;
+pub mod command_set;
+pub mod completions;
+pub mod context;
 pub mod error;
+pub mod events;
+pub mod line_mode;
+pub mod output;
+pub mod packs;
 mod parser;
+pub mod prompt;
+pub mod queue;
 mod readline;
+pub mod remote;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod screen;
+pub mod settings;
 pub mod shell;
-mod tokenizer;
+mod snippets;
+pub mod timestamp;
+pub mod tokenizer;
+pub mod tree;
+mod usage;
 
 pub type Result<T> = result::Result<T, error::ShiError>;
 
@@ -72,3 +98,116 @@ macro_rules! cmd {
         ))
     };
 }
+
+/// Creates a whole table of commands at once, as a `Vec<Command>`, without a proc-macro. Each
+/// entry is a name, optionally followed by options in parentheses, and either a handler, for a
+/// leaf command, or a braced table of subcommands, for a parent command:
+///
+/// ```
+/// # use shi::shell::Shell;
+/// # fn main() -> shi::Result<()> {
+/// let mut shell = Shell::new("| ");
+/// for cmd in shi::commands! {
+///     "server"(help: "Manages the server") => {
+///         "listen"(help: "Listens on a port", args: 1..=1, complete: ["80", "443"]) => |_, args| {
+///             Ok(format!("listening on {}", args[0]))
+///         },
+///         "stop" => |_, _| Ok(String::from("stopped")),
+///     },
+///     "ping"(help: "Pings the server") => |_, _| Ok(String::from("pong")),
+/// } {
+///     shell.register(cmd)?;
+/// }
+///
+/// assert_eq!(shell.eval("server listen 80")?, "listening on 80");
+/// assert!(shell.eval("server listen").is_err());
+/// assert_eq!(shell.eval("ping")?, "pong");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Leaf commands are `BasicCommand`s, and take the options `help`, `long_help`, `since`,
+/// `category` and `confirm`, as in `BasicCommandBuilder`, as well as `args`, the range of the
+/// number of their arguments, e.g. `1..`, and `complete`, the values their first argument completes
+/// to. Parent commands take only `help`. Handlers must be closures or paths to functions, since a
+/// braced handler is taken for a table of subcommands.
+#[macro_export]
+macro_rules! commands {
+    // Each entry is converted in turn, and appended to the commands converted so far.
+    (@munch [$($out:tt)*]) => {
+        vec![$($out)*]
+    };
+    (@munch [$($out:tt)*]
+        $name:literal $( ( $($key:ident : $value:expr),* $(,)? ) )? => { $($children:tt)* }
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::commands!(@munch
+            [$($out)* $crate::commands!(@parent $name [$($($key : $value),*)?] $($children)*),]
+            $($($rest)*)?
+        )
+    };
+    (@munch [$($out:tt)*]
+        $name:literal $( ( $($key:ident : $value:expr),* $(,)? ) )? => $exec:expr
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::commands!(@munch
+            [$($out)* $crate::commands!(@leaf $name [$($($key : $value),*)?] $exec),]
+            $($($rest)*)?
+        )
+    };
+    (@parent $name:literal [] $($children:tt)*) => {
+        $crate::command::Command::new_parent($name, $crate::commands!($($children)*))
+    };
+    (@parent $name:literal [help : $help:expr] $($children:tt)*) => {
+        $crate::command::Command::Parent($crate::command::ParentCommand::new_with_help(
+            $name,
+            $help,
+            $crate::commands!($($children)*),
+        ))
+    };
+    (@parent $name:literal [$($options:tt)*] $($children:tt)*) => {
+        compile_error!(concat!("parent command '", $name, "' only takes a `help` option"))
+    };
+    (@leaf $name:literal [$($options:tt)*] $exec:expr) => {
+        $crate::command::Command::new_leaf(
+            $crate::commands!(@options
+                $crate::command::BasicCommand::builder($name), $($options)*
+            )
+            .build($exec),
+        )
+    };
+    // Each option is applied in turn to the builder of a leaf command.
+    (@options $builder:expr $(,)?) => {
+        $builder
+    };
+    (@options $builder:expr, help : $help:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.help($help) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, long_help : $long_help:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.long_help($long_help) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, since : $since:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.since($since) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, category : $category:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.category($category) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, confirm : $question:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.confirm($question) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, args : $arity:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options $builder.arity($arity) $(, $($rest)*)?)
+    };
+    (@options $builder:expr, complete : $values:expr $(, $($rest:tt)*)?) => {
+        $crate::commands!(@options
+            $builder.arg($crate::command::ArgSpec::new().with_values(&$values))
+            $(, $($rest)*)?
+        )
+    };
+    (@options $builder:expr, $key:ident : $value:expr $(, $($rest:tt)*)?) => {
+        compile_error!(concat!("unknown command option `", stringify!($key), "`"))
+    };
+    ( $($entries:tt)* ) => {
+        $crate::commands!(@munch [] $($entries)*)
+    };
+}
@@ -0,0 +1,245 @@
+//! A module for line modes, in which every line goes to a handler as typed, bypassing the parser,
+//! e.g. for a query console embedded in a shell.
+//!
+//! Commands enter a line mode via a `LineModes` handle, obtained from `Shell::line_modes()` when
+//! they are constructed. The mode lasts until its escape line, `.exit` by default, is entered:
+//!
+//! ```
+//! # use shi::line_mode::LineMode;
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new_with_state("| ", Vec::new());
+//!
+//! let modes = shell.line_modes();
+//! shell.register(shi::cmd!("sql", move |_, _| {
+//!     modes.enter(LineMode::new(|queries: &mut Vec<String>, line| {
+//!         queries.push(line.to_string());
+//!         Ok(format!("ran '{}'", line))
+//!     }));
+//!     Ok(String::from("entered SQL mode, .exit to leave"))
+//! }))?;
+//!
+//! shell.eval("sql")?;
+//! assert_eq!(shell.eval("select *  from users")?, "ran 'select *  from users'");
+//! shell.eval(".exit")?;
+//! assert!(shell.eval("select 1").is_err());
+//! assert_eq!(shell.state(), &vec![String::from("select *  from users")]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::Result;
+
+/// The handler of a line mode, which is given each line as typed.
+type Handler<S> = Box<dyn FnMut(&mut S, &str) -> Result<String>>;
+
+/// LineMode is a mode in which every line goes to a handler, until the escape line is entered.
+pub struct LineMode<S> {
+    handler: Handler<S>,
+    escape: String,
+    prompt: Option<String>,
+}
+
+impl<S> LineMode<S> {
+    /// Creates a new LineMode, left via `.exit`.
+    ///
+    /// # Arguments
+    /// `handler` - Handles each line, as typed. Its output is printed as the output of a command
+    /// would be.
+    pub fn new<F>(handler: F) -> LineMode<S>
+    where
+        F: FnMut(&mut S, &str) -> Result<String> + 'static,
+    {
+        LineMode {
+            handler: Box::new(handler),
+            escape: String::from(".exit"),
+            prompt: None,
+        }
+    }
+
+    /// Sets the line that leaves the mode.
+    ///
+    /// # Arguments
+    /// `escape` - The line that leaves the mode, e.g. `\q`.
+    pub fn escape(mut self, escape: &str) -> Self {
+        self.escape = escape.to_string();
+        self
+    }
+
+    /// Sets the prompt shown while in the mode, in place of that of the shell.
+    ///
+    /// # Arguments
+    /// `prompt` - The prompt, e.g. `sql> `.
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+}
+
+impl<S> fmt::Debug for LineMode<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LineMode")
+            .field("escape", &self.escape)
+            .field("prompt", &self.prompt)
+            .finish()
+    }
+}
+
+struct Inner<S> {
+    mode: RefCell<Option<LineMode<S>>>,
+    // Whether the mode was left while its handler was running, i.e., while it was taken out.
+    left: Cell<bool>,
+}
+
+/// LineModes is a handle for entering and leaving line modes. Clones of a LineModes share the same
+/// mode.
+pub struct LineModes<S> {
+    inner: Rc<Inner<S>>,
+}
+
+impl<S> Clone for LineModes<S> {
+    fn clone(&self) -> Self {
+        LineModes {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> Default for LineModes<S> {
+    fn default() -> Self {
+        LineModes {
+            inner: Rc::new(Inner {
+                mode: RefCell::new(None),
+                left: Cell::new(false),
+            }),
+        }
+    }
+}
+
+impl<S> fmt::Debug for LineModes<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LineModes")
+            .field("mode", &self.inner.mode.borrow())
+            .finish()
+    }
+}
+
+impl<S> LineModes<S> {
+    /// Creates a new LineModes, not in any mode.
+    pub fn new() -> LineModes<S> {
+        LineModes::default()
+    }
+
+    /// Enters the given mode, replacing the current one, if any. The mode takes effect from the
+    /// next line on.
+    ///
+    /// # Arguments
+    /// `mode` - The mode to enter.
+    pub fn enter(&self, mode: LineMode<S>) {
+        self.inner.left.set(false);
+        *self.inner.mode.borrow_mut() = Some(mode);
+    }
+
+    /// Enters a mode with the given handler, left via `.exit`. See `LineMode::new()`.
+    ///
+    /// # Arguments
+    /// `handler` - Handles each line, as typed.
+    pub fn enter_line_mode<F>(&self, handler: F)
+    where
+        F: FnMut(&mut S, &str) -> Result<String> + 'static,
+    {
+        self.enter(LineMode::new(handler));
+    }
+
+    /// Leaves the current mode, if any, e.g. from its handler.
+    pub fn leave(&self) {
+        self.inner.left.set(true);
+        *self.inner.mode.borrow_mut() = None;
+    }
+
+    /// Returns whether a mode is active.
+    pub fn is_active(&self) -> bool {
+        self.inner.mode.borrow().is_some()
+    }
+
+    /// Returns the prompt of the current mode, if it has one.
+    pub(crate) fn prompt(&self) -> Option<String> {
+        self.inner.mode.borrow().as_ref()?.prompt.clone()
+    }
+
+    /// Hands the given line to the current mode, if any.
+    ///
+    /// # Arguments
+    /// `state` - The state of the shell.
+    /// `line` - The line, as typed.
+    ///
+    /// # Returns
+    /// `Option<Result<String>>` - The output of the handler, or nothing if the line was the escape
+    /// line. None if no mode is active.
+    pub(crate) fn handle(&self, state: &mut S, line: &str) -> Option<Result<String>> {
+        // The mode is taken out while its handler runs, so that the handler may enter or leave
+        // modes itself.
+        let mut mode = self.inner.mode.borrow_mut().take()?;
+        if line.trim() == mode.escape {
+            return Some(Ok(String::new()));
+        }
+
+        self.inner.left.set(false);
+        let res = (mode.handler)(state, line);
+        if !self.inner.left.get() && !self.is_active() {
+            *self.inner.mode.borrow_mut() = Some(mode);
+        }
+
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::error::ShiError;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn handles_lines_until_escaped() {
+        let modes: LineModes<u32> = LineModes::new();
+        let mut count = 0;
+        assert!(modes.handle(&mut count, "anything").is_none());
+
+        modes.enter(
+            LineMode::new(|count: &mut u32, line| {
+                *count += 1;
+                if line == "fail" {
+                    return Err(ShiError::general("failed"));
+                }
+                Ok(line.to_uppercase())
+            })
+            .escape("\\q")
+            .prompt("sql> "),
+        );
+        assert!(modes.is_active());
+        assert_eq!(modes.prompt(), Some(String::from("sql> ")));
+
+        assert_eq!(modes.handle(&mut count, "a  b").unwrap().unwrap(), "A  B");
+        assert!(modes.handle(&mut count, "fail").unwrap().is_err());
+        assert!(modes.is_active());
+        assert_eq!(modes.handle(&mut count, " \\q ").unwrap().unwrap(), "");
+        assert!(!modes.is_active());
+        assert_eq!(count, 2);
+
+        // The handler may leave the mode itself.
+        let inner = modes.clone();
+        modes.enter_line_mode(move |_, _| {
+            inner.leave();
+            Ok(String::new())
+        });
+        assert!(modes.handle(&mut count, "quit").unwrap().is_ok());
+        assert!(!modes.is_active());
+    }
+}
@@ -0,0 +1,421 @@
+//! A module for post-processing the output of a shell before it is displayed, and for where it is
+//! displayed.
+//!
+//! Commands may return structured output, a `CommandOutput`, which the shell turns into text via
+//! its `Renderer`. The default renderer lays output out for people, while `JsonRenderer` makes it
+//! machine-readable:
+//!
+//! ```
+//! # use shi::output::{CommandOutput, JsonRenderer, PlainRenderer, Renderer};
+//! let output = CommandOutput::table(
+//!     &["name", "status"],
+//!     vec![vec!["api", "up"], vec!["worker", "down"]],
+//! );
+//! assert_eq!(
+//!     PlainRenderer.render(&output),
+//!     "name    status\napi     up\nworker  down"
+//! );
+//! assert_eq!(
+//!     JsonRenderer.render(&output),
+//!     r#"[{"name":"api","status":"up"},{"name":"worker","status":"down"}]"#
+//! );
+//! ```
+
+use std::io::{self, Write};
+
+use unicode_width::UnicodeWidthStr;
+
+/// OutputSink is where a `Shell` writes what it displays, e.g. the output of commands and errors.
+/// By default, this is `Stdout`, but it can be replaced via `Shell::set_output_sink()`, e.g. to
+/// route output to a logger, a text widget of a GUI, or to capture it in tests.
+pub trait OutputSink {
+    /// Writes a line of output, e.g. the output of a command.
+    ///
+    /// # Arguments
+    /// `output` - The output to write.
+    fn write_output(&mut self, output: &str);
+
+    /// Writes an error or a warning, already prefixed, e.g. with `Error:`.
+    ///
+    /// # Arguments
+    /// `error` - The error to write.
+    fn write_error(&mut self, error: &str);
+
+    /// Writes the prompt, without a newline, for shells that read input themselves, e.g. via
+    /// `Shell::poll_input()`. Otherwise, the prompt is displayed by the line editor.
+    ///
+    /// The default implementation writes nothing, e.g. for sinks whose display has a prompt of
+    /// its own.
+    ///
+    /// # Arguments
+    /// `prompt` - The prompt to write.
+    fn write_prompt(&mut self, _prompt: &str) {}
+}
+
+#[derive(Debug, Default)]
+/// Stdout is the default `OutputSink`, which prints everything, including errors, to stdout.
+pub struct Stdout;
+
+impl OutputSink for Stdout {
+    fn write_output(&mut self, output: &str) {
+        println!("{}", output);
+    }
+
+    fn write_error(&mut self, error: &str) {
+        println!("{}", error);
+    }
+
+    fn write_prompt(&mut self, prompt: &str) {
+        print!("{}", prompt);
+        let _ = io::stdout().flush();
+    }
+}
+
+/// CommandOutput is the output of a command, which the shell renders into text via its `Renderer`.
+/// See `BaseCommand::execute_output()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutput {
+    /// Plain text, displayed as is.
+    Text(String),
+    /// Rows of cells, under the given headers.
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    /// Pairs of keys and values, in order.
+    KeyValue(Vec<(String, String)>),
+    /// Nothing to display, e.g. for commands that only change state.
+    Silent,
+}
+
+impl CommandOutput {
+    /// Creates a `Table` from the given headers and rows.
+    ///
+    /// # Arguments
+    /// `headers` - The headers of the columns.
+    /// `rows` - The rows, each a cell per column.
+    pub fn table<H, R, C>(headers: &[H], rows: R) -> CommandOutput
+    where
+        H: ToString,
+        R: IntoIterator,
+        R::Item: IntoIterator<Item = C>,
+        C: ToString,
+    {
+        CommandOutput::Table {
+            headers: headers.iter().map(ToString::to_string).collect(),
+            rows: rows
+                .into_iter()
+                .map(|row| row.into_iter().map(|cell| cell.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    /// Creates a `KeyValue` from the given pairs.
+    ///
+    /// # Arguments
+    /// `pairs` - The keys and their values, in the order to display them.
+    pub fn key_value<K, V, I>(pairs: I) -> CommandOutput
+    where
+        K: ToString,
+        V: ToString,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        CommandOutput::KeyValue(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl From<String> for CommandOutput {
+    fn from(text: String) -> Self {
+        CommandOutput::Text(text)
+    }
+}
+
+impl From<&str> for CommandOutput {
+    fn from(text: &str) -> Self {
+        CommandOutput::Text(text.to_string())
+    }
+}
+
+/// Renderer turns the structured output of commands into the text that a `Shell` displays. By
+/// default, this is `PlainRenderer`, but it can be replaced via `Shell::set_renderer()`, e.g. with
+/// `JsonRenderer` for scripts.
+pub trait Renderer {
+    /// Renders the given output.
+    ///
+    /// # Arguments
+    /// `output` - The output to render.
+    fn render(&self, output: &CommandOutput) -> String;
+}
+
+#[derive(Debug, Default)]
+/// PlainRenderer is the default `Renderer`, which lays output out for people: tables in aligned
+/// columns, and pairs as `key: value` lines, with their values aligned.
+pub struct PlainRenderer;
+
+impl Renderer for PlainRenderer {
+    fn render(&self, output: &CommandOutput) -> String {
+        match output {
+            CommandOutput::Text(text) => text.clone(),
+            CommandOutput::Table { headers, rows } => {
+                let columns = rows.iter().map(Vec::len).fold(headers.len(), usize::max);
+                let mut widths = vec![0; columns];
+                for row in std::iter::once(headers).chain(rows) {
+                    for (i, cell) in row.iter().enumerate() {
+                        widths[i] = widths[i].max(display_width(cell));
+                    }
+                }
+
+                std::iter::once(headers)
+                    .chain(rows)
+                    .map(|row| {
+                        let mut line = String::new();
+                        for (i, cell) in row.iter().enumerate() {
+                            line.push_str(cell);
+                            if i + 1 < row.len() {
+                                line.push_str(&" ".repeat(widths[i] - display_width(cell) + 2));
+                            }
+                        }
+                        line
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            CommandOutput::KeyValue(pairs) => {
+                let width = pairs
+                    .iter()
+                    .map(|(key, _)| display_width(key))
+                    .max()
+                    .unwrap_or(0);
+                pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}:{}{}",
+                            key,
+                            " ".repeat(width - display_width(key) + 1),
+                            value
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            CommandOutput::Silent => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// JsonRenderer is a `Renderer` for scripts, which renders output as JSON: text as a string,
+/// tables as arrays of objects keyed by the headers, pairs as an object, and silent output as
+/// nothing.
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render(&self, output: &CommandOutput) -> String {
+        match output {
+            CommandOutput::Text(text) => json_string(text),
+            CommandOutput::Table { headers, rows } => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| json_object(headers.iter().zip(row)))
+                    .collect();
+                format!("[{}]", rows.join(","))
+            }
+            CommandOutput::KeyValue(pairs) => {
+                json_object(pairs.iter().map(|(key, value)| (key, value)))
+            }
+            CommandOutput::Silent => String::new(),
+        }
+    }
+}
+
+/// Renders the given pairs as a JSON object of strings.
+fn json_object<'o>(pairs: impl Iterator<Item = (&'o String, &'o String)>) -> String {
+    let members: Vec<String> = pairs
+        .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+        .collect();
+
+    format!("{{{}}}", members.join(","))
+}
+
+/// Strips ANSI escape sequences, e.g. colors, from the given string.
+///
+/// This handles CSI sequences (`ESC [ ... <final byte>`), OSC sequences (`ESC ] ... BEL` or
+/// `ESC ] ... ESC \`) and other two-character escapes (`ESC <char>`).
+///
+/// # Arguments
+/// `s` - The string to strip ANSI escape sequences from.
+///
+/// # Returns
+/// `String` - The string, without any ANSI escape sequences.
+pub fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            stripped.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: parameters and intermediates, terminated by a byte in the range @ to ~.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by either BEL or ST (ESC \).
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Anything else is a two-character escape, which we've now consumed entirely.
+            _ => continue,
+        }
+    }
+
+    stripped
+}
+
+/// Returns how many columns the given string takes up on a terminal. Unlike its length, this
+/// accounts for wide characters, e.g. CJK characters, which take up two columns, and ignores ANSI
+/// escape sequences, which take up none.
+///
+/// # Arguments
+/// `s` - The string to measure.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(strip_ansi(s).as_str())
+}
+
+/// Quotes the given string as a JSON string, escaping it as necessary.
+///
+/// # Arguments
+/// `s` - The string to quote.
+///
+/// # Returns
+/// `String` - The JSON string, including the surrounding quotes.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn no_escapes() {
+        assert_eq!(strip_ansi("hello world"), "hello world");
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            strip_ansi("\x1b[1;30mhello\x1b[0m \x1b[38;5;82mworld\x1b[0m"),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        assert_eq!(
+            strip_ansi("\x1b]0;title\x07hello \x1b]8;;http://a.b\x1b\\world"),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(display_width("hello"), 5);
+        assert_eq!(display_width("こんにちは"), 10);
+        assert_eq!(display_width("\x1b[1mhi\x1b[0m 世界"), 7);
+    }
+
+    #[test]
+    fn two_char_escape() {
+        assert_eq!(strip_ansi("\x1bchello"), "hello");
+    }
+
+    #[test]
+    fn trailing_escape() {
+        assert_eq!(strip_ansi("hello\x1b"), "hello");
+        assert_eq!(strip_ansi("hello\x1b[1;3"), "hello");
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(strip_ansi("\x1b[31mこんにちは\x1b[0m"), "こんにちは");
+    }
+
+    #[test]
+    fn renders_plain() {
+        let table =
+            CommandOutput::table(&["id", "名前"], vec![vec!["1", "a"], vec!["22", "bb", "x"]]);
+        assert_eq!(PlainRenderer.render(&table), "id  名前\n1   a\n22  bb    x");
+        assert_eq!(
+            PlainRenderer.render(&CommandOutput::key_value(vec![("port", 80), ("uptime", 5)])),
+            "port:   80\nuptime: 5"
+        );
+        assert_eq!(PlainRenderer.render(&CommandOutput::from("hi")), "hi");
+        assert_eq!(PlainRenderer.render(&CommandOutput::Silent), "");
+        assert_eq!(
+            PlainRenderer.render(&CommandOutput::KeyValue(Vec::new())),
+            ""
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        let table = CommandOutput::table(&["id", "name"], vec![vec!["1", "a\"b"]]);
+        assert_eq!(JsonRenderer.render(&table), r#"[{"id":"1","name":"a\"b"}]"#);
+        assert_eq!(
+            JsonRenderer.render(&CommandOutput::key_value(vec![("port", "80")])),
+            r#"{"port":"80"}"#
+        );
+        assert_eq!(
+            JsonRenderer.render(&CommandOutput::from("a\nb")),
+            r#""a\nb""#
+        );
+        assert_eq!(JsonRenderer.render(&CommandOutput::Silent), "");
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("hello"), "\"hello\"");
+        assert_eq!(
+            json_string("say \"hi\"\n\tC:\\ \x1b"),
+            "\"say \\\"hi\\\"\\n\\tC:\\\\ \\u001b\""
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! A pack of commands for debugging a running shell.
+//!
+//! These can be mounted into a shell in one line:
+//!
+//! ```
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new("| ");
+//! shell.mount("debug", shi::packs::debug::commands(&shell))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
+
+use crate::command::{BaseCommand, Command, Completion};
+use crate::command_set::CommandSet;
+use crate::error::ShiError;
+use crate::shell::{Diagnostics, Shell, Verbosity};
+use crate::Result;
+
+/// Creates the debug commands for the given shell. The returned commands introspect `shell`, and
+/// are meant to be mounted into it, e.g. via `Shell::mount()`.
+///
+/// The commands are:
+/// * `uptime` - Prints how long the shell has been running.
+/// * `memory` - Prints the resident memory usage of the process, where supported.
+/// * `last-error` - Prints the last error produced by the shell.
+/// * `trace` - Toggles tracing of how each line is parsed and dispatched.
+/// * `tree` - Dumps the tree of registered commands.
+///
+/// # Arguments
+/// `shell` - The shell that the commands will introspect.
+pub fn commands<'a, S: 'a>(shell: &Shell<'a, S>) -> Vec<Command<'a, S>> {
+    vec![
+        Command::new_leaf(UptimeCommand::new(shell.diagnostics.clone())),
+        Command::new_leaf(MemoryCommand::new()),
+        Command::new_leaf(LastErrorCommand::new(shell.diagnostics.clone())),
+        Command::new_leaf(TraceCommand::new(shell.diagnostics.clone())),
+        Command::new_leaf(TreeCommand::new(Rc::downgrade(&shell.cmds))),
+    ]
+}
+
+fn expect_no_args(args: &[String]) -> Result<()> {
+    if !args.is_empty() {
+        return Err(ShiError::ExtraArgs { got: args.to_vec() });
+    }
+
+    Ok(())
+}
+
+/// UptimeCommand prints how long its shell has been running for.
+pub struct UptimeCommand<S> {
+    diagnostics: Rc<RefCell<Diagnostics>>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> UptimeCommand<S> {
+    fn new(diagnostics: Rc<RefCell<Diagnostics>>) -> UptimeCommand<S> {
+        UptimeCommand {
+            diagnostics,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> BaseCommand for UptimeCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "uptime"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        expect_no_args(args)
+    }
+
+    fn execute(&self, _: &mut S, _: &[String]) -> Result<String> {
+        let secs = self.diagnostics.borrow().started.elapsed().as_secs();
+        Ok(format!(
+            "up {}h {}m {}s",
+            secs / 3600,
+            (secs % 3600) / 60,
+            secs % 60
+        ))
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints how long the shell has been running")
+    }
+}
+
+/// MemoryCommand prints the resident memory usage of the process.
+///
+/// This is currently only supported on Linux.
+pub struct MemoryCommand<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S> MemoryCommand<S> {
+    fn new() -> MemoryCommand<S> {
+        MemoryCommand {
+            phantom: PhantomData,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn resident_memory(&self) -> Option<String> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .map(|rss| rss.trim().to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn resident_memory(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<S> BaseCommand for MemoryCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        expect_no_args(args)
+    }
+
+    fn execute(&self, _: &mut S, _: &[String]) -> Result<String> {
+        match self.resident_memory() {
+            Some(rss) => Ok(format!("resident memory: {}", rss)),
+            None => Err(ShiError::general(
+                "memory usage is not available on this platform",
+            )),
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints the memory usage of the shell")
+    }
+}
+
+/// LastErrorCommand prints the last error that its shell produced, along with the ID of the
+/// invocation that produced it.
+pub struct LastErrorCommand<S> {
+    diagnostics: Rc<RefCell<Diagnostics>>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> LastErrorCommand<S> {
+    fn new(diagnostics: Rc<RefCell<Diagnostics>>) -> LastErrorCommand<S> {
+        LastErrorCommand {
+            diagnostics,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> BaseCommand for LastErrorCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "last-error"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        expect_no_args(args)
+    }
+
+    fn execute(&self, _: &mut S, _: &[String]) -> Result<String> {
+        match &self.diagnostics.borrow().last_error {
+            Some((err, invocation)) => Ok(format!("{}\n(invocation {})", err, invocation)),
+            None => Ok(String::from("no errors yet")),
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Prints the last error")
+    }
+}
+
+/// TraceCommand toggles whether its shell prints traces of how each line is parsed and
+/// dispatched, by toggling between the `Verbose` and `Normal` verbosities. It optionally takes `on`
+/// or `off` to set tracing explicitly.
+pub struct TraceCommand<S> {
+    diagnostics: Rc<RefCell<Diagnostics>>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> TraceCommand<S> {
+    fn new(diagnostics: Rc<RefCell<Diagnostics>>) -> TraceCommand<S> {
+        TraceCommand {
+            diagnostics,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> BaseCommand for TraceCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "trace"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        match args {
+            [] => Ok(()),
+            [arg] if arg == "on" || arg == "off" => Ok(()),
+            [arg] => Err(ShiError::InvalidSubCommand {
+                got: arg.clone(),
+                expected: vec![String::from("on"), String::from("off")],
+            }),
+            _ => Err(ShiError::ExtraArgs {
+                got: args[1..].to_vec(),
+            }),
+        }
+    }
+
+    fn execute(&self, _: &mut S, args: &[String]) -> Result<String> {
+        // Whoever dispatches to this command may not have validated its arguments, so anything
+        // other than `on` or `off` is rejected here too.
+        self.validate_args(args)?;
+
+        let mut diagnostics = self.diagnostics.borrow_mut();
+        let trace = match args.first() {
+            Some(arg) => arg == "on",
+            None => diagnostics.verbosity != Verbosity::Verbose,
+        };
+
+        diagnostics.verbosity = if trace {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+
+        if trace {
+            Ok(String::from("tracing enabled"))
+        } else {
+            Ok(String::from("tracing disabled"))
+        }
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, _: bool) -> Completion {
+        match args.as_slice() {
+            [] => Completion::Possibilities(vec![String::from("on"), String::from("off")]),
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from("Toggles tracing of parsing and dispatch")
+    }
+}
+
+/// TreeCommand dumps the tree of commands registered in its shell.
+pub struct TreeCommand<'a, S> {
+    // This is weak, since this command is itself part of the tree it dumps.
+    cmds: Weak<RefCell<CommandSet<'a, S>>>,
+}
+
+impl<'a, S> TreeCommand<'a, S> {
+    fn new(cmds: Weak<RefCell<CommandSet<'a, S>>>) -> TreeCommand<'a, S> {
+        TreeCommand { cmds }
+    }
+}
+
+impl<'a, S> BaseCommand for TreeCommand<'a, S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        "tree"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        expect_no_args(args)
+    }
+
+    fn execute(&self, _: &mut S, _: &[String]) -> Result<String> {
+        let cmds = match self.cmds.upgrade() {
+            Some(cmds) => cmds,
+            None => return Err(ShiError::general("the shell no longer exists")),
+        };
+
+        let mut lines = Vec::new();
+        cmds.borrow().walk(|visit| {
+            lines.push(format!("{}{}", "  ".repeat(visit.depth), visit.cmd.name()));
+        });
+
+        Ok(lines.join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Dumps the tree of registered commands")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    fn make_shell<'a>() -> Result<Shell<'a, ()>> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.mount("debug", commands(&shell))?;
+
+        Ok(shell)
+    }
+
+    #[test]
+    fn uptime() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert!(shell.eval("debug uptime")?.starts_with("up 0h 0m"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn last_error() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert_eq!(shell.eval("debug last-error")?, "no errors yet");
+
+        assert!(shell.eval("cat").is_err());
+        let invocation = shell.invocation_id().unwrap();
+        assert_eq!(
+            shell.eval("debug last-error")?,
+            format!(
+                "command failed to parse: 'cat' is not a recognized command.\n\n\t => expected one \
+                of 'dog' or 'debug'.\n\nRun 'helptree' for more info on the entire command tree.\n\
+                \n(invocation {})",
+                invocation
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn trace() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert_eq!(shell.eval("debug trace")?, "tracing enabled");
+        assert_eq!(shell.verbosity(), Verbosity::Verbose);
+        assert_eq!(shell.eval("debug trace on")?, "tracing enabled");
+        assert_eq!(shell.eval("debug trace off")?, "tracing disabled");
+        assert_eq!(shell.verbosity(), Verbosity::Normal);
+        assert!(shell.eval("debug trace maybe").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tree() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert_eq!(
+            shell.eval("debug tree")?,
+            "dog\ndebug\n  uptime\n  memory\n  last-error\n  trace\n  tree"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+//! A module for reusable packs of commands.
+//!
+//! Packs are sets of commands that are not specific to any one application, and can be mounted
+//! into a `Shell` via `Shell::mount()`.
+
+pub mod debug;
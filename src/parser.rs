@@ -1,12 +1,20 @@
+use std::ops::Range;
+use std::rc::Rc;
+
 use crate::command::{Command, Completion};
 use crate::command_set::CommandSet;
 use crate::error::ShiError;
+use crate::output;
 use crate::shell::Shell;
-use crate::tokenizer::{DefaultTokenizer, Tokenization, Tokenizer};
+use crate::tokenizer::{DefaultTokenizer, Separator, Tokenization, Tokenizer};
+use crate::Result;
 
 /// A parser that parses input lines into `Command` invocations.
+#[derive(Clone)]
 pub struct Parser {
-    tokenizer: DefaultTokenizer,
+    // Splits lines into chains and pipelines, and tokenizes them unless there's a custom tokenizer.
+    default_tokenizer: DefaultTokenizer,
+    custom_tokenizer: Option<Rc<dyn Tokenizer>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -24,13 +32,13 @@ pub enum CommandType {
 /// from the parse:
 ///
 /// * `cmd_path` - The components of the command invocation. In particular, it shows the chain of
-/// ancestry in terms of `Parent` commands and the eventual `Leaf` command.
+///   ancestry in terms of `Parent` commands and the eventual `Leaf` command.
 /// * `remaining` - The remaining components of the string. In the case of a successful parse, this
-/// represents the arguments passed to the command. In the case of unsuccessful or incomplete
-/// parses, this represents the part of the input string that was not able to be parsed.
+///   represents the arguments passed to the command. In the case of unsuccessful or incomplete
+///   parses, this represents the part of the input string that was not able to be parsed.
 /// * `cmd_type` - The type of the command. See `CommandType`.
 /// * `possibilities` - Includes the potential candidates that the parser is expecting to see
-/// following the input line.
+///   following the input line.
 /// * `complete` - A flag denoting whether we had a successful and complete parse.
 pub struct Outcome<'a> {
     pub cmd_path: Vec<&'a str>,
@@ -55,6 +63,42 @@ impl<'a> Outcome<'a> {
         }
     }
 
+    /// Suggests a corrected version of the line this `Outcome` was parsed from, for when the parse
+    /// failed on a token that is a likely typo of exactly one of the possibilities, e.g.
+    /// `server lisen` for `server listen`.
+    ///
+    /// # Arguments
+    /// `line` - The line this `Outcome` was parsed from.
+    ///
+    /// # Returns
+    /// `Option<String>` - The corrected line, or None if there is no single close match.
+    pub fn correction(&self, line: &str) -> Option<String> {
+        if self.complete {
+            return None;
+        }
+
+        let token = self.remaining.first()?;
+        // Allow roughly one typo for every three characters, but always at least one.
+        let max_distance = (token.chars().count() / 3).max(1);
+        let mut matches = self
+            .possibilities
+            .iter()
+            .filter(|poss| edit_distance(token, poss) <= max_distance);
+        let fixed = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+
+        // Tokens are slices of the line, so we can splice the fix in, leaving the rest as-is.
+        let range = token_range(line, token)?;
+        Some(format!(
+            "{}{}{}",
+            &line[..range.start],
+            fixed,
+            &line[range.end..]
+        ))
+    }
+
     /// Prints an error message for the `Outcome`. Of course, if the `Outcome` was complete, the
     /// error message is empty.
     pub fn error_msg(&self) -> String {
@@ -98,7 +142,10 @@ impl<'a> Outcome<'a> {
             } else {
                 msg += &format!("\t => '{} {}'\n", valid_prefix, invalid_suffix);
             }
-            msg += &format!("\t     {}^\n", " ".repeat(valid_prefix.len() + 1));
+            msg += &format!(
+                "\t     {}^\n",
+                " ".repeat(output::display_width(&valid_prefix) + 1)
+            );
 
             msg += "expected a valid subcommand\n";
             msg += "instead, got: ";
@@ -134,14 +181,139 @@ impl<'a> Outcome<'a> {
     }
 }
 
+#[derive(Debug, PartialEq)]
+/// Resolution is the result of a lightweight parse attempt, which only identifies the command an
+/// input line invokes and where its tokens lie, without building possibilities or error text. It
+/// is meant for callers that parse on every keystroke, e.g. for syntax highlighting.
+///
+/// * `cmd_type` - The type of the command. See `CommandType`.
+/// * `tokens` - The byte ranges of the tokens of the line, in order. Quoted tokens exclude their
+///   quotation marks.
+/// * `depth` - How many of the leading tokens name the command, i.e., its path. The rest are its
+///   arguments, or, in the case of an unsuccessful parse, the part that could not be parsed.
+/// * `complete` - A flag denoting whether the line resolved to a leaf command.
+pub struct Resolution {
+    pub cmd_type: CommandType,
+    pub tokens: Vec<Range<usize>>,
+    pub depth: usize,
+    pub complete: bool,
+}
+
+impl Resolution {
+    /// Returns the byte ranges of the tokens that name the command.
+    pub fn cmd_spans(&self) -> &[Range<usize>] {
+        &self.tokens[..self.depth]
+    }
+
+    /// Returns the byte ranges of the tokens following the command.
+    pub fn arg_spans(&self) -> &[Range<usize>] {
+        &self.tokens[self.depth..]
+    }
+}
+
+/// Returns the byte range of the given token within the line it was tokenized from. Tokens are
+/// usually slices of their line, but a custom tokenizer may return others, e.g. `'static` ones,
+/// in which case there is no range.
+pub(crate) fn token_range(line: &str, token: &str) -> Option<Range<usize>> {
+    let start = (token.as_ptr() as usize).checked_sub(line.as_ptr() as usize)?;
+    let end = start.checked_add(token.len())?;
+    if end > line.len() {
+        return None;
+    }
+
+    Some(start..end)
+}
+
+/// Computes the edit distance between two strings, i.e., the number of single-character
+/// insertions, deletions, substitutions or transpositions of adjacent characters it takes to
+/// turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // dist[i][j] is the distance between the first i characters of a and the first j of b.
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
 impl Parser {
     /// Constructs a new Parser.
     pub fn new() -> Parser {
         Parser {
-            tokenizer: DefaultTokenizer::new(vec!['\'', '"']),
+            default_tokenizer: DefaultTokenizer::new(vec!['\'', '"']),
+            custom_tokenizer: None,
         }
     }
 
+    /// Constructs a new Parser that tokenizes commands with the given tokenizer. Lines are still
+    /// split into chains and pipelines as usual before they are tokenized.
+    ///
+    /// # Arguments
+    /// `tokenizer` - The tokenizer to use.
+    pub fn new_with_tokenizer(tokenizer: Box<dyn Tokenizer>) -> Parser {
+        Parser {
+            custom_tokenizer: Some(Rc::from(tokenizer)),
+            ..Parser::new()
+        }
+    }
+
+    fn tokenizer(&self) -> &dyn Tokenizer {
+        match &self.custom_tokenizer {
+            Some(tokenizer) => &**tokenizer,
+            None => &self.default_tokenizer,
+        }
+    }
+
+    /// Returns the arguments of the command invoked by the given line, i.e., everything after its
+    /// first token. With the default tokenizer, arguments are split by spaces only, and keep their
+    /// quotes.
+    ///
+    /// # Arguments
+    /// `line` - The input line.
+    pub fn args(&self, line: &str) -> Vec<String> {
+        match &self.custom_tokenizer {
+            Some(tokenizer) => tokenizer
+                .tokenize(line)
+                .tokens
+                .iter()
+                .skip(1)
+                .map(|token| token.to_string())
+                .collect(),
+            None => line.split(' ').skip(1).map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Splits the line into the commands of a chain, e.g. `add title && list`. See
+    /// `DefaultTokenizer::split_chain()`.
+    pub fn split_chain<'a>(&self, line: &'a str) -> Vec<(&'a str, Option<Separator>)> {
+        self.default_tokenizer.split_chain(line)
+    }
+
+    /// Splits the line into the stages of a pipeline, e.g. `history | grep foo`. See
+    /// `DefaultTokenizer::split_pipeline()`.
+    pub fn split_pipeline<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        self.default_tokenizer.split_pipeline(line)
+    }
+
     /// Parses a given Vector of tokens into a parse `Outcome`.
     ///
     /// # Arguments
@@ -189,7 +361,7 @@ impl Parser {
             // Now, if this command has children, we want to go deeper into the set.
             // If it is a leaf command, and has we're actually done and can return the current
             // cmd_path and remaining tokens and complete.
-            match &**looked_up_cmd {
+            match looked_up_cmd {
                 Command::Leaf(cmd) => {
                     // This is a leaf command, so we are actually almost done.
                     // Leaf commands themselves, can, given their arguments, attempt a local
@@ -272,9 +444,207 @@ impl Parser {
         cmds: &CommandSet<S>,
         builtins: &CommandSet<Shell<S>>,
     ) -> Outcome<'a> {
-        let tokenization = self.tokenizer.tokenize(line);
+        let tokenization = self.tokenizer().tokenize(line);
         self.parse_tokens(&tokenization, cmds, builtins)
     }
+
+    /// Walks the given tokens down the given set, as far as they name commands.
+    ///
+    /// # Arguments
+    /// `tokens` - The tokens produced from an input line.
+    /// `set` - The available commands to resolve into.
+    ///
+    /// # Returns
+    /// `(usize, bool)` - How many tokens name commands, and whether the last of them is a leaf.
+    fn resolve_with_set<T>(&self, tokens: &[&str], set: &CommandSet<T>) -> (usize, bool) {
+        let mut current_set = set;
+        for (i, token) in tokens.iter().enumerate() {
+            match current_set.get(token) {
+                Some(Command::Leaf(_)) => return (i + 1, true),
+                Some(Command::Parent(cmd)) => current_set = cmd.sub_commands(),
+                None => return (i, false),
+            }
+        }
+
+        (tokens.len(), false)
+    }
+
+    /// Resolves the given line into a `Resolution`. This follows the same rules as `parse()`, but
+    /// is cheaper, since it does not collect possibilities, leaf completions or error text.
+    ///
+    /// # Arguments
+    /// `line` - The input line.
+    /// `cmds` - The available custom commands to resolve into.
+    /// `builtins` - The available builtins to resolve into.
+    ///
+    /// # Returns
+    /// `Resolution` - The resolution, given the arguments.
+    pub fn resolve<S>(
+        &self,
+        line: &str,
+        cmds: &CommandSet<S>,
+        builtins: &CommandSet<Shell<S>>,
+    ) -> Resolution {
+        let tokenization = self.tokenizer().tokenize(line);
+        // Tokens are slices of the line, so their offsets are where they start in it. Those that
+        // aren't are taken to be empty, at its end.
+        let tokens = tokenization
+            .tokens
+            .iter()
+            .map(|token| token_range(line, token).unwrap_or(line.len()..line.len()))
+            .collect();
+
+        let (depth, complete) = self.resolve_with_set(&tokenization.tokens, cmds);
+        if complete {
+            return Resolution {
+                cmd_type: CommandType::Custom,
+                tokens,
+                depth,
+                complete,
+            };
+        }
+
+        let (builtin_depth, builtin_complete) =
+            self.resolve_with_set(&tokenization.tokens, builtins);
+        if builtin_complete {
+            return Resolution {
+                cmd_type: CommandType::Builtin,
+                tokens,
+                depth: builtin_depth,
+                complete: builtin_complete,
+            };
+        }
+
+        Resolution {
+            cmd_type: if depth == 0 {
+                CommandType::Unknown
+            } else {
+                CommandType::Custom
+            },
+            tokens,
+            depth,
+            complete: false,
+        }
+    }
+
+    /// Expands the tokens of the given line that are unambiguous prefixes of the names of the
+    /// commands it invokes, e.g. `serv st` for `server status`. A prefix is unambiguous if it is
+    /// the name of a command, or if only one command's name starts with it.
+    ///
+    /// # Arguments
+    /// `line` - The input line.
+    /// `cmds` - The available custom commands to expand into.
+    /// `builtins` - The available builtins to expand into.
+    ///
+    /// # Returns
+    /// `Result<Option<String>>` - The expanded line, None if no token was expanded, or an
+    /// `AmbiguousCommand` error listing the names that a prefix could be.
+    pub fn expand_prefixes<S>(
+        &self,
+        line: &str,
+        cmds: &CommandSet<S>,
+        builtins: &CommandSet<Shell<S>>,
+    ) -> Result<Option<String>> {
+        let tokenization = self.tokenizer().tokenize(line);
+        let (first, rest) = match tokenization.tokens.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+
+        let mut expansions = Vec::new();
+        let root = if cmds.contains(first) || builtins.contains(first) {
+            first.to_string()
+        } else {
+            let mut names = cmds.names_with_prefix(first);
+            names.extend(builtins.names_with_prefix(first));
+            names.sort();
+            names.dedup();
+            match names.len() {
+                0 => return Ok(None),
+                1 => names.remove(0),
+                _ => return Err(ambiguous(first, names)),
+            }
+        };
+        if root != *first {
+            expansions.push((*first, root.clone()));
+        }
+
+        // Custom commands win over builtins of the same name, as when parsing.
+        match cmds.get(&root) {
+            Some(cmd) => expand_with_cmd(cmd, rest, &mut expansions)?,
+            None => {
+                if let Some(cmd) = builtins.get(&root) {
+                    expand_with_cmd(cmd, rest, &mut expansions)?;
+                }
+            }
+        }
+
+        if expansions.is_empty() {
+            return Ok(None);
+        }
+
+        // Tokens are slices of the line, so we can splice the expansions in, leaving the rest
+        // as-is.
+        let mut expanded = String::with_capacity(line.len());
+        let mut end = 0;
+        for (token, name) in expansions {
+            // Tokens that aren't slices of the line can't be spliced, so the line is left as-is.
+            let range = match token_range(line, token) {
+                Some(range) if range.start >= end => range,
+                _ => return Ok(None),
+            };
+            expanded.push_str(&line[end..range.start]);
+            expanded.push_str(&name);
+            end = range.end;
+        }
+        expanded.push_str(&line[end..]);
+
+        Ok(Some(expanded))
+    }
+}
+
+/// Returns the error for a token that is a prefix of more than one command's name.
+fn ambiguous(token: &str, candidates: Vec<String>) -> ShiError {
+    ShiError::AmbiguousCommand {
+        got: token.to_string(),
+        candidates,
+    }
+}
+
+/// Expands the given tokens that are unambiguous prefixes of the names of the subcommands of the
+/// given command, down the tree, stopping at the first token that is not a prefix of any, or at a
+/// leaf command. See `Parser::expand_prefixes()`.
+fn expand_with_cmd<'t, T>(
+    cmd: &Command<T>,
+    tokens: &[&'t str],
+    expansions: &mut Vec<(&'t str, String)>,
+) -> Result<()> {
+    let mut current = cmd;
+    for token in tokens {
+        let set = match current {
+            Command::Parent(parent) => parent.sub_commands(),
+            Command::Leaf(_) => break,
+        };
+
+        let name = if set.contains(token) {
+            token.to_string()
+        } else {
+            let mut names = set.names_with_prefix(token);
+            match names.len() {
+                0 => break,
+                1 => names.remove(0),
+                _ => return Err(ambiguous(token, names)),
+            }
+        };
+        current = set
+            .get(&name)
+            .expect("expanded to a name that is not in the set");
+        if name != *token {
+            expansions.push((token, name));
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -793,6 +1163,141 @@ pub mod test {
         );
     }
 
+    mod resolution {
+        use super::{make_parser_cmds, CommandType, Parser, Resolution};
+
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn resolves_like_parse() {
+            let cmds = make_parser_cmds();
+            let parser = Parser::new();
+
+            for line in &[
+                "foo-c bar-c he",
+                "foo-c qux-c corge-c a b",
+                "foo-b bar-b",
+                "conflict-tie",
+                "conflict-custom-wins child",
+                "conflict-builtin-longer-match-but-still-loses child",
+                "foo-c",
+                "foo-c DNE",
+                "DNE foo-c",
+                "",
+            ] {
+                let outcome = parser.parse(line, &cmds.0, &cmds.1);
+                let resolution = parser.resolve(line, &cmds.0, &cmds.1);
+                assert_eq!(resolution.cmd_type, outcome.cmd_type, "{}", line);
+                assert_eq!(resolution.depth, outcome.cmd_path.len(), "{}", line);
+                assert_eq!(resolution.complete, outcome.complete, "{}", line);
+            }
+        }
+
+        #[test]
+        fn spans() {
+            let cmds = make_parser_cmds();
+            let line = "  foo-c  bar-c 'a b' c";
+
+            let resolution = Parser::new().resolve(line, &cmds.0, &cmds.1);
+            assert_eq!(
+                resolution,
+                Resolution {
+                    cmd_type: CommandType::Custom,
+                    tokens: vec![2..7, 9..14, 16..19, 21..22],
+                    depth: 2,
+                    complete: true,
+                }
+            );
+            assert_eq!(resolution.cmd_spans(), &[2..7, 9..14]);
+            assert_eq!(
+                resolution
+                    .arg_spans()
+                    .iter()
+                    .map(|span| &line[span.clone()])
+                    .collect::<Vec<_>>(),
+                vec!["a b", "c"]
+            );
+        }
+    }
+
+    #[test]
+    fn token_ranges() {
+        let line = "server listen 80";
+        assert_eq!(token_range(line, &line[7..13]), Some(7..13));
+        assert_eq!(token_range(line, &line[16..]), Some(16..16));
+        // Tokens that aren't slices of the line have no range.
+        assert_eq!(token_range(line, &String::from("listen")), None);
+        assert_eq!(token_range(&line[7..], &line[..6]), None);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("listen", "listen"), 0);
+        assert_eq!(edit_distance("lisen", "listen"), 1);
+        assert_eq!(edit_distance("srever", "server"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn correction() {
+        let cmds = make_parser_cmds();
+        let parser = Parser::new();
+        let correct = |line: &str| parser.parse(line, &cmds.0, &cmds.1).correction(line);
+
+        assert_eq!(
+            correct("foo-c  qux-c  curge-c 'a b'"),
+            Some(String::from("foo-c  qux-c  corge-c 'a b'"))
+        );
+        assert_eq!(correct("grault"), Some(String::from("grault-c")));
+        // Both 'bar-c' and 'baz-c' are close, so there is no single correction.
+        assert_eq!(correct("foo-c bax-c"), None);
+        assert_eq!(correct("foo-c zzzzz"), None);
+        assert_eq!(correct("foo-c"), None);
+        assert_eq!(correct("foo-c bar-c"), None);
+    }
+
+    #[test]
+    fn expand_prefixes() {
+        let cmds = make_parser_cmds();
+        let parser = Parser::new();
+        let expand = |line: &str| parser.expand_prefixes(line, &cmds.0, &cmds.1);
+
+        assert_eq!(
+            expand("foo-c  q co 'a b'").unwrap(),
+            Some(String::from("foo-c  qux-c corge-c 'a b'"))
+        );
+        assert_eq!(expand("g x").unwrap(), Some(String::from("grault-c x")));
+        // Commands of the same name in both sets are not ambiguous.
+        assert_eq!(
+            expand("conflict-t").unwrap(),
+            Some(String::from("conflict-tie"))
+        );
+        assert_eq!(
+            expand("conflict-cu ch").unwrap(),
+            Some(String::from("conflict-custom-wins child"))
+        );
+        assert_eq!(
+            expand("foo-b b").unwrap(),
+            Some(String::from("foo-b bar-b"))
+        );
+        assert_eq!(expand("foo-c bar-c ba").unwrap(), None);
+        assert_eq!(expand("zzz").unwrap(), None);
+        assert_eq!(expand("").unwrap(), None);
+
+        match expand("foo-c ba") {
+            Err(ShiError::AmbiguousCommand { got, candidates }) => {
+                assert_eq!(got, "ba");
+                assert_eq!(candidates, vec!["bar-c", "baz-c"]);
+            }
+            res => panic!("expected an ambiguity error, got {:?}", res),
+        }
+        assert_eq!(
+            expand("foo").unwrap_err().to_string(),
+            "ambiguous command 'foo', could be any of: foo-b, foo-c"
+        );
+    }
+
     mod outcome {
         use super::{CommandType, Completion, Outcome};
 
@@ -827,6 +1332,24 @@ pub mod test {
             );
         }
 
+        #[test]
+        fn wide_chars_in_error_msg() {
+            let outcome = Outcome {
+                cmd_path: vec!["サーバー", "bar"],
+                remaining: vec!["la"],
+                cmd_type: CommandType::Custom,
+                possibilities: Vec::new(),
+                leaf_completion: None,
+                complete: false,
+            };
+
+            // The caret lines up with the invalid suffix on the terminal, where each of the
+            // characters of the first command takes up two columns.
+            assert!(outcome
+                .error_msg()
+                .contains("\t => 'サーバー bar la'\n\t                  ^\n"));
+        }
+
         #[test]
         fn empty_remaining_in_outcome() {
             let outcome = Outcome {
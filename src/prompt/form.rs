@@ -0,0 +1,190 @@
+use std::io::{BufRead, Write};
+
+use super::Prompter;
+use crate::Result;
+
+/// A Form is a struct whose fields can be edited interactively, by `edit()`.
+///
+/// Rather than implementing this by hand, it is usually easier to use `impl_form!`, which
+/// implements it for any struct whose fields implement `Display` and `FromStr`.
+pub trait Form {
+    /// Returns the names of the editable fields, in the order they should be edited.
+    fn field_names(&self) -> Vec<&'static str>;
+
+    /// Returns the current value of the given field, for display.
+    ///
+    /// # Arguments
+    /// `name` - The name of the field.
+    fn get_field(&self, name: &str) -> String;
+
+    /// Sets the given field from its textual representation.
+    ///
+    /// # Arguments
+    /// `name` - The name of the field.
+    /// `value` - The new value of the field.
+    ///
+    /// # Returns
+    /// `Result<()>` - Errors if the value is not valid for the field's type.
+    fn set_field(&mut self, name: &str, value: &str) -> Result<()>;
+}
+
+/// Implements `Form` for a struct, given the names of its editable fields. Each of the fields must
+/// implement `Display` and `FromStr`.
+///
+/// ```
+/// # use shi::impl_form;
+/// struct Connection {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// impl_form!(Connection { host, port });
+/// ```
+#[macro_export]
+macro_rules! impl_form {
+    ( $type:ty { $( $field:ident ),* $(,)? } ) => {
+        impl $crate::prompt::Form for $type {
+            fn field_names(&self) -> Vec<&'static str> {
+                vec![ $( stringify!($field), )* ]
+            }
+
+            fn get_field(&self, name: &str) -> String {
+                match name {
+                    $( stringify!($field) => self.$field.to_string(), )*
+                    _ => String::new(),
+                }
+            }
+
+            fn set_field(&mut self, name: &str, value: &str) -> $crate::Result<()> {
+                match name {
+                    $(
+                        stringify!($field) => {
+                            self.$field = value.parse().map_err(|_| {
+                                $crate::error::ShiError::general(format!(
+                                    "'{}' is not a valid value for '{}'",
+                                    value, name
+                                ))
+                            })?;
+                            Ok(())
+                        }
+                    )*
+                    _ => Err($crate::error::ShiError::general(format!(
+                        "'{}' is not a field",
+                        name
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+/// Interactively edits the fields of the given form on stdin/stdout. See `edit_with()`.
+///
+/// # Arguments
+/// `form` - The form to edit.
+///
+/// # Returns
+/// `Result<Vec<&str>>` - The names of the fields that were changed.
+pub fn edit<T: Form>(form: &mut T) -> Result<Vec<&'static str>> {
+    edit_with(&mut Prompter::stdio(), form)
+}
+
+/// Interactively edits the fields of the given form with the given `Prompter`.
+///
+/// Each field is prompted for in turn, showing its current value. Entering nothing keeps the
+/// current value. Values that are invalid for the field's type are reported, and the field is
+/// prompted for again.
+///
+/// # Arguments
+/// `prompter` - The prompter to prompt the user with.
+/// `form` - The form to edit.
+///
+/// # Returns
+/// `Result<Vec<&str>>` - The names of the fields that were changed.
+pub fn edit_with<R: BufRead, W: Write, T: Form>(
+    prompter: &mut Prompter<R, W>,
+    form: &mut T,
+) -> Result<Vec<&'static str>> {
+    let mut changed = Vec::new();
+
+    for name in form.field_names() {
+        loop {
+            let current = form.get_field(name);
+            let value = prompter.read_line(&format!("{} [{}]: ", name, current))?;
+            let value = value.trim();
+            if value.is_empty() || value == current {
+                break;
+            }
+
+            match form.set_field(name, value) {
+                Ok(()) => {
+                    changed.push(name);
+                    break;
+                }
+                Err(err) => prompter.println(&err.to_string())?,
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[derive(Debug, PartialEq)]
+    struct Connection {
+        host: String,
+        port: u16,
+        tls: bool,
+    }
+
+    impl_form!(Connection { host, port, tls });
+
+    fn connection() -> Connection {
+        Connection {
+            host: String::from("localhost"),
+            port: 8080,
+            tls: false,
+        }
+    }
+
+    #[test]
+    fn edit() -> Result<()> {
+        let mut conn = connection();
+        let mut prompter = Prompter::new("example.com\nhttp\n443\n\n".as_bytes(), Vec::new());
+
+        assert_eq!(edit_with(&mut prompter, &mut conn)?, vec!["host", "port"]);
+        assert_eq!(
+            conn,
+            Connection {
+                host: String::from("example.com"),
+                port: 443,
+                tls: false,
+            }
+        );
+        assert_eq!(
+            String::from_utf8(prompter.output).unwrap(),
+            "host [localhost]: port [8080]: error: 'http' is not a valid value for 'port'\nport \
+            [8080]: tls [false]: "
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fields() -> Result<()> {
+        let mut conn = connection();
+
+        assert_eq!(conn.field_names(), vec!["host", "port", "tls"]);
+        assert_eq!(conn.get_field("port"), "8080");
+        conn.set_field("tls", "true")?;
+        assert!(conn.tls);
+        assert!(conn.set_field("user", "me").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,549 @@
+//! A module for interactively prompting the user from within a command's `execute()`.
+//!
+//! When a command executes, the shell is no longer reading a line, so these prompts can freely
+//! read from stdin and write to stdout; the shell's prompt is restored once the command returns.
+//!
+//! When stdin and stdout are terminals, menus are navigated with the arrow keys (or `j` and `k`):
+//! Enter selects the highlighted option, Space toggles options of multi-selects, and `q` or Ctrl-C
+//! cancels. Otherwise, e.g. on Windows, or when input is piped, menus are rendered as numbered
+//! lists, and options are chosen by either their number or their name.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, IsTerminal, StdinLock, Stdout, Write};
+use std::process;
+
+use crate::error::ShiError;
+use crate::screen;
+use crate::Result;
+
+pub mod form;
+pub mod wizard;
+
+pub use form::*;
+pub use wizard::*;
+
+/// Prompts the user to select one of the given options on stdin/stdout.
+///
+/// # Arguments
+/// `label` - The label describing what is being selected.
+/// `options` - The options to select from.
+///
+/// # Returns
+/// `Result<&T>` - The selected option.
+pub fn select<'o, T: Display>(label: &str, options: &'o [T]) -> Result<&'o T> {
+    Prompter::stdio().select(label, options)
+}
+
+/// Prompts the user to select any number of the given options on stdin/stdout.
+///
+/// # Arguments
+/// `label` - The label describing what is being selected.
+/// `options` - The options to select from.
+///
+/// # Returns
+/// `Result<Vec<&T>>` - The selected options, in the order they were listed.
+pub fn multi_select<'o, T: Display>(label: &str, options: &'o [T]) -> Result<Vec<&'o T>> {
+    Prompter::stdio().multi_select(label, options)
+}
+
+/// A Prompter prompts the user for input, reading responses from `input` and writing prompts to
+/// `output`.
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+    // Whether input is echoed by a terminal that we should silence for masked prompts.
+    echoes: bool,
+    // Whether menus can be navigated with keys, i.e., whether input and output are a terminal.
+    navigable: bool,
+}
+
+/// A key pressed while navigating a menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MenuKey {
+    Up,
+    Down,
+    Toggle,
+    Accept,
+    Cancel,
+    Other,
+}
+
+/// Puts the terminal into unbuffered mode, without echo or signals, for as long as it lives, so
+/// that keys are read as they are pressed.
+struct RawTerminal {
+    // The terminal settings to restore, as saved by `stty -g`.
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> Result<RawTerminal> {
+        let saved = screen::stty(&["-g"])
+            .ok_or_else(|| ShiError::general("failed to read the terminal's settings"))?;
+        if screen::stty(&["-icanon", "-echo", "-isig", "min", "1"]).is_none() {
+            return Err(ShiError::general(
+                "failed to switch the terminal to unbuffered input",
+            ));
+        }
+
+        Ok(RawTerminal { saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        screen::stty(&[&self.saved]);
+    }
+}
+
+impl Prompter<StdinLock<'static>, Stdout> {
+    /// Creates a Prompter that reads from stdin and writes to stdout.
+    pub fn stdio() -> Prompter<StdinLock<'static>, Stdout> {
+        let stdin = io::stdin();
+        let echoes = cfg!(unix) && stdin.is_terminal();
+        Prompter {
+            echoes,
+            navigable: echoes && io::stdout().is_terminal(),
+            ..Prompter::new(stdin.lock(), io::stdout())
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Creates a new Prompter.
+    ///
+    /// # Arguments
+    /// `input` - Where responses are read from.
+    /// `output` - Where prompts are written to.
+    pub fn new(input: R, output: W) -> Prompter<R, W> {
+        Prompter {
+            input,
+            output,
+            echoes: false,
+            navigable: false,
+        }
+    }
+
+    /// Writes the given prompt and reads a single line in response, without its line ending.
+    ///
+    /// # Arguments
+    /// `prompt` - The prompt to write.
+    ///
+    /// # Returns
+    /// `Result<String>` - The response. Errors if the input ends before a response is given.
+    pub fn read_line(&mut self, prompt: &str) -> Result<String> {
+        write!(self.output, "{}", prompt)?;
+        self.output.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(ShiError::general("input ended before a response was given"));
+        }
+
+        Ok(line.trim_end_matches(&['\n', '\r'][..]).to_string())
+    }
+
+    /// Writes the given message, followed by a newline.
+    ///
+    /// # Arguments
+    /// `msg` - The message to write.
+    pub fn println(&mut self, msg: &str) -> Result<()> {
+        writeln!(self.output, "{}", msg)?;
+        Ok(())
+    }
+
+    /// Prompts the user with a yes or no question. The user is prompted again until they answer
+    /// with one of `y`, `yes`, `n` or `no`.
+    ///
+    /// # Arguments
+    /// `label` - The question to ask.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether the user answered yes.
+    pub fn confirm(&mut self, label: &str) -> Result<bool> {
+        loop {
+            let answer = self.read_line(&format!("{} [y/n] ", label))?;
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => self.println("please answer 'y' or 'n'")?,
+            }
+        }
+    }
+
+    /// Prompts the user for a secret, e.g. a password. When reading from a terminal, the secret is
+    /// not echoed back as it is typed.
+    ///
+    /// # Arguments
+    /// `label` - The label describing the secret.
+    ///
+    /// # Returns
+    /// `Result<String>` - The secret.
+    pub fn masked(&mut self, label: &str) -> Result<String> {
+        if !self.echoes {
+            return self.read_line(&format!("{}: ", label));
+        }
+
+        Self::set_echo(false)?;
+        let secret = self.read_line(&format!("{}: ", label));
+        Self::set_echo(true)?;
+        // The user's newline was not echoed either.
+        self.println("")?;
+
+        secret
+    }
+
+    /// Turns the terminal's echoing of input on or off.
+    fn set_echo(on: bool) -> Result<()> {
+        let status = process::Command::new("stty")
+            .arg(if on { "echo" } else { "-echo" })
+            .stdin(process::Stdio::inherit())
+            .status()?;
+
+        if !status.success() {
+            return Err(ShiError::general(format!(
+                "failed to turn terminal echo {}",
+                if on { "on" } else { "off" }
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Errors if there are no options to select from.
+    fn check_options<T>(label: &str, options: &[T]) -> Result<()> {
+        if options.is_empty() {
+            return Err(ShiError::general(format!(
+                "there is nothing to select for '{}'",
+                label
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn print_menu<T: Display>(&mut self, label: &str, options: &[T]) -> Result<()> {
+        Self::check_options(label, options)?;
+
+        self.println(label)?;
+        for (i, option) in options.iter().enumerate() {
+            self.println(&format!("  {}) {}", i + 1, option))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        let mut byte = [0; 1];
+        if self.input.read(&mut byte)? == 0 {
+            return Err(ShiError::general("input ended before a selection was made"));
+        }
+
+        Ok(byte[0])
+    }
+
+    /// Reads the next key pressed while navigating a menu.
+    fn read_key(&mut self) -> Result<MenuKey> {
+        Ok(match self.read_byte()? {
+            b'k' => MenuKey::Up,
+            b'j' => MenuKey::Down,
+            b' ' => MenuKey::Toggle,
+            b'\r' | b'\n' => MenuKey::Accept,
+            // Ctrl-C arrives as a byte, since the terminal does not turn it into a signal.
+            b'q' | 0x03 => MenuKey::Cancel,
+            // Arrow keys arrive as escape sequences, e.g. `ESC [ A` for up.
+            0x1b => match (self.read_byte()?, self.read_byte()?) {
+                (b'[', b'A') | (b'O', b'A') => MenuKey::Up,
+                (b'[', b'B') | (b'O', b'B') => MenuKey::Down,
+                _ => MenuKey::Other,
+            },
+            _ => MenuKey::Other,
+        })
+    }
+
+    /// Draws the options of a menu, pointing at the one under the cursor, and with checkboxes if
+    /// `checked` is given. If `redraw`, the previous drawing is drawn over.
+    fn draw_menu<T: Display>(
+        &mut self,
+        options: &[T],
+        cursor: usize,
+        checked: Option<&[bool]>,
+        redraw: bool,
+    ) -> Result<()> {
+        if redraw {
+            write!(self.output, "\x1b[{}A", options.len())?;
+        }
+        for (i, option) in options.iter().enumerate() {
+            let pointer = if i == cursor { '>' } else { ' ' };
+            let checkbox = match checked {
+                Some(checked) if checked[i] => "[x] ",
+                Some(_) => "[ ] ",
+                None => "",
+            };
+            write!(self.output, "\r\x1b[2K{} {}{}\n", pointer, checkbox, option)?;
+        }
+        self.output.flush()?;
+
+        Ok(())
+    }
+
+    /// Lets the user pick from a menu with keys. See the module documentation.
+    ///
+    /// # Arguments
+    /// `label` - The label describing what is being selected.
+    /// `options` - The options to select from. Must not be empty.
+    /// `multi` - Whether any number of options may be picked, rather than exactly one.
+    ///
+    /// # Returns
+    /// `Result<Vec<usize>>` - The indices of the picked options, in the order they were listed.
+    fn navigate<T: Display>(
+        &mut self,
+        label: &str,
+        options: &[T],
+        multi: bool,
+    ) -> Result<Vec<usize>> {
+        let keys = if multi {
+            "arrows to move, space to toggle, enter to confirm, q to cancel"
+        } else {
+            "arrows to move, enter to select, q to cancel"
+        };
+        self.println(&format!("{} ({})", label, keys))?;
+
+        let mut cursor = 0;
+        let mut checked = vec![false; options.len()];
+        self.draw_menu(options, cursor, multi.then(|| &checked[..]), false)?;
+        loop {
+            match self.read_key()? {
+                MenuKey::Up => cursor = cursor.checked_sub(1).unwrap_or(options.len() - 1),
+                MenuKey::Down => cursor = (cursor + 1) % options.len(),
+                MenuKey::Toggle if multi => checked[cursor] = !checked[cursor],
+                MenuKey::Accept if multi => {
+                    return Ok((0..options.len()).filter(|i| checked[*i]).collect())
+                }
+                MenuKey::Accept => return Ok(vec![cursor]),
+                MenuKey::Cancel => {
+                    return Err(ShiError::general(format!(
+                        "cancelled the selection of '{}'",
+                        label
+                    )))
+                }
+                MenuKey::Toggle | MenuKey::Other => continue,
+            }
+            self.draw_menu(options, cursor, multi.then(|| &checked[..]), true)?;
+        }
+    }
+
+    /// Finds the index of the option that the given choice refers to, either by its number or by
+    /// its name.
+    fn parse_choice<T: Display>(choice: &str, options: &[T]) -> Option<usize> {
+        if let Ok(num) = choice.parse::<usize>() {
+            if (1..=options.len()).contains(&num) {
+                return Some(num - 1);
+            }
+        }
+
+        options
+            .iter()
+            .position(|option| option.to_string() == choice)
+    }
+
+    /// Prompts the user to select one of the given options, with keys if reading from a terminal.
+    /// Otherwise, the user is prompted again until they make a valid selection.
+    ///
+    /// # Arguments
+    /// `label` - The label describing what is being selected.
+    /// `options` - The options to select from.
+    ///
+    /// # Returns
+    /// `Result<&T>` - The selected option.
+    pub fn select<'o, T: Display>(&mut self, label: &str, options: &'o [T]) -> Result<&'o T> {
+        if self.navigable {
+            Self::check_options(label, options)?;
+            if let Ok(_raw) = RawTerminal::enter() {
+                let picked = self.navigate(label, options, false)?;
+                return Ok(&options[picked[0]]);
+            }
+        }
+
+        self.print_menu(label, options)?;
+
+        loop {
+            let choice = self.read_line("> ")?;
+            match Self::parse_choice(choice.trim(), options) {
+                Some(i) => return Ok(&options[i]),
+                None => self.println(&format!(
+                    "'{}' is not an option; enter a number from 1 to {} or a name",
+                    choice.trim(),
+                    options.len()
+                ))?,
+            }
+        }
+    }
+
+    /// Prompts the user to select any number of the given options, with keys if reading from a
+    /// terminal. Otherwise, the options are separated by spaces or commas, and the user is prompted
+    /// again until every selection they make is valid.
+    ///
+    /// # Arguments
+    /// `label` - The label describing what is being selected.
+    /// `options` - The options to select from.
+    ///
+    /// # Returns
+    /// `Result<Vec<&T>>` - The selected options, in the order they were listed.
+    pub fn multi_select<'o, T: Display>(
+        &mut self,
+        label: &str,
+        options: &'o [T],
+    ) -> Result<Vec<&'o T>> {
+        if self.navigable {
+            Self::check_options(label, options)?;
+            if let Ok(_raw) = RawTerminal::enter() {
+                let picked = self.navigate(label, options, true)?;
+                return Ok(picked.into_iter().map(|i| &options[i]).collect());
+            }
+        }
+
+        self.print_menu(label, options)?;
+
+        'prompt: loop {
+            let choices = self.read_line("(separate choices with spaces) > ")?;
+
+            let mut selected = vec![false; options.len()];
+            for choice in choices
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|choice| !choice.is_empty())
+            {
+                match Self::parse_choice(choice, options) {
+                    Some(i) => selected[i] = true,
+                    None => {
+                        self.println(&format!("'{}' is not an option", choice))?;
+                        continue 'prompt;
+                    }
+                }
+            }
+
+            return Ok(options
+                .iter()
+                .zip(selected)
+                .filter(|(_, selected)| *selected)
+                .map(|(option, _)| option)
+                .collect());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn prompter(input: &str) -> Prompter<&[u8], Vec<u8>> {
+        Prompter::new(input.as_bytes(), Vec::new())
+    }
+
+    #[test]
+    fn select_by_number_or_name() -> Result<()> {
+        let options = ["red", "green", "blue"];
+
+        assert_eq!(prompter("2\n").select("color?", &options)?, &"green");
+        assert_eq!(prompter("blue\n").select("color?", &options)?, &"blue");
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_reprompts() -> Result<()> {
+        let options = ["red", "green"];
+        let mut prompter = prompter("4\npurple\n1\n");
+
+        assert_eq!(prompter.select("color?", &options)?, &"red");
+        assert_eq!(
+            String::from_utf8(prompter.output).unwrap(),
+            "color?\n  1) red\n  2) green\n> '4' is not an option; enter a number from 1 to 2 or a \
+            name\n> 'purple' is not an option; enter a number from 1 to 2 or a name\n> "
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_errors() {
+        let empty: [&str; 0] = [];
+        assert!(prompter("1\n").select("color?", &empty).is_err());
+        assert!(prompter("").select("color?", &["red"]).is_err());
+    }
+
+    #[test]
+    fn confirm() -> Result<()> {
+        assert!(prompter("y\n").confirm("sure?")?);
+        assert!(prompter("YES\n").confirm("sure?")?);
+        assert!(!prompter("maybe\nn\n").confirm("sure?")?);
+        assert!(prompter("maybe\n").confirm("sure?").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn masked() -> Result<()> {
+        let mut prompter = prompter("hunter2\n");
+
+        assert_eq!(prompter.masked("password")?, "hunter2");
+        assert_eq!(String::from_utf8(prompter.output).unwrap(), "password: ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_select() -> Result<()> {
+        let options = ["red", "green", "blue"];
+
+        assert_eq!(
+            prompter("3, red 3\n").multi_select("colors?", &options)?,
+            vec![&"red", &"blue"]
+        );
+        assert_eq!(
+            prompter("red pink\n2\n").multi_select("colors?", &options)?,
+            vec![&"green"]
+        );
+        assert!(prompter("\n").multi_select("colors?", &options)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn navigate_with_keys() -> Result<()> {
+        let options = ["red", "green", "blue"];
+
+        assert_eq!(
+            prompter("j\x1b[B\x1b[Ak\r").navigate("color?", &options, false)?,
+            vec![0]
+        );
+        assert_eq!(
+            prompter("kj\x1b[Bxj\n").navigate("color?", &options, false)?,
+            vec![2]
+        );
+        let mut wrapping = prompter("k\n");
+        assert_eq!(wrapping.navigate("color?", &options, false)?, vec![2]);
+        assert_eq!(
+            String::from_utf8(wrapping.output).unwrap(),
+            "color? (arrows to move, enter to select, q to cancel)\n\
+            \r\x1b[2K> red\n\r\x1b[2K  green\n\r\x1b[2K  blue\n\
+            \x1b[3A\r\x1b[2K  red\n\r\x1b[2K  green\n\r\x1b[2K> blue\n"
+        );
+
+        assert_eq!(
+            prompter(" j\x1bOB \n").navigate("colors?", &options, true)?,
+            vec![0, 2]
+        );
+        assert!(prompter("\n")
+            .navigate("colors?", &options, true)?
+            .is_empty());
+        assert!(prompter("jq").navigate("color?", &options, false).is_err());
+        assert!(prompter("j\x03")
+            .navigate("color?", &options, false)
+            .is_err());
+        assert!(prompter("j").navigate("color?", &options, false).is_err());
+
+        Ok(())
+    }
+}
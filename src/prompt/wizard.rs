@@ -0,0 +1,270 @@
+use std::io::{BufRead, Write};
+
+use super::Prompter;
+use crate::error::ShiError;
+use crate::Result;
+
+/// What a step of a `Wizard` prompts for.
+enum StepKind {
+    Text,
+    Select(Vec<String>),
+    Confirm,
+    Masked,
+}
+
+type Validator = Box<dyn Fn(&str) -> Result<()>>;
+
+/// A single step of a `Wizard`.
+struct Step {
+    key: String,
+    label: String,
+    kind: StepKind,
+    validator: Option<Validator>,
+}
+
+/// A Wizard walks the user through a sequence of prompts, e.g. for a `configure connection`
+/// command that would otherwise demand many positional arguments.
+///
+/// Each step stores its answer under a key. Steps may be validated, in which case the user is
+/// prompted again until their answer passes validation. Once every step is answered, the user is
+/// shown a summary of their answers and asked to confirm them.
+///
+/// ```no_run
+/// # use shi::prompt::Wizard;
+/// # fn main() -> shi::Result<()> {
+/// let answers = Wizard::new("configure connection")
+///     .text("host", "Host")
+///     .text("port", "Port")
+///     .validate(|port| match port.parse::<u16>() {
+///         Ok(_) => Ok(()),
+///         Err(_) => Err(shi::error::ShiError::general("not a valid port")),
+///     })
+///     .select("protocol", "Protocol", &["tcp", "udp"])
+///     .masked("password", "Password")
+///     .confirm("tls", "Use TLS?")
+///     .run()?;
+///
+/// println!("connecting to {}", answers.get("host").unwrap());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Wizard {
+    title: String,
+    steps: Vec<Step>,
+}
+
+impl Wizard {
+    /// Creates a new Wizard without any steps.
+    ///
+    /// # Arguments
+    /// `title` - The title of the wizard, shown in its summary.
+    pub fn new(title: &str) -> Wizard {
+        Wizard {
+            title: title.to_string(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn step(mut self, key: &str, label: &str, kind: StepKind) -> Wizard {
+        self.steps.push(Step {
+            key: key.to_string(),
+            label: label.to_string(),
+            kind,
+            validator: None,
+        });
+        self
+    }
+
+    /// Adds a step that prompts for free-form text.
+    ///
+    /// # Arguments
+    /// `key` - The key to store the answer under.
+    /// `label` - The label to prompt with.
+    pub fn text(self, key: &str, label: &str) -> Wizard {
+        self.step(key, label, StepKind::Text)
+    }
+
+    /// Adds a step that prompts for one of the given options. See `Prompter::select()`.
+    ///
+    /// # Arguments
+    /// `key` - The key to store the answer under.
+    /// `label` - The label to prompt with.
+    /// `options` - The options to select from.
+    pub fn select(self, key: &str, label: &str, options: &[&str]) -> Wizard {
+        let options = options.iter().map(|option| option.to_string()).collect();
+        self.step(key, label, StepKind::Select(options))
+    }
+
+    /// Adds a step that prompts for a yes or no answer, stored as `true` or `false`. See
+    /// `Prompter::confirm()`.
+    ///
+    /// # Arguments
+    /// `key` - The key to store the answer under.
+    /// `label` - The question to ask.
+    pub fn confirm(self, key: &str, label: &str) -> Wizard {
+        self.step(key, label, StepKind::Confirm)
+    }
+
+    /// Adds a step that prompts for a secret, which is masked in the summary. See
+    /// `Prompter::masked()`.
+    ///
+    /// # Arguments
+    /// `key` - The key to store the answer under.
+    /// `label` - The label to prompt with.
+    pub fn masked(self, key: &str, label: &str) -> Wizard {
+        self.step(key, label, StepKind::Masked)
+    }
+
+    /// Sets the validator of the most recently added step. Answers that fail validation are
+    /// reported to the user, who is then prompted again.
+    ///
+    /// # Arguments
+    /// `validator` - The validator, which returns an error describing why an answer is invalid.
+    ///
+    /// # Panics
+    /// If no steps have been added yet.
+    pub fn validate<F>(mut self, validator: F) -> Wizard
+    where
+        F: Fn(&str) -> Result<()> + 'static,
+    {
+        self.steps
+            .last_mut()
+            .expect("validate() must follow the step it validates")
+            .validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Runs the wizard on stdin/stdout.
+    ///
+    /// # Returns
+    /// `Result<Answers>` - The answers to each step. Errors if the user does not confirm them.
+    pub fn run(&self) -> Result<Answers> {
+        self.run_with(&mut Prompter::stdio())
+    }
+
+    /// Runs the wizard with the given `Prompter`.
+    ///
+    /// # Arguments
+    /// `prompter` - The prompter to prompt the user with.
+    ///
+    /// # Returns
+    /// `Result<Answers>` - The answers to each step. Errors if the user does not confirm them.
+    pub fn run_with<R: BufRead, W: Write>(&self, prompter: &mut Prompter<R, W>) -> Result<Answers> {
+        let mut answers = Answers { values: Vec::new() };
+
+        for step in &self.steps {
+            let answer = loop {
+                let answer = match &step.kind {
+                    StepKind::Text => prompter.read_line(&format!("{}: ", step.label))?,
+                    StepKind::Select(options) => prompter.select(&step.label, options)?.clone(),
+                    StepKind::Confirm => prompter.confirm(&step.label)?.to_string(),
+                    StepKind::Masked => prompter.masked(&step.label)?,
+                };
+
+                match &step.validator {
+                    Some(validator) => match validator(&answer) {
+                        Ok(()) => break answer,
+                        Err(err) => prompter.println(&format!("invalid answer; {}", err))?,
+                    },
+                    None => break answer,
+                }
+            };
+
+            answers.values.push((step.key.clone(), answer));
+        }
+
+        prompter.println(&format!("Summary of '{}':", self.title))?;
+        for (step, (_, answer)) in self.steps.iter().zip(&answers.values) {
+            let shown = match step.kind {
+                StepKind::Masked => "*".repeat(answer.chars().count()),
+                _ => answer.clone(),
+            };
+            prompter.println(&format!("  {}: {}", step.label, shown))?;
+        }
+
+        if !prompter.confirm("Is this correct?")? {
+            return Err(ShiError::general(format!("'{}' was cancelled", self.title)));
+        }
+
+        Ok(answers)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// The answers given to the steps of a `Wizard`, by key.
+pub struct Answers {
+    values: Vec<(String, String)>,
+}
+
+impl Answers {
+    /// Returns the answer stored under the given key, if any.
+    ///
+    /// # Arguments
+    /// `key` - The key of the step.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, answer)| answer.as_str())
+    }
+
+    /// Returns the answer of the confirm step stored under the given key, if any.
+    ///
+    /// # Arguments
+    /// `key` - The key of the step.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(|answer| answer.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn wizard() -> Wizard {
+        Wizard::new("configure")
+            .text("host", "Host")
+            .text("port", "Port")
+            .validate(|port| match port.parse::<u16>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ShiError::general("not a valid port")),
+            })
+            .select("protocol", "Protocol", &["tcp", "udp"])
+            .masked("password", "Password")
+            .confirm("tls", "Use TLS?")
+    }
+
+    #[test]
+    fn run() -> Result<()> {
+        let input = "localhost\nhttp\n8080\n2\nhunter2\ny\ny\n";
+        let mut prompter = Prompter::new(input.as_bytes(), Vec::new());
+
+        let answers = wizard().run_with(&mut prompter)?;
+
+        assert_eq!(answers.get("host"), Some("localhost"));
+        assert_eq!(answers.get("port"), Some("8080"));
+        assert_eq!(answers.get("protocol"), Some("udp"));
+        assert_eq!(answers.get("password"), Some("hunter2"));
+        assert_eq!(answers.get_bool("tls"), Some(true));
+        assert_eq!(answers.get("user"), None);
+
+        let output = String::from_utf8(prompter.output).unwrap();
+        assert!(output.contains("invalid answer; error: not a valid port"));
+        assert!(output.ends_with(
+            "Summary of 'configure':\n  Host: localhost\n  Port: 8080\n  Protocol: udp\n  \
+            Password: *******\n  Use TLS?: true\nIs this correct? [y/n] "
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cancelled() {
+        let input = "localhost\n8080\ntcp\nhunter2\nn\nn\n";
+        let mut prompter = Prompter::new(input.as_bytes(), Vec::new());
+
+        assert!(wizard().run_with(&mut prompter).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! A module for driving a shell from multiple threads.
+//!
+//! A `Shell` cannot be shared between threads, so an `EvalQueue` owns one on a thread of its own.
+//! Other threads submit lines to the queue, and the shell evaluates them one at a time, in the
+//! order they were submitted, so that commands never observe each other's state half-way through.
+//!
+//! ```
+//! # use shi::queue::EvalQueue;
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let queue = EvalQueue::new(|| {
+//!     let mut shell = Shell::new_with_state("| ", 0);
+//!     shell.register(shi::cmd!("incr", |count: &mut u32, _| {
+//!         *count += 1;
+//!         Ok(count.to_string())
+//!     }))?;
+//!     Ok(shell)
+//! })?;
+//!
+//! let handles: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         let queue = queue.clone();
+//!         std::thread::spawn(move || queue.eval("incr"))
+//!     })
+//!     .collect();
+//! for handle in handles {
+//!     handle.join().unwrap()?;
+//! }
+//!
+//! assert_eq!(queue.eval("incr")?, "5");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// A line submitted to an `EvalQueue`, along with where to send its result.
+struct Job {
+    line: String,
+    result: Sender<Result<String>>,
+}
+
+#[derive(Debug, Clone)]
+/// EvalQueue evaluates lines submitted from any thread in a shell running on its own thread.
+///
+/// Clones of a queue submit to the same shell. The shell's thread ends once every clone has been
+/// dropped, or once the shell is exited.
+pub struct EvalQueue {
+    jobs: Sender<Job>,
+}
+
+#[derive(Debug)]
+/// Pending is the result of a line submitted to an `EvalQueue`, which may not have been evaluated
+/// yet.
+pub struct Pending {
+    result: Receiver<Result<String>>,
+}
+
+/// Returns the error for a line that will never be evaluated, because the shell has stopped.
+fn stopped() -> ShiError {
+    ShiError::general("the shell has stopped")
+}
+
+impl Pending {
+    /// Blocks until the line has been evaluated.
+    ///
+    /// # Returns
+    /// `Result<String>` - The output of the line, or an error if it failed or will never be
+    /// evaluated.
+    pub fn wait(self) -> Result<String> {
+        self.result.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+
+    /// Checks whether the line has been evaluated, without blocking.
+    ///
+    /// # Returns
+    /// `Option<Result<String>>` - The result of the line, or None if it has not been evaluated yet.
+    pub fn try_wait(&self) -> Option<Result<String>> {
+        match self.result.try_recv() {
+            Ok(res) => Some(res),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(stopped())),
+        }
+    }
+}
+
+impl EvalQueue {
+    /// Creates a new EvalQueue, which runs the shell created by `setup` on a new thread.
+    ///
+    /// # Arguments
+    /// `setup` - Creates the shell, e.g. registering its commands. It is called on the shell's
+    /// thread, since the shell cannot be sent between threads.
+    ///
+    /// # Returns
+    /// `Result<EvalQueue>` - The queue, or the error returned by `setup`.
+    pub fn new<S, F>(setup: F) -> Result<EvalQueue>
+    where
+        F: FnOnce() -> Result<Shell<'static, S>> + Send + 'static,
+    {
+        let (jobs, queued) = mpsc::channel::<Job>();
+        let (ready, started) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut shell = match setup() {
+                Ok(shell) => {
+                    let _ = ready.send(Ok(()));
+                    shell
+                }
+                Err(err) => {
+                    let _ = ready.send(Err(err));
+                    return;
+                }
+            };
+
+            for job in queued {
+                // The submitter may have stopped caring about the result, which is fine.
+                let _ = job.result.send(shell.eval(&job.line));
+                if shell.terminate {
+                    break;
+                }
+            }
+        });
+
+        started.recv().unwrap_or_else(|_| Err(stopped()))?;
+
+        Ok(EvalQueue { jobs })
+    }
+
+    /// Submits a line for evaluation, after all lines submitted before it.
+    ///
+    /// # Arguments
+    /// `line` - The line to evaluate.
+    ///
+    /// # Returns
+    /// `Pending` - The eventual result of the line.
+    pub fn submit(&self, line: &str) -> Pending {
+        let (result, pending) = mpsc::channel();
+        // If the shell has stopped, the job is dropped, and so `Pending` reports it.
+        let _ = self.jobs.send(Job {
+            line: line.to_string(),
+            result,
+        });
+
+        Pending { result: pending }
+    }
+
+    /// Submits a line for evaluation, and blocks until it has been evaluated.
+    ///
+    /// # Arguments
+    /// `line` - The line to evaluate.
+    ///
+    /// # Returns
+    /// `Result<String>` - The output of the line, or an error if it failed or will never be
+    /// evaluated.
+    pub fn eval(&self, line: &str) -> Result<String> {
+        self.submit(line).wait()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    fn make_queue() -> Result<EvalQueue> {
+        EvalQueue::new(|| {
+            let mut shell = Shell::new_with_state("| ", Vec::new());
+            shell.register(cmd!("push", |items: &mut Vec<String>, args: &[String]| {
+                items.extend_from_slice(args);
+                Ok(items.len().to_string())
+            }))?;
+            Ok(shell)
+        })
+    }
+
+    #[test]
+    fn serialized() -> Result<()> {
+        let queue = make_queue()?;
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    (0..10)
+                        .map(|_| queue.submit(&format!("push {}", i)))
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .map(Pending::wait)
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        assert_eq!(queue.eval("push last")?, "81");
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> Result<()> {
+        let queue = make_queue()?;
+
+        assert!(queue.eval("pull").is_err());
+        assert_eq!(queue.eval("push a")?, "1");
+
+        // Exiting stops the shell, so nothing after it is evaluated.
+        assert_eq!(queue.eval("exit")?, "bye");
+        let pending = queue.submit("push b");
+        assert_eq!(
+            pending.wait().unwrap_err().to_string(),
+            "error: the shell has stopped"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_setup() {
+        let res = EvalQueue::new(|| -> Result<Shell<'static, ()>> {
+            Err(ShiError::general("no shell for you"))
+        });
+
+        assert_eq!(res.unwrap_err().to_string(), "error: no shell for you");
+    }
+}
@@ -1,26 +1,40 @@
-use std::borrow::Cow::{self, Borrowed, Owned};
-use std::cell::RefCell;
+use std::borrow::Cow::{self, Borrowed};
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "color")]
 use colored::*;
 
 use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
 use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
 use rustyline::hint::{Hinter, HistoryHinter};
 use rustyline::validate::{self, MatchingBracketValidator, Validator};
-use rustyline::{Config, Context, Editor};
+use rustyline::{Cmd, Config, Context, Editor, KeyEvent};
 use rustyline_derive::Helper;
 
-use crate::command::Completion;
+use crate::abbreviations::Abbreviations;
+use crate::command::{BaseCommand, Command, Completion};
 use crate::command_set::CommandSet;
-use crate::parser::Parser;
+use crate::parser::{token_range, Outcome, Parser};
 use crate::shell::Shell;
+use crate::snippets::Snippets;
+use crate::tokenizer::{DefaultTokenizer, Tokenizer};
+use crate::usage::Usage;
 use crate::Result;
 
 /// A wrapper around `rustyline::Editor`.
 pub struct Readline<'a, S> {
     rl: Editor<ExecHelper<'a, S>>,
+    // Whether to bypass the editor for plain line reads, for terminals that can't handle it.
+    dumb: bool,
+    // Whether lines are read as typed. See `set_raw()`.
+    raw: bool,
+    // The key that inserts the last argument of the previous history entry, if any.
+    last_arg_key: Option<KeyEvent>,
 }
 
 impl<'a, S> Readline<'a, S> {
@@ -29,13 +43,28 @@ impl<'a, S> Readline<'a, S> {
         parser: Parser,
         cmds: Rc<RefCell<CommandSet<'a, S>>>,
         builtins: Rc<CommandSet<'a, Shell<'a, S>>>,
+        usage: Rc<RefCell<Usage>>,
+        snippets: Rc<RefCell<Snippets>>,
+        abbreviations: Rc<RefCell<Abbreviations>>,
     ) -> Readline<'a, S> {
         let config = Config::builder()
             .completion_type(rustyline::CompletionType::List)
             .build();
         let mut rl = Editor::with_config(config);
-        rl.set_helper(Some(ExecHelper::new(parser, cmds, builtins)));
-        Readline { rl }
+        rl.set_helper(Some(ExecHelper::new(
+            parser,
+            cmds,
+            builtins,
+            usage,
+            snippets,
+            abbreviations,
+        )));
+        Readline {
+            rl,
+            dumb: false,
+            raw: false,
+            last_arg_key: Some(KeyEvent::alt('.')),
+        }
     }
 
     /// Loads the readline history from the given file.
@@ -62,12 +91,132 @@ impl<'a, S> Readline<'a, S> {
         self.rl.add_history_entry(line)
     }
 
+    /// Sets whether lines should be read as if from a dumb terminal.
+    ///
+    /// When set, lines are read from stdin as-is, without any hints, highlighting, completion or
+    /// multi-line editing.
+    ///
+    /// # Arguments
+    /// `dumb` - Whether to read lines as if from a dumb terminal.
+    pub fn set_dumb(&mut self, dumb: bool) {
+        self.dumb = dumb;
+    }
+
+    /// Sets whether lines should be read as typed, e.g. while a line mode is active.
+    ///
+    /// When set, lines are neither completed nor validated, so that they are submitted on Enter
+    /// whatever brackets, quotes or trailing slashes they contain.
+    ///
+    /// # Arguments
+    /// `raw` - Whether to read lines as typed.
+    pub fn set_raw(&mut self, raw: bool) {
+        self.raw = raw;
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.raw = raw;
+        }
+    }
+
+    /// Clears the history. This is done in memory. Persistence is achieved via `save_history()`.
+    pub fn clear_history(&mut self) {
+        self.rl.clear_history();
+    }
+
+    /// Sets the key that inserts the last argument of the previous history entry at the cursor,
+    /// like `Alt-.` in bash, which is also the default.
+    ///
+    /// # Arguments
+    /// `key` - The key to use, or `None` to disable this.
+    pub fn set_last_arg_key(&mut self, key: Option<KeyEvent>) {
+        if let Some(old_key) = self.last_arg_key.take() {
+            self.rl.unbind_sequence(old_key);
+        }
+        self.last_arg_key = key;
+    }
+
+    /// Sets the parser with which lines are parsed for completion.
+    ///
+    /// # Arguments
+    /// `parser` - The parser to use.
+    pub fn set_parser(&mut self, parser: Parser) {
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.completer.parser = parser;
+        }
+    }
+
+    /// Sets the builtins to complete for.
+    ///
+    /// # Arguments
+    /// `builtins` - The builtins to complete for.
+    pub fn set_builtins(&mut self, builtins: Rc<CommandSet<'a, Shell<'a, S>>>) {
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.completer.builtins = builtins;
+        }
+    }
+
+    /// Sets the closure that observes how the line being typed parses. See
+    /// `Shell::set_line_observer()`.
+    ///
+    /// # Arguments
+    /// `observe` - The closure to call with the line and its parse.
+    pub fn set_line_observer(&mut self, observe: Observe) {
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.completer.observer = Some(LineObserver {
+                observe,
+                last_line: RefCell::new(None),
+                last_observed: Cell::new(None),
+            });
+        }
+    }
+
+    /// Sets the least time between two observations of the line observer. See
+    /// `Shell::set_line_observer_interval()`.
+    ///
+    /// # Arguments
+    /// `interval` - The least time between two observations.
+    pub fn set_line_observer_interval(&mut self, interval: Duration) {
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.completer.observe_interval = interval;
+        }
+    }
+
     /// Reads a line via the given prompt.
     ///
     /// # Arguments
     /// `prompt` - The prompt to display to the user.
     pub fn readline(&mut self, prompt: &str) -> rustyline::Result<String> {
-        let mut input = self.rl.readline(prompt)?;
+        self.readline_with_initial(prompt, "")
+    }
+
+    /// Reads a line via the given prompt, with the line initially filled with the given input. On
+    /// dumb terminals, the initial input is ignored.
+    ///
+    /// # Arguments
+    /// `prompt` - The prompt to display to the user.
+    /// `initial` - The input to fill the line with, before the cursor.
+    pub fn readline_with_initial(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+    ) -> rustyline::Result<String> {
+        if self.dumb {
+            return self.read_plain_line(prompt);
+        }
+
+        // Bindings in rustyline are static, so we rebind the key to insert the current last argument
+        // before every read.
+        if let Some(key) = self.last_arg_key {
+            let last_arg = self.history().last().and_then(|entry| last_arg(entry));
+            let cmd = last_arg.map_or(Cmd::Noop, |arg| Cmd::Insert(1, arg));
+            self.rl.bind_sequence(key, cmd);
+        }
+
+        // Rustyline measures the prompt it is given, but displays the highlighted one, so the two
+        // must be the same text for the cursor to be placed correctly, e.g. with wide characters.
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.colored_prompt = prompt.to_string();
+        }
+
+        let mut input = self.rl.readline_with_initial(prompt, (initial, ""))?;
         // This due to the multi line validation in the ExecValidator. We need to remove the
         // newline in multiline input, as well as, and more importantly, the slash that denotes
         // multi-line input for the feature to be useful (otherwise any command taking multi-line
@@ -77,11 +226,54 @@ impl<'a, S> Readline<'a, S> {
         // we're gonna remove it... I'm not really happy about it, but I'm going to optimistically
         // assume this won't happen, at least not for a long time, and I'll fix it when it becomes
         // a problem.
-        input = input.replace("\\\n", "");
+        if !self.raw {
+            input = input.replace("\\\n", "");
+        }
 
         Ok(input)
     }
 
+    /// Reads a line from stdin without any line editing. Used for dumb terminals.
+    fn read_plain_line(&mut self, prompt: &str) -> rustyline::Result<String> {
+        let mut stdout = io::stdout();
+        write!(stdout, "{}", prompt)?;
+        stdout.flush()?;
+
+        let mut input = String::new();
+        if io::stdin().lock().read_line(&mut input)? == 0 {
+            return Err(ReadlineError::Eof);
+        }
+
+        Ok(input.trim_end_matches(&['\n', '\r'][..]).to_string())
+    }
+
+    /// Offers completion candidates for a line, as tab completion would. See
+    /// `ExecCompleter::complete()`.
+    ///
+    /// # Arguments
+    /// `line` - The line to try offering completion candidates for.
+    /// `pos` - The position of the cursor on that line.
+    ///
+    /// # Returns
+    /// `(usize, Vec<Pair>)` - The position from which the candidates replace the line up to the
+    /// cursor, and the candidates.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        match self.rl.helper() {
+            Some(helper) => helper.completer.complete(line, pos),
+            None => (pos, Vec::new()),
+        }
+    }
+
+    /// Offers a hint for a line, as the hint shown while typing would be.
+    ///
+    /// # Arguments
+    /// `line` - The line to offer a hint for.
+    /// `pos` - The position of the cursor on that line.
+    pub fn hint(&self, line: &str, pos: usize) -> Option<String> {
+        let ctx = Context::new(self.rl.history());
+        self.rl.helper()?.hint(line, pos, &ctx)
+    }
+
     /// Returns the readline `History`.
     ///
     /// Repeated, subsequent commands are not duplicated in the history.
@@ -97,6 +289,28 @@ impl<'a, S> Readline<'a, S> {
     }
 }
 
+/// Returns the last argument of the given line, as it would be typed, i.e., quoted if it contains
+/// spaces. The command itself counts as an argument if there are no others, as in bash.
+fn last_arg(line: &str) -> Option<String> {
+    let tokens = DefaultTokenizer::new(vec!['\'', '"']).tokenize(line).tokens;
+    let arg = tokens.last()?;
+    if arg.contains(' ') {
+        Some(format!("\"{}\"", arg))
+    } else {
+        Some(arg.to_string())
+    }
+}
+
+/// Returns the byte offset of the given token within the line it was tokenized from. Tokens are
+/// slices of their line, so for a quoted token, this is the offset of its first character after
+/// the quotation mark. Should the token not be a slice of the line, it is assumed to end it.
+fn token_start(line: &str, token: &str) -> usize {
+    match token_range(line, token) {
+        Some(range) => range.start,
+        None => line.len().saturating_sub(token.len()),
+    }
+}
+
 #[derive(Helper)]
 /// An ExecHelper for supporting various `rustyline` features.
 pub struct ExecHelper<'a, S> {
@@ -104,7 +318,13 @@ pub struct ExecHelper<'a, S> {
     highlighter: MatchingBracketHighlighter,
     validator: ExecValidator,
     hinter: HistoryHinter,
+    // The snippet being filled in, if any, takes over completion and hints at the end of the line.
+    snippets: Rc<RefCell<Snippets>>,
+    // Abbreviations are expanded when completing the first word of a line.
+    abbreviations: Rc<RefCell<Abbreviations>>,
     colored_prompt: String,
+    // Whether lines are read as typed, neither completed nor validated. See `Readline::set_raw()`.
+    raw: bool,
 }
 
 impl<'a, S> ExecHelper<'a, S> {
@@ -113,13 +333,19 @@ impl<'a, S> ExecHelper<'a, S> {
         parser: Parser,
         cmds: Rc<RefCell<CommandSet<'a, S>>>,
         builtins: Rc<CommandSet<'a, Shell<'a, S>>>,
+        usage: Rc<RefCell<Usage>>,
+        snippets: Rc<RefCell<Snippets>>,
+        abbreviations: Rc<RefCell<Abbreviations>>,
     ) -> ExecHelper<'a, S> {
         ExecHelper {
-            completer: ExecCompleter::new(parser, cmds, builtins),
+            completer: ExecCompleter::new(parser, cmds, builtins, usage),
             highlighter: MatchingBracketHighlighter::new(),
             validator: ExecValidator::new(),
             hinter: HistoryHinter {},
+            snippets,
+            abbreviations,
             colored_prompt: "| ".to_string(),
+            raw: false,
         }
     }
 }
@@ -133,6 +359,33 @@ impl<'a, S> Completer for ExecHelper<'a, S> {
         pos: usize,
         _: &Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if self.raw {
+            return Ok((pos, Vec::new()));
+        }
+
+        if pos == line.len() {
+            if let Some(text) = self.snippets.borrow_mut().advance() {
+                let next = Pair {
+                    display: text.clone(),
+                    replacement: text,
+                };
+                return Ok((pos, vec![next]));
+            }
+        }
+
+        // Rustyline has no hook for keys other than Tab, so abbreviations are expanded on Tab
+        // rather than on space.
+        let word = line[..pos].trim_start();
+        if !word.is_empty() && !word.contains(' ') {
+            if let Some(expansion) = self.abbreviations.borrow().expansion(word) {
+                let expanded = Pair {
+                    display: expansion.to_string(),
+                    replacement: expansion.to_string(),
+                };
+                return Ok((pos - word.len(), vec![expanded]));
+            }
+        }
+
         Ok(self.completer.complete(line, pos))
     }
 }
@@ -141,6 +394,18 @@ impl<'a, S> Hinter for ExecHelper<'a, S> {
     type Hint = String;
 
     fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        // Hints are asked for on every keystroke, which makes them where lines are observed as
+        // they are typed. Raw lines don't invoke commands, so there is nothing to observe.
+        if let Some(partial) = line.get(..pos).filter(|_| !self.raw) {
+            self.completer.observe(partial, true);
+        }
+
+        if pos == line.len() {
+            if let Some(hint) = self.snippets.borrow().hint() {
+                return Some(hint);
+            }
+        }
+
         self.hinter.hint(line, pos, ctx)
     }
 }
@@ -166,8 +431,14 @@ impl<'a, S> Highlighter for ExecHelper<'a, S> {
         self.highlighter.highlight_char(line, pos)
     }
 
+    #[cfg(feature = "color")]
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
-        Owned(hint.black().bold().to_string())
+        Cow::Owned(hint.black().bold().to_string())
+    }
+
+    #[cfg(not(feature = "color"))]
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Borrowed(hint)
     }
 }
 
@@ -176,11 +447,18 @@ impl<'a, S> Validator for ExecHelper<'a, S> {
         &self,
         ctx: &mut validate::ValidationContext,
     ) -> rustyline::Result<validate::ValidationResult> {
+        if self.raw {
+            return Ok(validate::ValidationResult::Valid(None));
+        }
+
+        // Lines are validated when they are submitted, where they are observed regardless of
+        // throttling, so that the last keystrokes before submitting are never missed.
+        self.completer.observe(ctx.input(), false);
         self.validator.validate(ctx)
     }
 
     fn validate_while_typing(&self) -> bool {
-        self.validator.validate_while_typing()
+        !self.raw && self.validator.validate_while_typing()
     }
 }
 
@@ -325,11 +603,28 @@ impl Validator for ExecValidator {
     }
 }
 
+/// Called with the line being typed and its parse. See `Shell::set_line_observer()`.
+type Observe = Box<dyn Fn(&str, &Outcome<'_>)>;
+
+/// Observes how the line being typed parses. See `Shell::set_line_observer()`.
+struct LineObserver {
+    observe: Observe,
+    // The line last observed. The editor asks for completions and hints of the same line several
+    // times, e.g. when the cursor moves, so a line is not observed again until it changes.
+    last_line: RefCell<Option<String>>,
+    // When a line was last observed, so that observations can be throttled.
+    last_observed: Cell<Option<Instant>>,
+}
+
 /// ExecCompleter enables command completion in the shell.
 struct ExecCompleter<'a, S> {
     parser: Parser,
     cmds: Rc<RefCell<CommandSet<'a, S>>>,
     builtins: Rc<CommandSet<'a, Shell<'a, S>>>,
+    usage: Rc<RefCell<Usage>>,
+    observer: Option<LineObserver>,
+    // The least time between two observations of the line observer.
+    observe_interval: Duration,
 }
 
 impl<'a, S> ExecCompleter<'a, S> {
@@ -339,16 +634,79 @@ impl<'a, S> ExecCompleter<'a, S> {
     /// `parser` - The parser to use for command completion.
     /// `cmds` - The custom commands to complete for.
     /// `builtins` - The builtins to complete for.
+    /// `usage` - How often commands have been used, for ordering candidates.
     fn new(
         parser: Parser,
         cmds: Rc<RefCell<CommandSet<'a, S>>>,
         builtins: Rc<CommandSet<'a, Shell<'a, S>>>,
+        usage: Rc<RefCell<Usage>>,
     ) -> ExecCompleter<'a, S> {
         ExecCompleter {
             parser,
             cmds,
             builtins,
+            usage,
+            observer: None,
+            observe_interval: Duration::from_millis(50),
+        }
+    }
+
+    /// Returns the line observer, if there is one, the given line is not the one it last
+    /// observed, and, if `throttle`, it has not observed a line within the interval. The line is
+    /// then taken to be observed.
+    fn observer_for(&self, line: &str, throttle: bool) -> Option<&LineObserver> {
+        let observer = self.observer.as_ref()?;
+        let mut last_line = observer.last_line.borrow_mut();
+        if last_line.as_deref() == Some(line) {
+            return None;
         }
+        let now = Instant::now();
+        if let Some(last_observed) = observer.last_observed.get().filter(|_| throttle) {
+            if now.duration_since(last_observed) < self.observe_interval {
+                return None;
+            }
+        }
+        *last_line = Some(line.to_string());
+        observer.last_observed.set(Some(now));
+
+        Some(observer)
+    }
+
+    /// Parses the command being typed for the line observer, unless there is none, it has already
+    /// observed the line, or it is being throttled. The command being typed is the last one of a
+    /// chain or pipeline, e.g. `server st` of `build && server st`.
+    ///
+    /// # Arguments
+    /// `line` - The line being typed, up to the cursor.
+    /// `throttle` - Whether to skip the line if another was observed within the interval, as is
+    /// done for lines observed on every keystroke.
+    fn observe(&self, line: &str, throttle: bool) {
+        let observer = match self.observer_for(line, throttle) {
+            Some(observer) => observer,
+            None => return,
+        };
+
+        // Trailing separators end their commands, which split_chain() then drops, so the command
+        // being typed after them is still empty.
+        let command = match self.parser.split_chain(line).last() {
+            Some((command, None)) => *command,
+            _ => "",
+        };
+        let stage = self
+            .parser
+            .split_pipeline(command)
+            .last()
+            .copied()
+            .unwrap_or(command);
+        // Commands are trimmed, but whether the line ends in a space matters to its parse.
+        let stage = match token_range(line, stage) {
+            Some(range) => &line[range.start..],
+            None => stage,
+        };
+        let outcome = self
+            .parser
+            .parse(stage, &self.cmds.borrow(), &self.builtins);
+        (observer.observe)(stage, &outcome);
     }
 
     /// Offers completion candidates for a line.
@@ -387,60 +745,46 @@ impl<'a, S> ExecCompleter<'a, S> {
         let outcome = self
             .parser
             .parse(partial, &self.cmds.borrow(), &self.builtins);
+        self.observe(partial, false);
 
         // If the parse was complete, then we've gone down to a leaf command, and all we have left
         // is to try autocompletions on the arguments.
         if outcome.complete {
-            match outcome.leaf_completion {
-                None => {
-                    return (pos, vec![]);
-                }
-                Some(completion) => match completion {
-                    Completion::Nothing => {
-                        return (pos, vec![]);
-                    }
-                    Completion::PartialArgCompletion(arg_suffixes) => {
-                        return (
-                            pos,
-                            arg_suffixes
-                                .iter()
-                                .map(|suffix_poss| Pair {
-                                    display: suffix_poss.clone(),
-                                    replacement: suffix_poss.clone(),
-                                })
-                                .collect(),
-                        );
-                    }
-                    Completion::Possibilities(possibilities) => {
-                        // Although we'd like to immediately get around to giving back completions, what's
-                        // important is that we pad it with a space delimiter in case the user tabs when their
-                        // cursor is adjacent to the argument, so we don't complete 'foo bar' to 'foo barbaz'
-                        // and instead get 'foo bar baz'.
-                        // Note how we don't do this for partial arg completions, since this are
-                        // meant to be concatenated.
-                        if !partial.ends_with(' ') {
-                            return (
-                                pos,
-                                vec![Pair {
-                                    display: String::from(" "),
-                                    replacement: String::from(" "),
-                                }],
-                            );
-                        }
-
-                        return (
-                            pos,
-                            possibilities
-                                .iter()
-                                .map(|poss| Pair {
-                                    display: poss.clone(),
-                                    replacement: poss.clone(),
-                                })
-                                .collect(),
-                        );
-                    }
-                },
+            let completion = outcome.leaf_completion.unwrap_or(Completion::Nothing);
+            // Although we'd like to immediately get around to giving back possibilities, what's
+            // important is that we pad it with a space delimiter in case the user tabs when their
+            // cursor is adjacent to the argument, so we don't complete 'foo bar' to 'foo barbaz'
+            // and instead get 'foo bar baz'.
+            if matches!(completion, Completion::Possibilities(_)) && !partial.ends_with(' ') {
+                return (
+                    pos,
+                    vec![Pair {
+                        display: String::from(" "),
+                        replacement: String::from(" "),
+                    }],
+                );
             }
+
+            // The argument being completed is the last token, unless the cursor follows a space,
+            // in which case it is a new, empty one.
+            let (arg, arg_start) = match outcome.remaining.last() {
+                Some(last) if !partial.ends_with(' ') => (*last, token_start(partial, last)),
+                _ => ("", pos),
+            };
+
+            return match completion.span(arg) {
+                Some((start, candidates)) => (
+                    arg_start + start,
+                    candidates
+                        .into_iter()
+                        .map(|candidate| Pair {
+                            display: candidate.clone(),
+                            replacement: candidate,
+                        })
+                        .collect(),
+                ),
+                None => (pos, vec![]),
+            };
         }
 
         // The outcome includes what the parser would have allowed to have existed in the string.
@@ -476,17 +820,54 @@ impl<'a, S> ExecCompleter<'a, S> {
             }
         };
 
-        // So now, filter out those that have that aforementioned token as a prefix. And once we
-        // have that, grab the suffix for completion.
-        let candidates = outcome.possibilities.into_iter().filter_map(|poss| {
-            if poss.starts_with(prefix) {
-                // This really should never fail to get the remaining suffix, since the condition
-                // guarantees that the prefix exists... but no harm in being safe if we can.
-                poss.get(prefix.len()..).map(|s| s.to_string())
-            } else {
-                None
+        // Of the commands that the parse stopped at, only those starting with the prefix are
+        // wanted, which the sorted names of their set find without going through the rest.
+        let cmd_path = outcome.cmd_path;
+        let mut possibilities = {
+            let cmds = self.cmds.borrow();
+            match cmds.get_path(&cmd_path) {
+                _ if cmd_path.is_empty() => cmds.names_with_prefix_in_order(prefix),
+                Some(Command::Parent(parent)) => {
+                    parent.sub_commands().names_with_prefix_in_order(prefix)
+                }
+                _ => outcome
+                    .possibilities
+                    .into_iter()
+                    .filter(|poss| poss.starts_with(prefix))
+                    .collect(),
             }
+        };
+        // Hidden commands are never offered.
+        possibilities.retain(|poss| {
+            let mut path = cmd_path.clone();
+            path.push(poss);
+            let cmd_hidden = self
+                .cmds
+                .borrow()
+                .get_path(&path)
+                .is_some_and(|cmd| cmd.is_hidden());
+            let builtin_hidden = self
+                .builtins
+                .get_path(&path)
+                .is_some_and(|cmd| cmd.is_hidden());
+            !cmd_hidden && !builtin_hidden
         });
+        // The most-used commands are the likeliest to be wanted, so offer them first. Sorting is
+        // stable, so commands used equally often stay in the order they were registered in.
+        {
+            let usage = self.usage.borrow();
+            possibilities.sort_by_cached_key(|poss| {
+                let mut path = cmd_path.clone();
+                path.push(poss);
+                std::cmp::Reverse(usage.count(&path))
+            });
+        }
+
+        // All of them have that aforementioned token as a prefix, so grab the suffix for
+        // completion.
+        let candidates = possibilities
+            .into_iter()
+            .filter_map(|poss| poss.get(prefix.len()..).map(|s| s.to_string()));
 
         // Finally, map the candidates to `Pair`'s, which is what the Completer interface wants.
         let pairs: Vec<Pair> = candidates
@@ -506,8 +887,22 @@ impl<'a, S> ExecCompleter<'a, S> {
 mod test {
     use super::*;
 
+    #[test]
+    fn last_arg_of_line() {
+        use pretty_assertions::assert_eq;
+
+        assert_eq!(last_arg("server listen 8080"), Some(String::from("8080")));
+        assert_eq!(
+            last_arg("echo 'hello world'"),
+            Some(String::from("\"hello world\""))
+        );
+        assert_eq!(last_arg("history"), Some(String::from("history")));
+        assert_eq!(last_arg("  "), None);
+    }
+
     mod completions {
         use super::*;
+        use crate::command::{BaseCommand, BasicCommand, Command};
         use crate::parser::test::make_parser_cmds;
         use crate::parser::Parser;
 
@@ -520,7 +915,12 @@ mod test {
             let cmds = Rc::new(RefCell::new(cmds));
             let builtins = Rc::new(builtins);
 
-            ExecCompleter::new(Parser::new(), cmds, builtins)
+            ExecCompleter::new(
+                Parser::new(),
+                cmds,
+                builtins,
+                Rc::new(RefCell::new(Usage::new())),
+            )
         }
 
         fn test_completion(
@@ -549,6 +949,31 @@ mod test {
             }
         }
 
+        #[test]
+        fn hidden() {
+            let noop = |_: &mut (), _: &[String]| Ok(String::new());
+            let cmds = CommandSet::new_from_vec(vec![
+                Command::new_leaf(BasicCommand::builder("status").build(noop)),
+                Command::new_leaf(BasicCommand::builder("stats").hidden().build(noop)),
+            ]);
+            let completer = ExecCompleter::new(
+                Parser::new(),
+                Rc::new(RefCell::new(cmds)),
+                Rc::new(CommandSet::new()),
+                Rc::new(RefCell::new(Usage::new())),
+            );
+
+            test_completion(
+                completer,
+                "stat",
+                4,
+                vec![Pair {
+                    display: String::from("us"),
+                    replacement: String::from("us"),
+                }],
+            );
+        }
+
         #[test]
         fn simple() {
             let completer = make_completer();
@@ -736,6 +1161,143 @@ mod test {
                 }],
             )
         }
+
+        // Completes paths, replacing only the last component of the path, or the whole of it if
+        // it has just the one, ignoring case.
+        struct OpenCommand;
+
+        impl BaseCommand for OpenCommand {
+            type State = ();
+
+            fn name(&self) -> &str {
+                "open"
+            }
+
+            fn validate_args(&self, _: &[String]) -> Result<()> {
+                Ok(())
+            }
+
+            fn autocomplete(&self, args: Vec<&str>, _: bool) -> Completion {
+                let arg = args.last().copied().unwrap_or_default();
+                let (start, entries) = match arg.rfind('/') {
+                    Some(slash) => (slash + 1, vec!["main.rs", "mod.rs"]),
+                    None => (0, vec!["Cargo.toml", "src/"]),
+                };
+                let typed = arg[start..].to_lowercase();
+                Completion::Replacement {
+                    start,
+                    candidates: entries
+                        .into_iter()
+                        .filter(|entry| entry.to_lowercase().starts_with(&typed))
+                        .map(String::from)
+                        .collect(),
+                }
+            }
+
+            fn execute(&self, _: &mut (), _: &[String]) -> Result<String> {
+                Ok(String::new())
+            }
+        }
+
+        #[test]
+        fn replacement_spans() {
+            let completer = ExecCompleter::new(
+                Parser::new(),
+                Rc::new(RefCell::new(CommandSet::new_from_vec(vec![
+                    Command::new_leaf(OpenCommand),
+                ]))),
+                Rc::new(CommandSet::new()),
+                Rc::new(RefCell::new(Usage::new())),
+            );
+            let replacements = |line: &str, pos: usize| -> (usize, Vec<String>) {
+                let (start, pairs) = completer.complete(line, pos);
+                (
+                    start,
+                    pairs.into_iter().map(|pair| pair.replacement).collect(),
+                )
+            };
+
+            // The whole argument is replaced.
+            assert_eq!(
+                replacements("open ca", 7),
+                (5, vec![String::from("Cargo.toml")])
+            );
+            assert_eq!(
+                replacements("open ", 5),
+                (5, vec![String::from("Cargo.toml"), String::from("src/")])
+            );
+            // Just the last component of it is.
+            assert_eq!(
+                replacements("open src/m", 10),
+                (9, vec![String::from("main.rs"), String::from("mod.rs")])
+            );
+            assert_eq!(
+                replacements("open src/ma", 11),
+                (9, vec![String::from("main.rs")])
+            );
+            // In the middle of the line, only what precedes the cursor counts.
+            assert_eq!(
+                replacements("open src/mo --force", 11),
+                (9, vec![String::from("mod.rs")])
+            );
+            // Quotation marks are skipped over.
+            assert_eq!(
+                replacements("open \"src/ma", 12),
+                (10, vec![String::from("main.rs")])
+            );
+        }
+
+        // Completes the names of sessions, most recently attached first.
+        struct AttachCommand;
+
+        impl BaseCommand for AttachCommand {
+            type State = ();
+
+            fn name(&self) -> &str {
+                "attach"
+            }
+
+            fn validate_args(&self, _: &[String]) -> Result<()> {
+                Ok(())
+            }
+
+            fn autocomplete(&self, args: Vec<&str>, _: bool) -> Completion {
+                let sessions = vec![
+                    (String::from("build"), 3),
+                    (String::from("bench"), 7),
+                    (String::from("backup"), 3),
+                    (String::from("deploy"), 9),
+                ];
+                Completion::from_scored(args.last().copied().unwrap_or_default(), sessions)
+            }
+
+            fn execute(&self, _: &mut (), _: &[String]) -> Result<String> {
+                Ok(String::new())
+            }
+        }
+
+        #[test]
+        fn scored() {
+            let completer = ExecCompleter::new(
+                Parser::new(),
+                Rc::new(RefCell::new(CommandSet::new_from_vec(vec![
+                    Command::new_leaf(AttachCommand),
+                ]))),
+                Rc::new(CommandSet::new()),
+                Rc::new(RefCell::new(Usage::new())),
+            );
+            let replacements = |line: &str| -> Vec<String> {
+                let (_, pairs) = completer.complete(line, line.len());
+                pairs.into_iter().map(|pair| pair.replacement).collect()
+            };
+
+            assert_eq!(
+                replacements("attach "),
+                vec!["deploy", "bench", "build", "backup"]
+            );
+            assert_eq!(replacements("attach b"), vec!["ench", "uild", "ackup"]);
+            assert!(replacements("attach x").is_empty());
+        }
     }
 
     mod validator {
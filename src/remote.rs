@@ -0,0 +1,988 @@
+//! A module for serving a shell to remote clients over TCP.
+//!
+//! Every client that connects gets its own session: its own `Shell`, with its own history, but
+//! the application state is shared between all sessions behind a `Mutex`. Sessions do not use
+//! readline; lines are read from the connection as-is, and each line's output is written back.
+//!
+//! Sessions can message each other: every session has a `wall` command that broadcasts a message
+//! to all other sessions, and commands can push messages to sessions via the `Broadcaster` given
+//! to the setup of their session.
+//!
+//! Sessions only get the builtins in `SESSION_BUILTINS`, unless more are allowed via
+//! `Server::allow_builtin()`: the others read or write files on the server, or take over its
+//! terminal. Sessions are not interactive either, so commands that ask for confirmation fail
+//! instead of asking. See `Shell::set_interactive()`.
+//!
+//! Commands that mutate the shared state can be marked as such via `Server::add_mutating()`. Only
+//! one session at a time may run them: either the session holding the write lock, taken via `lock
+//! acquire`, or, if nobody holds it, any session, for the duration of the command.
+//!
+//! ```no_run
+//! # use std::net::TcpListener;
+//! # use std::sync::{Arc, Condvar, Mutex};
+//! # use shi::remote::Server;
+//! # fn main() -> shi::Result<()> {
+//! let server = Server::new("| ", Vec::<String>::new(), |shell, _| {
+//!     shell.register(shi::cmd!(
+//!         "push",
+//!         |items: &mut Arc<Mutex<Vec<String>>>, args: &[String]| {
+//!             items.lock().unwrap().extend_from_slice(args);
+//!             Ok(String::new())
+//!         }
+//!     ))
+//! });
+//! server.serve(TcpListener::bind("127.0.0.1:7777")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::command::{BaseCommand, Command};
+use crate::error::ShiError;
+use crate::shell::Shell;
+use crate::Result;
+
+/// The builtins that sessions get by default. See `Server::allow_builtin()`.
+pub const SESSION_BUILTINS: &[&str] = &[
+    "help", "helptree", "exit", "history", "show", "output", "grep", "filter", "fields", "sort",
+    "head", "tail", "metrics", "version",
+];
+
+/// The state of the shell of a remote session: the application state, shared between sessions.
+pub type SharedState<T> = Arc<Mutex<T>>;
+
+type Setup<T> =
+    dyn Fn(&mut Shell<'static, SharedState<T>>, &Broadcaster) -> Result<()> + Send + Sync;
+
+/// Where the output of a session is written to, shared between the session and broadcasters.
+type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// A session attached to a `Server`, as tracked by the server.
+struct Attached {
+    info: SessionInfo,
+    output: Output,
+}
+
+type Registry = Arc<Mutex<Vec<Attached>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Information about a session attached to a `Server`.
+pub struct SessionInfo {
+    /// The ID of the session, unique within its server.
+    pub id: usize,
+    /// The address of the client, e.g. `127.0.0.1:51234`.
+    pub peer: String,
+    /// When the client connected.
+    pub connected: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What happens when a session needs the write lock while another session holds it.
+pub enum LockPolicy {
+    /// The session is refused with an error. This is the default.
+    Reject,
+    /// The session waits until the lock is released.
+    Queue,
+}
+
+/// An advisory lock, held by at most one session at a time.
+#[derive(Default)]
+struct WriteLock {
+    holder: Mutex<Option<usize>>,
+    released: Condvar,
+}
+
+impl WriteLock {
+    /// Takes the lock for the given session, waiting for or refusing it as per `policy` if
+    /// another session holds it.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether the lock was taken, as opposed to being held by the session
+    /// already.
+    fn acquire(&self, id: usize, policy: LockPolicy) -> Result<bool> {
+        let mut holder = self.holder.lock().unwrap();
+        loop {
+            match *holder {
+                None => {
+                    *holder = Some(id);
+                    return Ok(true);
+                }
+                Some(held_by) if held_by == id => return Ok(false),
+                Some(held_by) => match policy {
+                    LockPolicy::Reject => {
+                        return Err(ShiError::general(format!(
+                            "the write lock is held by session #{}",
+                            held_by
+                        )))
+                    }
+                    LockPolicy::Queue => holder = self.released.wait(holder).unwrap(),
+                },
+            }
+        }
+    }
+
+    /// Releases the lock, if the given session holds it.
+    ///
+    /// # Returns
+    /// `bool` - Whether the session held the lock.
+    fn release(&self, id: usize) -> bool {
+        let mut holder = self.holder.lock().unwrap();
+        if *holder != Some(id) {
+            return false;
+        }
+
+        *holder = None;
+        self.released.notify_all();
+        true
+    }
+
+    fn holder(&self) -> Option<usize> {
+        *self.holder.lock().unwrap()
+    }
+}
+
+/// Holds the write lock for the duration of a command, if it was taken for the command rather
+/// than held by the session already.
+struct LockGuard {
+    lock: Arc<WriteLock>,
+    id: usize,
+    acquired: bool,
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if self.acquired {
+            self.lock.release(self.id);
+        }
+    }
+}
+
+/// A Server serves shell sessions to remote clients, one per connection.
+///
+/// Cloning a Server is cheap, and clones serve the same state and track the same sessions.
+pub struct Server<T> {
+    prompt: &'static str,
+    state: SharedState<T>,
+    setup: Arc<Setup<T>>,
+    sessions: Registry,
+    next_id: Arc<AtomicUsize>,
+    lock: Arc<WriteLock>,
+    lock_policy: LockPolicy,
+    // The paths of the commands that require the write lock.
+    mutating: Vec<String>,
+    // The names of the builtins that sessions get.
+    builtins: Vec<String>,
+}
+
+impl<T> Clone for Server<T> {
+    fn clone(&self) -> Self {
+        Server {
+            prompt: self.prompt,
+            state: self.state.clone(),
+            setup: self.setup.clone(),
+            sessions: self.sessions.clone(),
+            next_id: self.next_id.clone(),
+            lock: self.lock.clone(),
+            lock_policy: self.lock_policy,
+            mutating: self.mutating.clone(),
+            builtins: self.builtins.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Server<T> {
+    /// Creates a new Server.
+    ///
+    /// # Arguments
+    /// `prompt` - The prompt to display to clients.
+    /// `state` - The application state, shared by all sessions.
+    /// `setup` - Sets up the shell of each new session, e.g. by registering commands. It is also
+    /// given the session's `Broadcaster`, for commands that message other sessions.
+    pub fn new<F>(prompt: &'static str, state: T, setup: F) -> Server<T>
+    where
+        F: Fn(&mut Shell<'static, SharedState<T>>, &Broadcaster) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Server {
+            prompt,
+            state: Arc::new(Mutex::new(state)),
+            setup: Arc::new(setup),
+            sessions: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(AtomicUsize::new(1)),
+            lock: Arc::new(WriteLock::default()),
+            lock_policy: LockPolicy::Reject,
+            mutating: Vec::new(),
+            builtins: SESSION_BUILTINS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+
+    /// Gives sessions the builtin of the given name, on top of those in `SESSION_BUILTINS`.
+    ///
+    /// Beware that some builtins are unfit for remote clients: `tee` writes files on the server,
+    /// `diff` and `demo` read them, `reload` re-runs its rc file and `watch` and `demo` take over
+    /// its terminal.
+    ///
+    /// # Arguments
+    /// `name` - The name of the builtin, e.g. `alias`.
+    pub fn allow_builtin(&mut self, name: &str) {
+        self.builtins.push(name.to_string());
+    }
+
+    /// Marks the command at the given path, and any commands beneath it, as mutating the shared
+    /// state, so that only one session at a time may run them. See the module documentation.
+    ///
+    /// # Arguments
+    /// `path` - The path of the command, e.g. `server listen`.
+    pub fn add_mutating(&mut self, path: &str) {
+        self.mutating.push(path.to_string());
+    }
+
+    /// Sets what happens when a session needs the write lock while another session holds it.
+    ///
+    /// # Arguments
+    /// `policy` - The policy to use.
+    pub fn set_lock_policy(&mut self, policy: LockPolicy) {
+        self.lock_policy = policy;
+    }
+
+    /// Returns the ID of the session holding the write lock, if any.
+    pub fn lock_holder(&self) -> Option<usize> {
+        self.lock.holder()
+    }
+
+    /// Returns the application state shared by all sessions.
+    pub fn state(&self) -> SharedState<T> {
+        self.state.clone()
+    }
+
+    /// Returns the sessions that are currently attached, in the order they connected.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|session| session.info.clone())
+            .collect()
+    }
+
+    /// Writes the given message to the output of every attached session.
+    ///
+    /// # Arguments
+    /// `msg` - The message to write.
+    ///
+    /// # Returns
+    /// `usize` - The number of sessions the message was written to.
+    pub fn broadcast(&self, msg: &str) -> usize {
+        deliver(&self.sessions, |_| true, msg)
+    }
+
+    /// Accepts clients on the given listener until accepting fails, serving each on its own
+    /// thread.
+    ///
+    /// # Arguments
+    /// `listener` - The listener to accept clients on.
+    pub fn serve(&self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept()?;
+            let server = self.clone();
+            thread::spawn(move || -> Result<()> {
+                let input = BufReader::new(stream.try_clone()?);
+                server.run_session(&peer.to_string(), input, stream)
+            });
+        }
+    }
+
+    /// Runs a session until the client disconnects or exits, reading lines from `input` and
+    /// writing prompts and output to `output`.
+    ///
+    /// # Arguments
+    /// `peer` - The address of the client.
+    /// `input` - Where the client's lines are read from.
+    /// `output` - Where prompts and output are written to, as well as messages from other
+    /// sessions.
+    pub fn run_session<R, W>(&self, peer: &str, mut input: R, output: W) -> Result<()>
+    where
+        R: BufRead,
+        W: Write + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let output: Output = Arc::new(Mutex::new(output));
+        self.sessions.lock().unwrap().push(Attached {
+            info: SessionInfo {
+                id,
+                peer: peer.to_string(),
+                connected: Instant::now(),
+            },
+            output: output.clone(),
+        });
+
+        let res = self.serve_session(id, &mut input, &output);
+        if let Err(err) = &res {
+            // The client may be gone already, in which case there is nobody left to tell.
+            let _ = writeln!(output.lock().unwrap(), "Error: {}", err);
+        }
+        self.lock.release(id);
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|session| session.info.id != id);
+
+        res
+    }
+
+    fn serve_session<R: BufRead>(&self, id: usize, input: &mut R, output: &Output) -> Result<()> {
+        let broadcaster = Broadcaster {
+            id,
+            sessions: self.sessions.clone(),
+        };
+
+        let mut shell = Shell::new_with_state(self.prompt, self.state.clone());
+        // Sessions are not terminals, so their output should not be decorated for one.
+        shell.set_plain_output(true);
+        shell.set_dumb_terminal(true);
+        // Nor is the terminal of the server theirs to ask questions on.
+        shell.set_interactive(false);
+        shell.retain_builtins(|name| self.builtins.iter().any(|allowed| allowed == name));
+        shell.register(Command::new_leaf(SessionsCommand::new(
+            id,
+            self.sessions.clone(),
+        )))?;
+        shell.register(Command::new_leaf(WallCommand::new(broadcaster.clone())))?;
+        shell.register(Command::new_parent(
+            "lock",
+            vec![
+                Command::new_leaf(LockCommand::new(LockAction::Acquire, id, self)),
+                Command::new_leaf(LockCommand::new(LockAction::Release, id, self)),
+                Command::new_leaf(LockCommand::new(LockAction::Status, id, self)),
+            ],
+        ))?;
+        // The lock is taken for the commands that lines resolve to once chains, pipelines,
+        // aliases and the like have been taken apart, which all go through guards.
+        let lock = self.lock.clone();
+        let policy = self.lock_policy;
+        let mutating = self.mutating.clone();
+        shell.add_fallible_guard(move |path| {
+            let needs_lock = mutating
+                .iter()
+                .any(|m| path == m || path.starts_with(&format!("{} ", m)));
+            let acquired = needs_lock && lock.acquire(id, policy)?;
+            Ok(LockGuard {
+                lock: lock.clone(),
+                id,
+                acquired,
+            })
+        });
+        (self.setup)(&mut shell, &broadcaster)?;
+
+        while !shell.terminate {
+            {
+                let mut output = output.lock().unwrap();
+                write!(output, "{}", self.prompt)?;
+                output.flush()?;
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = line.trim_end_matches(&['\n', '\r'][..]);
+            let res = shell.eval(line);
+            let mut output = output.lock().unwrap();
+            match res {
+                Ok(out) => writeln!(output, "{}", shell.format_output(&out))?,
+                Err(err) => writeln!(
+                    output,
+                    "{} {}",
+                    shell.error_prefix(),
+                    shell.format_output(&err.to_string())
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the given message to the output of every attached session that `to` accepts.
+///
+/// # Returns
+/// `usize` - The number of sessions the message was written to.
+fn deliver<F: Fn(&SessionInfo) -> bool>(sessions: &Registry, to: F, msg: &str) -> usize {
+    // The registry is not held while writing, so that a slow client only holds up its own
+    // messages, not sessions attaching, detaching or messaging others.
+    let outputs: Vec<Output> = sessions
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|session| to(&session.info))
+        .map(|session| session.output.clone())
+        .collect();
+    let mut delivered = 0;
+    for output in outputs {
+        let mut output = output.lock().unwrap();
+        // A session whose client has gone away will be detached soon enough, so there is nothing
+        // to do for it.
+        if writeln!(output, "{}", msg)
+            .and_then(|_| output.flush())
+            .is_ok()
+        {
+            delivered += 1;
+        }
+    }
+
+    delivered
+}
+
+#[derive(Clone)]
+/// A Broadcaster pushes messages to the sessions attached to a `Server`, on behalf of one of them.
+pub struct Broadcaster {
+    id: usize,
+    sessions: Registry,
+}
+
+impl Broadcaster {
+    /// Returns the ID of the session this broadcaster acts on behalf of.
+    pub fn session_id(&self) -> usize {
+        self.id
+    }
+
+    /// Writes the given message to the output of every other attached session.
+    ///
+    /// # Arguments
+    /// `msg` - The message to write.
+    ///
+    /// # Returns
+    /// `usize` - The number of sessions the message was written to.
+    pub fn broadcast(&self, msg: &str) -> usize {
+        deliver(&self.sessions, |session| session.id != self.id, msg)
+    }
+
+    /// Writes the given message to the output of the given session.
+    ///
+    /// # Arguments
+    /// `id` - The ID of the session to write to.
+    /// `msg` - The message to write.
+    ///
+    /// # Returns
+    /// `Result<()>` - Errors if no session with the given ID is attached.
+    pub fn send(&self, id: usize, msg: &str) -> Result<()> {
+        if deliver(&self.sessions, |session| session.id == id, msg) == 0 {
+            return Err(ShiError::general(format!("no session #{} is attached", id)));
+        }
+
+        Ok(())
+    }
+}
+
+/// SessionsCommand lists the sessions attached to the server of the session it is registered in.
+struct SessionsCommand<T> {
+    id: usize,
+    sessions: Registry,
+    phantom: PhantomData<T>,
+}
+
+impl<T> SessionsCommand<T> {
+    fn new(id: usize, sessions: Registry) -> SessionsCommand<T> {
+        SessionsCommand {
+            id,
+            sessions,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> BaseCommand for SessionsCommand<T> {
+    type State = SharedState<T>;
+
+    fn name(&self) -> &str {
+        "sessions"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if !args.is_empty() {
+            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut SharedState<T>, _: &[String]) -> Result<String> {
+        let lines: Vec<String> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|Attached { info: session, .. }| {
+                format!(
+                    "#{} {} (connected {}s ago){}",
+                    session.id,
+                    session.peer,
+                    session.connected.elapsed().as_secs(),
+                    if session.id == self.id { " <- you" } else { "" }
+                )
+            })
+            .collect();
+
+        Ok(lines.join("\n"))
+    }
+
+    fn help(&self) -> String {
+        String::from("Lists the sessions attached to the server")
+    }
+}
+
+/// WallCommand broadcasts a message to all other sessions attached to the server.
+struct WallCommand<T> {
+    broadcaster: Broadcaster,
+    phantom: PhantomData<T>,
+}
+
+impl<T> WallCommand<T> {
+    fn new(broadcaster: Broadcaster) -> WallCommand<T> {
+        WallCommand {
+            broadcaster,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> BaseCommand for WallCommand<T> {
+    type State = SharedState<T>;
+
+    fn name(&self) -> &str {
+        "wall"
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.is_empty() {
+            return Err(ShiError::NoArgs);
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut SharedState<T>, args: &[String]) -> Result<String> {
+        let reached = self.broadcaster.broadcast(&format!(
+            "Broadcast from session #{}: {}",
+            self.broadcaster.session_id(),
+            args.join(" ")
+        ));
+
+        Ok(format!("sent to {} session(s)", reached))
+    }
+
+    fn help(&self) -> String {
+        String::from("Broadcasts a message to all other sessions")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LockAction {
+    Acquire,
+    Release,
+    Status,
+}
+
+/// LockCommand is one of the subcommands of the `lock` command, for managing the write lock on
+/// behalf of a session.
+struct LockCommand<T> {
+    action: LockAction,
+    id: usize,
+    lock: Arc<WriteLock>,
+    policy: LockPolicy,
+    phantom: PhantomData<T>,
+}
+
+impl<T> LockCommand<T> {
+    fn new(action: LockAction, id: usize, server: &Server<T>) -> LockCommand<T> {
+        LockCommand {
+            action,
+            id,
+            lock: server.lock.clone(),
+            policy: server.lock_policy,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> BaseCommand for LockCommand<T> {
+    type State = SharedState<T>;
+
+    fn name(&self) -> &str {
+        match self.action {
+            LockAction::Acquire => "acquire",
+            LockAction::Release => "release",
+            LockAction::Status => "status",
+        }
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if !args.is_empty() {
+            return Err(ShiError::ExtraArgs { got: args.to_vec() });
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut SharedState<T>, _: &[String]) -> Result<String> {
+        match self.action {
+            LockAction::Acquire => {
+                if self.lock.acquire(self.id, self.policy)? {
+                    Ok(String::from("acquired the write lock"))
+                } else {
+                    Ok(String::from("already holding the write lock"))
+                }
+            }
+            LockAction::Release => {
+                if self.lock.release(self.id) {
+                    Ok(String::from("released the write lock"))
+                } else {
+                    Err(ShiError::general("not holding the write lock"))
+                }
+            }
+            LockAction::Status => match self.lock.holder() {
+                Some(holder) if holder == self.id => {
+                    Ok(String::from("the write lock is held by this session"))
+                }
+                Some(holder) => Ok(format!("the write lock is held by session #{}", holder)),
+                None => Ok(String::from("the write lock is free")),
+            },
+        }
+    }
+
+    fn help(&self) -> String {
+        match self.action {
+            LockAction::Acquire => String::from("Takes the write lock for this session"),
+            LockAction::Release => String::from("Releases the write lock"),
+            LockAction::Status => String::from("Prints which session holds the write lock"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Read;
+    use std::net::TcpStream;
+
+    use crate::cmd;
+
+    use pretty_assertions::assert_eq;
+
+    #[derive(Clone, Default)]
+    /// A buffer that can be written to by a session while the test still holds onto it.
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuf {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn make_server() -> Server<Vec<String>> {
+        Server::new("| ", Vec::new(), |shell, _| {
+            shell.register(cmd!(
+                "push",
+                |items: &mut SharedState<Vec<String>>, args: &[String]| {
+                    items.lock().unwrap().extend_from_slice(args);
+                    Ok(String::from("pushed"))
+                }
+            ))?;
+            shell.register(cmd!(
+                "list",
+                |items: &mut SharedState<Vec<String>>, _: &[String]| {
+                    Ok(items.lock().unwrap().join(" "))
+                }
+            ))
+        })
+    }
+
+    #[test]
+    fn session() -> Result<()> {
+        let server = make_server();
+        let out = SharedBuf::default();
+
+        server.run_session(
+            "test",
+            "push a b\nlist\nDNE\nexit\nlist\n".as_bytes(),
+            out.clone(),
+        )?;
+
+        let transcript = out.contents();
+        assert!(transcript.starts_with("| pushed\n| a b\n| Error: "));
+        // Nothing is evaluated after the client exits.
+        assert!(transcript.ends_with("| bye\n"));
+        assert!(server.sessions().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn restricted_session() -> Result<()> {
+        let mut server = Server::new("| ", (), |shell, _| {
+            shell.register(Command::new_leaf(
+                crate::command::BasicCommand::builder("wipe")
+                    .confirm("Wipe everything?")
+                    .build(|_: &mut SharedState<()>, _| Ok(String::from("wiped"))),
+            ))
+        });
+        let out = SharedBuf::default();
+        server.run_session(
+            "test",
+            "tee /tmp/shi-remote-tee version
+wipe
+"
+            .as_bytes(),
+            out.clone(),
+        )?;
+
+        let transcript = out.contents();
+        assert!(transcript.starts_with("| Error: command failed to parse: 'tee'"));
+        assert!(transcript.ends_with(
+            "| Error: error: 'wipe' needs confirmation, but input is not interactive\n| "
+        ));
+
+        server.allow_builtin("alias");
+        let out = SharedBuf::default();
+        server.run_session(
+            "test",
+            "alias
+"
+            .as_bytes(),
+            out.clone(),
+        )?;
+        assert_eq!(out.contents(), "| no aliases\n| ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_session_output() -> Result<()> {
+        let server = Server::new("| ", (), |shell, _| {
+            shell.register(crate::cmd!("status", |_: &mut SharedState<()>, _| {
+                Ok(String::from("\x1b[32mup\x1b[0m"))
+            }))?;
+            shell.register(crate::cmd!("crash", |_: &mut SharedState<()>, _| {
+                Err(ShiError::general("\x1b[31mdown\x1b[0m"))
+            }))
+        });
+        let out = SharedBuf::default();
+        server.run_session("test", "status\ncrash\n".as_bytes(), out.clone())?;
+
+        assert_eq!(out.contents(), "| up\n| Error: error: down\n| ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_session_errors() {
+        let server = Server::new("| ", (), |_, _| Err(ShiError::general("no setup")));
+        let out = SharedBuf::default();
+
+        assert!(server
+            .run_session("test", "".as_bytes(), out.clone())
+            .is_err());
+        assert_eq!(out.contents(), "Error: error: no setup\n");
+    }
+
+    #[test]
+    fn shared_state() -> Result<()> {
+        let server = make_server();
+
+        server.run_session("first", "push a\n".as_bytes(), Vec::new())?;
+        let out = SharedBuf::default();
+        server.run_session("second", "push b\nlist\nhistory\n".as_bytes(), out.clone())?;
+
+        let transcript = out.contents();
+        // Each session has its own history.
+        assert_eq!(
+            transcript,
+            "| pushed\n| a b\n| \tpush b\n\tlist\n\thistory\n| "
+        );
+        assert_eq!(*server.state().lock().unwrap(), vec!["a", "b"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_sessions() -> Result<()> {
+        let server = make_server();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+
+        let mut first = TcpStream::connect(addr)?;
+        let mut second = TcpStream::connect(addr)?;
+        let mut first_out = BufReader::new(first.try_clone()?);
+
+        // Wait for the first session to be served before asking who is attached.
+        let mut prompt = [0; 2];
+        first_out.read_exact(&mut prompt)?;
+        second.read_exact(&mut prompt)?;
+
+        first.write_all(b"sessions\n")?;
+        let mut listing = String::new();
+        first_out.read_line(&mut listing)?;
+        first_out.read_line(&mut listing)?;
+        assert_eq!(listing.lines().count(), 2);
+        assert!(listing.contains("<- you"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn broadcast() -> Result<()> {
+        let server = make_server();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+
+        let mut first = TcpStream::connect(addr)?;
+        let second = TcpStream::connect(addr)?;
+        let mut first_out = BufReader::new(first.try_clone()?);
+        let mut second_out = BufReader::new(second);
+
+        // Wait for both sessions to be attached.
+        let mut prompt = [0; 2];
+        first_out.read_exact(&mut prompt)?;
+        second_out.read_exact(&mut prompt)?;
+
+        first.write_all(b"wall deploying in 5\n")?;
+        let mut line = String::new();
+        first_out.read_line(&mut line)?;
+        assert_eq!(line, "sent to 1 session(s)\n");
+
+        // The message is delivered while the other session is idle at its prompt.
+        line.clear();
+        second_out.read_line(&mut line)?;
+        assert!(line.starts_with("Broadcast from session #"));
+        assert!(line.ends_with(": deploying in 5\n"));
+
+        line.clear();
+        assert_eq!(server.broadcast("maintenance over"), 2);
+        second_out.read_line(&mut line)?;
+        assert_eq!(line, "maintenance over\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send() -> Result<()> {
+        let senders = Arc::new(Mutex::new(Vec::new()));
+        let collected = senders.clone();
+        let server = Server::new("| ", (), move |_, broadcaster| {
+            collected.lock().unwrap().push(broadcaster.clone());
+            Ok(())
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+
+        let mut client = BufReader::new(TcpStream::connect(addr)?);
+        client.read_exact(&mut [0; 2])?;
+        let broadcaster = senders.lock().unwrap().pop().unwrap();
+
+        broadcaster.send(broadcaster.session_id(), "hello")?;
+        let mut line = String::new();
+        client.read_line(&mut line)?;
+        assert_eq!(line, "hello\n");
+        assert!(broadcaster
+            .send(broadcaster.session_id() + 1, "hello")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_lock() {
+        let lock = Arc::new(WriteLock::default());
+
+        assert_eq!(lock.acquire(1, LockPolicy::Reject).ok(), Some(true));
+        assert_eq!(lock.acquire(1, LockPolicy::Reject).ok(), Some(false));
+        assert!(lock.acquire(2, LockPolicy::Reject).is_err());
+        assert!(!lock.release(2));
+
+        // Queued sessions get the lock once it is released.
+        let queued = lock.clone();
+        let waiter = thread::spawn(move || queued.acquire(2, LockPolicy::Queue).ok());
+        assert!(lock.release(1));
+        assert_eq!(waiter.join().unwrap(), Some(true));
+        assert_eq!(lock.holder(), Some(2));
+    }
+
+    #[test]
+    fn locking() -> Result<()> {
+        let mut server = make_server();
+        server.add_mutating("push");
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+
+        let mut first = TcpStream::connect(addr)?;
+        let mut second = TcpStream::connect(addr)?;
+        let mut first_out = BufReader::new(first.try_clone()?);
+        let mut second_out = BufReader::new(second.try_clone()?);
+        let mut prompt = [0; 2];
+        first_out.read_exact(&mut prompt)?;
+        second_out.read_exact(&mut prompt)?;
+
+        let eval = |stream: &mut TcpStream, out: &mut BufReader<TcpStream>, line: &str| {
+            stream.write_all(format!("{}\n", line).as_bytes()).unwrap();
+            let mut output = String::new();
+            out.read_line(&mut output).unwrap();
+            out.read_exact(&mut [0; 2]).unwrap();
+            output
+        };
+
+        assert_eq!(
+            eval(&mut first, &mut first_out, "lock acquire"),
+            "acquired the write lock\n"
+        );
+        assert!(eval(&mut second, &mut second_out, "push a")
+            .starts_with("Error: error: the write lock is held by session #"));
+        // Commands that aren't mutating can still be run.
+        assert_eq!(eval(&mut second, &mut second_out, "list"), "\n");
+        // Mutating commands are caught wherever they are in the line.
+        assert!(eval(&mut second, &mut second_out, "list && push a")
+            .starts_with("Error: error: the write lock is held by session #"));
+        assert_eq!(eval(&mut first, &mut first_out, "push a"), "pushed\n");
+        assert_eq!(
+            eval(&mut first, &mut first_out, "lock release"),
+            "released the write lock\n"
+        );
+        assert_eq!(eval(&mut second, &mut second_out, "push b"), "pushed\n");
+        assert_eq!(server.lock_holder(), None);
+        assert_eq!(*server.state().lock().unwrap(), vec!["a", "b"]);
+
+        Ok(())
+    }
+}
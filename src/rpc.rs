@@ -0,0 +1,733 @@
+//! A module for driving a shell as a backend service over JSON-RPC 2.0, e.g. from editors or web
+//! UIs. This is only available with the `rpc` feature.
+//!
+//! Requests and responses are JSON objects, one per line. The supported methods are:
+//! * `eval` - Takes `{"line": "..."}` and evaluates the line, returning `{"output": "..."}`.
+//!   Errors from evaluating the line are returned as JSON-RPC errors, with code `-32000`.
+//! * `complete` - Takes `{"line": "...", "pos": n}`, where `pos` defaults to the end of the line,
+//!   and returns the completion candidates as `[{"display": "...", "replacement": "..."}]`.
+//!
+//! ```
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let mut shell = Shell::new("| ");
+//! shell.register(shi::cmd!("dog", |_, _| Ok(String::from("woof"))))?;
+//!
+//! let mut responses = Vec::new();
+//! let requests = r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"line": "dog"}}"#;
+//! shi::rpc::serve(&mut shell, requests.as_bytes(), &mut responses)?;
+//!
+//! assert_eq!(
+//!     String::from_utf8(responses).unwrap(),
+//!     "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"output\":\"woof\"}}\n"
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+use crate::error::ShiError;
+use crate::output::{json_string, OutputSink};
+use crate::shell::Shell;
+use crate::Result;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The code of errors produced by evaluating a line.
+const EVAL_ERROR: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A JSON value.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses the given text as a single JSON value.
+    fn parse(text: &str) -> Result<Json> {
+        let mut parser = JsonParser {
+            chars: text.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the value of the given member, if this is an object that has it.
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::Str(s) => write!(f, "{}", json_string(s)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", json_string(key), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// How deeply arrays and objects may be nested in a request, so that parsing one cannot overflow
+/// the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A recursive descent parser for JSON.
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+    // How many arrays and objects the parser is in.
+    depth: usize,
+}
+
+impl JsonParser {
+    fn error(&self, msg: &str) -> ShiError {
+        ShiError::general(format!("invalid JSON at offset {}: {}", self.pos, msg))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("expected '{}'", expected)));
+        }
+
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json> {
+        let end = self.pos + keyword.len();
+        if end > self.chars.len() || self.chars[self.pos..end].iter().collect::<String>() != keyword
+        {
+            return Err(self.error("unexpected token"));
+        }
+
+        self.pos = end;
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        match self.peek() {
+            Some(c @ '{') | Some(c @ '[') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("nested too deeply"));
+                }
+
+                self.depth += 1;
+                let res = if c == '{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                res
+            }
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                Some(c) => *c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escaped = match self.chars.get(self.pos) {
+                        Some(c) => *c,
+                        None => return Err(self.error("unterminated escape")),
+                    };
+                    self.pos += 1;
+                    s.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Parses the digits of a `\u` escape, including the low half of a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid code point"));
+        }
+
+        if self.chars.get(self.pos) != Some(&'\\') || self.chars.get(self.pos + 1) != Some(&'u') {
+            return Err(self.error("unpaired surrogate"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err(self.error("unpaired surrogate"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+            .ok_or_else(|| self.error("invalid code point"))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let end = self.pos + 4;
+        if end > self.chars.len() {
+            return Err(self.error("truncated unicode escape"));
+        }
+
+        let digits: String = self.chars[self.pos..end].iter().collect();
+        self.pos = end;
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && matches!(
+                self.chars[self.pos],
+                '-' | '+' | '.' | 'e' | 'E' | '0'..='9'
+            )
+        {
+            self.pos += 1;
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+}
+
+/// Builds the members of a JSON-RPC response with the given ID.
+fn response(id: Json, outcome: std::result::Result<Json, (i64, String)>) -> Json {
+    let mut members = vec![
+        (String::from("jsonrpc"), Json::Str(String::from("2.0"))),
+        (String::from("id"), id),
+    ];
+    match outcome {
+        Ok(result) => members.push((String::from("result"), result)),
+        Err((code, message)) => members.push((
+            String::from("error"),
+            Json::Object(vec![
+                (String::from("code"), Json::Number(code as f64)),
+                (String::from("message"), Json::Str(message)),
+            ]),
+        )),
+    }
+
+    Json::Object(members)
+}
+
+/// Handles the given request line.
+///
+/// # Returns
+/// `Option<String>` - The response line, or None if the request was a notification.
+fn handle<S>(shell: &mut Shell<S>, line: &str) -> Option<String> {
+    let request = match Json::parse(line) {
+        Ok(request) => request,
+        Err(err) => {
+            return Some(response(Json::Null, Err((PARSE_ERROR, err.to_string()))).to_string())
+        }
+    };
+
+    let id = request.get("id").cloned();
+    let method = match request.get("method").and_then(Json::as_str) {
+        Some(method) => method,
+        None => {
+            let err = (INVALID_REQUEST, String::from("missing method"));
+            return Some(response(id.unwrap_or(Json::Null), Err(err)).to_string());
+        }
+    };
+
+    let params = request.get("params");
+    let line_param = params
+        .and_then(|params| params.get("line"))
+        .and_then(Json::as_str);
+    let outcome = match (method, line_param) {
+        ("eval", Some(line)) => shell
+            .eval(line)
+            .map(|output| Json::Object(vec![(String::from("output"), Json::Str(output))]))
+            .map_err(|err| (EVAL_ERROR, err.to_string())),
+        ("complete", Some(line)) => {
+            let pos = match params.and_then(|params| params.get("pos")) {
+                Some(Json::Number(pos)) => *pos as usize,
+                _ => line.len(),
+            };
+            if pos > line.len() || !line.is_char_boundary(pos) {
+                Err((INVALID_PARAMS, format!("invalid position {}", pos)))
+            } else {
+                Ok(Json::Array(
+                    shell
+                        .complete(line, pos)
+                        .into_iter()
+                        .map(|candidate| {
+                            Json::Object(vec![
+                                (String::from("display"), Json::Str(candidate.display)),
+                                (
+                                    String::from("replacement"),
+                                    Json::Str(candidate.replacement),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                ))
+            }
+        }
+        ("eval", None) | ("complete", None) => {
+            Err((INVALID_PARAMS, String::from("expected a 'line' parameter")))
+        }
+        (method, _) => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    };
+
+    id.map(|id| response(id, outcome).to_string())
+}
+
+/// The output sink of a shell while it is served, which keeps what the shell displays besides
+/// responses, e.g. warnings and traces, off the transport, which may well be stdout.
+struct Stderr;
+
+impl OutputSink for Stderr {
+    fn write_output(&mut self, output: &str) {
+        eprintln!("{}", output);
+    }
+
+    fn write_error(&mut self, error: &str) {
+        eprintln!("{}", error);
+    }
+}
+
+/// Serves JSON-RPC requests read from `input` with the given shell, writing responses to
+/// `output`, until the input ends or the shell is exited. See the module documentation.
+///
+/// While serving, the shell is not interactive, since its clients cannot answer questions, and
+/// what it displays besides responses goes to stderr. Both are restored once serving ends.
+///
+/// # Arguments
+/// `shell` - The shell to serve.
+/// `input` - Where requests are read from, one per line.
+/// `output` - Where responses are written to, one per line.
+pub fn serve<S, R: BufRead, W: Write>(shell: &mut Shell<S>, input: R, output: W) -> Result<()> {
+    let interactive = shell.replace_interactive(Some(false));
+    let sink = shell.replace_output_sink(Box::new(Stderr));
+    let res = serve_requests(shell, input, output);
+    shell.replace_output_sink(sink);
+    shell.replace_interactive(interactive);
+
+    res
+}
+
+fn serve_requests<S, R: BufRead, W: Write>(
+    shell: &mut Shell<S>,
+    input: R,
+    mut output: W,
+) -> Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle(shell, &line) {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+
+        if shell.terminate {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves JSON-RPC requests from clients of the given listener with the given shell, until a
+/// client exits the shell. Clients are served one at a time, and share the shell.
+///
+/// Failing to serve a client does not stop the listener; the failure is logged to stderr, and
+/// the next client is accepted. Failing to accept a client does, since the listener is unlikely
+/// to recover from it, e.g. if the process ran out of file descriptors.
+///
+/// # Arguments
+/// `shell` - The shell to serve.
+/// `listener` - The listener to accept clients on.
+pub fn serve_listener<S>(shell: &mut Shell<S>, listener: TcpListener) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+        let res = stream
+            .try_clone()
+            .map_err(ShiError::from)
+            .and_then(|input| serve(shell, BufReader::new(input), stream));
+        if let Err(err) = res {
+            eprintln!("rpc: failed to serve a client: {}", err);
+        }
+
+        if shell.terminate {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{cmd, parent};
+
+    use pretty_assertions::assert_eq;
+
+    fn make_shell<'a>() -> Result<Shell<'a, ()>> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, args| Ok(format!(
+            "woof {}",
+            args.join(" ")
+        ))))?;
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| Ok(String::new())),
+            cmd!("unlisten", |_, _| Ok(String::new())),
+        ))?;
+
+        Ok(shell)
+    }
+
+    fn call(shell: &mut Shell<()>, request: &str) -> String {
+        let mut out = Vec::new();
+        serve(shell, request.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json() -> Result<()> {
+        let value = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b\n": "é😀\"" } "#)?;
+        assert_eq!(
+            value,
+            Json::Object(vec![
+                (
+                    String::from("a"),
+                    Json::Array(vec![
+                        Json::Number(1.0),
+                        Json::Number(-25.0),
+                        Json::Bool(true),
+                        Json::Null
+                    ])
+                ),
+                (String::from("b\n"), Json::Str(String::from("é😀\""))),
+            ])
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"{"a":[1,-25,true,null],"b\n":"é😀\""}"#
+        );
+
+        assert!(Json::parse("{").is_err());
+        assert!(Json::parse(r#"{"a" 1}"#).is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("tru").is_err());
+        assert!(Json::parse("1 2").is_err());
+        // Nesting is bounded, rather than by the stack.
+        assert!(Json::parse(&"[".repeat(200_000)).is_err());
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Json::parse(&nested).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn eval() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert_eq!(
+            call(
+                &mut shell,
+                r#"{"jsonrpc":"2.0","id":"a","method":"eval","params":{"line":"dog a b"}}"#
+            ),
+            "{\"jsonrpc\":\"2.0\",\"id\":\"a\",\"result\":{\"output\":\"woof a b\"}}\n"
+        );
+
+        let failed = call(
+            &mut shell,
+            r#"{"jsonrpc":"2.0","id":2,"method":"eval","params":{"line":"cat"}}"#,
+        );
+        assert!(failed.starts_with(
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"error\":{\"code\":-32000,\"message\":\"command failed"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn complete() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        assert_eq!(
+            call(
+                &mut shell,
+                r#"{"jsonrpc":"2.0","id":1,"method":"complete","params":{"line":"server un"}}"#
+            ),
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":[{\"display\":\"listen\",\
+            \"replacement\":\"listen\"}]}\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_transport_to_responses() -> Result<()> {
+        use std::cell::RefCell;
+        use std::io;
+        use std::rc::Rc;
+
+        use crate::command::{BasicCommand, Command};
+        use crate::shell::Verbosity;
+
+        // Stands in for stdout, which both the sink of the shell and the transport write to.
+        #[derive(Clone, Default)]
+        struct Stdout(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Stdout {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl OutputSink for Stdout {
+            fn write_output(&mut self, output: &str) {
+                writeln!(self, "{}", output).unwrap();
+            }
+
+            fn write_error(&mut self, error: &str) {
+                writeln!(self, "{}", error).unwrap();
+            }
+        }
+
+        let stdout = Stdout::default();
+        let mut shell = make_shell()?;
+        shell.register(Command::new_leaf(
+            BasicCommand::builder("wipe")
+                .confirm("Wipe everything?")
+                .build(|_, _| Ok(String::from("wiped"))),
+        ))?;
+        shell.set_output_sink(Box::new(stdout.clone()));
+        shell.set_interactive(true);
+        shell.set_verbosity(Verbosity::Verbose);
+
+        let requests: String = ["dog", "retry 2 cat", "wipe"]
+            .iter()
+            .enumerate()
+            .map(|(id, line)| {
+                format!(
+                    "{{\"jsonrpc\":\"2.0\",\"id\":{},\"method\":\"eval\",\
+                     \"params\":{{\"line\":\"{}\"}}}}\n",
+                    id + 1,
+                    line
+                )
+            })
+            .collect();
+        serve(&mut shell, requests.as_bytes(), stdout.clone())?;
+
+        let written = String::from_utf8(stdout.0.borrow().clone()).unwrap();
+        let responses: Vec<Json> = written.lines().map(Json::parse).collect::<Result<_>>()?;
+        assert_eq!(responses.len(), 3);
+        assert!(responses[1].get("error").is_some());
+        // Confirmation is not asked for, since nobody could answer.
+        assert!(written.contains("needs confirmation, but input is not interactive"));
+
+        // The shell is as it was once serving ends.
+        assert!(shell.is_interactive());
+        shell.eval("dog")?;
+        assert!(stdout.0.borrow().len() > written.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn listener_survives_clients() -> Result<()> {
+        use std::io::{BufRead, Write};
+        use std::net::TcpStream;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let serving = std::thread::spawn(move || -> Result<()> {
+            serve_listener(&mut make_shell()?, listener)
+        });
+
+        // A client whose requests aren't even text fails to be served, but not the next one.
+        TcpStream::connect(addr)?.write_all(b"\xff\xfe\n")?;
+        let mut client = TcpStream::connect(addr)?;
+        client.write_all(
+            b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"eval\",\"params\":{\"line\":\"dog\"}}\n",
+        )?;
+        let mut response = String::new();
+        BufReader::new(client.try_clone()?).read_line(&mut response)?;
+        assert!(response.contains("woof"));
+
+        client.write_all(
+            b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"eval\",\"params\":{\"line\":\"exit\"}}\n",
+        )?;
+        serving.join().unwrap()
+    }
+
+    #[test]
+    fn listener_stops_on_accept_errors() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        // Without clients, accepting on a non-blocking listener fails right away.
+        listener.set_nonblocking(true)?;
+        assert!(serve_listener(&mut make_shell()?, listener).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> Result<()> {
+        let mut shell = make_shell()?;
+
+        let responses = call(
+            &mut shell,
+            "not json\n\
+            {\"jsonrpc\":\"2.0\",\"id\":1}\n\
+            {\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"fly\"}\n\
+            {\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"eval\",\"params\":{}}\n\
+            {\"jsonrpc\":\"2.0\",\"method\":\"eval\",\"params\":{\"line\":\"dog\"}}\n",
+        );
+        let codes: Vec<&str> = responses
+            .lines()
+            .map(|response| {
+                let start = response.find("\"code\":").unwrap() + 7;
+                &response[start..start + 6]
+            })
+            .collect();
+        // Notifications, i.e. requests without IDs, get no response.
+        assert_eq!(codes, vec!["-32700", "-32600", "-32601", "-32602"]);
+        assert_eq!(shell.history_len(), 1);
+
+        Ok(())
+    }
+}
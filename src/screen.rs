@@ -0,0 +1,295 @@
+//! A module for commands that take over the terminal, like `less` or `htop` do.
+//!
+//! A command enters the terminal's alternate screen with `FullScreen::enter()`, draws to it, e.g.
+//! with a draw loop via `FullScreen::run()`, and the prompt is restored as it was once the
+//! `FullScreen` is dropped:
+//!
+//! ```no_run
+//! # use std::time::{Duration, Instant};
+//! # use shi::screen::FullScreen;
+//! # fn main() -> shi::Result<()> {
+//! let started = Instant::now();
+//! FullScreen::enter()?.run(Duration::from_secs(1), |_key| {
+//!     Ok(format!("up for {:?}\n\npress q to quit", started.elapsed()))
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! On Unix, the terminal is switched to unbuffered input via `stty`, so that keys are seen as
+//! they are pressed. Elsewhere, or if stdin is not a terminal, keys are not read at all, so that
+//! they are left for the prompt, and draw loops run until the process is interrupted.
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR: &str = "\x1b[H\x1b[2J";
+
+/// Runs `stty` on the terminal with the given arguments, returning its output if it succeeded.
+pub(crate) fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Reads the keys pressed on stdin on a background thread, until told to stop.
+struct KeyReader {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl KeyReader {
+    /// Starts reading keys. The terminal must be in unbuffered mode, with reads that time out
+    /// after a tenth of a second, as set up by `FullScreen::enter()`.
+    fn spawn() -> (KeyReader, Receiver<char>) {
+        let (tx, keys) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut byte = [0; 1];
+            // The bytes of the key being read, which may take several in UTF-8.
+            let mut pending = Vec::with_capacity(4);
+            // Reads time out regularly, which is when the flag is checked.
+            while !stopped.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                match stdin.read(&mut byte) {
+                    Ok(0) => {
+                        // A read that returns nothing before timing out is at the end of the
+                        // input, e.g. because the terminal hung up.
+                        if started.elapsed() < Duration::from_millis(50) {
+                            break;
+                        }
+                    }
+                    Ok(_) => {
+                        pending.push(byte[0]);
+                        let key = match std::str::from_utf8(&pending) {
+                            Ok(key) => key.chars().next(),
+                            Err(err) if err.error_len().is_some() => {
+                                Some(char::REPLACEMENT_CHARACTER)
+                            }
+                            // The rest of the key is yet to be read.
+                            Err(_) => continue,
+                        };
+                        pending.clear();
+                        if let Some(key) = key {
+                            if tx.send(key).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        (KeyReader { stop, handle }, keys)
+    }
+}
+
+/// FullScreen is the alternate screen of the terminal, taken over by a command. The terminal is
+/// restored when it is dropped, even if the command fails.
+pub struct FullScreen<W: Write = io::Stdout> {
+    out: W,
+    keys: Receiver<char>,
+    reader: Option<KeyReader>,
+    // The terminal settings to restore, as saved by `stty -g`.
+    saved_tty: Option<String>,
+}
+
+impl FullScreen {
+    /// Enters the alternate screen of the terminal, hiding the cursor.
+    pub fn enter() -> Result<FullScreen> {
+        let mut saved_tty = None;
+        if cfg!(unix) {
+            if let Some(settings) = stty(&["-g"]) {
+                if stty(&["-icanon", "-echo", "min", "0", "time", "1"]).is_some() {
+                    saved_tty = Some(settings);
+                }
+            }
+        }
+        // Keys are only read in unbuffered mode, since a read of buffered input would block
+        // until Enter is pressed, and take the line from the prompt once the screen is left.
+        let (reader, keys) = if saved_tty.is_some() {
+            let (reader, keys) = KeyReader::spawn();
+            (Some(reader), keys)
+        } else {
+            (None, mpsc::channel().1)
+        };
+
+        FullScreen::new(io::stdout(), keys, reader, saved_tty)
+    }
+
+    /// Returns the size of the terminal as rows and columns, if it can be determined.
+    pub fn size(&self) -> Option<(u16, u16)> {
+        let size = stty(&["size"])?;
+        let mut dimensions = size.split_whitespace().map(|n| n.parse().ok());
+        Some((dimensions.next()??, dimensions.next()??))
+    }
+}
+
+impl<W: Write> FullScreen<W> {
+    fn new(
+        mut out: W,
+        keys: Receiver<char>,
+        reader: Option<KeyReader>,
+        saved_tty: Option<String>,
+    ) -> Result<FullScreen<W>> {
+        write!(out, "{}", ENTER_ALTERNATE_SCREEN)?;
+        out.flush()?;
+
+        Ok(FullScreen {
+            out,
+            keys,
+            reader,
+            saved_tty,
+        })
+    }
+
+    /// Replaces what is on the screen with the given content.
+    ///
+    /// # Arguments
+    /// `content` - The content to draw.
+    pub fn draw(&mut self, content: &str) -> Result<()> {
+        // Without line buffering, the terminal may not return the carriage on newlines.
+        write!(self.out, "{}{}", CLEAR, content.replace('\n', "\r\n"))?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    /// Waits up to the given timeout for a key to be pressed. If keys are not being read, this
+    /// waits for the whole timeout.
+    ///
+    /// # Arguments
+    /// `timeout` - How long to wait.
+    ///
+    /// # Returns
+    /// `Option<char>` - The key that was pressed, if any.
+    pub fn poll_key(&mut self, timeout: Duration) -> Option<char> {
+        let deadline = Instant::now() + timeout;
+        match self.keys.recv_timeout(timeout) {
+            Ok(key) => Some(key),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                None
+            }
+        }
+    }
+
+    /// Runs a draw loop until `q` is pressed. The content returned by `render` is drawn
+    /// every `interval`, and whenever a key is pressed, in which case `render` is given the key.
+    ///
+    /// # Arguments
+    /// `interval` - How often to redraw if no key is pressed.
+    /// `render` - Renders the content to draw, given the key that was pressed, if any.
+    pub fn run<F>(&mut self, interval: Duration, mut render: F) -> Result<()>
+    where
+        F: FnMut(Option<char>) -> Result<String>,
+    {
+        let mut key = None;
+        loop {
+            let content = render(key)?;
+            self.draw(&content)?;
+
+            let deadline = Instant::now() + interval;
+            key = self.poll_key(interval);
+            match key {
+                Some('q') => return Ok(()),
+                // Enter isn't passed on, as it doesn't change what is drawn.
+                Some('\n') | Some('\r') => {
+                    key = None;
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<W: Write> Drop for FullScreen<W> {
+    fn drop(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.stop.store(true, Ordering::Relaxed);
+            // Reads time out after a tenth of a second, so the reader notices soon.
+            let _ = reader.handle.join();
+        }
+        if let Some(saved_tty) = &self.saved_tty {
+            stty(&[saved_tty]);
+        }
+
+        let _ = write!(self.out, "{}", LEAVE_ALTERNATE_SCREEN);
+        let _ = self.out.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn draw_loop() -> Result<()> {
+        let (tx, keys) = mpsc::channel();
+        let mut out = Vec::new();
+        let mut renders = Vec::new();
+        {
+            let mut screen = FullScreen::new(&mut out, keys, None, None)?;
+            tx.send('j').unwrap();
+            tx.send('q').unwrap();
+            screen.run(Duration::from_secs(5), |key| {
+                renders.push(key);
+                Ok(format!("pressed {:?}\nbye", key))
+            })?;
+        }
+
+        assert_eq!(renders, vec![None, Some('j')]);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{}{}pressed None\r\nbye{}pressed Some('j')\r\nbye{}",
+                ENTER_ALTERNATE_SCREEN, CLEAR, CLEAR, LEAVE_ALTERNATE_SCREEN
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn restores_the_screen_on_errors() -> Result<()> {
+        let (_tx, keys) = mpsc::channel();
+        let mut out = Vec::new();
+        {
+            let mut screen = FullScreen::new(&mut out, keys, None, None)?;
+            let res = screen.run(Duration::from_secs(5), |_| {
+                Err(crate::error::ShiError::general("failed to render"))
+            });
+            assert!(res.is_err());
+        }
+
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with(LEAVE_ALTERNATE_SCREEN));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,680 @@
+//! A module for typed settings, which users can inspect and change via a `config` command.
+//!
+//! Settings are registered on a `Settings` with a type, a default and a description. The
+//! `Settings` is shared, via `Rc<RefCell<Settings>>`, between the embedder and the `config`
+//! command returned by `command()`:
+//!
+//! ```
+//! # use std::cell::RefCell;
+//! # use std::rc::Rc;
+//! # use shi::settings::{self, Settings};
+//! # use shi::shell::Shell;
+//! # fn main() -> shi::Result<()> {
+//! let settings = Rc::new(RefCell::new(Settings::new()));
+//! settings
+//!     .borrow_mut()
+//!     .register_enum("color", "The color of the output", &["red", "blue"], "red")?;
+//!
+//! let mut shell = Shell::new("| ");
+//! shell.register(settings::command(settings.clone()))?;
+//!
+//! shell.eval("config set color blue")?;
+//! assert_eq!(settings.borrow().get_str("color"), Some("blue"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::command::{BaseCommand, Command, Completion};
+use crate::error::ShiError;
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The type of a setting, which determines the values it may be set to.
+pub enum SettingKind {
+    /// `true` or `false`. `on` and `off` are also accepted.
+    Bool,
+    /// A 64-bit signed integer.
+    Int,
+    /// Any string.
+    Str,
+    /// One of the given values.
+    Enum(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The value of a setting.
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingValue::Bool(b) => write!(f, "{}", b),
+            SettingValue::Int(i) => write!(f, "{}", i),
+            SettingValue::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl SettingKind {
+    /// Parses the given value as a value of this kind.
+    pub(crate) fn parse(&self, value: &str) -> Result<SettingValue> {
+        match self {
+            SettingKind::Bool => match value {
+                "true" | "on" => Ok(SettingValue::Bool(true)),
+                "false" | "off" => Ok(SettingValue::Bool(false)),
+                _ => Err(ShiError::general(format!(
+                    "'{}' is not a boolean; expected 'true' or 'false'",
+                    value
+                ))),
+            },
+            SettingKind::Int => value
+                .parse()
+                .map(SettingValue::Int)
+                .map_err(|_| ShiError::general(format!("'{}' is not an integer", value))),
+            SettingKind::Str => Ok(SettingValue::Str(value.to_string())),
+            SettingKind::Enum(values) => {
+                if values.iter().any(|v| v == value) {
+                    Ok(SettingValue::Str(value.to_string()))
+                } else {
+                    Err(ShiError::InvalidSubCommand {
+                        got: value.to_string(),
+                        expected: values.clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Returns the values a setting of this kind can be set to, if there are finitely many.
+    pub(crate) fn values(&self) -> Vec<String> {
+        match self {
+            SettingKind::Bool => vec![String::from("true"), String::from("false")],
+            SettingKind::Enum(values) => values.clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A single registered setting.
+pub struct Setting {
+    /// The name of the setting.
+    pub name: String,
+    /// A description of what the setting controls.
+    pub description: String,
+    /// The type of the setting.
+    pub kind: SettingKind,
+    /// The value of the setting when it has not been set.
+    pub default: SettingValue,
+    /// The current value of the setting.
+    pub value: SettingValue,
+}
+
+#[derive(Debug, Default)]
+/// A registry of typed settings, optionally persisted to a file.
+pub struct Settings {
+    settings: Vec<Setting>,
+    file: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Creates a new Settings, without any registered settings.
+    pub fn new() -> Settings {
+        Settings::default()
+    }
+
+    /// Registers a setting.
+    ///
+    /// # Arguments
+    /// `name` - The name of the setting.
+    /// `description` - A description of what the setting controls.
+    /// `kind` - The type of the setting.
+    /// `default` - The default value of the setting. Must be valid for `kind`.
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        kind: SettingKind,
+        default: &str,
+    ) -> Result<()> {
+        if self.setting(name).is_some() {
+            return Err(ShiError::AlreadyRegistered {
+                cmd: name.to_string(),
+            });
+        }
+
+        let default = kind.parse(default)?;
+        self.settings.push(Setting {
+            name: name.to_string(),
+            description: description.to_string(),
+            kind,
+            value: default.clone(),
+            default,
+        });
+
+        Ok(())
+    }
+
+    /// Registers a boolean setting. See `register()`.
+    pub fn register_bool(&mut self, name: &str, description: &str, default: bool) -> Result<()> {
+        self.register(name, description, SettingKind::Bool, &default.to_string())
+    }
+
+    /// Registers an integer setting. See `register()`.
+    pub fn register_int(&mut self, name: &str, description: &str, default: i64) -> Result<()> {
+        self.register(name, description, SettingKind::Int, &default.to_string())
+    }
+
+    /// Registers a string setting. See `register()`.
+    pub fn register_str(&mut self, name: &str, description: &str, default: &str) -> Result<()> {
+        self.register(name, description, SettingKind::Str, default)
+    }
+
+    /// Registers a setting that may only be one of the given values. See `register()`.
+    pub fn register_enum(
+        &mut self,
+        name: &str,
+        description: &str,
+        values: &[&str],
+        default: &str,
+    ) -> Result<()> {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        self.register(name, description, SettingKind::Enum(values), default)
+    }
+
+    /// Returns the setting with the given name, if it is registered.
+    pub fn setting(&self, name: &str) -> Option<&Setting> {
+        self.settings.iter().find(|setting| setting.name == name)
+    }
+
+    fn setting_mut(&mut self, name: &str) -> Result<&mut Setting> {
+        match self
+            .settings
+            .iter()
+            .position(|setting| setting.name == name)
+        {
+            Some(i) => Ok(&mut self.settings[i]),
+            None => Err(self.unknown_setting(name)),
+        }
+    }
+
+    /// Returns the error for a setting that is not registered.
+    fn unknown_setting(&self, name: &str) -> ShiError {
+        ShiError::InvalidSubCommand {
+            got: name.to_string(),
+            expected: self.names(),
+        }
+    }
+
+    /// Returns all the registered settings, in the order they were registered.
+    pub fn settings(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    /// Returns the names of the registered settings, in the order they were registered.
+    pub fn names(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .map(|setting| setting.name.clone())
+            .collect()
+    }
+
+    /// Returns the value of the setting with the given name, if it is registered.
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.setting(name).map(|setting| &setting.value)
+    }
+
+    /// Returns the value of the boolean setting with the given name, if it is registered.
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(SettingValue::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the integer setting with the given name, if it is registered.
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(SettingValue::Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the string or enum setting with the given name, if it is registered.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(SettingValue::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Sets the setting with the given name, persisting the change if a file was set via
+    /// `set_and_load_file()`.
+    /// If persisting fails, the setting keeps its previous value.
+    ///
+    /// # Arguments
+    /// `name` - The name of the setting.
+    /// `value` - The new value of the setting. Must be valid for the setting's type.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let setting = self.setting_mut(name)?;
+        let value = setting.kind.parse(value)?;
+        self.replace_and_save(name, value)
+    }
+
+    /// Resets the setting with the given name to its default, persisting the change if a file was
+    /// set via `set_and_load_file()`.
+    /// If persisting fails, the setting keeps its previous value.
+    ///
+    /// # Arguments
+    /// `name` - The name of the setting.
+    pub fn reset(&mut self, name: &str) -> Result<()> {
+        let default = self.setting_mut(name)?.default.clone();
+        self.replace_and_save(name, default)
+    }
+
+    /// Replaces the value of the setting with the given name & persists it, restoring the
+    /// previous value if persisting fails.
+    fn replace_and_save(&mut self, name: &str, value: SettingValue) -> Result<()> {
+        let previous = std::mem::replace(&mut self.setting_mut(name)?.value, value);
+        if let Err(err) = self.save() {
+            self.setting_mut(name)?.value = previous;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the file the settings are persisted to & loads the settings from it, if it exists.
+    ///
+    /// The file consists of `name = value` lines. Blank lines and lines starting with `#` are
+    /// ignored.
+    ///
+    /// # Arguments
+    /// `path` - The path to the settings file.
+    pub fn set_and_load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                for (i, line) in contents.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+
+                    let res = match line.split_once('=') {
+                        Some((name, value)) => self.setting_mut(name.trim()).and_then(|setting| {
+                            setting
+                                .kind
+                                .parse(value.trim())
+                                .map(|value| setting.value = value)
+                        }),
+                        None => Err(ShiError::general("expected 'name = value'")),
+                    };
+
+                    if let Err(err) = res {
+                        return Err(ShiError::general(format!(
+                            "{}:{}: {}",
+                            path.display(),
+                            i + 1,
+                            err
+                        )));
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        self.file = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Saves the settings that differ from their defaults to the settings file, if one was set
+    /// via `set_and_load_file()`.
+    pub fn save(&self) -> Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        let contents: String = self
+            .settings
+            .iter()
+            .filter(|setting| setting.value != setting.default)
+            .map(|setting| format!("{} = {}\n", setting.name, setting.value))
+            .collect();
+        fs::write(file, contents)?;
+
+        Ok(())
+    }
+}
+
+/// Creates a `config` command for inspecting and changing the given settings, with the
+/// subcommands:
+/// * `get <name>` - Prints the value of a setting.
+/// * `set <name> <value>` - Sets a setting.
+/// * `list` - Lists every setting, with its value, default and description.
+/// * `reset <name>` - Resets a setting to its default.
+///
+/// # Arguments
+/// `settings` - The settings for the command to manage.
+pub fn command<'a, S: 'a>(settings: Rc<RefCell<Settings>>) -> Command<'a, S> {
+    Command::new_parent(
+        "config",
+        vec![
+            Command::new_leaf(ConfigCommand::new(ConfigAction::Get, settings.clone())),
+            Command::new_leaf(ConfigCommand::new(ConfigAction::Set, settings.clone())),
+            Command::new_leaf(ConfigCommand::new(ConfigAction::List, settings.clone())),
+            Command::new_leaf(ConfigCommand::new(ConfigAction::Reset, settings)),
+        ],
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigAction {
+    Get,
+    Set,
+    List,
+    Reset,
+}
+
+/// ConfigCommand is one of the subcommands of the `config` command. See `command()`.
+pub struct ConfigCommand<S> {
+    action: ConfigAction,
+    settings: Rc<RefCell<Settings>>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> ConfigCommand<S> {
+    fn new(action: ConfigAction, settings: Rc<RefCell<Settings>>) -> ConfigCommand<S> {
+        ConfigCommand {
+            action,
+            settings,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The number of arguments this command takes.
+    fn arity(&self) -> usize {
+        match self.action {
+            ConfigAction::List => 0,
+            ConfigAction::Get | ConfigAction::Reset => 1,
+            ConfigAction::Set => 2,
+        }
+    }
+
+    /// Returns the error for the given arguments, which are not as many as this command takes.
+    fn arity_error(&self, args: &[String]) -> ShiError {
+        if args.len() > self.arity() {
+            return ShiError::ExtraArgs {
+                got: args[self.arity()..].to_vec(),
+            };
+        }
+
+        ShiError::general(format!(
+            "'{}' expects {} argument(s), but got {}",
+            self.name(),
+            self.arity(),
+            args.len()
+        ))
+    }
+}
+
+impl<S> BaseCommand for ConfigCommand<S> {
+    type State = S;
+
+    fn name(&self) -> &str {
+        match self.action {
+            ConfigAction::Get => "get",
+            ConfigAction::Set => "set",
+            ConfigAction::List => "list",
+            ConfigAction::Reset => "reset",
+        }
+    }
+
+    fn validate_args(&self, args: &[String]) -> Result<()> {
+        if args.len() != self.arity() {
+            return Err(self.arity_error(args));
+        }
+
+        if let Some(name) = args.first() {
+            let settings = self.settings.borrow();
+            if settings.setting(name).is_none() {
+                return Err(settings.unknown_setting(name));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, _: &mut S, args: &[String]) -> Result<String> {
+        let mut settings = self.settings.borrow_mut();
+        match (self.action, args) {
+            (ConfigAction::Get, [name]) => match settings.get(name) {
+                Some(value) => Ok(value.to_string()),
+                None => Err(settings.unknown_setting(name)),
+            },
+            (ConfigAction::Set, [name, value]) => {
+                settings.set(name, value)?;
+                Ok(format!("{} set to {}", name, value))
+            }
+            (ConfigAction::List, []) => Ok(settings
+                .settings()
+                .iter()
+                .map(|setting| {
+                    format!(
+                        "{} = {} (default: {}) - {}",
+                        setting.name, setting.value, setting.default, setting.description
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n")),
+            (ConfigAction::Reset, [name]) => {
+                settings.reset(name)?;
+                let value = settings.get(name).map(ToString::to_string);
+                let value = value.unwrap_or_default();
+                Ok(format!("{} reset to {}", name, value))
+            }
+            _ => Err(self.arity_error(args)),
+        }
+    }
+
+    fn autocomplete(&self, args: Vec<&str>, trailing_space: bool) -> Completion {
+        let settings = self.settings.borrow();
+        let values = |name: &str| {
+            settings
+                .setting(name)
+                .map_or_else(Vec::new, |setting| setting.kind.values())
+        };
+
+        match (args.as_slice(), trailing_space) {
+            ([], _) if self.arity() > 0 => Completion::Possibilities(settings.names()),
+            ([name], false) => Completion::from_partial(name, settings.names()),
+            ([name], true) if self.action == ConfigAction::Set => {
+                Completion::Possibilities(values(name))
+            }
+            ([name, value], false) if self.action == ConfigAction::Set => {
+                Completion::from_partial(value, values(name))
+            }
+            _ => Completion::Nothing,
+        }
+    }
+
+    fn help(&self) -> String {
+        String::from(match self.action {
+            ConfigAction::Get => "Prints the value of a setting",
+            ConfigAction::Set => "Sets a setting",
+            ConfigAction::List => "Lists all settings",
+            ConfigAction::Reset => "Resets a setting to its default",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::shell::Shell;
+
+    use pretty_assertions::assert_eq;
+
+    fn make_settings() -> Result<Settings> {
+        let mut settings = Settings::new();
+        settings.register_bool("colors", "Whether to use colors", true)?;
+        settings.register_int("retries", "How many times to retry", 3)?;
+        settings.register_str("name", "The name to greet", "world")?;
+        settings.register_enum("mode", "The editing mode", &["emacs", "vi"], "emacs")?;
+
+        Ok(settings)
+    }
+
+    #[test]
+    fn get_and_set() -> Result<()> {
+        let mut settings = make_settings()?;
+
+        assert_eq!(settings.get_bool("colors"), Some(true));
+        assert_eq!(settings.get_int("retries"), Some(3));
+        assert_eq!(settings.get_str("name"), Some("world"));
+        assert_eq!(settings.get_str("mode"), Some("emacs"));
+
+        settings.set("colors", "off")?;
+        settings.set("retries", "5")?;
+        settings.set("mode", "vi")?;
+        assert_eq!(settings.get_bool("colors"), Some(false));
+        assert_eq!(settings.get_int("retries"), Some(5));
+        assert_eq!(settings.get_str("mode"), Some("vi"));
+
+        assert!(settings.set("retries", "many").is_err());
+        assert!(settings.set("mode", "nano").is_err());
+        assert!(settings.set("missing", "1").is_err());
+
+        settings.reset("retries")?;
+        assert_eq!(settings.get_int("retries"), Some(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn register_errors() -> Result<()> {
+        let mut settings = make_settings()?;
+
+        assert!(settings.register_bool("colors", "again", false).is_err());
+        assert!(settings
+            .register_enum("shape", "The shape", &["circle"], "square")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn persistence() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-settings-{}", std::process::id()));
+
+        let mut settings = make_settings()?;
+        settings.set_and_load_file(&path)?;
+        settings.set("retries", "7")?;
+        settings.set("mode", "vi")?;
+        assert_eq!(fs::read_to_string(&path)?, "retries = 7\nmode = vi\n");
+
+        let mut reloaded = make_settings()?;
+        reloaded.set_and_load_file(&path)?;
+        assert_eq!(reloaded.get_int("retries"), Some(7));
+        assert_eq!(reloaded.get_str("mode"), Some("vi"));
+
+        fs::write(&path, "# comment\n\nretries = lots\n")?;
+        let err = make_settings()?.set_and_load_file(&path).unwrap_err();
+        assert!(err.to_string().contains(":3: "));
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn rolls_back_failed_saves() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("shi-settings-missing-{}", std::process::id()))
+            .join("settings");
+
+        let settings = Rc::new(RefCell::new(make_settings()?));
+        settings.borrow_mut().set_and_load_file(&path)?;
+        let mut shell = Shell::new("| ");
+        shell.register(command(settings.clone()))?;
+
+        assert!(shell.eval("config set retries 7").is_err());
+        assert!(settings.borrow_mut().reset("retries").is_err());
+        assert_eq!(settings.borrow().get_int("retries"), Some(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_command_checks_args_on_execute() -> Result<()> {
+        let get: ConfigCommand<()> =
+            ConfigCommand::new(ConfigAction::Get, Rc::new(RefCell::new(make_settings()?)));
+        assert!(get.execute(&mut (), &[]).is_err());
+        assert!(get.execute(&mut (), &[String::from("missing")]).is_err());
+        assert_eq!(get.execute(&mut (), &[String::from("retries")])?, "3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_command() -> Result<()> {
+        let settings = Rc::new(RefCell::new(make_settings()?));
+        let mut shell = Shell::new("| ");
+        shell.register(command(settings.clone()))?;
+
+        assert_eq!(shell.eval("config get retries")?, "3");
+        assert_eq!(shell.eval("config set retries 4")?, "retries set to 4");
+        assert_eq!(settings.borrow().get_int("retries"), Some(4));
+        assert_eq!(shell.eval("config reset retries")?, "retries reset to 3");
+        assert_eq!(
+            shell.eval("config list")?,
+            "colors = true (default: true) - Whether to use colors\nretries = 3 (default: 3) - \
+            How many times to retry\nname = world (default: world) - The name to greet\nmode = \
+            emacs (default: emacs) - The editing mode"
+        );
+
+        assert!(shell.eval("config get missing").is_err());
+        assert!(shell.eval("config set retries").is_err());
+        assert!(shell.eval("config set retries many").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_autocomplete() -> Result<()> {
+        let settings = Rc::new(RefCell::new(make_settings()?));
+        let set_cmd: ConfigCommand<()> = ConfigCommand::new(ConfigAction::Set, settings.clone());
+        let list_cmd: ConfigCommand<()> = ConfigCommand::new(ConfigAction::List, settings);
+
+        assert_eq!(
+            set_cmd.autocomplete(vec!["re"], false),
+            Completion::PartialArgCompletion(vec![String::from("tries")])
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["mode"], true),
+            Completion::Possibilities(vec![String::from("emacs"), String::from("vi")])
+        );
+        assert_eq!(
+            set_cmd.autocomplete(vec!["colors", "f"], false),
+            Completion::PartialArgCompletion(vec![String::from("alse")])
+        );
+        assert_eq!(list_cmd.autocomplete(vec![], true), Completion::Nothing);
+
+        Ok(())
+    }
+}
@@ -4,27 +4,65 @@
 //! Namely, it exposes the `Shell` struct, which is the heart of shi. It makes use of `Command`'s
 //! to create a shell interface.
 
+use std::any::Any;
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use rustyline::error::ReadlineError;
+pub use rustyline::KeyEvent;
 
+use crate::abbreviations::Abbreviations;
+use crate::aliases::Aliases;
 use crate::command::{
-    builtin::{ExitCommand, HelpCommand, HelpTreeCommand, HistoryCommand},
-    BaseCommand, Command,
+    builtin::{
+        AliasCommand, CompletionsCommand, DemoCommand, DiffCommand, ExitCommand, FieldsCommand,
+        FilterCommand, GrepCommand, HeadCommand, HelpCommand, HelpTreeCommand, HistoryCommand,
+        MetricsCommand, OutputCommand, ParallelCommand, ReloadCommand, RetryCommand, SetCommand,
+        ShowCommand, SnippetCommand, SortCommand, TailCommand, TeeCommand, VersionCommand,
+        WatchCommand,
+    },
+    BaseCommand, Command, Completion, ConcurrentJob, ExternalCommand,
 };
 use crate::command_set::CommandSet;
+use crate::completions::{self, Level, ShellKind};
+use crate::context::{Context, Mounted};
 use crate::error::ShiError;
-use crate::parser::{CommandType, Outcome, Parser};
+use crate::events::Notifier;
+use crate::line_mode::LineModes;
+use crate::output::{self, CommandOutput, OutputSink, PlainRenderer, Renderer};
+use crate::parser::Parser;
+pub use crate::parser::{CommandType, Outcome, Resolution};
+use crate::prompt::Prompter;
 use crate::readline::Readline;
+use crate::settings::Settings;
+use crate::snippets::Snippets;
+use crate::timestamp::TimeFormat;
+use crate::tokenizer::{Separator, Tokenizer};
+use crate::tree::TreeStyle;
+use crate::usage::Usage;
 use crate::Result;
 
+/// How many outputs of previous evaluations are kept by default.
+const DEFAULT_OUTPUT_HISTORY_LEN: usize = 10;
+
 /// The shell.
 ///
 /// This gives the shell interface for shi. It is constructed and registered with commands.
 /// Execution is done through a run-loop of input/output.
 pub struct Shell<'a, S> {
-    prompt: &'a str,
+    pub(crate) prompt: &'a str,
     // TODO: We likely should NOT be exporting these, even within the crate. Instead, we should add
     // public getters, perhaps?
     // We need Rc<RefCell> because:
@@ -38,8 +76,410 @@ pub struct Shell<'a, S> {
     pub(crate) rl: Readline<'a, S>,
     parser: Parser,
     history_file: Option<&'a str>,
+    rc_file: Option<&'a str>,
+    commands_dir: Option<PathBuf>,
+    // The names of the commands that were registered from `commands_dir`, and their executables.
+    dir_commands: Vec<(String, PathBuf)>,
+    // The modification time of `commands_dir` as of its last scan, and when that scan was.
+    commands_dir_scanned: Option<(SystemTime, SystemTime)>,
+    // The directory & prefix in which to look up unrecognized commands, if enabled.
+    external_commands: Option<(PathBuf, &'a str)>,
     state: S,
     pub(crate) terminate: bool,
+    // None implies that we should decide based on whether stdout is a terminal.
+    plain_output: Option<bool>,
+    // None implies that we should detect whether the terminal is dumb.
+    dumb_terminal: Option<bool>,
+    // None implies that we should decide based on the `SHI_SCREEN_READER` environment variable.
+    screen_reader: Option<bool>,
+    // None implies that we should decide based on whether stdin is a terminal.
+    interactive: Option<bool>,
+    tree_style: TreeStyle,
+    deterministic: bool,
+    autocorrect: bool,
+    subcommand_chooser: bool,
+    inline_help: bool,
+    strict: bool,
+    prefix_matching: bool,
+    json_output: bool,
+    // The path of the command that the last line evaluated resolved to, and its arguments, for
+    // `json_result()`.
+    last_invoked: Option<(String, Vec<String>)>,
+    assume_yes: bool,
+    pub(crate) diagnostics: Rc<RefCell<Diagnostics>>,
+    notifier: Notifier,
+    // The input to fill the next line read by `run()` with.
+    pub(crate) next_input: Option<String>,
+    app_info: Option<AppInfo>,
+    help_lookup: Option<HelpLookup>,
+    pub(crate) usage: Rc<RefCell<Usage>>,
+    pub(crate) snippets: Rc<RefCell<Snippets>>,
+    sink: Box<dyn OutputSink>,
+    renderer: Box<dyn Renderer>,
+    abbreviations: Rc<RefCell<Abbreviations>>,
+    pub(crate) aliases: Aliases,
+    // Whether aliases are saved & loaded alongside the history file.
+    persist_aliases: bool,
+    time_format: TimeFormat,
+    // The options registered by the embedder, which are shared with the `set` builtin.
+    options: Rc<RefCell<Settings>>,
+    // The input read in the background for `try_update()`, once it has been started.
+    polled_input: Option<PolledInput>,
+    guards: Vec<GuardFactory>,
+    pre_hooks: Vec<PreHook>,
+    post_hooks: Vec<PostHook>,
+    contexts: Vec<Context<'a, S>>,
+    line_modes: LineModes<S>,
+}
+
+/// Sets up a guard around the execution of the command at the given path. See
+/// `Shell::add_guard()`.
+type GuardFactory = Box<dyn Fn(&str) -> Result<Box<dyn Any>>>;
+
+/// Called with the path and arguments of every command before it executes. See
+/// `Shell::add_pre_hook()`.
+type PreHook = Box<dyn Fn(&str, &[String])>;
+
+/// Called with the path, result and duration of every command after it executes. See
+/// `Shell::add_post_hook()`.
+type PostHook = Box<dyn Fn(&str, &Result<String>, Duration)>;
+
+/// A guard that calls a closure when it is dropped, e.g. to tear down what was set up before a
+/// command was executed. See `Shell::add_guard()`.
+pub struct Teardown {
+    teardown: Option<Box<dyn FnOnce()>>,
+}
+
+impl Teardown {
+    /// Creates a new Teardown.
+    ///
+    /// # Arguments
+    /// `teardown` - The closure to call when the Teardown is dropped.
+    pub fn new<F: FnOnce() + 'static>(teardown: F) -> Teardown {
+        Teardown {
+            teardown: Some(Box::new(teardown)),
+        }
+    }
+}
+
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        if let Some(teardown) = self.teardown.take() {
+            teardown();
+        }
+    }
+}
+
+/// A command that runs on another thread, as set up by `Shell::start_concurrent()`, and what to
+/// tear down once it has finished.
+pub(crate) struct ConcurrentRun {
+    path: String,
+    guards: Vec<Box<dyn Any>>,
+    started: Instant,
+}
+
+/// Input that is read on a background thread, so that it can be polled without blocking.
+struct PolledInput {
+    lines: Receiver<io::Result<String>>,
+    // Whether the prompt has been printed since the last line was received.
+    prompted: bool,
+}
+
+impl PolledInput {
+    /// Starts reading lines from the given reader on a background thread.
+    fn spawn<R: BufRead + Send + 'static>(reader: R) -> PolledInput {
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let failed = line.is_err();
+                if tx.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        PolledInput {
+            lines,
+            prompted: false,
+        }
+    }
+}
+
+/// Maps the path of a command to its help text. See `Shell::set_help_lookup()`.
+type HelpLookup = Rc<dyn Fn(&str) -> Option<String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The verbosity of a `Shell`, i.e., how much it prints beyond the output of commands.
+pub enum Verbosity {
+    /// Only errors are printed. The output of successful commands and farewells are not.
+    Quiet,
+    /// Command output and errors are printed. This is the default.
+    Normal,
+    /// Like `Normal`, but traces of how each line is parsed and dispatched are printed as well.
+    Verbose,
+}
+
+impl Verbosity {
+    /// Returns all the verbosities, in increasing order of verbosity.
+    pub fn all() -> Vec<Verbosity> {
+        vec![Verbosity::Quiet, Verbosity::Normal, Verbosity::Verbose]
+    }
+}
+
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Verbosity::Quiet => "quiet",
+            Verbosity::Normal => "normal",
+            Verbosity::Verbose => "verbose",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = ShiError;
+
+    fn from_str(s: &str) -> Result<Verbosity> {
+        Verbosity::all()
+            .into_iter()
+            .find(|verbosity| verbosity.to_string() == s)
+            .ok_or_else(|| ShiError::InvalidSubCommand {
+                got: s.to_string(),
+                expected: Verbosity::all().iter().map(|v| v.to_string()).collect(),
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reason a `Shell`'s run-loop ended.
+pub enum ExitReason {
+    /// The shell was exited, e.g. via the `exit` builtin.
+    Exit,
+    /// The user pressed CTRL+C.
+    CtrlC,
+    /// The user pressed CTRL+D.
+    CtrlD,
+    /// Reading input failed with the given error.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A summary of a session of a `Shell`'s run-loop, as returned by `Shell::run()`.
+pub struct SessionSummary {
+    /// The number of lines that were evaluated, including those that failed.
+    pub commands_executed: usize,
+    /// The number of lines whose evaluation failed.
+    pub failures: usize,
+    /// How long the session lasted.
+    pub duration: Duration,
+    /// How the session ended.
+    pub exit_reason: ExitReason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Counts describing the size and activity of a `Shell`, as returned by `Shell::metrics()`, e.g.
+/// to tune the performance of very large command trees.
+///
+/// Completion keeps no cache, so there are no cache hits to count. Allocations are not counted
+/// either, since that takes a global allocator, which is for the application to install.
+pub struct Metrics {
+    /// The number of registered commands, counting every command in the tree.
+    pub commands: usize,
+    /// How many of the registered commands were added lazily and have not been constructed yet.
+    /// Their subcommands are not included in `commands`.
+    pub unconstructed_commands: usize,
+    /// The number of builtins.
+    pub builtins: usize,
+    /// The number of entries in the history.
+    pub history_entries: usize,
+    /// The number of evaluations so far, counting each command of a chain separately.
+    pub evaluations: u64,
+    /// How many of those evaluations failed.
+    pub failures: u64,
+    /// The number of outputs kept for the `output` builtin.
+    pub kept_outputs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A candidate for completing a line, as returned by `Shell::complete()`.
+pub struct CompletionCandidate {
+    /// How the candidate should be displayed to the user.
+    pub display: String,
+    /// The text to accept the candidate with, which replaces the line from `start` up to the
+    /// cursor.
+    pub replacement: String,
+    /// The position in the line, in bytes, from which `replacement` replaces it. This is the
+    /// cursor itself, unless the candidate replaces some of what was already typed.
+    pub start: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A problem with a line of a script, as found by `Shell::check_script()`.
+pub struct ScriptProblem {
+    /// The number of the offending line, starting from 1.
+    pub line: usize,
+    /// What is wrong with the line.
+    pub message: String,
+}
+
+impl fmt::Display for ScriptProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What `Shell::run_script()` does when a line of a script fails.
+pub enum OnScriptError {
+    /// The script is stopped, and the failure is returned as an error.
+    Stop,
+    /// The failure is printed and recorded, and the script carries on with the next line.
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Information about the application that embeds a `Shell`, as printed by the `version` builtin.
+pub struct AppInfo {
+    /// The name of the application.
+    pub name: String,
+    /// The version of the application.
+    pub version: String,
+    /// Information about the build of the application, e.g. a commit hash or build date.
+    pub build: Option<String>,
+}
+
+impl AppInfo {
+    /// Creates a new AppInfo, without build information.
+    ///
+    /// # Arguments
+    /// `name` - The name of the application.
+    /// `version` - The version of the application.
+    pub fn new(name: &str, version: &str) -> AppInfo {
+        AppInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            build: None,
+        }
+    }
+
+    /// Sets the build information of the application.
+    ///
+    /// # Arguments
+    /// `build` - Information about the build, e.g. a commit hash or build date.
+    pub fn with_build(mut self, build: &str) -> AppInfo {
+        self.build = Some(build.to_string());
+        self
+    }
+}
+
+/// Runtime information about a `Shell`, for use by commands that introspect it, such as those in
+/// `packs::debug`.
+pub(crate) struct Diagnostics {
+    /// When the shell was constructed.
+    pub started: Instant,
+    /// The message of the last error produced by an evaluation, if any, and the ID of the
+    /// invocation that produced it.
+    pub last_error: Option<(String, String)>,
+    /// How much the shell prints beyond the output of commands.
+    pub verbosity: Verbosity,
+    /// Distinguishes the invocation IDs of this shell from those of other shells and processes.
+    session: u32,
+    /// The number of invocations so far.
+    invocations: u64,
+    /// The number of invocations that failed.
+    failures: u64,
+    /// The outputs of the most recent evaluations, oldest first.
+    outputs: VecDeque<String>,
+    /// How many outputs are kept in `outputs`.
+    output_history_len: usize,
+}
+
+impl Diagnostics {
+    fn new() -> Diagnostics {
+        // This needn't be cryptographically random; it only needs to make collisions between the
+        // invocation IDs of concurrent sessions unlikely.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        // Shells constructed by the same process within the clock's resolution are told apart by
+        // the order they were constructed in.
+        static SHELLS: AtomicU32 = AtomicU32::new(0);
+        let nth = SHELLS.fetch_add(1, Ordering::Relaxed);
+        Diagnostics {
+            started: Instant::now(),
+            last_error: None,
+            verbosity: Verbosity::Normal,
+            session: nanos ^ process::id().rotate_left(16) ^ nth.wrapping_mul(0x9e37_79b9),
+            invocations: 0,
+            failures: 0,
+            outputs: VecDeque::new(),
+            output_history_len: DEFAULT_OUTPUT_HISTORY_LEN,
+        }
+    }
+
+    /// Starts a new invocation, returning its ID.
+    fn next_invocation(&mut self) -> String {
+        self.invocations += 1;
+        self.invocation_id()
+            .expect("no invocation after starting one")
+    }
+
+    /// Returns the ID of the current or, if none is in progress, the most recent invocation.
+    pub fn invocation_id(&self) -> Option<String> {
+        if self.invocations == 0 {
+            return None;
+        }
+
+        Some(format!("{:08x}-{}", self.session, self.invocations))
+    }
+
+    /// Records the output of an evaluation, forgetting the oldest output if too many are kept.
+    fn record_output(&mut self, output: &str) {
+        self.outputs.push_back(output.to_string());
+        while self.outputs.len() > self.output_history_len {
+            self.outputs.pop_front();
+        }
+    }
+
+    /// Returns the output of the `nth` most recent evaluation, where 1 is the most recent one.
+    pub fn output(&self, nth: usize) -> Option<String> {
+        if nth == 0 {
+            return None;
+        }
+
+        self.outputs
+            .len()
+            .checked_sub(nth)
+            .and_then(|i| self.outputs.get(i))
+            .cloned()
+    }
+}
+
+#[derive(Clone)]
+/// A handle through which commands can find out the ID of the invocation they are executing in,
+/// e.g. to tag the output of background work so that it can be correlated with the input line
+/// that started it. See `Shell::invocation_context()`.
+pub struct InvocationContext {
+    diagnostics: Rc<RefCell<Diagnostics>>,
+}
+
+impl InvocationContext {
+    /// Returns the ID of the current invocation or, if none is in progress, the most recent one.
+    /// Returns None if nothing has been evaluated yet.
+    pub fn id(&self) -> Option<String> {
+        self.diagnostics.borrow().invocation_id()
+    }
+
+    /// Returns the output of the `nth` most recent evaluation, where 1 is the most recent one.
+    /// Returns None if that output was never produced or is no longer kept. See
+    /// `Shell::set_output_history_len()`.
+    ///
+    /// # Arguments
+    /// `nth` - How far back to look.
+    pub fn output(&self, nth: usize) -> Option<String> {
+        self.diagnostics.borrow().output(nth)
+    }
 }
 
 impl<'a> Shell<'a, ()> {
@@ -49,23 +489,73 @@ impl<'a> Shell<'a, ()> {
     /// `prompt` - The prompt to display to the user.
     pub fn new(prompt: &'a str) -> Shell<()> {
         let cmds = Rc::new(RefCell::new(CommandSet::new()));
-        let builtins = Rc::new(Shell::build_builtins());
+        let options = Rc::new(RefCell::new(Settings::new()));
+        let builtins = Rc::new(Shell::build_builtins(&options));
+        let usage = Rc::new(RefCell::new(Usage::new()));
+        let snippets = Rc::new(RefCell::new(Snippets::new()));
+        let abbreviations = Rc::new(RefCell::new(Abbreviations::new()));
         Shell {
             prompt,
-            rl: Readline::new(Parser::new(), cmds.clone(), builtins.clone()),
+            rl: Readline::new(
+                Parser::new(),
+                cmds.clone(),
+                builtins.clone(),
+                usage.clone(),
+                snippets.clone(),
+                abbreviations.clone(),
+            ),
             parser: Parser::new(),
             cmds,
             builtins,
             history_file: None,
+            rc_file: None,
+            commands_dir: None,
+            commands_dir_scanned: None,
+            dir_commands: Vec::new(),
+            external_commands: None,
             state: (),
             terminate: false,
+            plain_output: None,
+            dumb_terminal: None,
+            screen_reader: None,
+            interactive: None,
+            tree_style: TreeStyle::default(),
+            deterministic: false,
+            autocorrect: true,
+            subcommand_chooser: false,
+            inline_help: false,
+            strict: false,
+            prefix_matching: false,
+            json_output: false,
+            last_invoked: None,
+            assume_yes: false,
+            diagnostics: Rc::new(RefCell::new(Diagnostics::new())),
+            notifier: Notifier::new(),
+            next_input: None,
+            polled_input: None,
+            guards: Vec::new(),
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
+            contexts: Vec::new(),
+            line_modes: LineModes::new(),
+            aliases: Aliases::new(),
+            persist_aliases: false,
+            time_format: TimeFormat::default(),
+            app_info: None,
+            help_lookup: None,
+            usage,
+            snippets,
+            abbreviations,
+            sink: Box::new(output::Stdout),
+            renderer: Box::new(PlainRenderer),
+            options,
         }
     }
 }
 
 impl<'a, S> Shell<'a, S> {
     /// Constructs the various builtin commands and returns a `CommandSet` of them.
-    fn build_builtins() -> CommandSet<'a, Shell<'a, S>>
+    fn build_builtins(options: &Rc<RefCell<Settings>>) -> CommandSet<'a, Shell<'a, S>>
     where
         S: 'a,
     {
@@ -74,6 +564,37 @@ impl<'a, S> Shell<'a, S> {
         builtins.add(Command::new_leaf(HelpTreeCommand::new()));
         builtins.add(Command::new_leaf(ExitCommand::new()));
         builtins.add(Command::new_leaf(HistoryCommand::new()));
+        builtins.add(Command::new_leaf(SetCommand::new_with_options(
+            options.clone(),
+        )));
+        builtins.add(Command::new_leaf(ShowCommand::new()));
+        builtins.add(Command::new_leaf(ReloadCommand::new()));
+        builtins.add(Command::new_leaf(DemoCommand::new()));
+        builtins.add(Command::new_leaf(TeeCommand::new()));
+        builtins.add(Command::new_leaf(DiffCommand::new()));
+        builtins.add(Command::new_leaf(OutputCommand::new()));
+        builtins.add(Command::new_leaf(RetryCommand::new()));
+        builtins.add(Command::new_leaf(ParallelCommand::new()));
+        builtins.add(Command::new_leaf(GrepCommand::new()));
+        builtins.add(Command::new_leaf(FilterCommand::new()));
+        builtins.add(Command::new_leaf(FieldsCommand::new()));
+        builtins.add(Command::new_leaf(SortCommand::new()));
+        builtins.add(Command::new_leaf(HeadCommand::new()));
+        builtins.add(Command::new_leaf(TailCommand::new()));
+        builtins.add(Command::new_leaf(WatchCommand::new()));
+        builtins.add(Command::new_leaf(SnippetCommand::new()));
+        builtins.add(Command::new_leaf(AliasCommand::new()));
+        builtins.add(Command::new_leaf(MetricsCommand::new()));
+        builtins.add(Command::new_leaf(CompletionsCommand::new()));
+        builtins.add(Command::new_leaf(VersionCommand::new()));
+        #[cfg(feature = "palette")]
+        builtins.add(Command::new_leaf(
+            crate::command::builtin::PaletteCommand::new(),
+        ));
+        #[cfg(feature = "clipboard")]
+        builtins.add(Command::new_leaf(
+            crate::command::builtin::CopyCommand::new(),
+        ));
 
         builtins
     }
@@ -88,19 +609,86 @@ impl<'a, S> Shell<'a, S> {
         S: 'a,
     {
         let cmds = Rc::new(RefCell::new(CommandSet::new()));
-        let builtins = Rc::new(Shell::build_builtins());
+        let options = Rc::new(RefCell::new(Settings::new()));
+        let builtins = Rc::new(Shell::build_builtins(&options));
+        let usage = Rc::new(RefCell::new(Usage::new()));
+        let snippets = Rc::new(RefCell::new(Snippets::new()));
+        let abbreviations = Rc::new(RefCell::new(Abbreviations::new()));
         Shell {
             prompt,
-            rl: Readline::new(Parser::new(), cmds.clone(), builtins.clone()),
+            rl: Readline::new(
+                Parser::new(),
+                cmds.clone(),
+                builtins.clone(),
+                usage.clone(),
+                snippets.clone(),
+                abbreviations.clone(),
+            ),
             parser: Parser::new(),
             cmds,
             builtins,
             history_file: None,
+            rc_file: None,
+            commands_dir: None,
+            commands_dir_scanned: None,
+            dir_commands: Vec::new(),
+            external_commands: None,
             state,
             terminate: false,
+            plain_output: None,
+            dumb_terminal: None,
+            screen_reader: None,
+            interactive: None,
+            tree_style: TreeStyle::default(),
+            deterministic: false,
+            autocorrect: true,
+            subcommand_chooser: false,
+            inline_help: false,
+            strict: false,
+            prefix_matching: false,
+            json_output: false,
+            last_invoked: None,
+            assume_yes: false,
+            diagnostics: Rc::new(RefCell::new(Diagnostics::new())),
+            notifier: Notifier::new(),
+            next_input: None,
+            polled_input: None,
+            guards: Vec::new(),
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
+            contexts: Vec::new(),
+            line_modes: LineModes::new(),
+            aliases: Aliases::new(),
+            persist_aliases: false,
+            time_format: TimeFormat::default(),
+            app_info: None,
+            help_lookup: None,
+            usage,
+            snippets,
+            abbreviations,
+            sink: Box::new(output::Stdout),
+            renderer: Box::new(PlainRenderer),
+            options,
         }
     }
 
+    /// Returns the state of the shell, e.g. for the host program to inspect it between calls to
+    /// `eval()`, `try_update()` or `update_with_timeout()`.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Returns the state of the shell mutably, e.g. for the host program to change it between
+    /// calls to `eval()`, `try_update()` or `update_with_timeout()`.
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    /// Consumes the shell, returning its state, e.g. once the host program is done with the shell.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
     /// Registers the given command under this `Shell`.
     ///
     /// # Arguments
@@ -117,164 +705,4052 @@ impl<'a, S> Shell<'a, S> {
         Ok(())
     }
 
-    // TODO: Should we be doing something similar to `rustyline` where we take `P: Path` or
-    // whatever it is?
-    /// Sets the history file & loads the history from it, if it exists already.
-    ///
-    /// This is necessary to call if one wishes for their command history to persist across
-    /// sessions.
+    /// Registers a command that is only constructed once it is first looked up, e.g. when a line
+    /// invokes or completes it. This lets shells with huge command trees start without building
+    /// every command up front.
     ///
     /// # Arguments
-    /// `history-file` - The path to the history file.
-    pub fn set_and_load_history_file(&mut self, history_file: &'a str) -> Result<()> {
-        self.rl.load_history(history_file)?;
-        self.history_file = Some(history_file);
+    /// `name` - The name of the command. This must be the name of the command that `factory`
+    /// constructs.
+    /// `factory` - Constructs the command.
+    pub fn register_lazy<F>(&mut self, name: &str, factory: F) -> Result<()>
+    where
+        F: FnOnce() -> Command<'a, S> + 'a,
+    {
+        if self.cmds.borrow().contains(name) {
+            return Err(ShiError::AlreadyRegistered {
+                cmd: name.to_string(),
+            });
+        }
+
+        self.cmds.borrow_mut().add_lazy(name, factory);
+
         Ok(())
     }
 
-    /// Saves the history.
+    /// Reserves room for at least `additional` more commands at the top level of the command tree,
+    /// e.g. before registering many generated commands at once.
     ///
-    /// This is effectively a no-op if no history file has been set.
+    /// # Arguments
+    /// `additional` - The number of commands to make room for.
+    pub fn reserve(&mut self, additional: usize) {
+        self.cmds.borrow_mut().reserve(additional);
+    }
+
+    /// Releases any memory that the registered command tree holds beyond what it needs. This is
+    /// worthwhile for large trees that are registered once and never changed afterwards.
+    pub fn shrink_to_fit(&mut self) {
+        self.cmds.borrow_mut().shrink_to_fit();
+    }
+
+    /// Removes the builtins for which `keep` returns false, e.g. those that touch the filesystem
+    /// or the terminal of the process, for shells whose users should not have them. Removed
+    /// builtins are neither run, listed nor completed.
     ///
-    /// This must also be called to actually persist the current session's history. It is necessary
-    /// to persist the history if one wishes to see it in future sessions.
-    pub fn save_history(&mut self) -> Result<()> {
-        if let Some(history_file) = self.history_file {
-            self.rl.save_history(history_file)?;
+    /// # Arguments
+    /// `keep` - Whether to keep the builtin of the given name.
+    pub fn retain_builtins<F: Fn(&str) -> bool>(&mut self, keep: F)
+    where
+        S: 'a,
+    {
+        // The builtins are shared with the completer, so the set is rebuilt rather than changed.
+        let mut builtins = Self::build_builtins(&self.options);
+        for name in builtins.names() {
+            if !keep(&name) || !self.builtins.contains(&name) {
+                builtins.remove(&name);
+            }
         }
-        Ok(())
-    }
 
-    pub(crate) fn parse<'b>(&mut self, line: &'b str) -> Outcome<'b> {
-        self.parser.parse(line, &self.cmds.borrow(), &self.builtins)
+        self.builtins = Rc::new(builtins);
+        self.rl.set_builtins(self.builtins.clone());
     }
 
-    /// Eval executes a single loop of the shell's run-loop.
+    /// Mounts the given commands underneath a parent command named `prefix`.
     ///
-    /// In other words, it takes a single input line and executes on it; `run()` is a loop over
-    /// `eval()`.
+    /// If no command named `prefix` is registered yet, a new `Parent` command is registered for
+    /// it. If a `Parent` command of that name already exists, the given commands are added to its
+    /// children. This makes it possible to share whole subtrees of commands (e.g. a `debug` pack)
+    /// across shells.
+    ///
+    /// Nothing is mounted if any of the commands collide with each other, or with an existing
+    /// child of `prefix`, or if `prefix` is already registered as a `Leaf` command.
     ///
     /// # Arguments
-    /// `line` - The line to evaluate.
-    pub fn eval(&mut self, line: &str) -> Result<String> {
-        self.rl.add_history_entry(line);
-        let outcome = self.parse(line);
+    /// `prefix` - The name of the parent command to mount the commands under.
+    /// `cmds` - The commands to mount.
+    pub fn mount(&mut self, prefix: &'a str, cmds: Vec<Command<'a, S>>) -> Result<()> {
+        let mut root = self.cmds.borrow_mut();
 
-        if !outcome.complete {
-            return Err(outcome
-                .error()
-                .expect("incomplete parse, but failed to produce an error")); // This should never happen.
+        let mut names: Vec<&str> = Vec::with_capacity(cmds.len());
+        for cmd in cmds.iter() {
+            if names.contains(&cmd.name()) {
+                return Err(ShiError::AlreadyRegistered {
+                    cmd: format!("{} {}", prefix, cmd.name()),
+                });
+            }
+            names.push(cmd.name());
         }
 
-        match outcome.cmd_type {
-            CommandType::Custom => {
-                // TODO: This recursive walking through the arguments when we pass this into the
-                // ParentCommand is redundant, since we already did that work when we parsed
-                // things. We should avoid doing this.
-                if let Some(base_cmd_name) = outcome.cmd_path.first() {
-                    if let Some(base_cmd) = self.cmds.borrow().get(base_cmd_name) {
-                        let args: Vec<String> =
-                            line.split(' ').skip(1).map(|s| s.to_string()).collect();
-                        base_cmd.validate_args(&args)?;
-                        return base_cmd.execute(&mut self.state, &args);
-                    }
-                }
-
-                Err(ShiError::UnrecognizedCommand {
-                    got: line.to_string(),
-                })
+        match root.get_mut(prefix) {
+            None => {
+                root.add(Command::new_parent(prefix, cmds));
+                Ok(())
             }
-            CommandType::Builtin => {
-                if let Some(base_cmd_name) = outcome.cmd_path.first() {
-                    if let Some(base_cmd) = self.builtins.clone().get(base_cmd_name) {
-                        let args: Vec<String> =
-                            line.split(' ').skip(1).map(|s| s.to_string()).collect();
-                        base_cmd.validate_args(&args)?;
-                        return base_cmd.execute(self, &args);
-                    }
+            Some(Command::Leaf(_)) => Err(ShiError::AlreadyRegistered {
+                cmd: prefix.to_string(),
+            }),
+            Some(Command::Parent(parent)) => {
+                let sub_cmds = parent.sub_commands_mut();
+                if let Some(name) = names.iter().find(|name| sub_cmds.contains(name)) {
+                    return Err(ShiError::AlreadyRegistered {
+                        cmd: format!("{} {}", prefix, name),
+                    });
                 }
 
-                Err(ShiError::UnrecognizedCommand {
-                    got: line.to_string(),
-                })
+                for cmd in cmds {
+                    sub_cmds.add(cmd);
+                }
+                Ok(())
             }
-            CommandType::Unknown => Err(outcome
-                .error()
-                .expect("parsed an Unknown, but failed to produce an error")), // This should never happen.
         }
     }
 
-    /// Executes the shell's run-loop.
+    /// Mounts the given commands underneath a parent command named `prefix`, like `mount()`, but
+    /// for commands that operate on a projection of the state of the shell, e.g. a field of it.
+    /// This lets modules define commands against their own slice of the state, without knowing
+    /// the full state of the application:
     ///
-    /// This will run indefinitely until the user exits, otherwise terminates the shell or
-    /// process or the shell encounters an error and stops.
+    /// ```
+    /// # use shi::cmd;
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// #[derive(Default)]
+    /// struct Network {
+    ///     connected: bool,
+    /// }
     ///
-    /// Note that invalid command invocations, e.g., nonexistent commands, are not considered fatal
-    /// errors and do _not_ cause a return from this method.
-    pub fn run(&mut self) -> Result<()> {
-        while !self.terminate {
-            let input = self.rl.readline(self.prompt);
+    /// #[derive(Default)]
+    /// struct AppState {
+    ///     network: Network,
+    /// }
+    ///
+    /// let mut shell = Shell::new_with_state("| ", AppState::default());
+    /// shell.mount_with(
+    ///     "net",
+    ///     |s: &mut AppState| &mut s.network,
+    ///     vec![cmd!("connect", |network: &mut Network, _| {
+    ///         network.connected = true;
+    ///         Ok(String::from("connected"))
+    ///     })],
+    /// )?;
+    ///
+    /// shell.eval("net connect")?;
+    /// assert!(shell.state().network.connected);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// `prefix` - The name of the parent command to mount the commands under.
+    /// `project` - Returns the part of the state that the commands operate on.
+    /// `cmds` - The commands to mount.
+    pub fn mount_with<T, F>(
+        &mut self,
+        prefix: &'a str,
+        project: F,
+        cmds: Vec<Command<'a, T>>,
+    ) -> Result<()>
+    where
+        F: Fn(&mut S) -> &mut T + Clone + 'a,
+        S: 'a,
+        T: 'a,
+    {
+        let cmds = cmds
+            .into_iter()
+            .map(|cmd| cmd.map_state(project.clone()))
+            .collect();
+        self.mount(prefix, cmds)
+    }
 
-            match input {
-                Ok(line) => match self.eval(&line) {
-                    Ok(output) => println!("{}", output),
-                    Err(err) => println!("Error: {}", err),
-                },
-                Err(ReadlineError::Interrupted) => {
-                    println!("-> CTRL+C; bye.");
-                    break;
-                }
-                Err(ReadlineError::Eof) => {
-                    println!("-> CTRL+D; bye.");
-                    break;
-                }
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                    break;
-                }
-            }
+    /// Invokes the given closure with a mutable reference to the registered command of the given
+    /// name, returning whatever the closure returns.
+    ///
+    /// This allows tweaking a command at runtime, e.g. updating its help message or swapping it for
+    /// another command of the same name, without having to rebuild the `Shell`. The name of the
+    /// command must not be changed by the closure.
+    ///
+    /// # Arguments
+    /// `name` - The name of the registered command to modify.
+    /// `f` - The closure to invoke with the command.
+    pub fn with_command_mut<F, R>(&mut self, name: &str, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Command<'a, S>) -> R,
+    {
+        match self.cmds.borrow_mut().get_mut(name) {
+            Some(cmd) => Ok(f(cmd)),
+            None => Err(ShiError::UnrecognizedCommand {
+                got: name.to_string(),
+            }),
         }
+    }
 
-        self.save_history()?;
-
-        Ok(())
+    /// Sets whether output should be plain, i.e., stripped of ANSI escape sequences like colors,
+    /// before it is displayed.
+    ///
+    /// By default, output is plain only if stdout is not a terminal, e.g. when it is redirected to
+    /// a file.
+    ///
+    /// # Arguments
+    /// `plain` - Whether output should be plain.
+    pub fn set_plain_output(&mut self, plain: bool) {
+        self.plain_output = Some(plain);
     }
-}
 
-#[cfg(test)]
-pub mod test {
-    use super::*;
+    /// Returns whether output is plain. See `set_plain_output()`.
+    pub fn is_plain_output(&self) -> bool {
+        if self.deterministic {
+            return true;
+        }
 
-    use crate::Result;
-    use crate::{cmd, parent};
+        self.plain_output
+            .unwrap_or_else(|| !io::stdout().is_terminal())
+    }
 
-    use pretty_assertions::assert_eq;
+    /// Sets whether the shell is being served over a dumb terminal.
+    ///
+    /// On dumb terminals, lines are read plainly, without hints, highlighting, completion menus or
+    /// other line editing features that would otherwise garble the display.
+    ///
+    /// By default, this is detected: the terminal is considered dumb if `TERM` is `dumb`, if the
+    /// shell is running inside Emacs, or if stdin is not a terminal, e.g. in CI.
+    ///
+    /// # Arguments
+    /// `dumb` - Whether the terminal is dumb.
+    pub fn set_dumb_terminal(&mut self, dumb: bool) {
+        self.dumb_terminal = Some(dumb);
+    }
 
-    // TODO: Replace or add more tests that trigger the full codepath of the shell.
-    #[test]
-    fn issue6() -> Result<()> {
-        let mut shell = Shell::new("| ");
-        shell.register(parent!(
-            "server",
-            cmd!("listen", "Start listening on the given port", |_, args| {
-                Ok(format!("start: {:?}", args))
-            }),
-            cmd!("unlisten", "stop listening", |_, args| {
-                Ok(format!("stop: {:?}", args))
-            })
-        ))?;
+    /// Returns whether the shell is being served over a dumb terminal. See `set_dumb_terminal()`.
+    pub fn is_dumb_terminal(&self) -> bool {
+        self.dumb_terminal.unwrap_or_else(|| {
+            env::var("TERM").as_deref() == Ok("dumb")
+                || env::var_os("INSIDE_EMACS").is_some()
+                || !io::stdin().is_terminal()
+        })
+    }
 
-        let output = shell.eval("server listen")?;
-        assert_eq!(output, "start: []");
+    /// Sets whether the user can be asked questions, e.g. to confirm running a command. See
+    /// `BaseCommand::confirmation()`.
+    ///
+    /// When the shell is not interactive, commands that ask for confirmation fail without asking,
+    /// unless `set_assume_yes()` is set, and the run-loop does not offer to correct mistyped
+    /// commands or to choose subcommands. This is meant for shells whose input is not typed on the
+    /// terminal of the process, e.g. remote sessions.
+    ///
+    /// By default, the shell is interactive only if stdin is a terminal.
+    ///
+    /// # Arguments
+    /// `interactive` - Whether the user can be asked questions.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = Some(interactive);
+    }
 
-        let output = shell.eval("server listen foo")?;
-        assert_eq!(output, "start: [\"foo\"]");
+    /// Replaces whether the user can be asked questions, returning the setting it replaces, so
+    /// that it can be restored as it was, including when it was left to be detected.
+    pub(crate) fn replace_interactive(&mut self, interactive: Option<bool>) -> Option<bool> {
+        std::mem::replace(&mut self.interactive, interactive)
+    }
 
-        let output = shell.eval("server unlisten")?;
+    /// Returns whether the user can be asked questions. See `set_interactive()`.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+            .unwrap_or_else(|| io::stdin().is_terminal())
+    }
+
+    /// Sets whether the shell's output should be friendly to screen readers.
+    ///
+    /// In screen reader mode, output is plain (see `set_plain_output()`), `helptree` lists commands
+    /// with `COMMAND:` and `SUBCOMMAND:` prefixes instead of drawing a tree, and errors are
+    /// prefixed with `ERROR:`, so that nothing is signaled only by colors or box-drawing
+    /// characters.
+    ///
+    /// By default, this is enabled if the `SHI_SCREEN_READER` environment variable is set to
+    /// anything other than an empty string or `0`.
+    ///
+    /// # Arguments
+    /// `screen_reader` - Whether output should be friendly to screen readers.
+    pub fn set_screen_reader(&mut self, screen_reader: bool) {
+        self.screen_reader = Some(screen_reader);
+    }
+
+    /// Returns whether the shell's output is friendly to screen readers. See
+    /// `set_screen_reader()`.
+    pub fn is_screen_reader(&self) -> bool {
+        self.screen_reader.unwrap_or_else(|| {
+            env::var("SHI_SCREEN_READER")
+                .map(|val| !val.is_empty() && val != "0")
+                .unwrap_or(false)
+        })
+    }
+
+    /// Sets the style in which trees, e.g. the output of `helptree`, are drawn. By default, trees
+    /// are drawn with unicode box-drawing characters.
+    ///
+    /// # Arguments
+    /// `style` - The style in which to draw trees.
+    pub fn set_tree_style(&mut self, style: TreeStyle) {
+        self.tree_style = style;
+    }
+
+    /// Returns the style in which trees are drawn. See `set_tree_style()`.
+    pub fn tree_style(&self) -> TreeStyle {
+        self.tree_style
+    }
+
+    /// Sets whether the shell's output should be deterministic, e.g. for snapshot tests of shells
+    /// built on shi.
+    ///
+    /// In deterministic mode, output is always plain (see `set_plain_output()`), regardless of
+    /// whether stdout is a terminal. `help`, `helptree`, completion candidates and the expected
+    /// commands listed by parse errors are sorted alphabetically, instead of being ordered by
+    /// registration or usage. Together, this makes output byte-stable across platforms and runs.
+    ///
+    /// # Arguments
+    /// `deterministic` - Whether output should be deterministic.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Returns whether the shell's output is deterministic. See `set_deterministic()`.
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Sets whether `run()` offers to run a corrected line when a line fails to parse because of a
+    /// likely typo, e.g. `did you mean 'server listen'? [y/N]`. See `suggest_correction()`.
+    ///
+    /// Corrections are only offered when stdin is a terminal, and are never run without
+    /// confirmation. Still, this can be turned off for safety, e.g. when a shell is scripted via a
+    /// pseudo-terminal. It is on by default.
+    ///
+    /// # Arguments
+    /// `autocorrect` - Whether to offer corrections.
+    pub fn set_autocorrect(&mut self, autocorrect: bool) {
+        self.autocorrect = autocorrect;
+    }
+
+    /// Returns whether `run()` offers corrections. See `set_autocorrect()`.
+    pub fn is_autocorrect(&self) -> bool {
+        self.autocorrect
+    }
+
+    /// Suggests a corrected version of a line that fails to parse, if the token it fails on is a
+    /// likely typo of exactly one of the commands expected in its place.
+    ///
+    /// # Arguments
+    /// `line` - The line to correct.
+    ///
+    /// # Returns
+    /// `Option<String>` - The corrected line, or None if there is no single likely correction.
+    pub fn suggest_correction(&mut self, line: &str) -> Option<String> {
+        let mut outcome = self.parse(line);
+        // A failure on the first token may just as well be a typo of a builtin.
+        if outcome.cmd_path.is_empty() {
+            outcome.possibilities.extend(self.builtins.names());
+        }
+
+        outcome.correction(line)
+    }
+
+    /// Offers to run the correction of a line that failed to parse, if there is one. See
+    /// `suggest_correction()`.
+    ///
+    /// # Arguments
+    /// `line` - The line that failed to parse.
+    /// `prompter` - The prompter with which to ask for confirmation.
+    ///
+    /// # Returns
+    /// `Option<Result<String>>` - The result of the corrected line, or None if it was not run.
+    fn offer_correction<R: BufRead, W: Write>(
+        &mut self,
+        line: &str,
+        prompter: &mut Prompter<R, W>,
+    ) -> Option<Result<String>> {
+        let correction = self.suggest_correction(line)?;
+        let answer = prompter
+            .read_line(&format!("did you mean '{}'? [y/N] ", correction))
+            .ok()?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Some(self.eval(&correction)),
+            _ => None,
+        }
+    }
+
+    /// Sets whether `run()` lets the user interactively choose a subcommand when a line names a
+    /// parent command without any of its subcommands, e.g. just `server`, instead of failing. The
+    /// chosen command is placed on the next input line, for the user to add arguments to and run.
+    ///
+    /// The chooser is only shown when stdin is a terminal. It is off by default.
+    ///
+    /// # Arguments
+    /// `chooser` - Whether to offer the subcommand chooser.
+    pub fn set_subcommand_chooser(&mut self, chooser: bool) {
+        self.subcommand_chooser = chooser;
+    }
+
+    /// Returns whether `run()` offers the subcommand chooser. See `set_subcommand_chooser()`.
+    pub fn is_subcommand_chooser(&self) -> bool {
+        self.subcommand_chooser
+    }
+
+    /// Lets the user choose a subcommand of the parent command that the given line names, level by
+    /// level, until they reach a leaf command.
+    ///
+    /// # Arguments
+    /// `line` - The line naming a parent command.
+    /// `prompter` - The prompter with which to present the choices.
+    ///
+    /// # Returns
+    /// `Result<Option<String>>` - The path of the chosen leaf command, followed by a space, or None
+    /// if the line does not name a parent command on its own.
+    fn choose_subcommand<R: BufRead, W: Write>(
+        &mut self,
+        line: &str,
+        prompter: &mut Prompter<R, W>,
+    ) -> Result<Option<String>> {
+        let outcome = self.parse(line);
+        if outcome.complete || outcome.cmd_path.is_empty() || !outcome.remaining.is_empty() {
+            return Ok(None);
+        }
+
+        let mut path: Vec<String> = outcome.cmd_path.iter().map(|s| s.to_string()).collect();
+        loop {
+            let path_refs: Vec<&str> = path.iter().map(String::as_str).collect();
+            let children = match outcome.cmd_type {
+                CommandType::Builtin => subcommands(&self.builtins, &path_refs),
+                _ => subcommands(&self.cmds.borrow(), &path_refs),
+            };
+            let names: Vec<&String> = children.iter().map(|(name, _)| name).collect();
+            if names.is_empty() {
+                return Ok(None);
+            }
+
+            let label = format!("'{}' has these subcommands:", path.join(" "));
+            let chosen = prompter.select(&label, &names)?.to_string();
+            let is_parent = children
+                .iter()
+                .any(|(name, is_parent)| *name == chosen && *is_parent);
+            path.push(chosen);
+
+            if !is_parent {
+                return Ok(Some(format!("{} ", path.join(" "))));
+            }
+        }
+    }
+
+    /// Sets whether `run()` treats lines ending in `?` as requests for inline help, in the style of
+    /// network device CLIs: instead of running the line, the valid continuations of the line
+    /// before the `?` are listed, and that line is re-displayed for continued editing. See
+    /// `inline_help()`.
+    ///
+    /// This is off by default, since commands may take arguments that end in `?`.
+    ///
+    /// # Arguments
+    /// `inline_help` - Whether lines ending in `?` request inline help.
+    pub fn set_inline_help(&mut self, inline_help: bool) {
+        self.inline_help = inline_help;
+    }
+
+    /// Returns whether lines ending in `?` request inline help. See `set_inline_help()`.
+    pub fn is_inline_help(&self) -> bool {
+        self.inline_help
+    }
+
+    /// Sets a lookup for the help text of commands, e.g. to show translated help in multi-language
+    /// deployments. Wherever the shell shows the help text of a command, it first looks up the
+    /// command's path, e.g. `server listen`, and shows the result instead, if there is one.
+    /// Otherwise, it falls back to the command's own help text.
+    ///
+    /// ```
+    /// # use shi::cmd;
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// let mut shell = Shell::new("| ");
+    /// shell.register(cmd!("greet", "Greets the user", |_, _| Ok(String::from("hi"))))?;
+    /// shell.set_help_lookup(|path| match path {
+    ///     "greet" => Some(String::from("Saluda al usuario")),
+    ///     _ => None,
+    /// });
+    /// assert!(shell.eval("help greet")?.contains("greet - Saluda al usuario"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// `lookup` - Maps the path of a command to its help text, if it has been provided.
+    pub fn set_help_lookup<F>(&mut self, lookup: F)
+    where
+        F: Fn(&str) -> Option<String> + 'static,
+    {
+        self.help_lookup = Some(Rc::new(lookup));
+    }
+
+    /// Generates a completion script for the given host shell from the registered commands, so
+    /// that a program which also accepts commands via argv, e.g. `mytool server listen`, gets them
+    /// completed by the user's login shell. Builtins and hidden commands are not included.
+    ///
+    /// # Arguments
+    /// `kind` - The shell to generate the script for.
+    /// `program` - The name of the program, as it is invoked.
+    ///
+    /// # Returns
+    /// `String` - The completion script, e.g. to be sourced from `~/.bashrc`.
+    pub fn generate_completions(&self, kind: ShellKind, program: &str) -> String {
+        let mut levels = vec![Level {
+            path: Vec::new(),
+            children: Vec::new(),
+        }];
+        // The index into `levels` of each parent command's level, by its path.
+        let mut level_of: HashMap<Vec<String>, usize> = HashMap::new();
+        level_of.insert(Vec::new(), 0);
+        self.cmds.borrow().walk(|visit| {
+            // Hidden commands are not offered, nor is anything beneath them, whose parents then
+            // have no level.
+            if visit.cmd.is_hidden() {
+                return;
+            }
+            let path: Vec<String> = visit.path.iter().map(|name| name.to_string()).collect();
+            let (name, parent_path) = path.split_last().expect("visited an empty path");
+            let parent = match level_of.get(parent_path) {
+                Some(parent) => *parent,
+                None => return,
+            };
+            let help = self.localized_help(visit.path, visit.cmd.help());
+            levels[parent].children.push((name.clone(), help));
+
+            if let Command::Parent(_) = visit.cmd {
+                level_of.insert(path.clone(), levels.len());
+                levels.push(Level {
+                    path,
+                    children: Vec::new(),
+                });
+            }
+        });
+
+        completions::generate(kind, program, &levels)
+    }
+
+    /// Returns the help text to show for the command at the given path, which is the result of the
+    /// lookup set via `set_help_lookup()`, if any, and the given help text of the command
+    /// otherwise.
+    ///
+    /// # Arguments
+    /// `path` - The names of the commands leading to, and including, the command.
+    /// `help` - The help text of the command itself.
+    pub fn localized_help(&self, path: &[&str], help: String) -> String {
+        self.help_lookup
+            .as_ref()
+            .and_then(|lookup| lookup(&path.join(" ")))
+            .unwrap_or(help)
+    }
+
+    /// Sets the key that inserts the last argument of the previous command at the cursor, like
+    /// `Alt-.` in bash. By default, this is `Alt-.` as well, i.e., `KeyEvent::alt('.')`.
+    ///
+    /// # Arguments
+    /// `key` - The key to use, or `None` to disable this.
+    pub fn set_last_arg_key(&mut self, key: Option<KeyEvent>) {
+        self.rl.set_last_arg_key(key);
+    }
+
+    /// Sets whether arguments are validated strictly, i.e., via
+    /// `BaseCommand::validate_args_strict()` rather than `BaseCommand::validate_args()`. In strict
+    /// mode, commands that declare their arguments, e.g. `BasicCommand`s with `ArgSpec`s, reject
+    /// extra arguments and undeclared flags with a usage error instead of ignoring them.
+    ///
+    /// This is off by default.
+    ///
+    /// # Arguments
+    /// `strict` - Whether arguments are validated strictly.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns whether arguments are validated strictly. See `set_strict()`.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets whether commands may be invoked by unambiguous prefixes of their names, e.g. `hist`
+    /// for `history`, or `serv st` for `server status`. A prefix is unambiguous if it is the name
+    /// of a command, or if only one command's name starts with it. Lines with an ambiguous prefix
+    /// fail with an error listing the names it could be. Completion is unaffected.
+    ///
+    /// This is off by default.
+    ///
+    /// # Arguments
+    /// `prefix_matching` - Whether commands may be invoked by prefixes.
+    pub fn set_prefix_matching(&mut self, prefix_matching: bool) {
+        self.prefix_matching = prefix_matching;
+    }
+
+    /// Returns whether commands may be invoked by prefixes. See `set_prefix_matching()`.
+    pub fn is_prefix_matching(&self) -> bool {
+        self.prefix_matching
+    }
+
+    /// Sets whether the results of lines, and their errors, are printed as JSON, one object per
+    /// line, so that the shell can be driven by other programs over a pipe, e.g. behind a `--json`
+    /// flag:
+    ///
+    /// ```no_run
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// let mut shell = Shell::new("| ");
+    /// shell.set_json_output(std::env::args().any(|arg| arg == "--json"));
+    /// shell.run()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Each object has the path of the `command` that the line invoked, its remaining `args`, and a
+    /// `status` of `ok` or `error`, along with the `output` or the `error`, e.g.
+    /// `{"command":"server status","args":["api"],"status":"ok","output":"up"}`. Both go to the
+    /// output of the sink, and are printed regardless of verbosity. The command is the one that
+    /// the line resolved to, after expanding aliases and prefixes, and is the last one that ran if
+    /// the line is a chain or a pipeline.
+    ///
+    /// Everything else that the shell would print, e.g. warnings, traces and the farewell on
+    /// `CTRL+D`, goes to stderr instead, so that the output of the sink is JSON alone.
+    ///
+    /// This is off by default.
+    ///
+    /// # Arguments
+    /// `json_output` - Whether results are printed as JSON.
+    pub fn set_json_output(&mut self, json_output: bool) {
+        self.json_output = json_output;
+    }
+
+    /// Returns whether results are printed as JSON. See `set_json_output()`.
+    pub fn is_json_output(&self) -> bool {
+        self.json_output
+    }
+
+    /// Sets whether commands that ask for confirmation before they run, e.g. destructive ones, run
+    /// without asking, as if the user had answered yes. See `BaseCommand::confirmation()`.
+    ///
+    /// Otherwise, the user is asked on the terminal, and such commands fail if the shell is not
+    /// interactive, e.g. in scripts. See `set_interactive()`. This is off by default.
+    ///
+    /// # Arguments
+    /// `assume_yes` - Whether to run commands without asking for confirmation.
+    pub fn set_assume_yes(&mut self, assume_yes: bool) {
+        self.assume_yes = assume_yes;
+    }
+
+    /// Returns whether commands run without asking for confirmation. See `set_assume_yes()`.
+    pub fn is_assume_yes(&self) -> bool {
+        self.assume_yes
+    }
+
+    /// Asks the user to confirm running the command at the given path, if it asks for
+    /// confirmation. See `set_assume_yes()`.
+    ///
+    /// # Arguments
+    /// `cmd_path` - The path of the command to run.
+    /// `question` - The question that the command asks, if any.
+    ///
+    /// # Returns
+    /// `Result<()>` - An error if the command must not run.
+    fn confirm_run(&self, cmd_path: &[&str], question: Option<String>) -> Result<()> {
+        let question = match question {
+            Some(question) if !self.assume_yes => question,
+            _ => return Ok(()),
+        };
+
+        if !self.is_interactive() {
+            return Err(ShiError::general(format!(
+                "'{}' needs confirmation, but input is not interactive",
+                cmd_path.join(" ")
+            )));
+        }
+
+        ask_confirmation(cmd_path, &question, &mut Prompter::stdio())
+    }
+
+    /// Lists the valid continuations of the given partial line, one per line.
+    ///
+    /// If the line ends in a space, or is empty, the commands that may follow it are listed. If it
+    /// ends in a partially typed command, the commands it may be completed to are listed. If it
+    /// already names a leaf command, the arguments it may be given are listed, if known, followed
+    /// by `<cr>`, denoting that the line may be run as-is.
+    ///
+    /// # Arguments
+    /// `prefix` - The partial line.
+    ///
+    /// # Returns
+    /// `String` - The listing.
+    pub fn inline_help(&mut self, prefix: &str) -> String {
+        let outcome = self.parse(prefix);
+
+        let mut lines = Vec::new();
+        if outcome.complete {
+            let partial = match outcome.remaining.last() {
+                Some(last) if !prefix.ends_with(' ') => last,
+                _ => "",
+            };
+            match outcome.leaf_completion {
+                Some(Completion::Possibilities(_)) if !prefix.ends_with(' ') => {}
+                Some(completion) => {
+                    if let Some((start, candidates)) = completion.span(partial) {
+                        lines.extend(
+                            candidates
+                                .iter()
+                                .map(|candidate| format!("  {}{}", &partial[..start], candidate)),
+                        )
+                    }
+                }
+                None => {}
+            }
+            lines.push(String::from("  <cr>"));
+
+            return lines.join("\n");
+        }
+
+        let partial = match outcome.remaining.as_slice() {
+            [] => "",
+            [partial] if !prefix.ends_with(' ') => partial,
+            _ => {
+                return format!(
+                    "  invalid input: '{}' is not a command",
+                    outcome.remaining[0]
+                )
+            }
+        };
+
+        lines.extend(describe_children(
+            self,
+            &self.cmds.borrow(),
+            &outcome.cmd_path,
+            partial,
+        ));
+        lines.extend(describe_children(
+            self,
+            &self.builtins,
+            &outcome.cmd_path,
+            partial,
+        ));
+        if lines.is_empty() {
+            return format!("  no commands match '{}'", partial);
+        }
+
+        lines.join("\n")
+    }
+
+    /// Expands the first word of the given line, if it is an abbreviation. See
+    /// `add_abbreviation()`.
+    fn expand_abbreviation(&self, line: String) -> String {
+        self.abbreviations.borrow().expand(&line).unwrap_or(line)
+    }
+
+    /// Sets how the shell formats the timestamps it displays, e.g. in the header of `watch`. By
+    /// default, timestamps are ISO-8601 in UTC.
+    ///
+    /// # Arguments
+    /// `format` - The format to use.
+    pub fn set_time_format(&mut self, format: TimeFormat) {
+        self.time_format = format;
+    }
+
+    /// Formats the given timestamp the way the shell formats the timestamps it displays, e.g. for
+    /// timestamps in audit logs or prompts kept by the embedder. See `set_time_format()`.
+    ///
+    /// # Arguments
+    /// `time` - The timestamp to format.
+    pub fn format_time(&self, time: SystemTime) -> String {
+        self.time_format.format(time)
+    }
+
+    /// Sets where the shell writes what it displays, e.g. the output of commands, errors and
+    /// warnings. By default, everything is printed to stdout.
+    ///
+    /// # Arguments
+    /// `sink` - The sink to write to.
+    pub fn set_output_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.sink = sink;
+    }
+
+    /// Replaces the output sink, returning the one it replaces, so that it can be restored.
+    pub(crate) fn replace_output_sink(&mut self, sink: Box<dyn OutputSink>) -> Box<dyn OutputSink> {
+        std::mem::replace(&mut self.sink, sink)
+    }
+
+    /// Sets how the structured output of commands, e.g. tables, is turned into text. By default,
+    /// this is `PlainRenderer`, which lays it out for people. See `BaseCommand::execute_output()`.
+    ///
+    /// # Arguments
+    /// `renderer` - The renderer to render output with, e.g. `JsonRenderer`.
+    pub fn set_renderer(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderer = renderer;
+    }
+
+    /// Renders the given output of a command via the renderer of the shell.
+    ///
+    /// # Arguments
+    /// `output` - The output to render.
+    pub fn render(&self, output: &CommandOutput) -> String {
+        self.renderer.render(output)
+    }
+
+    /// Writes the given output to the output sink, or to stderr if results are printed as JSON.
+    /// See `set_json_output()`.
+    fn write_output(&mut self, output: &str) {
+        if self.json_output {
+            eprintln!("{}", output);
+        } else {
+            self.sink.write_output(output);
+        }
+    }
+
+    /// Writes the given prompt to the output sink, or to stderr if results are printed as JSON.
+    /// See `set_json_output()`.
+    fn write_prompt(&mut self, prompt: &str) {
+        if self.json_output {
+            eprint!("{}", prompt);
+        } else {
+            self.sink.write_prompt(prompt);
+        }
+    }
+
+    /// Writes the given error or warning to the output sink, or to stderr if results are printed
+    /// as JSON. See `set_json_output()`.
+    pub(crate) fn write_error(&mut self, error: &str) {
+        if self.json_output {
+            eprintln!("{}", error);
+        } else {
+            self.sink.write_error(error);
+        }
+    }
+
+    /// Prints the result of evaluating a line, as the run-loop does.
+    fn print_result(&mut self, line: &str, res: &Result<String>) {
+        if self.json_output {
+            let json = self.json_result(line, res);
+            self.sink.write_output(&json);
+            return;
+        }
+
+        match res {
+            Ok(output) => {
+                if self.verbosity() != Verbosity::Quiet {
+                    let output = self.format_output(output);
+                    self.write_output(&output);
+                }
+            }
+            Err(err) => {
+                let error = format!(
+                    "{} {}",
+                    self.error_prefix(),
+                    self.format_output(&err.to_string())
+                );
+                self.write_error(&error);
+            }
+        }
+    }
+
+    /// Returns the result of evaluating the given line as a JSON object. See `set_json_output()`.
+    fn json_result(&mut self, line: &str, res: &Result<String>) -> String {
+        // The command is the one that the line resolved to, or its first word, if it resolved to
+        // none, e.g. if it is a typo.
+        let (command, args) = match self.last_invoked.take() {
+            Some(invoked) => invoked,
+            None => {
+                let outcome = self.parse(line);
+                match outcome.remaining.split_first() {
+                    Some((first, rest)) => (
+                        first.to_string(),
+                        rest.iter().map(|arg| arg.to_string()).collect(),
+                    ),
+                    None => (String::new(), Vec::new()),
+                }
+            }
+        };
+
+        let args: Vec<String> = args.iter().map(|arg| output::json_string(arg)).collect();
+        let (status, key, value) = match res {
+            Ok(output) => ("ok", "output", output::strip_ansi(output)),
+            Err(err) => ("error", "error", err.to_string()),
+        };
+
+        format!(
+            "{{\"command\":{},\"args\":[{}],\"status\":\"{}\",\"{}\":{}}}",
+            output::json_string(&command),
+            args.join(","),
+            status,
+            key,
+            output::json_string(&value)
+        )
+    }
+
+    /// Returns the prefix with which errors are displayed.
+    pub(crate) fn error_prefix(&self) -> &'static str {
+        if self.is_screen_reader() {
+            "ERROR:"
+        } else {
+            "Error:"
+        }
+    }
+
+    /// Returns a handle to the shell's `Notifier`, for commands to publish events through. See
+    /// `events`.
+    pub fn notifier(&self) -> Notifier {
+        self.notifier.clone()
+    }
+
+    /// Returns a handle for commands to enter line modes through, e.g. for a query console. See
+    /// `line_mode`.
+    pub fn line_modes(&self) -> LineModes<S> {
+        self.line_modes.clone()
+    }
+
+    /// Subscribes the given callback to an event published by a command. Events are delivered
+    /// after the line that published them has been evaluated. See `events`.
+    ///
+    /// # Arguments
+    /// `event` - The name of the event to subscribe to, or `*` for every event.
+    /// `callback` - The callback to call when the event is published.
+    pub fn subscribe<F: FnMut(&str) + 'static>(&mut self, event: &str, callback: F) {
+        self.notifier.subscribe(event, callback);
+    }
+
+    /// Adds a guard around the execution of every command, e.g. to hold a lock, or to flush
+    /// buffers once the command is done.
+    ///
+    /// The given closure is called with the path of the command, e.g. `server listen`, right before
+    /// the command executes. What it returns is dropped once the command has finished executing,
+    /// whether it succeeded or not, so a value whose `Drop` does the teardown, e.g. a lock guard or
+    /// a `Teardown`, is guaranteed to run. Guards are set up in the order they were added, and are
+    /// dropped in the reverse order.
+    ///
+    /// # Arguments
+    /// `setup` - The closure that sets up the guard.
+    pub fn add_guard<F, G>(&mut self, setup: F)
+    where
+        F: Fn(&str) -> G + 'static,
+        G: 'static,
+    {
+        self.guards.push(Box::new(move |path| {
+            Ok(Box::new(setup(path)) as Box<dyn Any>)
+        }));
+    }
+
+    /// Like `add_guard()`, but the guard may refuse to let the command execute, e.g. because a
+    /// lock it needs is taken, by returning an error. The command then fails with that error, and
+    /// the guards set up before it are torn down.
+    ///
+    /// # Arguments
+    /// `setup` - The closure that sets up the guard.
+    pub fn add_fallible_guard<F, G>(&mut self, setup: F)
+    where
+        F: Fn(&str) -> Result<G> + 'static,
+        G: 'static,
+    {
+        self.guards.push(Box::new(move |path| {
+            Ok(Box::new(setup(path)?) as Box<dyn Any>)
+        }));
+    }
+
+    /// Sets up the guards for executing the command at the given path. Dropping the returned
+    /// guards tears them down.
+    fn set_up_guards(&self, path: &str) -> Result<Vec<Box<dyn Any>>> {
+        let mut guards: Vec<Box<dyn Any>> = Vec::with_capacity(self.guards.len());
+        for setup in &self.guards {
+            match setup(path) {
+                Ok(guard) => guards.push(guard),
+                Err(err) => {
+                    // The guards set up so far are torn down, last first.
+                    while guards.pop().is_some() {}
+                    return Err(err);
+                }
+            }
+        }
+        // Vectors drop their elements front to back, but the guards set up last should be torn
+        // down first.
+        guards.reverse();
+        Ok(guards)
+    }
+
+    /// Adds a hook that is called before every command executes, e.g. for logging or auditing.
+    ///
+    /// The given closure is called with the path of the command, e.g. `server listen`, and its
+    /// arguments, once the command has been validated and, if needed, confirmed. Hooks are called
+    /// in the order they were added, before any guards are set up. See `add_guard()`.
+    ///
+    /// # Arguments
+    /// `hook` - The closure to call.
+    pub fn add_pre_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&str, &[String]) + 'static,
+    {
+        self.pre_hooks.push(Box::new(hook));
+    }
+
+    /// Adds a hook that is called after every command executes, e.g. for logging or metrics.
+    ///
+    /// The given closure is called with the path of the command, e.g. `server listen`, its result
+    /// as it is printed, and how long it took to execute, whether it succeeded or not. Hooks are
+    /// called in the order they were added, after any guards have been torn down.
+    ///
+    /// ```
+    /// # use std::cell::RefCell;
+    /// # use std::rc::Rc;
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// let mut shell = Shell::new("| ");
+    /// shell.register(shi::cmd!("ping", |_, _| Ok(String::from("pong"))))?;
+    ///
+    /// let log = Rc::new(RefCell::new(Vec::new()));
+    /// let audit = log.clone();
+    /// shell.add_post_hook(move |path, res, _| {
+    ///     let status = if res.is_ok() { "ok" } else { "failed" };
+    ///     audit.borrow_mut().push(format!("{}: {}", path, status));
+    /// });
+    ///
+    /// shell.eval("ping")?;
+    /// assert_eq!(*log.borrow(), vec!["ping: ok"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// `hook` - The closure to call.
+    pub fn add_post_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&str, &Result<String>, Duration) + 'static,
+    {
+        self.post_hooks.push(Box::new(hook));
+    }
+
+    /// Calls the pre hooks for executing the command at the given path, returning when the
+    /// command started, for the post hooks.
+    fn run_pre_hooks(&self, path: &str, args: &[&str]) -> Instant {
+        if !self.pre_hooks.is_empty() {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            for hook in &self.pre_hooks {
+                hook(path, &args);
+            }
+        }
+
+        Instant::now()
+    }
+
+    /// Calls the post hooks for the command at the given path, which started at `started`.
+    fn run_post_hooks(&self, path: &str, res: &Result<String>, started: Instant) {
+        let elapsed = started.elapsed();
+        for hook in &self.post_hooks {
+            hook(path, res, elapsed);
+        }
+    }
+
+    /// Returns the ID of the current invocation, i.e. evaluation of a line, or, if none is in
+    /// progress, the most recent one. Returns None if nothing has been evaluated yet.
+    ///
+    /// Every call to `eval()` is a new invocation with a unique ID.
+    pub fn invocation_id(&self) -> Option<String> {
+        self.diagnostics.borrow().invocation_id()
+    }
+
+    /// Returns a handle for commands to find out the ID of the invocation they are executing in.
+    /// See `invocation_id()`.
+    pub fn invocation_context(&self) -> InvocationContext {
+        InvocationContext {
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+
+    /// Sets the verbosity of the shell. See `Verbosity`.
+    ///
+    /// # Arguments
+    /// `verbosity` - The verbosity to use.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.diagnostics.borrow_mut().verbosity = verbosity;
+    }
+
+    /// Returns the verbosity of the shell.
+    pub fn verbosity(&self) -> Verbosity {
+        self.diagnostics.borrow().verbosity
+    }
+
+    /// Returns counts describing the size and activity of the shell. See `Metrics`.
+    pub fn metrics(&self) -> Metrics {
+        let (commands, unconstructed_commands) = self.cmds.borrow().count();
+        let diagnostics = self.diagnostics.borrow();
+        Metrics {
+            commands,
+            unconstructed_commands,
+            builtins: self.builtins.len(),
+            history_entries: self.history_len(),
+            evaluations: diagnostics.invocations,
+            failures: diagnostics.failures,
+            kept_outputs: diagnostics.outputs.len(),
+        }
+    }
+
+    /// Sets how many outputs of previous evaluations are kept for the `output` builtin and
+    /// `InvocationContext::output()`. Setting it to 0 disables the keeping of outputs. Defaults to
+    /// 10.
+    ///
+    /// # Arguments
+    /// `len` - The number of outputs to keep.
+    pub fn set_output_history_len(&mut self, len: usize) {
+        let mut diagnostics = self.diagnostics.borrow_mut();
+        diagnostics.output_history_len = len;
+        while diagnostics.outputs.len() > len {
+            diagnostics.outputs.pop_front();
+        }
+    }
+
+    /// Returns the output of the `nth` most recent evaluation, where 1 is the most recent one.
+    /// Returns None if that output was never produced or is no longer kept.
+    ///
+    /// # Arguments
+    /// `nth` - How far back to look.
+    pub fn output(&self, nth: usize) -> Option<String> {
+        self.diagnostics.borrow().output(nth)
+    }
+
+    /// Returns the registry of options that the embedder can register its own options with, e.g.
+    /// a `dry-run` flag. Registered options can be changed by users with the `set` builtin and are
+    /// listed, after the options of the shell itself, by `show options`.
+    pub fn options(&self) -> Rc<RefCell<Settings>> {
+        self.options.clone()
+    }
+
+    /// Post-processes the given output so that it is ready for display.
+    pub(crate) fn format_output(&self, output: &str) -> String {
+        if self.is_plain_output() || self.is_screen_reader() {
+            output::strip_ansi(output)
+        } else {
+            output.to_string()
+        }
+    }
+
+    // TODO: Should we be doing something similar to `rustyline` where we take `P: Path` or
+    // whatever it is?
+    /// Sets the history file & loads the history from it, if it exists already.
+    ///
+    /// This is necessary to call if one wishes for their command history to persist across
+    /// sessions. How often each command is used is persisted alongside it, in a file of the same
+    /// path with a `.usage` suffix, and is used to order completion candidates. Aliases are too,
+    /// in a file with an `.aliases` suffix, if enabled via `set_persist_aliases()`.
+    ///
+    /// # Arguments
+    /// `history-file` - The path to the history file.
+    pub fn set_and_load_history_file(&mut self, history_file: &'a str) -> Result<()> {
+        self.rl.load_history(history_file)?;
+        self.usage
+            .borrow_mut()
+            .load(Shell::<S>::usage_file(history_file))?;
+        if self.persist_aliases {
+            self.aliases.load(Shell::<S>::aliases_file(history_file))?;
+        }
+        self.history_file = Some(history_file);
+        Ok(())
+    }
+
+    /// Returns the path of the usage file kept alongside the given history file.
+    fn usage_file(history_file: &str) -> String {
+        format!("{}.usage", history_file)
+    }
+
+    /// Returns the path of the aliases file kept alongside the given history file.
+    fn aliases_file(history_file: &str) -> String {
+        format!("{}.aliases", history_file)
+    }
+
+    /// Sets whether aliases are persisted across sessions, alongside the history file. If a
+    /// history file is already set, the aliases saved with it are loaded right away. Otherwise,
+    /// they are loaded once one is set via `set_and_load_history_file()`.
+    ///
+    /// Aliases are saved whenever the history is, see `save_history()`.
+    ///
+    /// # Arguments
+    /// `persist` - Whether to persist aliases.
+    pub fn set_persist_aliases(&mut self, persist: bool) -> Result<()> {
+        if persist && !self.persist_aliases {
+            if let Some(history_file) = self.history_file {
+                self.aliases.load(Shell::<S>::aliases_file(history_file))?;
+            }
+        }
+        self.persist_aliases = persist;
+
+        Ok(())
+    }
+
+    /// Saves the history.
+    ///
+    /// This is effectively a no-op if no history file has been set.
+    ///
+    /// This must also be called to actually persist the current session's history. It is necessary
+    /// to persist the history if one wishes to see it in future sessions.
+    pub fn save_history(&mut self) -> Result<()> {
+        if let Some(history_file) = self.history_file {
+            self.rl.save_history(history_file)?;
+            self.usage
+                .borrow()
+                .save(Shell::<S>::usage_file(history_file))?;
+            if self.persist_aliases {
+                // The aliases of active contexts are not the user's, so they are not persisted.
+                let mut aliases = self.aliases.clone();
+                for context in self.contexts.iter().rev() {
+                    let mounted = match context.mounted() {
+                        Some(mounted) => mounted,
+                        None => continue,
+                    };
+                    for (name, shadowed) in mounted.aliases.iter().rev() {
+                        aliases.restore(name, shadowed.as_deref());
+                    }
+                }
+                aliases.save(Shell::<S>::aliases_file(history_file))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the rc file & evaluates it.
+    ///
+    /// An rc file is a file of commands, one per line, that configure the shell, e.g.
+    /// `set verbosity quiet`. Blank lines and lines starting with `#` are ignored. Evaluated lines
+    /// are not added to the history.
+    ///
+    /// Evaluation stops at the first line that fails, and its error is returned.
+    ///
+    /// # Arguments
+    /// `rc_file` - The path to the rc file.
+    pub fn set_and_load_rc_file(&mut self, rc_file: &'a str) -> Result<()> {
+        self.rc_file = Some(rc_file);
+        self.load_rc_file(rc_file)
+    }
+
+    fn load_rc_file(&mut self, rc_file: &str) -> Result<()> {
+        let contents = fs::read_to_string(rc_file).map_err(|err| {
+            ShiError::general(format!("failed to read rc file '{}': {}", rc_file, err))
+        })?;
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Err(err) = self.eval_in_mode(line) {
+                return Err(ShiError::general(format!("{}:{}: {}", rc_file, i + 1, err)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the script at the given path without running it, returning every problem found.
+    ///
+    /// Like an rc file, a script has one command per line, and blank lines and lines starting with
+    /// `#` are skipped. Each remaining line is checked for unbalanced quotes, unknown commands and
+    /// invalid arguments, as far as the arguments are validated by the commands.
+    ///
+    /// # Arguments
+    /// `path` - The path to the script.
+    ///
+    /// # Returns
+    /// `Result<Vec<ScriptProblem>>` - The problems, in order of their lines. An error if the script
+    /// could not be read.
+    pub fn check_script<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<ScriptProblem>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| {
+            ShiError::general(format!(
+                "failed to read script '{}': {}",
+                path.display(),
+                err
+            ))
+        })?;
+
+        let mut problems = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Err(err) = self.check_line(line) {
+                problems.push(ScriptProblem {
+                    line: i + 1,
+                    message: err.to_string(),
+                });
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Implements a `--check` mode for embedders: if the given command-line arguments start with
+    /// `--check`, every script that follows it is checked via `check_script()` and its problems
+    /// are printed to stderr as `path:line: message`.
+    ///
+    /// ```no_run
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// let mut shell = Shell::new("| ");
+    /// if let Some(ok) = shell.check_mode(std::env::args().skip(1))? {
+    ///     std::process::exit(if ok { 0 } else { 1 });
+    /// }
+    /// shell.run()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// `args` - The command-line arguments, without the name of the program.
+    ///
+    /// # Returns
+    /// `Result<Option<bool>>` - `None` if the arguments don't ask for `--check`, otherwise whether
+    /// every script was free of problems. An error if a script could not be read.
+    pub fn check_mode<I, T>(&mut self, args: I) -> Result<Option<bool>>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut args = args.into_iter();
+        match args.next() {
+            Some(flag) if flag.as_ref() == "--check" => {}
+            _ => return Ok(None),
+        }
+
+        let mut ok = true;
+        for path in args {
+            let path = path.as_ref();
+            for problem in self.check_script(path)? {
+                eprintln!("{}:{}", path, problem);
+                ok = false;
+            }
+        }
+
+        Ok(Some(ok))
+    }
+
+    /// Runs the script at the given path, e.g. for setup scripts or non-interactive batch jobs.
+    ///
+    /// Like an rc file, a script has one command per line, and blank lines and lines starting with
+    /// `#` are skipped. Each remaining line is evaluated in order, without being added to the
+    /// history, and its result is printed as the run-loop would print it. While a line mode is
+    /// active, lines go to its handler, as for `eval()`. The script ends early if the shell is
+    /// exited, e.g. via the `exit` builtin.
+    ///
+    /// # Arguments
+    /// `path` - The path to the script.
+    /// `on_error` - Whether to stop or continue when a line fails.
+    ///
+    /// # Returns
+    /// `Result<Vec<ScriptProblem>>` - The lines that failed, in order, when continuing on errors.
+    /// An error if the script could not be read, or, when stopping on errors, for the first line
+    /// that failed.
+    pub fn run_script<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        on_error: OnScriptError,
+    ) -> Result<Vec<ScriptProblem>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| {
+            ShiError::general(format!(
+                "failed to read script '{}': {}",
+                path.display(),
+                err
+            ))
+        })?;
+
+        let mut problems = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let res = self.eval_in_mode(line);
+            if let Err(err) = &res {
+                if on_error == OnScriptError::Stop {
+                    return Err(ShiError::general(format!(
+                        "{}:{}: {}",
+                        path.display(),
+                        i + 1,
+                        err
+                    )));
+                }
+                problems.push(ScriptProblem {
+                    line: i + 1,
+                    message: err.to_string(),
+                });
+            }
+            self.print_result(line, &res);
+
+            if self.terminate {
+                break;
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Validates the arguments to the given command, strictly if the shell is in strict mode.
+    fn validate<C: BaseCommand + ?Sized>(&self, cmd: &C, args: &[String]) -> Result<()> {
+        if self.strict {
+            cmd.validate_args_strict(args)
+        } else {
+            cmd.validate_args(args)
+        }
+    }
+
+    /// Checks that the given line would parse & pass validation, without executing it.
+    fn check_line(&mut self, line: &str) -> Result<()> {
+        if let Some(quote) = unbalanced_quote(line) {
+            return Err(ShiError::general(format!("unbalanced quote ({})", quote)));
+        }
+
+        for (segment, _) in self.parser.split_chain(line) {
+            for stage in self.parser.split_pipeline(segment) {
+                self.check_command(stage)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks a single command of a line, see `check_line()`.
+    fn check_command(&mut self, line: &str) -> Result<()> {
+        let outcome = self.parse(line);
+        if outcome.cmd_path.is_empty() {
+            if let Some(name) = outcome.remaining.first() {
+                if self.find_external_command(name).is_some() {
+                    return Ok(());
+                }
+            }
+        }
+
+        if !outcome.complete {
+            return Err(outcome
+                .error()
+                .expect("incomplete parse, but failed to produce an error")); // This should never happen.
+        }
+
+        let args = self.parser.args(line);
+        let name = outcome.cmd_path.first().copied().unwrap_or_default();
+        let res = match outcome.cmd_type {
+            CommandType::Custom => self
+                .cmds
+                .borrow()
+                .get(name)
+                .map(|cmd| self.validate(cmd, &args)),
+            CommandType::Builtin => self.builtins.get(name).map(|cmd| self.validate(cmd, &args)),
+            CommandType::Unknown => None,
+        };
+
+        res.unwrap_or_else(|| {
+            Err(ShiError::UnrecognizedCommand {
+                got: line.to_string(),
+            })
+        })
+    }
+
+    /// Enables looking up unrecognized commands as external executables.
+    ///
+    /// When enabled, a line whose first word, `<name>`, is not a registered command is executed via
+    /// the executable named `<prefix>-<name>` in `dir`, if one exists, similar to how `git` finds
+    /// its subcommands. The remaining words of the line are passed to it as arguments, and its
+    /// stdout becomes the output. See `ExternalCommand`.
+    ///
+    /// # Arguments
+    /// `dir` - The directory to look up executables in.
+    /// `prefix` - The prefix of the names of the executables.
+    pub fn set_external_commands<P: AsRef<Path>>(&mut self, dir: P, prefix: &'a str) {
+        self.external_commands = Some((dir.as_ref().to_path_buf(), prefix));
+    }
+
+    /// Finds the external executable for the given command name, if external commands are enabled
+    /// and such an executable exists.
+    fn find_external_command(&self, name: &str) -> Option<ExternalCommand<S>> {
+        let (dir, prefix) = self.external_commands.as_ref()?;
+
+        // Don't let the name escape the directory.
+        if name.is_empty() || name.contains(std::path::is_separator) || name.starts_with('.') {
+            return None;
+        }
+
+        let path = dir.join(format!("{}-{}", prefix, name));
+        if !path.is_file() {
+            return None;
+        }
+
+        Some(ExternalCommand::new(name, path))
+    }
+
+    /// Returns the external executable that the given line would run, along with its arguments, if
+    /// the line invokes an external command rather than one that runs in the shell's process.
+    ///
+    /// # Arguments
+    /// `line` - The line to resolve.
+    pub(crate) fn external_invocation(
+        &mut self,
+        line: &str,
+    ) -> Option<(ExternalCommand<S>, Vec<String>)> {
+        let outcome = self.parse(line);
+        if outcome.cmd_path.is_empty() {
+            let (name, args) = outcome.remaining.split_first()?;
+            let external_cmd = self.find_external_command(name)?;
+            return Some((external_cmd, args.iter().map(|s| s.to_string()).collect()));
+        }
+
+        if outcome.cmd_type != CommandType::Custom || outcome.cmd_path.len() != 1 {
+            return None;
+        }
+        let (name, path) = self
+            .dir_commands
+            .iter()
+            .find(|(name, _)| name == outcome.cmd_path[0])?;
+        let args = self.parser.args(line);
+
+        Some((ExternalCommand::new(name, path), args))
+    }
+
+    /// Returns the entries of the history, from oldest to newest.
+    ///
+    /// Repeated, subsequent lines are a single entry. This includes the lines of prior sessions if
+    /// a history file was loaded via `set_and_load_history_file()`.
+    pub fn history_entries(&self) -> impl Iterator<Item = String> + '_ {
+        self.rl.history().iter().map(|entry| entry.to_string())
+    }
+
+    /// Returns the number of entries in the history.
+    pub fn history_len(&self) -> usize {
+        self.rl.history().len()
+    }
+
+    /// Adds an entry to the history, as if it were a line that was evaluated.
+    ///
+    /// # Arguments
+    /// `line` - The line to add.
+    ///
+    /// # Returns
+    /// `bool` - Whether the line was added. Empty lines and repeats of the newest entry are not.
+    pub fn add_history_entry(&mut self, line: &str) -> bool {
+        self.rl.add_history_entry(line)
+    }
+
+    /// Clears the history. This does not affect the history file until it is saved.
+    pub fn clear_history(&mut self) {
+        self.rl.clear_history();
+    }
+
+    /// Offers the candidates for completing the given line that tab completion would offer, e.g.
+    /// for frontends other than the terminal.
+    ///
+    /// Only the portion of the line before the cursor is considered.
+    ///
+    /// # Arguments
+    /// `line` - The line to complete.
+    /// `pos` - The position of the cursor on that line, in bytes.
+    ///
+    /// # Returns
+    /// `Vec<CompletionCandidate>` - The candidates.
+    pub fn complete(&self, line: &str, pos: usize) -> Vec<CompletionCandidate> {
+        let (start, pairs) = self.rl.complete(line, pos);
+        let mut candidates: Vec<CompletionCandidate> = pairs
+            .into_iter()
+            .map(|pair| CompletionCandidate {
+                display: pair.display,
+                replacement: pair.replacement,
+                start,
+            })
+            .collect();
+        if self.deterministic {
+            candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        }
+
+        candidates
+    }
+
+    /// Offers the hint that would be shown while typing the given line, e.g. for frontends other
+    /// than the terminal. Hints are suggested from the history.
+    ///
+    /// # Arguments
+    /// `line` - The line to offer a hint for.
+    /// `pos` - The position of the cursor on that line, in bytes. Hints are only offered when the
+    /// cursor is at the end of the line.
+    ///
+    /// # Returns
+    /// `Option<String>` - The hint, which is the text that would follow `line`, if any.
+    pub fn hint(&self, line: &str, pos: usize) -> Option<String> {
+        self.rl.hint(line, pos)
+    }
+
+    /// Sets the information about the application embedding this shell, which the `version`
+    /// builtin prints alongside the version of shi.
+    ///
+    /// # Arguments
+    /// `info` - The information about the application.
+    pub fn set_app_info(&mut self, info: AppInfo) {
+        self.app_info = Some(info);
+    }
+
+    /// Returns the information about the application embedding this shell, if it was set. See
+    /// `set_app_info()`.
+    pub fn app_info(&self) -> Option<&AppInfo> {
+        self.app_info.as_ref()
+    }
+
+    /// Sets the input that the next line read by `run()` is initially filled with, for the user
+    /// to edit or complete before submitting it.
+    ///
+    /// # Arguments
+    /// `input` - The input to fill the next line with.
+    pub fn set_next_input(&mut self, input: &str) {
+        self.next_input = Some(input.to_string());
+    }
+
+    /// Makes the shell tokenize commands with the given tokenizer, e.g. one that understands the
+    /// syntax of a domain, like `key=value` tokens. The tokens of a line are what its command path
+    /// and arguments are made of. Lines are still split into chains and pipelines as usual before
+    /// they are tokenized.
+    ///
+    /// Tokens must be slices of the line they were tokenized from.
+    ///
+    /// # Arguments
+    /// `tokenizer` - The tokenizer to use.
+    pub fn with_tokenizer(mut self, tokenizer: Box<dyn Tokenizer>) -> Self {
+        self.parser = Parser::new_with_tokenizer(tokenizer);
+        self.rl.set_parser(self.parser.clone());
+        self
+    }
+
+    /// Sets a closure that observes how the line being typed parses, e.g. for a TUI to render the
+    /// path of the command that the line resolves to as breadcrumbs while the user types.
+    ///
+    /// The closure is called with the command being typed and its parse whenever the line is
+    /// completed, hinted or submitted, i.e. on every keystroke when reading lines in `run()`, and
+    /// on every call to `complete()` or `hint()`. The command being typed is the line up to the
+    /// cursor, or the last command of it if it is a chain or pipeline, e.g. `server li` of
+    /// `build && server li`. It is called at most once per change of the line, however often the
+    /// line is parsed in between. Keystrokes are throttled, so that lines hinted within 50ms of the
+    /// last observation are skipped; see `set_line_observer_interval()`.
+    ///
+    /// ```
+    /// # use std::cell::RefCell;
+    /// # use std::rc::Rc;
+    /// # use shi::shell::Shell;
+    /// # fn main() -> shi::Result<()> {
+    /// let mut shell = Shell::new("| ");
+    /// shell.register(shi::parent!(
+    ///     "server",
+    ///     shi::cmd!("listen", |_, _| Ok(String::new())),
+    /// ))?;
+    ///
+    /// let breadcrumbs = Rc::new(RefCell::new(String::new()));
+    /// let rendered = breadcrumbs.clone();
+    /// shell.set_line_observer(move |_, outcome| {
+    ///     *rendered.borrow_mut() = outcome.cmd_path.join(" > ");
+    /// });
+    ///
+    /// shell.hint("server listen 80", 16);
+    /// assert_eq!(*breadcrumbs.borrow(), "server > listen");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// `observer` - The closure to call with the line and its parse.
+    pub fn set_line_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&str, &Outcome<'_>) + 'static,
+    {
+        self.rl.set_line_observer(Box::new(observer));
+    }
+
+    /// Sets the least time between two observations of the line observer on keystrokes, i.e. when
+    /// lines are hinted. Completing or submitting a line is never throttled. Defaults to 50ms. See
+    /// `set_line_observer()`.
+    ///
+    /// # Arguments
+    /// `interval` - The least time between two observations.
+    pub fn set_line_observer_interval(&mut self, interval: Duration) {
+        self.rl.set_line_observer_interval(interval);
+    }
+
+    /// Adds an abbreviation, i.e., a word that expands into longer text when it is the first word
+    /// of a line, e.g. `gs` into `server status`. Unlike an alias, the expanded line is what lands
+    /// in the history. Abbreviations are expanded when Tab is pressed after them, and when the line
+    /// is submitted. Any existing abbreviation of the same word is replaced.
+    ///
+    /// # Arguments
+    /// `word` - The word to expand.
+    /// `expansion` - The text to expand it into.
+    pub fn add_abbreviation(&mut self, word: &str, expansion: &str) {
+        self.abbreviations.borrow_mut().add(word, expansion);
+    }
+
+    /// Adds an alias, i.e., a shortcut for a command, e.g. `ll` for `list --long`, so that
+    /// `ll /tmp` runs `list --long /tmp`. Aliases are resolved when a line is evaluated, before
+    /// the command it invokes is looked up. End users can also manage aliases via the `alias`
+    /// builtin. Any existing alias of the same name is replaced.
+    ///
+    /// # Arguments
+    /// `name` - The name of the alias.
+    /// `expansion` - The text that the alias stands for.
+    pub fn add_alias(&mut self, name: &str, expansion: &str) {
+        self.aliases.add(name, expansion);
+    }
+
+    /// Adds a snippet, i.e., a named template of an input line with placeholders in braces, e.g.
+    /// `deploy {env} {version}`. A snippet is used via the `snippet` builtin, e.g.
+    /// `snippet use deploy`, which fills the next line up to the first placeholder. Tab then moves
+    /// on to the next placeholder. Any existing snippet of the same name is replaced.
+    ///
+    /// # Arguments
+    /// `name` - The name of the snippet.
+    /// `template` - The template of the snippet.
+    pub fn add_snippet(&mut self, name: &str, template: &str) {
+        self.snippets.borrow_mut().add(name, template);
+    }
+
+    pub(crate) fn parse<'b>(&mut self, line: &'b str) -> Outcome<'b> {
+        self.parser.parse(line, &self.cmds.borrow(), &self.builtins)
+    }
+
+    /// Resolves the given line to the command it invokes, without evaluating it. This is cheap
+    /// enough to call on every keystroke, e.g. for syntax highlighting or live validation.
+    ///
+    /// # Arguments
+    /// `line` - The line to resolve.
+    ///
+    /// # Returns
+    /// `Resolution` - Which command the line invokes, and where its tokens lie in the line.
+    pub fn resolve(&self, line: &str) -> Resolution {
+        self.parser
+            .resolve(line, &self.cmds.borrow(), &self.builtins)
+    }
+
+    /// Eval executes a single loop of the shell's run-loop.
+    ///
+    /// In other words, it takes a single input line and executes on it; `run()` is a loop over
+    /// `eval()`.
+    ///
+    /// Lines starting with `!` rerun an entry of the history, as in bash: `!!` reruns the newest
+    /// entry, `!42` the 42nd, counting from 1 for the oldest, and `!dep` the newest entry starting
+    /// with `dep`. The rest of the line is appended to the entry, e.g. `!! --force`. The line is
+    /// added to the history as it was expanded.
+    ///
+    /// While a line mode is active, the line goes to its handler as is instead. See `line_mode`.
+    ///
+    /// # Arguments
+    /// `line` - The line to evaluate.
+    pub fn eval(&mut self, line: &str) -> Result<String> {
+        if self.line_modes.is_active() {
+            self.rl.add_history_entry(line);
+            return self.eval_in_mode(line);
+        }
+
+        let expanded = self.expand_history(line)?;
+        let line = expanded.as_deref().unwrap_or(line);
+        self.rl.add_history_entry(line);
+        let res = self.eval_without_history(line);
+        if let Ok(output) = &res {
+            self.diagnostics.borrow_mut().record_output(output);
+        }
+
+        res
+    }
+
+    /// Expands the history designator that the given line starts with, if any. See `eval()`.
+    ///
+    /// # Arguments
+    /// `line` - The line to expand.
+    ///
+    /// # Returns
+    /// `Result<Option<String>>` - The expanded line, None if the line does not start with a
+    /// designator, or an error if no entry of the history matches it.
+    fn expand_history(&self, line: &str) -> Result<Option<String>> {
+        let trimmed = line.trim_start();
+        let (word, rest) = match trimmed.find(' ') {
+            Some(end) => trimmed.split_at(end),
+            None => (trimmed, ""),
+        };
+        let designator = match word.strip_prefix('!') {
+            Some(designator) if !designator.is_empty() => designator,
+            _ => return Ok(None),
+        };
+
+        let entries: Vec<String> = self.history_entries().collect();
+        let entry = if designator == "!" {
+            entries.last()
+        } else if let Ok(n) = designator.parse::<usize>() {
+            n.checked_sub(1).and_then(|i| entries.get(i))
+        } else {
+            entries
+                .iter()
+                .rev()
+                .find(|entry| entry.starts_with(designator))
+        };
+
+        match entry {
+            Some(entry) => Ok(Some(format!("{}{}", entry, rest))),
+            None => Err(ShiError::general(format!(
+                "no history entry for '{}'",
+                word
+            ))),
+        }
+    }
+
+    /// Evaluates the given line without adding it to the history, handing it to the active line
+    /// mode, if any, as `eval()` does. Used for lines read from files, e.g. scripts.
+    fn eval_in_mode(&mut self, line: &str) -> Result<String> {
+        match self.line_modes.handle(&mut self.state, line) {
+            Some(res) => res,
+            None => self.eval_without_history(line),
+        }
+    }
+
+    /// Like `eval()`, but does not add the line to the history.
+    ///
+    /// The line may chain several commands, e.g. `add title && list`. Commands following `;` run
+    /// regardless, while those following `&&` only run if the previous one succeeded. The outputs
+    /// of the commands are joined by newlines, with the errors of failed commands in their place,
+    /// unless the last command to run failed, in which case its error is returned.
+    pub(crate) fn eval_without_history(&mut self, line: &str) -> Result<String> {
+        let segments = self.parser.split_chain(line);
+        if segments.len() <= 1 {
+            return self.eval_segment(line);
+        }
+
+        let mut outputs = Vec::new();
+        let mut failed = None;
+        let mut skipping = false;
+        for (segment, separator) in segments {
+            if !skipping {
+                if let Some(err) = failed.take() {
+                    outputs.push(format!("{} {}", self.error_prefix(), err));
+                }
+                match self.eval_segment(segment) {
+                    Ok(output) => outputs.push(output),
+                    Err(err) => failed = Some(err),
+                }
+            }
+
+            skipping = separator == Some(Separator::OnSuccess) && (skipping || failed.is_some());
+        }
+
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(outputs.join("\n")),
+        }
+    }
+
+    /// Sets up the command that the given line invokes to run on another thread, if the command
+    /// supports it, see `BaseCommand::concurrent()`. The pre hooks are called and the guards set
+    /// up, as for `eval()`, and are torn down, along with the post hooks, by
+    /// `finish_concurrent()`.
+    ///
+    /// # Returns
+    /// `Option<Result<(ConcurrentJob, ConcurrentRun)>>` - The work of the command, and what to
+    /// tear down once it has finished, or None if the line must be evaluated on this thread, e.g.
+    /// because it is a chain or its command does not support it.
+    pub(crate) fn start_concurrent(
+        &mut self,
+        line: &str,
+    ) -> Option<Result<(ConcurrentJob, ConcurrentRun)>> {
+        if self.parser.split_chain(line).len() > 1 || self.parser.split_pipeline(line).len() > 1 {
+            return None;
+        }
+
+        let resolved = self.aliases.resolve(line);
+        let line = resolved.as_deref().unwrap_or(line);
+        let prefixed = if self.prefix_matching {
+            self.parser
+                .expand_prefixes(line.trim_start(), &self.cmds.borrow(), &self.builtins)
+                .ok()?
+        } else {
+            None
+        };
+        let line = prefixed.as_deref().unwrap_or(line);
+        let outcome = self.parse(line);
+        if !outcome.complete || outcome.cmd_type != CommandType::Custom {
+            return None;
+        }
+
+        let cmds = self.cmds.clone();
+        let cmds = cmds.borrow();
+        let base_cmd = cmds.get(outcome.cmd_path.first()?)?;
+        let args = self.parser.args(line);
+        // Commands that fail to validate or need confirmation are left to `eval()`, which
+        // reports the failure or asks.
+        if self.validate(base_cmd, &args).is_err()
+            || cmds.get_path(&outcome.cmd_path)?.confirmation().is_some()
+        {
+            return None;
+        }
+        let job = base_cmd.concurrent(&mut self.state, &args)?;
+
+        self.usage.borrow_mut().record(&outcome.cmd_path);
+        let path = outcome.cmd_path.join(" ");
+        let started = self.run_pre_hooks(&path, &outcome.remaining);
+        match self.set_up_guards(&path) {
+            Ok(guards) => Some(Ok((
+                job,
+                ConcurrentRun {
+                    path,
+                    guards,
+                    started,
+                },
+            ))),
+            Err(err) => {
+                let failed = Err(err);
+                self.run_post_hooks(&path, &failed, started);
+                failed.err().map(Err)
+            }
+        }
+    }
+
+    /// Tears down a command that ran on another thread, see `start_concurrent()`.
+    ///
+    /// # Arguments
+    /// `run` - What to tear down.
+    /// `res` - The result of the command.
+    pub(crate) fn finish_concurrent(&self, run: ConcurrentRun, res: &Result<String>) {
+        drop(run.guards);
+        self.run_post_hooks(&run.path, res, run.started);
+    }
+
+    /// Evaluates a single command of a line, see `eval_without_history()`.
+    ///
+    /// The command may be a pipeline, e.g. `history | grep foo`, in which case the output of each
+    /// stage is the input of the next, and the output of the last stage is the result.
+    fn eval_segment(&mut self, line: &str) -> Result<String> {
+        let invocation = self.diagnostics.borrow_mut().next_invocation();
+        let res = self.eval_pipeline(line, &invocation);
+        if let Err(err) = &res {
+            let mut diagnostics = self.diagnostics.borrow_mut();
+            diagnostics.last_error = Some((err.to_string(), invocation));
+            diagnostics.failures += 1;
+        }
+        self.notifier.dispatch();
+
+        res
+    }
+
+    fn eval_pipeline(&mut self, line: &str, invocation: &str) -> Result<String> {
+        let stages = self.parser.split_pipeline(line);
+        if stages.len() <= 1 {
+            return self.eval_line(line, invocation, None);
+        }
+
+        if stages.iter().any(|stage| stage.is_empty()) {
+            return Err(ShiError::general(format!(
+                "empty command in pipeline '{}'",
+                line
+            )));
+        }
+
+        let mut input: Option<String> = None;
+        for stage in stages {
+            input = Some(self.eval_line(stage, invocation, input.as_deref())?);
+        }
+
+        Ok(input.unwrap_or_default())
+    }
+
+    fn eval_line(&mut self, line: &str, invocation: &str, input: Option<&str>) -> Result<String> {
+        let mut invoked = None;
+        let res = self.eval_resolved(line, invocation, input, &mut invoked);
+        // Lines evaluated by the command itself, e.g. by `filter`, were recorded in the meantime,
+        // and are overridden.
+        self.last_invoked = invoked;
+
+        res
+    }
+
+    /// Evaluates a single stage of a pipeline, see `eval_line()`, recording the path of the
+    /// command that it resolves to and its arguments into `invoked`.
+    fn eval_resolved(
+        &mut self,
+        line: &str,
+        invocation: &str,
+        input: Option<&str>,
+        invoked: &mut Option<(String, Vec<String>)>,
+    ) -> Result<String> {
+        let resolved = self.aliases.resolve(line);
+        let line = resolved.as_deref().unwrap_or(line);
+        let prefixed = if self.prefix_matching {
+            // Expanded lines drop any indent, which parent commands would take as an empty word.
+            self.parser
+                .expand_prefixes(line.trim_start(), &self.cmds.borrow(), &self.builtins)?
+        } else {
+            None
+        };
+        let line = prefixed.as_deref().unwrap_or(line);
+        let mut outcome = self.parse(line);
+        if self.deterministic {
+            outcome.possibilities.sort();
+        }
+
+        if self.verbosity() == Verbosity::Verbose {
+            let trace = format!(
+                "trace [{}]: parsed '{}' as {:?} command {:?} with remaining {:?}",
+                invocation, line, outcome.cmd_type, outcome.cmd_path, outcome.remaining
+            );
+            self.write_output(&trace);
+        }
+
+        if outcome.cmd_path.is_empty() {
+            if let Some((name, args)) = outcome.remaining.split_first() {
+                if let Some(external_cmd) = self.find_external_command(name) {
+                    let started = self.run_pre_hooks(name, args);
+                    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+                    *invoked = Some((name.to_string(), args.clone()));
+                    let res = match self.set_up_guards(name) {
+                        Ok(_guards) => execute(&external_cmd, &mut self.state, &args, input)
+                            .map(|output| self.render(&output)),
+                        Err(err) => Err(err),
+                    };
+                    self.run_post_hooks(name, &res, started);
+                    return res;
+                }
+            }
+        }
+
+        if !outcome.complete {
+            return Err(outcome
+                .error()
+                .expect("incomplete parse, but failed to produce an error")); // This should never happen.
+        }
+
+        self.usage.borrow_mut().record(&outcome.cmd_path);
+        if !outcome.cmd_path.is_empty() {
+            let args = outcome
+                .remaining
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect();
+            *invoked = Some((outcome.cmd_path.join(" "), args));
+        }
+
+        match outcome.cmd_type {
+            CommandType::Custom => {
+                // TODO: This recursive walking through the arguments when we pass this into the
+                // ParentCommand is redundant, since we already did that work when we parsed
+                // things. We should avoid doing this.
+                if let Some(base_cmd_name) = outcome.cmd_path.first() {
+                    if let Some(base_cmd) = self.cmds.borrow().get(base_cmd_name) {
+                        let args = self.parser.args(line);
+                        self.validate(base_cmd, &args)?;
+                        let question = self
+                            .cmds
+                            .borrow()
+                            .get_path(&outcome.cmd_path)
+                            .and_then(|cmd| cmd.confirmation());
+                        self.confirm_run(&outcome.cmd_path, question)?;
+                        let path = outcome.cmd_path.join(" ");
+                        let started = self.run_pre_hooks(&path, &outcome.remaining);
+                        let res = match self.set_up_guards(&path) {
+                            Ok(_guards) => execute(base_cmd, &mut self.state, &args, input)
+                                .map(|output| self.render(&output)),
+                            Err(err) => Err(err),
+                        };
+                        self.run_post_hooks(&path, &res, started);
+                        return res;
+                    }
+                }
+
+                Err(ShiError::UnrecognizedCommand {
+                    got: line.to_string(),
+                })
+            }
+            CommandType::Builtin => {
+                if let Some(base_cmd_name) = outcome.cmd_path.first() {
+                    if let Some(base_cmd) = self.builtins.clone().get(base_cmd_name) {
+                        let args = self.parser.args(line);
+                        self.validate(base_cmd, &args)?;
+                        let question = self
+                            .builtins
+                            .get_path(&outcome.cmd_path)
+                            .and_then(|cmd| cmd.confirmation());
+                        self.confirm_run(&outcome.cmd_path, question)?;
+                        let path = outcome.cmd_path.join(" ");
+                        let started = self.run_pre_hooks(&path, &outcome.remaining);
+                        let res = match self.set_up_guards(&path) {
+                            Ok(_guards) => execute(base_cmd, self, &args, input)
+                                .map(|output| self.render(&output)),
+                            Err(err) => Err(err),
+                        };
+                        self.run_post_hooks(&path, &res, started);
+                        return res;
+                    }
+                }
+
+                Err(ShiError::UnrecognizedCommand {
+                    got: line.to_string(),
+                })
+            }
+            CommandType::Unknown => Err(outcome
+                .error()
+                .expect("parsed an Unknown, but failed to produce an error")), // This should never happen.
+        }
+    }
+}
+
+// Commands registered from the commands directory are of type `S` and must live for `'a`, hence
+// the extra bound.
+impl<'a, S: 'a> Shell<'a, S> {
+    /// Sets the commands directory & registers the commands defined in it.
+    ///
+    /// Every file in the commands directory defines a command, named after the file's stem, that
+    /// runs the file as an executable. See `ExternalCommand`. The directory is re-scanned on
+    /// `reload()`, and before every line read by `run()` or on every call to `try_update()` or
+    /// `update_with_timeout()` if its modification time changed, so that commands are registered
+    /// and deregistered as files are added and removed.
+    ///
+    /// Files whose names collide with commands that are already registered are skipped, with a
+    /// warning.
+    ///
+    /// # Arguments
+    /// `dir` - The path to the commands directory.
+    pub fn set_commands_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        self.commands_dir = Some(dir.as_ref().to_path_buf());
+        for warning in self.sync_commands_dir()? {
+            self.write_error(&format!("Warning: {}", warning));
+        }
+
+        Ok(())
+    }
+
+    /// Synchronizes the registered commands with the contents of the commands directory.
+    ///
+    /// # Returns
+    /// `Result<Vec<String>>` - Warnings about files that could not be registered as commands.
+    fn sync_commands_dir(&mut self) -> Result<Vec<String>> {
+        let dir = match &self.commands_dir {
+            Some(dir) => dir,
+            None => return Ok(Vec::new()),
+        };
+        let scanned_at = SystemTime::now();
+        let modified = fs::metadata(dir)?.modified().ok();
+
+        let mut defined: Vec<(String, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                defined.push((name.to_string(), path.clone()));
+            }
+        }
+        // Directory listings are in no particular order, so sort them to register commands in a
+        // predictable one.
+        defined.sort();
+
+        let mut cmds = self.cmds.borrow_mut();
+
+        // Deregister the commands whose files have disappeared...
+        let (kept, removed): (Vec<_>, Vec<_>) = self
+            .dir_commands
+            .drain(..)
+            .partition(|(name, _)| defined.iter().any(|(defined_name, _)| defined_name == name));
+        for (name, _) in removed {
+            cmds.remove(&name);
+        }
+        self.dir_commands = kept;
+
+        // ... and register those whose files have appeared.
+        let mut warnings = Vec::new();
+        for (name, path) in defined {
+            if self
+                .dir_commands
+                .iter()
+                .any(|(dir_name, _)| dir_name == &name)
+            {
+                continue;
+            }
+
+            if cmds.contains(&name) || self.builtins.contains(&name) {
+                warnings.push(format!(
+                    "skipping '{}', since a command named '{}' already exists",
+                    path.display(),
+                    name
+                ));
+                continue;
+            }
+
+            cmds.add(Command::new_leaf(ExternalCommand::new(&name, &path)));
+            self.dir_commands.push((name, path));
+        }
+        self.commands_dir_scanned = modified.map(|modified| (modified, scanned_at));
+
+        Ok(warnings)
+    }
+
+    /// Like `sync_commands_dir()`, but skips the scan if the commands directory has not been
+    /// modified since the last one. Adding, removing or renaming files modifies it, while changes
+    /// to their contents don't matter, since the files are run afresh every time.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether the directory was scanned.
+    fn sync_commands_dir_if_modified(&mut self) -> Result<bool> {
+        let dir = match &self.commands_dir {
+            Some(dir) => dir,
+            None => return Ok(false),
+        };
+
+        let modified = fs::metadata(dir)?.modified().ok();
+        if let (Some(modified), Some((last_modified, scanned_at))) =
+            (modified, self.commands_dir_scanned)
+        {
+            // Modification times may be as coarse as a second, so a change made in the second of
+            // the last scan may not have moved it.
+            let settled = scanned_at
+                .duration_since(modified)
+                .is_ok_and(|since| since > Duration::from_secs(1));
+            if modified == last_modified && settled {
+                return Ok(false);
+            }
+        }
+
+        self.sync_commands_dir()?;
+        Ok(true)
+    }
+
+    /// Adds a context, i.e., a set of commands, abbreviations and aliases that are only available
+    /// while a predicate holds, e.g. while the working directory is inside a project, or while a
+    /// cluster is connected. Contexts are checked right away, and before each line is read, so that
+    /// the shell only surfaces the commands that are relevant at the time.
+    ///
+    /// Commands of a context that collide with commands that are already registered are skipped,
+    /// with a warning.
+    ///
+    /// # Arguments
+    /// `context` - The context to add.
+    pub fn add_context(&mut self, context: Context<'a, S>) {
+        self.contexts.push(context);
+        for warning in self.sync_contexts() {
+            self.write_error(&format!("Warning: {}", warning));
+        }
+    }
+
+    /// Returns the names of the contexts that are currently active, in the order they were added.
+    pub fn active_contexts(&self) -> Vec<&str> {
+        self.contexts
+            .iter()
+            .filter(|context| context.is_active())
+            .map(|context| context.name())
+            .collect()
+    }
+
+    /// Mounts the commands, abbreviations and aliases of the contexts that became active, and
+    /// unmounts those of the contexts that stopped being active, restoring the abbreviations and
+    /// aliases they shadowed.
+    ///
+    /// # Returns
+    /// `Vec<String>` - Warnings about commands that could not be mounted.
+    fn sync_contexts(&mut self) -> Vec<String> {
+        let changed: Vec<(usize, bool)> = self
+            .contexts
+            .iter()
+            .enumerate()
+            .map(|(i, context)| (i, context.detect()))
+            .filter(|(i, active)| *active != self.contexts[*i].is_active())
+            .collect();
+
+        let mut cmds = self.cmds.borrow_mut();
+        let mut abbreviations = self.abbreviations.borrow_mut();
+
+        // Unmount first, so that contexts which replace each other can share command names. Later
+        // contexts are unmounted first, so that what earlier ones shadowed is restored last.
+        for (i, _) in changed.iter().rev().filter(|(_, active)| !active) {
+            let mounted = self.contexts[*i].take_mounted().unwrap_or_default();
+            for name in mounted.commands {
+                cmds.remove(&name);
+            }
+            for (word, shadowed) in mounted.abbreviations.into_iter().rev() {
+                abbreviations.restore(&word, shadowed.as_deref());
+            }
+            for (name, shadowed) in mounted.aliases.into_iter().rev() {
+                self.aliases.restore(&name, shadowed.as_deref());
+            }
+        }
+
+        let mut warnings = Vec::new();
+        for (i, _) in changed.iter().filter(|(_, active)| *active) {
+            let context = &mut self.contexts[*i];
+            let mut mounted = Mounted::default();
+            for cmd in context.build_commands() {
+                if cmds.contains(cmd.name()) || self.builtins.contains(cmd.name()) {
+                    warnings.push(format!(
+                        "skipping '{}' of context '{}', since a command named '{}' already exists",
+                        cmd.name(),
+                        context.name(),
+                        cmd.name()
+                    ));
+                    continue;
+                }
+
+                mounted.commands.push(cmd.name().to_string());
+                cmds.add(cmd);
+            }
+            for (word, expansion) in context.abbreviations() {
+                let shadowed = abbreviations.expansion(word).map(str::to_string);
+                abbreviations.add(word, expansion);
+                mounted.abbreviations.push((word.clone(), shadowed));
+            }
+            for (name, expansion) in context.aliases() {
+                let shadowed = self.aliases.expansions().get(name).cloned();
+                self.aliases.add(name, expansion);
+                mounted.aliases.push((name.clone(), shadowed));
+            }
+            context.set_mounted(Some(mounted));
+        }
+
+        warnings
+    }
+
+    /// Reloads the shell's configuration at runtime, without restarting the session.
+    ///
+    /// This re-evaluates the rc file, if one was set via `set_and_load_rc_file()`, and re-scans the
+    /// commands directory, if one was set via `set_commands_dir()`. Otherwise, this is a no-op.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether there was anything to reload.
+    pub fn reload(&mut self) -> Result<bool> {
+        if let Some(rc_file) = self.rc_file {
+            self.load_rc_file(rc_file)?;
+        }
+
+        for warning in self.sync_commands_dir()? {
+            self.write_error(&format!("Warning: {}", warning));
+        }
+
+        Ok(self.rc_file.is_some() || self.commands_dir.is_some())
+    }
+
+    /// Like `update_with_timeout()`, but returns immediately if no line has been entered.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether the session is still going, i.e., false once the user exits.
+    pub fn try_update(&mut self) -> Result<bool> {
+        self.update_with_timeout(Duration::ZERO)
+    }
+
+    /// Executes a single step of the shell's run-loop without blocking indefinitely, so that the
+    /// shell can be embedded in an application's own event loop: if the user enters a line within
+    /// the given timeout, it is evaluated and its result is printed, otherwise nothing happens.
+    ///
+    /// Stdin is read on a background thread, which is started by the first call. The line editor
+    /// used by `run()` needs to block on the terminal, so lines read this way are plain text
+    /// without completion or history navigation. Nor are corrections or subcommand choices
+    /// offered, since those would block on stdin themselves. The prompt is written via
+    /// `OutputSink::write_prompt()`.
+    ///
+    /// # Arguments
+    /// `timeout` - How long to wait for a line.
+    ///
+    /// # Returns
+    /// `Result<bool>` - Whether the session is still going, i.e., false once the user exits.
+    pub fn update_with_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        if self.polled_input.is_none() {
+            self.polled_input = Some(PolledInput::spawn(io::BufReader::new(io::stdin())));
+        }
+
+        self.poll_input(timeout)
+    }
+
+    fn poll_input(&mut self, timeout: Duration) -> Result<bool> {
+        if self.terminate {
+            return Ok(false);
+        }
+
+        // The commands directory is checked on every call, so that commands added while the
+        // prompt is up can be run, but failures are only reported once per prompt.
+        let scanned = self.sync_commands_dir_if_modified();
+        if self
+            .polled_input
+            .as_ref()
+            .is_some_and(|input| !input.prompted)
+        {
+            if let Err(err) = scanned {
+                self.write_error(&format!(
+                    "Warning: failed to scan the commands directory: {}",
+                    err
+                ));
+            }
+            for warning in self.sync_contexts() {
+                self.write_error(&format!("Warning: {}", warning));
+            }
+        }
+
+        let prompted = match self.polled_input.as_mut() {
+            Some(input) => std::mem::replace(&mut input.prompted, true),
+            None => return Ok(true),
+        };
+        if !prompted {
+            let prompt = self.line_modes.prompt();
+            let prompt = prompt.as_deref().unwrap_or(self.prompt).to_string();
+            self.write_prompt(&prompt);
+        }
+        let input = match self.polled_input.as_mut() {
+            Some(input) => input,
+            None => return Ok(true),
+        };
+
+        let line = match input.lines.recv_timeout(timeout) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => return Ok(true),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.save_history()?;
+                return Ok(false);
+            }
+        };
+        input.prompted = false;
+        let line = if self.line_modes.is_active() {
+            line
+        } else {
+            self.expand_abbreviation(line)
+        };
+
+        match line.strip_suffix('?') {
+            Some(prefix) if self.inline_help && !self.line_modes.is_active() => {
+                let help = self.inline_help(prefix);
+                let help = self.format_output(&help);
+                self.write_output(&help);
+            }
+            _ => {
+                let res = self.eval(&line);
+                self.print_result(&line, &res);
+            }
+        }
+
+        if self.terminate {
+            self.save_history()?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Executes the shell's run-loop.
+    ///
+    /// This will run indefinitely until the user exits, otherwise terminates the shell or
+    /// process or the shell encounters an error and stops.
+    ///
+    /// Note that invalid command invocations, e.g., nonexistent commands, are not considered fatal
+    /// errors and do _not_ cause a return from this method.
+    ///
+    /// # Returns
+    /// `Result<SessionSummary>` - A summary of the session, including how it ended.
+    pub fn run(&mut self) -> Result<SessionSummary> {
+        let started = Instant::now();
+        let mut commands_executed = 0;
+        let mut failures = 0;
+        let mut exit_reason = ExitReason::Exit;
+
+        while !self.terminate {
+            // Conflicts were already warned about when the commands directory was set or
+            // reloaded, and there is no point in repeating them on every line.
+            if let Err(err) = self.sync_commands_dir_if_modified() {
+                self.write_error(&format!(
+                    "Warning: failed to scan the commands directory: {}",
+                    err
+                ));
+            }
+
+            for warning in self.sync_contexts() {
+                self.write_error(&format!("Warning: {}", warning));
+            }
+
+            let dumb = self.is_dumb_terminal();
+            self.rl.set_dumb(dumb);
+            self.rl.set_raw(self.line_modes.is_active());
+            let prompt = self
+                .line_modes
+                .prompt()
+                .unwrap_or_else(|| self.prompt.to_string());
+            let input = match self.next_input.take() {
+                Some(initial) => self.rl.readline_with_initial(&prompt, &initial),
+                None => self.rl.readline(&prompt),
+            };
+            // Snippets are only filled in on the line they were used for.
+            self.snippets.borrow_mut().finish();
+
+            match input {
+                Ok(line) if self.line_modes.is_active() => {
+                    let res = self.eval(&line);
+                    self.print_result(&line, &res);
+                }
+                Ok(line) => {
+                    let line = self.expand_abbreviation(line);
+                    if self.inline_help {
+                        if let Some(prefix) = line.strip_suffix('?') {
+                            let help = self.inline_help(prefix);
+                            let help = self.format_output(&help);
+                            self.write_output(&help);
+                            self.set_next_input(prefix);
+                            continue;
+                        }
+                    }
+
+                    commands_executed += 1;
+                    let res = self.eval(&line);
+                    if res.is_err() {
+                        failures += 1;
+                    }
+
+                    if res.is_err() && self.subcommand_chooser && self.is_interactive() {
+                        match self.choose_subcommand(&line, &mut Prompter::stdio()) {
+                            Ok(Some(chosen)) => {
+                                self.set_next_input(&chosen);
+                                continue;
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                let error = format!("{} {}", self.error_prefix(), err);
+                                self.write_error(&error);
+                            }
+                        }
+                    }
+
+                    self.print_result(&line, &res);
+
+                    let parse_failed = matches!(res, Err(ShiError::ParseError { .. }));
+                    if parse_failed && self.autocorrect && self.is_interactive() {
+                        if let Some(res) = self.offer_correction(&line, &mut Prompter::stdio()) {
+                            self.print_result(&line, &res);
+                        }
+                    }
+                }
+                Err(ReadlineError::Interrupted) => {
+                    if self.verbosity() != Verbosity::Quiet {
+                        self.write_output("-> CTRL+C; bye.");
+                    }
+                    exit_reason = ExitReason::CtrlC;
+                    break;
+                }
+                Err(ReadlineError::Eof) => {
+                    if self.verbosity() != Verbosity::Quiet {
+                        self.write_output("-> CTRL+D; bye.");
+                    }
+                    exit_reason = ExitReason::CtrlD;
+                    break;
+                }
+                Err(err) => {
+                    let error = format!("{} {:?}", self.error_prefix(), err);
+                    self.write_error(&error);
+                    exit_reason = ExitReason::Error(err.to_string());
+                    break;
+                }
+            }
+        }
+
+        self.save_history()?;
+
+        Ok(SessionSummary {
+            commands_executed,
+            failures,
+            duration: started.elapsed(),
+            exit_reason,
+        })
+    }
+}
+
+/// Returns the names of the children of the parent command at the given path, and whether each
+/// is a parent itself. If the path does not name a parent command, there are no children.
+fn subcommands<T>(cmds: &CommandSet<T>, path: &[&str]) -> Vec<(String, bool)> {
+    match cmds.get_path(path) {
+        Some(Command::Parent(parent)) => parent
+            .sub_commands()
+            .iter()
+            .map(|cmd| {
+                let is_parent = matches!(cmd, Command::Parent(_));
+                (cmd.name().to_string(), is_parent)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Executes the given command, with the given input if it is part of a pipeline.
+fn execute<C: BaseCommand + ?Sized>(
+    cmd: &C,
+    state: &mut C::State,
+    args: &[String],
+    input: Option<&str>,
+) -> Result<CommandOutput> {
+    match input {
+        Some(input) => cmd
+            .execute_with_input(state, args, input)
+            .map(CommandOutput::Text),
+        None => cmd.execute_output(state, args),
+    }
+}
+
+/// Asks the user to confirm running the command at the given path. See `Shell::set_assume_yes()`.
+///
+/// # Arguments
+/// `cmd_path` - The path of the command to run.
+/// `question` - The question that the command asks.
+/// `prompter` - The prompter with which to ask.
+///
+/// # Returns
+/// `Result<()>` - An error if the user did not confirm.
+fn ask_confirmation<R: BufRead, W: Write>(
+    cmd_path: &[&str],
+    question: &str,
+    prompter: &mut Prompter<R, W>,
+) -> Result<()> {
+    if !prompter.confirm(question)? {
+        return Err(ShiError::general(format!(
+            "'{}' was not confirmed",
+            cmd_path.join(" ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the kind of quote that is left open at the end of the given line, if any.
+fn unbalanced_quote(line: &str) -> Option<char> {
+    let mut open = None;
+    for c in line.chars() {
+        match open {
+            None if c == '"' || c == '\'' => open = Some(c),
+            Some(quote) if c == quote => open = None,
+            _ => {}
+        }
+    }
+
+    open
+}
+
+/// Describes the children of the parent command at the given path whose names start with
+/// `partial`, one line each, with their help text. An empty path denotes the root of the set.
+fn describe_children<S, T>(
+    shell: &Shell<S>,
+    cmds: &CommandSet<T>,
+    path: &[&str],
+    partial: &str,
+) -> Vec<String> {
+    let children = if path.is_empty() {
+        cmds
+    } else {
+        match cmds.get_path(path) {
+            Some(Command::Parent(parent)) => parent.sub_commands(),
+            _ => return Vec::new(),
+        }
+    };
+
+    children
+        .iter()
+        .filter(|cmd| cmd.name().starts_with(partial))
+        .map(|cmd| {
+            let cmd_path: Vec<&str> = path.iter().copied().chain([cmd.name()]).collect();
+            match shell.localized_help(&cmd_path, cmd.help()).as_str() {
+                "" => format!("  {}", cmd.name()),
+                help => format!("  {} - {}", cmd.name(), help),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    use crate::command::{ArgSpec, BasicCommand};
+    use crate::Result;
+    use crate::{cmd, leaf, parent};
+
+    use pretty_assertions::assert_eq;
+
+    // TODO: Replace or add more tests that trigger the full codepath of the shell.
+    #[test]
+    fn issue6() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", "Start listening on the given port", |_, args| {
+                Ok(format!("start: {:?}", args))
+            }),
+            cmd!("unlisten", "stop listening", |_, args| {
+                Ok(format!("stop: {:?}", args))
+            })
+        ))?;
+
+        let output = shell.eval("server listen")?;
+        assert_eq!(output, "start: []");
+
+        let output = shell.eval("server listen foo")?;
+        assert_eq!(output, "start: [\"foo\"]");
+
+        let output = shell.eval("server unlisten")?;
         assert_eq!(output, "stop: []");
 
-        let output = shell.eval("server unlisten foo")?;
-        assert_eq!(output, "stop: [\"foo\"]");
+        let output = shell.eval("server unlisten foo")?;
+        assert_eq!(output, "stop: [\"foo\"]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_output() {
+        let mut shell = Shell::new("| ");
+
+        shell.set_plain_output(true);
+        assert!(shell.is_plain_output());
+        assert_eq!(shell.format_output("\x1b[31mred\x1b[0m"), "red");
+
+        shell.set_plain_output(false);
+        assert!(!shell.is_plain_output());
+        assert_eq!(
+            shell.format_output("\x1b[31mred\x1b[0m"),
+            "\x1b[31mred\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn dumb_terminal() {
+        let mut shell = Shell::new("| ");
+
+        shell.set_dumb_terminal(true);
+        assert!(shell.is_dumb_terminal());
+
+        shell.set_dumb_terminal(false);
+        assert!(!shell.is_dumb_terminal());
+    }
+
+    #[test]
+    fn history() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+
+        shell.eval("dog")?;
+        shell.eval("dog")?;
+        assert!(shell.eval("cat").is_err());
+        assert!(shell.add_history_entry("dog"));
+        assert!(!shell.add_history_entry(""));
+
+        assert_eq!(shell.history_len(), 3);
+        assert_eq!(
+            shell.history_entries().collect::<Vec<String>>(),
+            vec!["dog", "cat", "dog"]
+        );
+
+        shell.clear_history();
+        assert_eq!(shell.history_len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_matching() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("status", |_, _| Ok(String::from("up"))),
+            cmd!("stop", |_, args| Ok(format!("stopped {}", args.join(" ")))),
+        ))?;
+        shell.register(cmd!("hello", |_, _| Ok(String::from("hi"))))?;
+
+        assert!(shell.eval("serv sta").is_err());
+        shell.set_prefix_matching(true);
+        assert!(shell.is_prefix_matching());
+
+        assert_eq!(shell.eval("serv sta")?, "up");
+        assert_eq!(shell.eval("  ser sto st")?, "stopped st");
+        assert_eq!(shell.eval("hell")?, "hi");
+        // Builtins are matched too, and the line is recorded as typed.
+        assert!(shell.eval("hist")?.contains("hist"));
+        assert_eq!(
+            shell.eval("h").unwrap_err().to_string(),
+            "ambiguous command 'h', could be any of: head, hello, help, helptree, history"
+        );
+        assert!(shell.eval("server st").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn history_expansion() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+        shell.register(cmd!("deploy", |_, args| Ok(format!(
+            "deployed {}",
+            args.join(" ")
+        ))))?;
+
+        assert!(shell.eval("!!").is_err());
+        shell.eval("deploy api")?;
+        shell.eval("echo hi")?;
+
+        assert_eq!(shell.eval("!!")?, "hi");
+        assert_eq!(shell.eval("!1 --force")?, "deployed api --force");
+        assert_eq!(shell.eval("!dep")?, "deployed api --force");
+        assert_eq!(
+            shell.history_entries().collect::<Vec<String>>(),
+            vec!["deploy api", "echo hi", "deploy api --force"]
+        );
+
+        assert_eq!(
+            shell.eval("!0").unwrap_err().to_string(),
+            "error: no history entry for '!0'"
+        );
+        assert!(shell.eval("!9").is_err());
+        assert!(shell.eval("!status").is_err());
+        // Lone, or later, exclamation marks are left alone.
+        assert_eq!(shell.eval("echo wow!")?, "wow!");
+        assert!(shell.eval("!").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn state_access() -> Result<()> {
+        let mut shell = Shell::new_with_state("| ", vec![String::from("a")]);
+        shell.register(cmd!("push", |list: &mut Vec<String>, args| {
+            list.extend(args.iter().cloned());
+            Ok(String::new())
+        }))?;
+
+        shell.eval("push b")?;
+        assert_eq!(shell.state(), &vec!["a", "b"]);
+
+        shell.state_mut().clear();
+        shell.eval("push c")?;
+        assert_eq!(shell.into_state(), vec!["c"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn complete() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.register(cmd!("dolphin", |_, _| { Ok(String::from("click")) }))?;
+
+        let candidate = |s: &str, start: usize| CompletionCandidate {
+            display: s.to_string(),
+            replacement: s.to_string(),
+            start,
+        };
+
+        assert_eq!(
+            shell.complete("do", 2),
+            vec![candidate("g", 2), candidate("lphin", 2)]
+        );
+        assert_eq!(shell.complete("dolp", 4), vec![candidate("hin", 4)]);
+        assert_eq!(shell.complete("zebra", 5), vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_observer() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| Ok(String::new())),
+            cmd!("stop", |_, _| Ok(String::new())),
+        ))?;
+        // Lines are not observed without an observer.
+        shell.hint("server", 6);
+
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let log = observed.clone();
+        shell.set_line_observer_interval(Duration::ZERO);
+        shell.set_line_observer(move |line, outcome| {
+            log.borrow_mut().push(format!(
+                "{}: {:?} {}",
+                line, outcome.cmd_path, outcome.complete
+            ))
+        });
+
+        shell.hint("server li", 9);
+        // The same line is only observed once, however often it is parsed.
+        shell.hint("server li", 9);
+        shell.complete("server li", 9);
+        shell.complete("server listen 80", 16);
+        // Only the line up to the cursor is observed.
+        shell.hint("server stop", 6);
+        shell.hint("nope", 4);
+        // Only the last command of a chain or pipeline is observed.
+        shell.complete("nope && server st", 17);
+        shell.complete("nope; history | server ", 23);
+
+        // Hints within the interval are skipped, unlike completions.
+        shell.set_line_observer_interval(Duration::from_secs(3600));
+        shell.hint("server", 6);
+        shell.complete("server stop", 11);
+
+        assert_eq!(
+            *observed.borrow(),
+            vec![
+                "server li: [\"server\"] false",
+                "server listen 80: [\"server\", \"listen\"] true",
+                "server: [\"server\"] false",
+                "nope: [] false",
+                "server st: [\"server\"] false",
+                "server : [\"server\"] false",
+                "server stop: [\"server\", \"stop\"] true",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn complete_by_usage() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| Ok(String::new())),
+            cmd!("stop", |_, _| Ok(String::new())),
+            cmd!("status", |_, _| Ok(String::new())),
+        ))?;
+
+        let displays = |shell: &Shell<()>, line: &str| -> Vec<String> {
+            shell
+                .complete(line, line.len())
+                .into_iter()
+                .map(|candidate| candidate.display)
+                .collect()
+        };
+
+        assert_eq!(
+            displays(&shell, "server "),
+            vec!["listen", "stop", "status"]
+        );
+
+        shell.eval("server status")?;
+        shell.eval("server status")?;
+        shell.eval("server stop")?;
+        // Failed parses are not usage.
+        assert!(shell.eval("server start").is_err());
+
+        assert_eq!(
+            displays(&shell, "server "),
+            vec!["status", "stop", "listen"]
+        );
+        assert_eq!(displays(&shell, "server st"), vec!["atus", "op"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hint() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+
+        assert_eq!(shell.hint("do", 2), None);
+
+        shell.eval("dog bark loudly")?;
+        assert_eq!(shell.hint("do", 2), Some(String::from("g bark loudly")));
+        assert_eq!(shell.hint("do", 1), None);
+        assert_eq!(shell.hint("cat", 3), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn verbosity() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        assert_eq!(shell.verbosity(), Verbosity::Normal);
+
+        shell.set_verbosity(Verbosity::Quiet);
+        assert_eq!(shell.verbosity(), Verbosity::Quiet);
+
+        assert_eq!(
+            "verbose".parse::<Verbosity>()?.to_string(),
+            String::from("verbose")
+        );
+        assert!("loud".parse::<Verbosity>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rc_file() -> Result<()> {
+        let rc_path = std::env::temp_dir().join(format!("shi-rc-test-{}", std::process::id()));
+        fs::write(
+            &rc_path,
+            "# Comments and blank lines are skipped.\n\ninc\nset verbosity quiet\n",
+        )?;
+        let rc_path = rc_path.to_str().unwrap().to_string();
+
+        let mut shell = Shell::new_with_state("| ", 0);
+        shell.register(cmd!("inc", |count: &mut u64, _| {
+            *count += 1;
+            Ok(String::new())
+        }))?;
+
+        shell.set_and_load_rc_file(&rc_path)?;
+        assert_eq!(shell.state, 1);
+        assert_eq!(shell.verbosity(), Verbosity::Quiet);
+
+        shell.set_verbosity(Verbosity::Normal);
+        assert!(shell.reload()?);
+        assert_eq!(shell.state, 2);
+        assert_eq!(shell.verbosity(), Verbosity::Quiet);
+
+        // Failing lines are reported with their line number.
+        fs::write(&rc_path, "inc\nDNE\n")?;
+        let err = shell.reload().unwrap_err();
+        assert!(err.to_string().contains(&format!("{}:2:", rc_path)));
+
+        fs::remove_file(&rc_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_script() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-check-test-{}", std::process::id()));
+        fs::write(
+            &path,
+            "# Nothing is run.\n\ninc\nDNE\nset verbosity\necho \"unbalanced\nhelp\nset colors on\n",
+        )?;
+
+        let mut shell = Shell::new_with_state("| ", 0);
+        shell.register(cmd!("inc", |count: &mut u64, _| {
+            *count += 1;
+            Ok(String::new())
+        }))?;
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+
+        let problems = shell.check_script(&path)?;
+        assert_eq!(shell.state, 0);
+        assert_eq!(
+            problems.iter().map(|p| p.line).collect::<Vec<usize>>(),
+            vec![4, 5, 6, 8]
+        );
+        assert_eq!(problems[2].to_string(), "6: error: unbalanced quote (\")");
+
+        let path = path.to_str().unwrap().to_string();
+        assert_eq!(shell.check_mode(vec!["--check", &path])?, Some(false));
+        assert_eq!(shell.check_mode(vec!["--verbose"])?, None);
+        assert!(shell.check_script("/does/not/exist").is_err());
+
+        fs::write(&path, "inc\nhelp\n")?;
+        assert_eq!(shell.check_mode(vec!["--check", &path])?, Some(true));
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_script() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-run-test-{}", std::process::id()));
+        fs::write(&path, "# Counts to three.\ninc\n\nfail\ninc\nexit\ninc\n")?;
+
+        let mut shell = Shell::new_with_state("| ", 0);
+        shell.register(cmd!("inc", |count: &mut u64, _| {
+            *count += 1;
+            Ok(String::new())
+        }))?;
+        shell.register(cmd!("fail", |_, _| Err(ShiError::general("failed"))))?;
+
+        let err = shell.run_script(&path, OnScriptError::Stop).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("error: {}:4: error: failed", path.display())
+        );
+        assert_eq!(shell.state, 1);
+
+        let problems = shell.run_script(&path, OnScriptError::Continue)?;
+        assert_eq!(
+            problems,
+            vec![ScriptProblem {
+                line: 4,
+                message: String::from("error: failed"),
+            }]
+        );
+        // The script ends at `exit`, so the last line is never run.
+        assert_eq!(shell.state, 3);
+        assert_eq!(shell.history_len(), 0);
+        assert!(shell
+            .run_script("/does/not/exist", OnScriptError::Continue)
+            .is_err());
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_script_in_line_mode() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("shi-mode-test-{}", std::process::id()));
+        fs::write(&path, "sql\nselect ')' \\\n.exit\nsql\n")?;
+
+        let mut shell = Shell::new_with_state("| ", Vec::new());
+        let modes = shell.line_modes();
+        shell.register(cmd!("sql", move |_, _| {
+            modes.enter_line_mode(|queries: &mut Vec<String>, line| {
+                queries.push(line.to_string());
+                Ok(String::new())
+            });
+            Ok(String::new())
+        }))?;
+
+        shell.run_script(&path, OnScriptError::Stop)?;
+        fs::remove_file(&path)?;
+        // Lines go to the mode as they are, until it is left.
+        assert_eq!(shell.state, vec![String::from("select ')' \\")]);
+        assert!(shell.line_modes.is_active());
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_tokenizer() -> Result<()> {
+        use crate::tokenizer::Tokenization;
+
+        struct KeyValueTokenizer;
+
+        impl Tokenizer for KeyValueTokenizer {
+            fn tokenize<'a>(&self, line: &'a str) -> Tokenization<'a> {
+                Tokenization {
+                    tokens: line
+                        .split(&[' ', '='][..])
+                        .filter(|token| !token.is_empty())
+                        .collect(),
+                    trailing_space: line.ends_with(' '),
+                }
+            }
+        }
+
+        let mut shell = Shell::new("| ").with_tokenizer(Box::new(KeyValueTokenizer));
+        shell.register(cmd!("config", |_, args| Ok(args.join(","))))?;
+
+        assert_eq!(
+            shell.eval("config env=prod  region=eu")?,
+            "env,prod,region,eu"
+        );
+        assert_eq!(shell.eval("config=debug")?, "debug");
+        assert_eq!(shell.eval("config a=b && config c")?, "a,b\nc");
+        assert!(shell.eval("conf=x").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict() -> Result<()> {
+        let mut greet = BasicCommand::new("greet", |_: &mut (), args: &[String]| {
+            Ok(format!("hello {}", args[0]))
+        });
+        greet.set_arg(0, ArgSpec::new().with_name("name"));
+
+        let mut shell = Shell::new("| ");
+        shell.register(Command::new_leaf(greet))?;
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+
+        assert_eq!(shell.eval("greet bob smith")?, "hello bob");
+        shell.set_strict(true);
+        assert_eq!(
+            shell.eval("greet bob smith").unwrap_err().to_string(),
+            "error: unexpected arguments [\"smith\"]\nusage: greet <name>"
+        );
+        assert_eq!(shell.eval("greet bob")?, "hello bob");
+        // Commands that declare no arguments are unaffected.
+        assert_eq!(shell.eval("echo a --b")?, "a --b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reload_without_rc_file() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        assert!(!shell.reload()?);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_dir() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let write_script = |path: &Path, body: &str| -> Result<()> {
+            fs::write(path, format!("#!/bin/sh\n{}\n", body))?;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+            Ok(())
+        };
+
+        let dir = std::env::temp_dir().join(format!("shi-cmds-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        write_script(&dir.join("greet.sh"), "echo \"hello $1\"")?;
+        write_script(&dir.join("dog"), "echo meow")?;
+
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.set_commands_dir(&dir)?;
+
+        assert_eq!(shell.eval("greet world")?, "hello world");
+        // The colliding file must not have replaced the registered command.
+        assert_eq!(shell.eval("dog")?, "woof");
+
+        // New files are picked up, and removed files are forgotten, on the next scan.
+        write_script(&dir.join("fail"), "echo oops >&2; exit 3")?;
+        fs::remove_file(dir.join("greet.sh"))?;
+        assert_eq!(shell.sync_commands_dir()?.len(), 1);
+
+        assert!(shell.eval("greet world").is_err());
+        let err = shell.eval("fail").unwrap_err();
+        assert!(err.to_string().contains("oops"));
+
+        // Scans are skipped until the directory is modified again.
+        fs::File::open(&dir)?.set_modified(SystemTime::now() - Duration::from_secs(10))?;
+        shell.sync_commands_dir()?;
+        assert!(!shell.sync_commands_dir_if_modified()?);
+        write_script(&dir.join("greet"), "echo hi")?;
+        assert!(shell.sync_commands_dir_if_modified()?);
+        assert_eq!(shell.eval("greet")?, "hi");
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn polls_the_commands_dir() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("shi-polled-cmds-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let mut shell = Shell::new("| ");
+        shell.set_commands_dir(&dir)?;
+        shell.polled_input = Some(PolledInput::spawn(io::Cursor::new("greet\n")));
+
+        let script = dir.join("greet");
+        fs::write(&script, "#!/bin/sh\necho hi\n")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+        assert!(shell.poll_input(Duration::from_secs(5))?);
+        assert_eq!(shell.output(1), Some(String::from("hi")));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_commands() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("shi-ext-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let script = dir.join("mytool-greet");
+        fs::write(&script, "#!/bin/sh\necho \"hello $*\"\n")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("greet", |_, _| { Ok(String::from("hi")) }))?;
+
+        // Lookups are opt-in.
+        assert!(shell.eval("greet2").is_err());
+
+        shell.set_external_commands(&dir, "mytool");
+        fs::copy(&script, dir.join("mytool-greet2"))?;
+
+        // Registered commands take precedence.
+        assert_eq!(shell.eval("greet")?, "hi");
+        assert_eq!(shell.eval("greet2 big world")?, "hello big world");
+        assert!(shell.eval("greet3").is_err());
+        assert!(shell.eval("../mytool-greet").is_err());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn mount() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+
+        shell.mount(
+            "debug",
+            vec![cmd!("ping", |_, _| { Ok(String::from("pong")) })],
+        )?;
+        assert_eq!(shell.eval("debug ping")?, "pong");
+
+        // Mounting onto an existing parent merges the commands into it.
+        shell.mount(
+            "debug",
+            vec![cmd!("pong", |_, _| { Ok(String::from("ping")) })],
+        )?;
+        assert_eq!(shell.eval("debug ping")?, "pong");
+        assert_eq!(shell.eval("debug pong")?, "ping");
+
+        // Colliding with an existing child, a leaf, or each other, is an error.
+        assert!(shell
+            .mount("debug", vec![cmd!("ping", |_, _| { Ok(String::new()) })])
+            .is_err());
+        assert!(shell
+            .mount("dog", vec![cmd!("ping", |_, _| { Ok(String::new()) })])
+            .is_err());
+        assert!(shell
+            .mount(
+                "cat",
+                vec![
+                    cmd!("meow", |_, _| { Ok(String::new()) }),
+                    cmd!("meow", |_, _| { Ok(String::new()) }),
+                ]
+            )
+            .is_err());
+        assert!(!shell.cmds.borrow().contains("cat"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parents_validate_the_args_of_subcommands() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            Command::new_leaf(
+                BasicCommand::builder("listen")
+                    .arity(1..=1)
+                    .build(|_, args| Ok(format!("listening on {}", args[0]))),
+            ),
+        ))?;
+
+        assert_eq!(shell.eval("server listen 80")?, "listening on 80");
+        assert!(shell.eval("server listen").is_err());
+        assert!(shell.eval("server listen 80 443").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commands_macro() -> Result<()> {
+        fn stop(count: &mut u32, _: &[String]) -> Result<String> {
+            *count += 1;
+            Ok(format!("stopped {} times", count))
+        }
+
+        let mut shell = Shell::new_with_state("| ", 0);
+        for cmd in crate::commands! {
+            "server"(help: "Manages the server") => {
+                "listen"(
+                    help: "Listens on a port",
+                    long_help: "Listens until stopped.",
+                    args: 1..=2,
+                    complete: ["80", "443"],
+                    category: "network",
+                ) => |_, args| Ok(format!("listening on {}", args.join(" "))),
+                "stop" => stop,
+                "config" => {
+                    "get"(args: 1..) => |_, args| Ok(args.join(",")),
+                },
+            },
+            "wipe"(confirm: "Wipe it?", since: "0.2.0") => |count, _| {
+                *count = 0;
+                Ok(String::from("wiped"))
+            }
+        } {
+            shell.register(cmd)?;
+        }
+
+        assert_eq!(shell.eval("server listen 80 tcp")?, "listening on 80 tcp");
+        assert_eq!(
+            shell.eval("server listen").unwrap_err().to_string(),
+            "error: too few arguments, expected at least 1\nusage: listen <arg0>"
+        );
+        assert!(shell.eval("server listen 1 2 3").is_err());
+        assert_eq!(shell.eval("server stop")?, "stopped 1 times");
+        assert_eq!(shell.eval("server config get a b")?, "a,b");
+        assert!(shell.eval("server config get").is_err());
+
+        let candidate = |s: &str, start: usize| CompletionCandidate {
+            display: s.to_string(),
+            replacement: s.to_string(),
+            start,
+        };
+        assert_eq!(
+            shell.complete("server listen 4", 15),
+            vec![candidate("43", 15)]
+        );
+
+        let cmds = shell.cmds.borrow();
+        let server = cmds.get("server").unwrap();
+        assert_eq!(server.help(), "Manages the server");
+        let listen = cmds.get_path(&["server", "listen"]).unwrap();
+        assert_eq!(
+            listen.long_help(),
+            Some(String::from("Listens until stopped."))
+        );
+        assert_eq!(listen.category(), Some(String::from("network")));
+        let wipe = cmds.get_path(&["wipe"]).unwrap();
+        assert_eq!(wipe.confirmation(), Some(String::from("Wipe it?")));
+        assert_eq!(wipe.since(), Some(String::from("0.2.0")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mount_with() -> Result<()> {
+        #[derive(Default)]
+        struct AppState {
+            hosts: Vec<String>,
+            verbose: bool,
+        }
+
+        let mut shell = Shell::new_with_state("| ", AppState::default());
+        shell.mount_with(
+            "net",
+            |s: &mut AppState| &mut s.hosts,
+            vec![
+                cmd!("add", |hosts: &mut Vec<String>, args| {
+                    hosts.extend(args.iter().cloned());
+                    Ok(hosts.join(","))
+                }),
+                parent!(
+                    "hosts",
+                    cmd!("count", |hosts: &mut Vec<String>, _| Ok(hosts
+                        .len()
+                        .to_string())),
+                ),
+            ],
+        )?;
+        // Other projections can be mounted under the same prefix.
+        shell.mount_with(
+            "net",
+            |s: &mut AppState| &mut s.verbose,
+            vec![cmd!("verbose", |verbose: &mut bool, _| {
+                *verbose = !*verbose;
+                Ok(verbose.to_string())
+            })],
+        )?;
+
+        assert_eq!(shell.eval("net add a b")?, "a,b");
+        assert_eq!(shell.eval("net hosts count")?, "2");
+        assert_eq!(shell.eval("net verbose")?, "true");
+        assert_eq!(shell.state().hosts, vec!["a", "b"]);
+        assert!(shell.state().verbose);
+
+        assert!(shell
+            .mount_with(
+                "net",
+                |s: &mut AppState| &mut s.verbose,
+                vec![cmd!("verbose", |_: &mut bool, _| Ok(String::new()))],
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn contexts() -> Result<()> {
+        use std::cell::Cell;
+
+        let connected = Rc::new(Cell::new(false));
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+
+        let detect = connected.clone();
+        shell.add_context(
+            Context::new("cluster", move || detect.get())
+                .commands(|| {
+                    vec![
+                        cmd!("pods", |_, _| { Ok(String::from("web-1")) }),
+                        cmd!("dog", |_, _| { Ok(String::from("meow")) }),
+                    ]
+                })
+                .abbreviation("p", "pods")
+                .alias("ls", "pods")
+                .alias("bark", "dog"),
+        );
+        shell.add_alias("ls", "dog");
+        assert!(shell.active_contexts().is_empty());
+        assert!(shell.eval("pods").is_err());
+
+        connected.set(true);
+        // The colliding command is skipped.
+        assert_eq!(shell.sync_contexts().len(), 1);
+        assert_eq!(shell.active_contexts(), vec!["cluster"]);
+        assert_eq!(shell.eval("pods")?, "web-1");
+        assert_eq!(shell.eval("dog")?, "woof");
+        assert_eq!(shell.expand_abbreviation(String::from("p")), "pods");
+        assert_eq!(shell.eval("ls")?, "web-1");
+        assert_eq!(shell.eval("bark")?, "woof");
+
+        connected.set(false);
+        assert!(shell.sync_contexts().is_empty());
+        assert!(shell.active_contexts().is_empty());
+        assert!(shell.eval("pods").is_err());
+        assert_eq!(shell.eval("dog")?, "woof");
+        assert_eq!(shell.expand_abbreviation(String::from("p")), "p");
+        // The shadowed alias is restored, and the others are gone.
+        assert_eq!(shell.eval("ls")?, "woof");
+        assert!(shell.eval("bark").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_command_mut() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| { Ok(String::from("listening")) })
+        ))?;
+
+        shell.with_command_mut("server", |cmd| {
+            if let Command::Parent(parent) = cmd {
+                parent.set_help("Manages the server");
+                parent
+                    .sub_commands_mut()
+                    .add(cmd!("unlisten", |_, _| { Ok(String::from("unlistening")) }));
+            }
+        })?;
+
+        assert_eq!(
+            shell.cmds.borrow().get("server").unwrap().help(),
+            "Manages the server"
+        );
+        assert_eq!(shell.eval("server unlisten")?, "unlistening");
+
+        // Commands that aren't registered can't be modified.
+        assert!(shell.with_command_mut("DNE", |_| {}).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "zoo",
+            cmd!("zebra", |_, _| Ok(String::new())),
+            cmd!("yak", |_, _| Ok(String::new())),
+        ))?;
+        shell.register(cmd!("dog", |_, _| Ok(String::new())))?;
+        shell.register(cmd!("cat", |_, _| Ok(String::new())))?;
+        shell.eval("zoo zebra")?;
+        shell.set_deterministic(true);
+
+        assert!(shell.is_plain_output());
+        assert!(shell
+            .eval("help")?
+            .starts_with("Normal commands:\n\t'cat' - \n\t'dog' - \n\t'zoo' - \nBuilt-in"));
+        assert!(shell.eval("helptree")?.starts_with(
+            "Normal commands\n├── cat\n├── dog\n└── zoo\n    ├── yak\n    └── zebra\n"
+        ));
+
+        // Usage no longer affects the order of completions.
+        let displays: Vec<String> = shell
+            .complete("zoo ", 4)
+            .into_iter()
+            .map(|candidate| candidate.display)
+            .collect();
+        assert_eq!(displays, vec!["yak", "zebra"]);
+
+        match shell.eval("zoo lion") {
+            Err(ShiError::ParseError { possibilities, .. }) => {
+                assert_eq!(possibilities, vec!["yak", "zebra"])
+            }
+            res => panic!("expected a parse error, got {:?}", res),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn autocorrect() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, args| Ok(format!(
+                "listening on {}",
+                args.join(" ")
+            ))),
+            cmd!("unlisten", |_, _| Ok(String::new())),
+        ))?;
+
+        assert_eq!(
+            shell.suggest_correction("server lisen 80"),
+            Some(String::from("server listen 80"))
+        );
+        assert_eq!(shell.suggest_correction("hepl"), Some(String::from("help")));
+        assert_eq!(shell.suggest_correction("server zzz"), None);
+
+        let mut out = Vec::new();
+        let res = shell.offer_correction(
+            "srever listen 80",
+            &mut Prompter::new("y\n".as_bytes(), &mut out),
+        );
+        assert_eq!(res.unwrap()?, "listening on 80");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "did you mean 'server listen 80'? [y/N] "
+        );
+
+        // Anything but yes declines, including just pressing Enter.
+        let mut out = Vec::new();
+        let mut prompter = Prompter::new("\n".as_bytes(), &mut out);
+        assert!(shell
+            .offer_correction("srever listen 80", &mut prompter)
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn subcommand_chooser() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| Ok(String::new())),
+            parent!("tls", cmd!("enable", |_, _| Ok(String::new()))),
+        ))?;
+
+        let mut out = Vec::new();
+        let mut prompter = Prompter::new("tls\n1\n".as_bytes(), &mut out);
+        assert_eq!(
+            shell.choose_subcommand("server", &mut prompter)?,
+            Some(String::from("server tls enable "))
+        );
+        let transcript = String::from_utf8(out).unwrap();
+        assert!(transcript.starts_with("'server' has these subcommands:\n"));
+        assert!(transcript.contains("'server tls' has these subcommands:\n"));
+
+        // Lines that don't stop at a parent have nothing to choose from.
+        let mut prompter = Prompter::new("".as_bytes(), Vec::new());
+        assert_eq!(
+            shell.choose_subcommand("server listen", &mut prompter)?,
+            None
+        );
+        assert_eq!(shell.choose_subcommand("server zzz", &mut prompter)?, None);
+        assert_eq!(shell.choose_subcommand("zzz", &mut prompter)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn inline_help() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", "Starts listening", |_, _| Ok(String::new())),
+            cmd!("limit", |_, _| Ok(String::new())),
+            cmd!("stop", |_, _| Ok(String::new())),
+        ))?;
+
+        assert_eq!(
+            shell.inline_help("server "),
+            "  listen - Starts listening\n  limit\n  stop"
+        );
+        assert_eq!(
+            shell.inline_help("server li"),
+            "  listen - Starts listening\n  limit"
+        );
+        assert!(shell.inline_help("he").starts_with("  help - "));
+        assert_eq!(shell.inline_help("server listen "), "  <cr>");
+        assert_eq!(
+            shell.inline_help("server zzz "),
+            "  invalid input: 'zzz' is not a command"
+        );
+        assert_eq!(shell.inline_help("server x"), "  no commands match 'x'");
+
+        Ok(())
+    }
+
+    #[test]
+    fn register_lazy() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register_lazy("dog", || cmd!("dog", |_, _| Ok(String::from("woof"))))?;
+
+        assert!(!shell.cmds.borrow().is_constructed("dog"));
+        assert!(shell
+            .register_lazy("dog", || cmd!("dog", |_, _| Ok(String::new())))
+            .is_err());
+        assert!(shell
+            .register(cmd!("dog", |_, _| Ok(String::new())))
+            .is_err());
+
+        assert_eq!(shell.eval("dog")?, "woof");
+        assert!(shell.cmds.borrow().is_constructed("dog"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invocation_ids() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        let ctx = shell.invocation_context();
+        shell.register(cmd!("whoami", move |_, _| { Ok(ctx.id().unwrap()) }))?;
+        assert_eq!(shell.invocation_id(), None);
+
+        let first = shell.eval("whoami")?;
+        assert_eq!(shell.invocation_id(), Some(first.clone()));
+
+        let second = shell.eval("whoami")?;
+        assert_ne!(first, second);
+        assert!(shell.eval("DNE").is_err());
+        assert_ne!(shell.invocation_id(), Some(second));
+
+        // Other shells generate their own IDs.
+        let mut other = Shell::new("| ");
+        other.eval("help")?;
+        assert_ne!(other.invocation_id(), Some(first));
+
+        Ok(())
+    }
+
+    #[test]
+    fn polls_input() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.polled_input = Some(PolledInput::spawn(io::Cursor::new("dog\nexit\ndog\n")));
+
+        let timeout = Duration::from_secs(5);
+        assert!(shell.poll_input(timeout)?);
+        assert_eq!(shell.output(1), Some(String::from("woof")));
+        assert!(!shell.poll_input(timeout)?);
+        // Nothing is evaluated once the user has exited.
+        assert!(!shell.poll_input(timeout)?);
+        assert_eq!(shell.history_len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn expands_abbreviations() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+        shell.add_abbreviation("e", "echo hello");
+        shell.polled_input = Some(PolledInput::spawn(io::Cursor::new(
+            "e world
+",
+        )));
+
+        assert!(shell.poll_input(Duration::from_secs(5))?);
+        assert_eq!(shell.output(1), Some(String::from("hello world")));
+        assert_eq!(
+            shell.history_entries().collect::<Vec<String>>(),
+            vec![String::from("echo hello world")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_to_output_sink() -> Result<()> {
+        struct Capture(Rc<RefCell<Vec<String>>>);
+
+        impl OutputSink for Capture {
+            fn write_output(&mut self, output: &str) {
+                self.0.borrow_mut().push(format!("out: {}", output));
+            }
+
+            fn write_error(&mut self, error: &str) {
+                self.0.borrow_mut().push(format!("err: {}", error));
+            }
+        }
+
+        let captured = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new("| ");
+        shell.set_plain_output(true);
+        shell.set_output_sink(Box::new(Capture(captured.clone())));
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.register(cmd!("fail", |_, _| Err(ShiError::general("failed"))))?;
+        shell.polled_input = Some(PolledInput::spawn(io::Cursor::new("dog\nfail\n")));
+
+        let timeout = Duration::from_secs(5);
+        assert!(shell.poll_input(timeout)?);
+        assert!(shell.poll_input(timeout)?);
+        assert_eq!(
+            *captured.borrow(),
+            vec![
+                String::from("out: woof"),
+                String::from("err: Error: error: failed")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn confirmation() -> Result<()> {
+        let mut shell = Shell::new_with_state("| ", 3);
+        shell.register(leaf!(BasicCommand::builder("wipe")
+            .confirm("Wipe everything?")
+            .build(|count: &mut u32, _: &[String]| {
+                *count = 0;
+                Ok(String::from("wiped"))
+            })))?;
+
+        assert!(!shell.is_assume_yes());
+        shell.set_assume_yes(true);
+        assert!(shell.is_assume_yes());
+        assert_eq!(shell.eval("wipe")?, "wiped");
+        assert_eq!(shell.state, 0);
+
+        let mut out = Vec::new();
+        assert_eq!(
+            ask_confirmation(
+                &["wipe"],
+                "Wipe everything?",
+                &mut Prompter::new("n\n".as_bytes(), &mut out)
+            )
+            .unwrap_err()
+            .to_string(),
+            "error: 'wipe' was not confirmed"
+        );
+        assert!(ask_confirmation(
+            &["wipe"],
+            "Wipe everything?",
+            &mut Prompter::new("y\n".as_bytes(), &mut out)
+        )
+        .is_ok());
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "Wipe everything? [y/n] Wipe everything? [y/n] "
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_output() -> Result<()> {
+        struct Capture(Rc<RefCell<Vec<String>>>);
+
+        impl OutputSink for Capture {
+            fn write_output(&mut self, output: &str) {
+                self.0.borrow_mut().push(output.to_string());
+            }
+
+            fn write_error(&mut self, error: &str) {
+                self.0.borrow_mut().push(format!("err: {}", error));
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("shi-json-test-{}", std::process::id()));
+        fs::write(
+            &path,
+            "server status api\nsrv\nserver stop\nst a && server status b\nserv stat c | grep c\n",
+        )?;
+
+        let captured = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new("| ");
+        assert!(!shell.is_json_output());
+        shell.set_json_output(true);
+        assert!(shell.is_json_output());
+        shell.set_output_sink(Box::new(Capture(captured.clone())));
+        shell.register(parent!(
+            "server",
+            cmd!("status", |_, args| Ok(format!(
+                "\x1b[32mup\x1b[0m\n{}",
+                args.join(" ")
+            ))),
+            cmd!("stop", |_, _| Err(ShiError::general("refused"))),
+        ))?;
+        shell.add_alias("st", "server status");
+        shell.set_prefix_matching(true);
+
+        shell.run_script(&path, OnScriptError::Continue)?;
+        fs::remove_file(&path)?;
+        let captured = captured.borrow();
+        assert_eq!(captured.len(), 5);
+        assert_eq!(
+            captured[0],
+            r#"{"command":"server status","args":["api"],"status":"ok","output":"up\napi"}"#
+        );
+        assert!(captured[1].starts_with(
+            r#"{"command":"srv","args":[],"status":"error","error":"command failed to parse: "#
+        ));
+        assert_eq!(
+            captured[2],
+            r#"{"command":"server stop","args":[],"status":"error","error":"error: refused"}"#
+        );
+        // Chains and pipelines report the last command that ran, as resolved.
+        assert_eq!(
+            captured[3],
+            r#"{"command":"server status","args":["b"],"status":"ok","output":"up\na\nup\nb"}"#
+        );
+        assert_eq!(
+            captured[4],
+            r#"{"command":"grep","args":["c"],"status":"ok","output":"c"}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn polls_closed_input() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.polled_input = Some(PolledInput::spawn(io::Cursor::new("")));
+
+        assert!(!shell.poll_input(Duration::from_secs(5))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn chains() -> Result<()> {
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.register(cmd!("echo", |_, args| Ok(args.join(" "))))?;
+        shell.register(cmd!("fail", |_, _| Err(ShiError::general("nope"))))?;
+
+        assert_eq!(shell.eval("dog && echo 'a && b'")?, "woof\n'a && b'");
+        assert_eq!(shell.eval("fail; dog")?, "Error: error: nope\nwoof");
+        // Commands following && are skipped once a command fails, up until the next ;.
+        assert_eq!(
+            shell.eval("fail && echo skipped && echo skipped; dog")?,
+            "Error: error: nope\nwoof"
+        );
+        assert!(shell.eval("dog && fail").is_err());
+        assert!(shell.eval("dog; fail").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn guards() -> Result<()> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, _| { Ok(String::from("listening")) }),
+            cmd!("crash", |_, _| { Err(ShiError::general("crashed")) }),
+        ))?;
+
+        for name in &["outer", "inner"] {
+            let log = log.clone();
+            shell.add_guard(move |path| {
+                log.borrow_mut()
+                    .push(format!("{} set up for {}", name, path));
+                let log = log.clone();
+                Teardown::new(move || log.borrow_mut().push(format!("{} torn down", name)))
+            });
+        }
+
+        assert_eq!(shell.eval("server listen")?, "listening");
+        assert!(shell.eval("server crash").is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "outer set up for server listen",
+                "inner set up for server listen",
+                "inner torn down",
+                "outer torn down",
+                "outer set up for server crash",
+                "inner set up for server crash",
+                "inner torn down",
+                "outer torn down",
+            ]
+        );
+
+        log.borrow_mut().clear();
+        shell.add_fallible_guard(|path| match path {
+            "server crash" => Err(ShiError::general("refused")),
+            _ => Ok(()),
+        });
+        assert_eq!(shell.eval("server listen")?, "listening");
+        assert_eq!(
+            shell.eval("server crash").unwrap_err().to_string(),
+            "error: refused"
+        );
+        assert_eq!(
+            &log.borrow()[4..],
+            [
+                "outer set up for server crash",
+                "inner set up for server crash",
+                "inner torn down",
+                "outer torn down",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn hooks() -> Result<()> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new("| ");
+        shell.register(parent!(
+            "server",
+            cmd!("listen", |_, args| {
+                Ok(format!("listening on {}", args[0]))
+            }),
+            cmd!("crash", |_, _| { Err(ShiError::general("crashed")) }),
+        ))?;
+
+        let pre_log = log.clone();
+        shell.add_pre_hook(move |path, args| {
+            pre_log
+                .borrow_mut()
+                .push(format!("before {} {:?}", path, args))
+        });
+        let guard_log = log.clone();
+        shell.add_guard(move |path| guard_log.borrow_mut().push(format!("guard {}", path)));
+        let post_log = log.clone();
+        shell.add_post_hook(move |path, res, elapsed| {
+            assert!(elapsed < Duration::from_secs(10));
+            let res = match res {
+                Ok(output) => output.clone(),
+                Err(err) => err.to_string(),
+            };
+            post_log
+                .borrow_mut()
+                .push(format!("after {}: {}", path, res))
+        });
+
+        assert_eq!(shell.eval("server listen 80")?, "listening on 80");
+        assert!(shell.eval("server crash").is_err());
+        // Commands that fail to parse or validate are never executed, so are not hooked.
+        assert!(shell.eval("server nope").is_err());
+        shell.eval("help --search nothing")?;
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "before server listen [\"80\"]",
+                "guard server listen",
+                "after server listen: listening on 80",
+                "before server crash []",
+                "guard server crash",
+                "after server crash: error: crashed",
+                "before help [\"--search\", \"nothing\"]",
+                "guard help",
+                "after help: No commands match 'nothing'.",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_into_external_commands() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("shi-pipe-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let script = dir.join("mytool-upper");
+        fs::write(&script, "#!/bin/sh\ntr a-z A-Z\n")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+        let mut shell = Shell::new("| ");
+        shell.register(cmd!("dog", |_, _| { Ok(String::from("woof")) }))?;
+        shell.set_external_commands(&dir, "mytool");
+
+        assert_eq!(shell.eval("dog | upper")?, "WOOF");
+        assert_eq!(shell.eval("dog | upper | grep WO")?, "WOOF");
+
+        fs::remove_dir_all(&dir)?;
 
         Ok(())
     }
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::error::ShiError;
+use crate::Result;
+
+/// Splits a template into the text before its first placeholder, and each placeholder along
+/// with the text that follows it, up to the next placeholder. Placeholders are written in
+/// braces, e.g. `{env}`.
+fn split(template: &str) -> (String, VecDeque<(String, String)>) {
+    let mut lead = String::new();
+    let mut parts: VecDeque<(String, String)> = VecDeque::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let text = &rest[..start];
+        match parts.back_mut() {
+            Some((_, after)) => after.push_str(text),
+            None => lead.push_str(text),
+        }
+        parts.push_back((rest[start..end].to_string(), String::new()));
+        rest = &rest[end..];
+    }
+    match parts.back_mut() {
+        Some((_, after)) => after.push_str(rest),
+        None => lead.push_str(rest),
+    }
+
+    (lead, parts)
+}
+
+/// Snippets are named templates of input lines with placeholders, e.g. `deploy {env} {version}`,
+/// for speeding up repetitive, long invocations.
+///
+/// Using a snippet fills the input line up to its first placeholder, with the rest of the
+/// template shown as a hint. Pressing Tab then moves on to the next placeholder, by inserting the
+/// text between the two.
+#[derive(Debug, Default)]
+pub(crate) struct Snippets {
+    templates: BTreeMap<String, String>,
+    // The placeholders of the snippet being filled in, starting from the current one, each with
+    // the text that follows it.
+    active: VecDeque<(String, String)>,
+}
+
+impl Snippets {
+    /// Creates a new, empty Snippets.
+    pub fn new() -> Snippets {
+        Snippets::default()
+    }
+
+    /// Adds a snippet, replacing any existing snippet of the same name.
+    ///
+    /// # Arguments
+    /// `name` - The name of the snippet.
+    /// `template` - The template of the snippet.
+    pub fn add(&mut self, name: &str, template: &str) {
+        self.templates
+            .insert(name.to_string(), template.to_string());
+    }
+
+    /// Removes the snippet of the given name, returning whether there was one.
+    ///
+    /// # Arguments
+    /// `name` - The name of the snippet.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.templates.remove(name).is_some()
+    }
+
+    /// Returns the names and templates of the snippets, ordered by name.
+    pub fn templates(&self) -> &BTreeMap<String, String> {
+        &self.templates
+    }
+
+    /// Starts filling in the snippet of the given name.
+    ///
+    /// # Arguments
+    /// `name` - The name of the snippet.
+    ///
+    /// # Returns
+    /// `Result<String>` - The input to fill the line with, up to the first placeholder, or an
+    /// error if there is no such snippet.
+    pub fn start(&mut self, name: &str) -> Result<String> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| ShiError::general(format!("no snippet named '{}'", name)))?;
+
+        let (lead, parts) = split(template);
+        self.active = parts;
+
+        Ok(lead)
+    }
+
+    /// Returns the rest of the snippet being filled in, from its current placeholder on, if any.
+    pub fn hint(&self) -> Option<String> {
+        if self.active.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.active
+                .iter()
+                .map(|(placeholder, after)| format!("{}{}", placeholder, after))
+                .collect(),
+        )
+    }
+
+    /// Moves on from the current placeholder of the snippet being filled in.
+    ///
+    /// # Returns
+    /// `Option<String>` - The text to insert to get to the next placeholder, or to the end of the
+    /// snippet if it was the last one. `None` if no snippet is being filled in.
+    pub fn advance(&mut self) -> Option<String> {
+        self.active.pop_front().map(|(_, after)| after)
+    }
+
+    /// Stops filling in the current snippet, if any.
+    pub fn finish(&mut self) {
+        self.active.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn fills_placeholders() -> Result<()> {
+        let mut snippets = Snippets::new();
+        snippets.add("deploy", "deploy {env} {version} --wait");
+        snippets.add("plain", "server status");
+
+        assert_eq!(snippets.start("deploy")?, "deploy ");
+        assert_eq!(
+            snippets.hint(),
+            Some(String::from("{env} {version} --wait"))
+        );
+        assert_eq!(snippets.advance(), Some(String::from(" ")));
+        assert_eq!(snippets.hint(), Some(String::from("{version} --wait")));
+        assert_eq!(snippets.advance(), Some(String::from(" --wait")));
+        assert_eq!(snippets.hint(), None);
+        assert_eq!(snippets.advance(), None);
+
+        assert_eq!(snippets.start("plain")?, "server status");
+        assert_eq!(snippets.hint(), None);
+
+        snippets.start("deploy")?;
+        snippets.finish();
+        assert_eq!(snippets.advance(), None);
+
+        assert!(snippets.start("DNE").is_err());
+        assert!(snippets.remove("plain"));
+        assert!(!snippets.remove("plain"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn splits_templates() {
+        assert_eq!(
+            split("{a}-{b}"),
+            (
+                String::new(),
+                VecDeque::from(vec![
+                    (String::from("{a}"), String::from("-")),
+                    (String::from("{b}"), String::new()),
+                ])
+            )
+        );
+        assert_eq!(
+            split("no { end"),
+            (String::from("no { end"), VecDeque::new())
+        );
+    }
+}
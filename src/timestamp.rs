@@ -0,0 +1,179 @@
+//! A module for formatting the timestamps that a shell displays, e.g. in the header of `watch`.
+//!
+//! Timestamps are formatted as ISO-8601 in UTC by default. This can be changed via
+//! `Shell::set_time_format()`, to a strftime-like pattern, or to a closure, e.g. one that renders
+//! local time via a date & time crate:
+//!
+//! ```
+//! # use std::time::{Duration, UNIX_EPOCH};
+//! # use shi::timestamp::TimeFormat;
+//! let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! assert_eq!(TimeFormat::default().format(time), "2023-11-14T22:13:20Z");
+//! assert_eq!(TimeFormat::pattern("%d/%m/%Y %H:%M").format(time), "14/11/2023 22:13");
+//! ```
+//!
+//! Embedders can format their own timestamps the same way as the shell, e.g. for audit logs, via
+//! `Shell::format_time()`.
+
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// TimeFormat is how a timestamp is turned into text.
+#[derive(Clone, Default)]
+pub enum TimeFormat {
+    /// ISO-8601 in UTC, e.g. `2023-11-14T22:13:20Z`.
+    #[default]
+    Iso8601Utc,
+    /// A strftime-like pattern, rendered in UTC. See `TimeFormat::pattern()`.
+    Pattern(String),
+    /// A closure that formats the timestamp itself.
+    Custom(Rc<dyn Fn(SystemTime) -> String>),
+}
+
+impl fmt::Debug for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeFormat::Iso8601Utc => write!(f, "Iso8601Utc"),
+            TimeFormat::Pattern(pattern) => f.debug_tuple("Pattern").field(pattern).finish(),
+            TimeFormat::Custom(_) => write!(f, "Custom(<fn>)"),
+        }
+    }
+}
+
+impl TimeFormat {
+    /// Creates a TimeFormat from a strftime-like pattern, rendered in UTC. The supported
+    /// specifiers are `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%F` (`%Y-%m-%d`), `%T` (`%H:%M:%S`),
+    /// `%s` (seconds since the Unix epoch), `%z` (always `+0000`), `%Z` (always `UTC`) and `%%`.
+    /// Anything else is left as is.
+    ///
+    /// # Arguments
+    /// `pattern` - The pattern, e.g. `%Y-%m-%d %H:%M`.
+    pub fn pattern(pattern: &str) -> TimeFormat {
+        TimeFormat::Pattern(pattern.to_string())
+    }
+
+    /// Creates a TimeFormat from a closure.
+    ///
+    /// # Arguments
+    /// `format` - Formats a timestamp.
+    pub fn custom<F>(format: F) -> TimeFormat
+    where
+        F: Fn(SystemTime) -> String + 'static,
+    {
+        TimeFormat::Custom(Rc::new(format))
+    }
+
+    /// Formats the given timestamp.
+    ///
+    /// # Arguments
+    /// `time` - The timestamp to format.
+    pub fn format(&self, time: SystemTime) -> String {
+        match self {
+            TimeFormat::Iso8601Utc => render("%FT%TZ", time),
+            TimeFormat::Pattern(pattern) => render(pattern, time),
+            TimeFormat::Custom(format) => format(time),
+        }
+    }
+}
+
+/// Returns the number of whole seconds between the Unix epoch and the given time, which is
+/// negative for times before the epoch.
+fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(err) => {
+            let before = err.duration();
+            // Round down, so that e.g. half a second before the epoch is in the previous second.
+            let secs = before.as_secs() as i64 + i64::from(before.subsec_nanos() > 0);
+            -secs
+        }
+    }
+}
+
+/// Converts a number of days since the Unix epoch into a proleptic Gregorian year, month and day.
+/// See Howard Hinnant's `civil_from_days()`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Renders the given time, in UTC, according to a pattern. See `TimeFormat::pattern()`.
+fn render(pattern: &str, time: SystemTime) -> String {
+    let secs = unix_seconds(time);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let of_day = secs.rem_euclid(86_400);
+    let (hour, minute, second) = (of_day / 3600, of_day % 3600 / 60, of_day % 60);
+
+    let mut rendered = String::with_capacity(pattern.len() * 2);
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rendered.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('Y') => rendered.push_str(&format!("{:04}", year)),
+            Some('m') => rendered.push_str(&format!("{:02}", month)),
+            Some('d') => rendered.push_str(&format!("{:02}", day)),
+            Some('H') => rendered.push_str(&format!("{:02}", hour)),
+            Some('M') => rendered.push_str(&format!("{:02}", minute)),
+            Some('S') => rendered.push_str(&format!("{:02}", second)),
+            Some('F') => rendered.push_str(&format!("{:04}-{:02}-{:02}", year, month, day)),
+            Some('T') => rendered.push_str(&format!("{:02}:{:02}:{:02}", hour, minute, second)),
+            Some('s') => rendered.push_str(&secs.to_string()),
+            Some('z') => rendered.push_str("+0000"),
+            Some('Z') => rendered.push_str("UTC"),
+            Some('%') => rendered.push('%'),
+            Some(other) => {
+                rendered.push('%');
+                rendered.push(other);
+            }
+            None => rendered.push('%'),
+        }
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn formats() {
+        let time = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(TimeFormat::default().format(time), "2000-02-29T12:34:56Z");
+        assert_eq!(
+            TimeFormat::pattern("%Y/%m/%d %T %Z (%s) 100%% %q").format(time),
+            "2000/02/29 12:34:56 UTC (951827696) 100% %q"
+        );
+        assert_eq!(
+            TimeFormat::custom(|_| String::from("now")).format(time),
+            "now"
+        );
+
+        assert_eq!(
+            TimeFormat::default().format(UNIX_EPOCH),
+            "1970-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            TimeFormat::default().format(UNIX_EPOCH - Duration::from_millis(500)),
+            "1969-12-31T23:59:59Z"
+        );
+    }
+}
@@ -13,9 +13,19 @@ pub trait Tokenizer {
     fn tokenize<'a>(&self, line: &'a str) -> Tokenization<'a>;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Separates the commands of a chain, e.g. `add title && list`.
+pub enum Separator {
+    /// `;`, after which the next command runs whether or not the previous one failed.
+    Always,
+    /// `&&`, after which the next command only runs if the previous one succeeded.
+    OnSuccess,
+}
+
 /// DefaultTokenizer tokenizes an input string into tokens based on some default, basic rules.
 ///
 /// Handles things like splitting by space, acknowledging quotation marks, etc.
+#[derive(Clone)]
 pub struct DefaultTokenizer {
     quotations: Vec<char>,
 }
@@ -70,6 +80,74 @@ impl DefaultTokenizer {
         DefaultTokenizer { quotations }
     }
 
+    /// Splits the line into the commands of a chain, each along with the separator that follows
+    /// it, if any. Separators inside of quotes do not split the line, and empty commands, e.g.
+    /// after a trailing `;`, are dropped.
+    ///
+    /// # Arguments
+    /// `line` - The input line.
+    ///
+    /// # Returns
+    /// `Vec<(&str, Option<Separator>)>` - The trimmed commands of the chain, in order.
+    pub fn split_chain<'a>(&self, line: &'a str) -> Vec<(&'a str, Option<Separator>)> {
+        let quote_pairs = self.find_quote_pairs(self.find_quotes(line));
+
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, ch)) = chars.next() {
+            if is_quoted(&quote_pairs, i) {
+                continue;
+            }
+
+            let separator = match ch {
+                ';' => Separator::Always,
+                '&' if matches!(chars.peek(), Some((_, '&'))) => {
+                    chars.next();
+                    Separator::OnSuccess
+                }
+                _ => continue,
+            };
+
+            let segment = line[start..i].trim();
+            if !segment.is_empty() {
+                segments.push((segment, Some(separator)));
+            }
+            start = chars.peek().map_or(line.len(), |(next, _)| *next);
+        }
+
+        let segment = line[start..].trim();
+        if !segment.is_empty() {
+            segments.push((segment, None));
+        }
+
+        segments
+    }
+
+    /// Splits the line into the stages of a pipeline, e.g. `history | grep foo`. Pipes inside of
+    /// quotes do not split the line.
+    ///
+    /// # Arguments
+    /// `line` - The input line.
+    ///
+    /// # Returns
+    /// `Vec<&str>` - The trimmed stages of the pipeline, in order. Empty stages are kept.
+    pub fn split_pipeline<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        let quote_pairs = self.find_quote_pairs(self.find_quotes(line));
+
+        let mut stages = Vec::new();
+        let mut start = 0;
+        for (i, ch) in line.char_indices() {
+            if ch == '|' && !is_quoted(&quote_pairs, i) {
+                stages.push(line[start..i].trim());
+                start = i + 1;
+            }
+        }
+        stages.push(line[start..].trim());
+
+        stages
+    }
+
     /// Finds quotes in the line string, and returns them.
     ///
     /// This method does not have any intelligence around pairing of quotation marks, it simply
@@ -303,6 +381,13 @@ impl Tokenizer for DefaultTokenizer {
     }
 }
 
+/// Returns whether the character at the given position is inside of one of the given quote pairs.
+fn is_quoted(quote_pairs: &[QuotePair], pos: usize) -> bool {
+    quote_pairs
+        .iter()
+        .any(|pair| pair.start < pos && pos < pair.end)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -338,6 +423,35 @@ mod test {
         )
     }
 
+    #[test]
+    fn split_chain() {
+        use pretty_assertions::assert_eq;
+        let tokenizer = DefaultTokenizer::new(vec!['"', '\'']);
+        assert_eq!(
+            tokenizer.split_chain("add 'a; b' && list;count ;"),
+            vec![
+                ("add 'a; b'", Some(Separator::OnSuccess)),
+                ("list", Some(Separator::Always)),
+                ("count", Some(Separator::Always)),
+            ]
+        );
+        assert_eq!(tokenizer.split_chain("list"), vec![("list", None)]);
+        assert_eq!(tokenizer.split_chain("a & b"), vec![("a & b", None)]);
+        assert_eq!(tokenizer.split_chain(""), vec![]);
+    }
+
+    #[test]
+    fn split_pipeline() {
+        use pretty_assertions::assert_eq;
+        let tokenizer = DefaultTokenizer::new(vec!['"', '\'']);
+        assert_eq!(
+            tokenizer.split_pipeline("history | grep 'a | b'|count"),
+            vec!["history", "grep 'a | b'", "count"]
+        );
+        assert_eq!(tokenizer.split_pipeline("history"), vec!["history"]);
+        assert_eq!(tokenizer.split_pipeline("history |"), vec!["history", ""]);
+    }
+
     mod glob_quotes {
         use super::*;
         use pretty_assertions::assert_eq;
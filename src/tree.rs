@@ -0,0 +1,246 @@
+//! A module for building and rendering trees, like the one printed by the `helptree` builtin.
+//!
+//! ```
+//! # use shi::tree::{Tree, TreeStyle};
+//! let tree = Tree::new_with_children(
+//!     "felid",
+//!     vec![
+//!         Tree::new_with_children("felinae", vec![Tree::new("domestic-cat")]),
+//!         Tree::new("panther"),
+//!     ],
+//! );
+//!
+//! assert_eq!(
+//!     tree.render(TreeStyle::Unicode),
+//!     vec![
+//!         "felid",
+//!         "├── felinae",
+//!         "│   └── domestic-cat",
+//!         "└── panther",
+//!     ]
+//! );
+//! ```
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// The glyphs with which trees are drawn.
+pub enum TreeStyle {
+    /// Box-drawing characters, e.g. `├──` and `└──`. This is the default.
+    #[default]
+    Unicode,
+    /// ASCII characters, e.g. `|--` and `\--`, for terminals and log collectors that cannot
+    /// display box-drawing characters.
+    Ascii,
+}
+
+impl TreeStyle {
+    /// Returns the glyph continuing the vertical pipe of an ancestor that has later siblings.
+    fn pipe(&self) -> &'static str {
+        match self {
+            TreeStyle::Unicode => "│   ",
+            TreeStyle::Ascii => "|   ",
+        }
+    }
+
+    /// Returns the connector for an element that has later siblings.
+    fn fork(&self) -> &'static str {
+        match self {
+            TreeStyle::Unicode => "├",
+            TreeStyle::Ascii => "|",
+        }
+    }
+
+    /// Returns the connector for the last element of its level.
+    fn elbow(&self) -> &'static str {
+        match self {
+            TreeStyle::Unicode => "└",
+            TreeStyle::Ascii => "\\",
+        }
+    }
+
+    /// Returns the horizontal pipes leading from a connector to an element.
+    fn dashes(&self) -> &'static str {
+        match self {
+            TreeStyle::Unicode => "──",
+            TreeStyle::Ascii => "--",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A tree of values, where every node has a value and zero or more children.
+pub struct Tree<T> {
+    /// The value of this node.
+    pub value: T,
+    /// The children of this node, in the order they are rendered.
+    pub children: Vec<Tree<T>>,
+}
+
+impl<T> Tree<T> {
+    /// Creates a new Tree with no children.
+    ///
+    /// # Arguments
+    /// `value` - The value of the root.
+    pub fn new(value: T) -> Tree<T> {
+        Tree::new_with_children(value, Vec::new())
+    }
+
+    /// Creates a new Tree with the given children.
+    ///
+    /// # Arguments
+    /// `value` - The value of the root.
+    /// `children` - The children of the root.
+    pub fn new_with_children(value: T, children: Vec<Tree<T>>) -> Tree<T> {
+        Tree { value, children }
+    }
+
+    /// Adds a child to the root of this tree, after its existing children.
+    ///
+    /// # Arguments
+    /// `child` - The child to add.
+    pub fn add_child(&mut self, child: Tree<T>) {
+        self.children.push(child);
+    }
+}
+
+impl<T: Ord> Tree<T> {
+    /// Sorts the children of every node of this tree by their values.
+    pub fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.value.cmp(&b.value));
+        for child in self.children.iter_mut() {
+            child.sort();
+        }
+    }
+}
+
+impl<T: Display> Tree<T> {
+    /// Renders the tree, one line per node, in the manner of the `tree` program. The root is
+    /// rendered as-is on the first line, and every other node is rendered beneath its parent,
+    /// connected to it with the glyphs of the given style.
+    ///
+    /// # Arguments
+    /// `style` - The style in which to draw the tree.
+    ///
+    /// # Returns
+    /// `Vec<String>` - The lines of the rendered tree.
+    pub fn render(&self, style: TreeStyle) -> Vec<String> {
+        let mut lines = vec![self.value.to_string()];
+        self.render_children(style, "", &mut lines);
+        lines
+    }
+
+    /// Renders the descendants of this node.
+    ///
+    /// # Arguments
+    /// `style` - The style in which to draw the tree.
+    /// `indent` - What to prefix the lines of the children with, i.e., the continuations of the
+    /// vertical pipes of the ancestors.
+    /// `lines` - The lines of the rendered tree, which this adds to.
+    fn render_children(&self, style: TreeStyle, indent: &str, lines: &mut Vec<String>) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i == self.children.len() - 1;
+
+            // If we're the last child, we want a clean elbow cut-off, otherwise, we want a fork.
+            let connector = if last { style.elbow() } else { style.fork() };
+            lines.push(format!(
+                "{}{}{} {}",
+                indent,
+                connector,
+                style.dashes(),
+                child.value
+            ));
+
+            // The last child's pipe ends at its elbow, so its own children don't continue it:
+            //   └── Foo
+            //       └── SubFoo
+            // Whereas for any other child, the pipe continues on to its later siblings:
+            //   ├── Foo
+            //   │   └── SubFoo
+            let continuation = if last { "    " } else { style.pipe() };
+            child.render_children(style, &format!("{}{}", indent, continuation), lines);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn felid() -> Tree<String> {
+        let mut felinae = Tree::new(String::from("felinae"));
+        felinae.add_child(Tree::new(String::from("dangerous-tiger")));
+        felinae.add_child(Tree::new(String::from("domestic-cat")));
+
+        Tree::new_with_children(
+            String::from("felid"),
+            vec![
+                Tree::new(String::from("panther")),
+                felinae,
+                Tree::new_with_children(
+                    String::from("felinae2"),
+                    vec![Tree::new(String::from("domestic-cat"))],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn leaf() {
+        assert_eq!(Tree::new("dog").render(TreeStyle::Unicode), vec!["dog"]);
+    }
+
+    #[test]
+    fn sort() {
+        let mut tree = felid();
+        tree.children.reverse();
+        tree.sort();
+
+        assert_eq!(
+            tree.render(TreeStyle::Unicode),
+            vec![
+                "felid",
+                "├── felinae",
+                "│   ├── dangerous-tiger",
+                "│   └── domestic-cat",
+                "├── felinae2",
+                "│   └── domestic-cat",
+                "└── panther",
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(
+            felid().render(TreeStyle::Unicode),
+            vec![
+                "felid",
+                "├── panther",
+                "├── felinae",
+                "│   ├── dangerous-tiger",
+                "│   └── domestic-cat",
+                "└── felinae2",
+                "    └── domestic-cat",
+            ]
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(
+            felid().render(TreeStyle::Ascii),
+            vec![
+                "felid",
+                "|-- panther",
+                "|-- felinae",
+                "|   |-- dangerous-tiger",
+                "|   \\-- domestic-cat",
+                "\\-- felinae2",
+                "    \\-- domestic-cat",
+            ]
+        );
+    }
+}
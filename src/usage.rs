@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Result;
+
+/// Usage counts how often each command has been invoked, so that completion candidates and
+/// palette results can surface the most-used commands first.
+///
+/// Commands are identified by their paths, e.g. `server listen`. Invoking a command also counts
+/// as using each of its ancestors.
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    counts: HashMap<String, u64>,
+}
+
+impl Usage {
+    /// Creates a new, empty Usage.
+    pub fn new() -> Usage {
+        Usage::default()
+    }
+
+    /// Records an invocation of the command at the given path.
+    ///
+    /// # Arguments
+    /// `path` - The names of the commands leading to, and including, the invoked command.
+    pub fn record(&mut self, path: &[&str]) {
+        for depth in 1..=path.len() {
+            *self.counts.entry(path[..depth].join(" ")).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns how often the command at the given path has been used.
+    ///
+    /// # Arguments
+    /// `path` - The names of the commands leading to, and including, the command.
+    pub fn count(&self, path: &[&str]) -> u64 {
+        self.counts.get(&path.join(" ")).copied().unwrap_or(0)
+    }
+
+    /// Loads the counts from the given file, adding them to the current counts. A missing file is
+    /// treated as empty, and malformed lines are skipped.
+    ///
+    /// # Arguments
+    /// `path` - The path to the usage file.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for line in contents.lines() {
+            if let Some((count, cmd_path)) = line.split_once('\t') {
+                if let Ok(count) = count.parse::<u64>() {
+                    *self.counts.entry(cmd_path.to_string()).or_insert(0) += count;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the counts to the given file, one `count<TAB>path` line per command, most-used first.
+    ///
+    /// # Arguments
+    /// `path` - The path to the usage file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut counts: Vec<(&String, &u64)> = self.counts.iter().collect();
+        counts.sort_by(|(a_path, a), (b_path, b)| b.cmp(a).then(a_path.cmp(b_path)));
+
+        let contents: String = counts
+            .into_iter()
+            .map(|(cmd_path, count)| format!("{}\t{}\n", count, cmd_path))
+            .collect();
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn record() {
+        let mut usage = Usage::new();
+        usage.record(&["server", "listen"]);
+        usage.record(&["server", "unlisten"]);
+        usage.record(&["server", "listen"]);
+
+        assert_eq!(usage.count(&["server"]), 3);
+        assert_eq!(usage.count(&["server", "listen"]), 2);
+        assert_eq!(usage.count(&["server", "unlisten"]), 1);
+        assert_eq!(usage.count(&["dog"]), 0);
+    }
+
+    #[test]
+    fn persistence() -> Result<()> {
+        let path = env::temp_dir().join(format!("shi-usage-test-{}", process::id()));
+
+        let mut usage = Usage::new();
+        usage.record(&["server", "listen"]);
+        usage.record(&["dog"]);
+        usage.record(&["dog"]);
+        usage.save(&path)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "2\tdog\n1\tserver\n1\tserver listen\n"
+        );
+
+        let mut loaded = Usage::new();
+        loaded.load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(loaded.count(&["dog"]), 2);
+        assert_eq!(loaded.count(&["server", "listen"]), 1);
+
+        // A missing file is just no usage.
+        loaded.load(&path)?;
+        assert_eq!(loaded.count(&["dog"]), 2);
+
+        Ok(())
+    }
+}